@@ -0,0 +1,41 @@
+use responses::body_preprocessor::BodyPreprocessor;
+
+const COMMENT_START: &str = "<!--";
+const COMMENT_END: &str = "-->";
+
+pub fn preprocess_body(body: &str, preprocessors: &[BodyPreprocessor]) -> String {
+    preprocessors.iter()
+        .fold(String::from(body), |current_body, preprocessor| {
+            match preprocessor {
+                BodyPreprocessor::StripHtmlComments => rewrite_html_comments(&current_body, false),
+                BodyPreprocessor::UncommentHtmlComments => rewrite_html_comments(&current_body, true),
+            }
+        })
+}
+
+fn rewrite_html_comments(body: &str, keep_content: bool) -> String {
+    let mut result = String::with_capacity(body.len());
+    let mut remainder = body;
+    while let Some(start) = remainder.find(COMMENT_START) {
+        result.push_str(&remainder[..start]);
+        let comment = &remainder[start + COMMENT_START.len()..];
+        match comment.find(COMMENT_END) {
+            Some(end) => {
+                if keep_content {
+                    result.push_str(&comment[..end]);
+                }
+                remainder = &comment[end + COMMENT_END.len()..];
+            }
+            None => {
+                // unterminated comment: browsers treat the rest of the document as comment
+                if keep_content {
+                    result.push_str(comment);
+                }
+                remainder = "";
+            }
+        }
+    }
+    result.push_str(remainder);
+
+    result
+}
@@ -0,0 +1,39 @@
+use std::sync::OnceLock;
+
+use regex::Regex;
+use scraper::node::Element;
+
+// marks low-confidence links guessed from inline event handlers and data attributes
+pub const HEURISTIC_SOURCE_TAG: &str = "heuristic";
+
+static URL_PATTERN: OnceLock<Regex> = OnceLock::new();
+
+// e.g. onclick="location.href='/next'" or data-target="https://example.com/page"
+pub fn is_heuristic_attribute(name: &str) -> bool {
+    name.starts_with("on") || name.starts_with("data-")
+}
+
+// absolute urls anywhere in the value, site-relative ones only if quoted or the whole value, as a plain slash is no url in code
+pub fn extract_heuristic_urls(value: &str) -> Vec<String> {
+    let value = value.trim();
+    if is_site_relative(value) && !value.contains(char::is_whitespace) {
+        return vec![value.to_string()];
+    }
+    let url_pattern = URL_PATTERN.get_or_init(|| Regex::new(r#"(?P<absolute>https?://[^\s'"<>()]+)|['"](?P<relative>/[^\s'"<>()]*)['"]"#).unwrap());
+    url_pattern.captures_iter(value)
+        .filter_map(|captures| captures.name("absolute").or_else(|| captures.name("relative")))
+        .map(|url| url.as_str().to_string())
+        .filter(|url| url.starts_with("http") || is_site_relative(url))
+        .collect()
+}
+
+pub fn get_heuristic_urls(element: &Element, link_attributes: &[String]) -> Vec<String> {
+    element.attrs()
+        .filter(|(name, _)| is_heuristic_attribute(name) && !link_attributes.iter().any(|link_attribute| link_attribute == name))
+        .flat_map(|(_, value)| extract_heuristic_urls(value))
+        .collect()
+}
+
+fn is_site_relative(value: &str) -> bool {
+    value.starts_with('/') && !value.starts_with("//")
+}
@@ -0,0 +1,41 @@
+use serde_json::Value;
+
+// marks links found in <script type="application/ld+json"> blocks
+pub const JSON_LD_SOURCE_TAG: &str = "json-ld";
+
+const URL_KEYS: [&str; 5] = ["url", "sameAs", "image", "logo", "contentUrl"];
+
+// malformed json yields no urls, as structured data is optional for a page
+pub fn extract_json_ld_urls(json: &str) -> Vec<String> {
+    let value: Value = match serde_json::from_str(json.trim()) {
+        Ok(value) => value,
+        Err(_) => return vec![],
+    };
+    let mut urls = vec![];
+    collect_urls(&value, &mut urls);
+    urls
+}
+
+fn collect_urls(value: &Value, urls: &mut Vec<String>) {
+    match value {
+        Value::Array(values) => values.iter().for_each(|value| collect_urls(value, urls)),
+        Value::Object(map) => map.iter().for_each(|(key, value)| {
+            if URL_KEYS.contains(&key.as_str()) {
+                collect_url_values(value, urls);
+            }
+            // nested objects, e.g. an ImageObject, bring their own url fields
+            if value.is_object() || value.is_array() {
+                collect_urls(value, urls);
+            }
+        }),
+        _ => {}
+    }
+}
+
+fn collect_url_values(value: &Value, urls: &mut Vec<String>) {
+    match value {
+        Value::String(url) if !url.trim().is_empty() => urls.push(url.trim().to_string()),
+        Value::Array(values) => values.iter().for_each(|value| collect_url_values(value, urls)),
+        _ => {}
+    }
+}
@@ -1,62 +1,370 @@
+pub mod body_preprocessor;
+pub mod heuristic;
+pub mod json_ld;
+pub mod microdata;
+pub mod sitemap;
+
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use chrono::Utc;
-use ego_tree::Tree;
+use ego_tree::{NodeRef, Tree};
+use html5ever::tendril::{StrTendril, TendrilSink};
 use scraper::{Html, Node};
+use scraper::node::Element;
 
 use linkresult::link_type_checker::LinkTypeChecker;
 use linkresult::uri_result::UriResult;
+use responses::body_preprocessor::BodyPreprocessor;
+use responses::alternate_variant::AlternateVariant;
 use responses::link::Link;
 
+use crate::body_preprocessor::preprocess_body;
+use crate::heuristic::{get_heuristic_urls, HEURISTIC_SOURCE_TAG};
+use crate::json_ld::{extract_json_ld_urls, JSON_LD_SOURCE_TAG};
+use crate::microdata::{get_itemprop_url, MICRODATA_SOURCE_TAG};
+
+pub const DEFAULT_MAX_LINK_TEXT_LEN: usize = 256;
+pub const DEFAULT_LINK_ATTRIBUTES: [&str; 3] = ["href", "src", "data-src"];
+
+const INVISIBLE_TEXT_ELEMENTS: [&str; 5] = ["head", "script", "style", "noscript", "template"];
+// with max_parse_ms, the document is fed to the parser in chunks of this size, the budget is checked in between
+const PARSE_CHUNK_BYTES: usize = 4096;
+
 pub trait DomParser: Sync + Send {
     fn get_links(&self, parent_protocol: &str, source_domain: &str, body: &String) -> Option<UriResult>;
 }
 
+#[derive(Clone)]
 pub struct DomParserService {
     link_type_checker: Arc<LinkTypeChecker>,
+    body_preprocessors: Vec<BodyPreprocessor>,
+    max_parse_ms: Option<u64>,
+    max_link_text_len: usize,
+    extract_json_ld_links: bool,
+    extract_microdata_links: bool,
+    heuristic_link_extraction: bool,
+    link_attributes: Vec<String>,
 }
 
 impl DomParser for DomParserService {
+    // the budget is checked while parsing and while extracting, so a pathological document stops consuming cpu once
+    // max_parse_ms is spent, instead of only being abandoned
     fn get_links(&self, parent_protocol: &str, source_domain: &str, body: &String) -> Option<UriResult> {
-        let dom = Html::parse_document(body);
-
-        let mut links = self.extract_links(&parent_protocol, &source_domain, dom.tree);
-        let parse_complete_time = Utc::now();
-        links.sort_by(|a, b| a.uri.cmp(&b.uri));
-
-        Some(UriResult {
-            links,
-            parse_complete_time,
-        })
+        let deadline = self.max_parse_ms.map(|max_parse_ms| Instant::now() + Duration::from_millis(max_parse_ms));
+        self.parse_links(parent_protocol, source_domain, body, deadline)
     }
 }
 
 impl DomParserService{
     pub fn new(link_type_checker: Arc<LinkTypeChecker>) -> DomParserService {
+        DomParserService::new_with_preprocessors(link_type_checker, vec![])
+    }
+
+    pub fn new_with_preprocessors(link_type_checker: Arc<LinkTypeChecker>, body_preprocessors: Vec<BodyPreprocessor>) -> DomParserService {
+        DomParserService::new_with_limits(link_type_checker, body_preprocessors, None, DEFAULT_MAX_LINK_TEXT_LEN)
+    }
+
+    pub fn new_with_limits(link_type_checker: Arc<LinkTypeChecker>, body_preprocessors: Vec<BodyPreprocessor>, max_parse_ms: Option<u64>, max_link_text_len: usize) -> DomParserService {
+        DomParserService::new_with_options(link_type_checker, body_preprocessors, max_parse_ms, max_link_text_len, false, false, false, vec![])
+    }
+
+    // additional link_attributes are scanned along with DEFAULT_LINK_ATTRIBUTES
+    pub fn new_with_options(link_type_checker: Arc<LinkTypeChecker>, body_preprocessors: Vec<BodyPreprocessor>, max_parse_ms: Option<u64>, max_link_text_len: usize, extract_json_ld_links: bool, extract_microdata_links: bool, heuristic_link_extraction: bool, link_attributes: Vec<String>) -> DomParserService {
+        let mut all_link_attributes: Vec<String> = DEFAULT_LINK_ATTRIBUTES.iter().map(|it| it.to_string()).collect();
+        link_attributes.into_iter()
+            .map(|link_attribute| link_attribute.trim().to_lowercase())
+            .filter(|link_attribute| !link_attribute.is_empty())
+            .for_each(|link_attribute| if !all_link_attributes.contains(&link_attribute) { all_link_attributes.push(link_attribute) });
         DomParserService {
             link_type_checker,
+            body_preprocessors,
+            max_parse_ms,
+            max_link_text_len,
+            extract_json_ld_links,
+            extract_microdata_links,
+            heuristic_link_extraction,
+            link_attributes: all_link_attributes,
         }
     }
+
+    fn parse_links(&self, parent_protocol: &str, source_domain: &str, body: &String, deadline: Option<Instant>) -> Option<UriResult> {
+        let dom = if self.body_preprocessors.is_empty() {
+            parse_document(body, deadline)?
+        } else {
+            parse_document(&preprocess_body(body, &self.body_preprocessors), deadline)?
+        };
+
+        let mut uri_result = self.extract_links(&parent_protocol, &source_domain, dom.tree, deadline)?;
+        uri_result.links.sort_by(|a, b| a.uri.cmp(&b.uri));
+
+        Some(uri_result)
+    }
+
     fn extract_links(
         &self,
         parent_protocol: &str,
         host: &str,
         node: Tree<Node>,
-    ) -> Vec<Link> {
-        let link_attribute_identifiers = vec!["href", "src", "data-src"];
-        node.values()
-            .filter_map(|current_node| {
-                let (_, link) = current_node
-                    .as_element()?
-                    .attrs()
-                    .find(|attribute| link_attribute_identifiers.contains(&attribute.0))?;
-                Some(Link {
+        deadline: Option<Instant>,
+    ) -> Option<UriResult> {
+        let mut links = vec![];
+        let mut images_total = 0;
+        let mut images_missing_alt = 0;
+        let mut images_missing_dimensions = 0;
+        let mut element_ids = HashSet::new();
+        let mut meta_robots = None;
+        let mut content_text_length = 0;
+        let mut language = None;
+        let mut meta_refresh = None;
+        let mut title = None;
+        let mut meta_description = None;
+        for current_node in node.nodes() {
+            if is_past(deadline) {
+                return None;
+            }
+
+            if let Some(text) = current_node.value().as_text() {
+                if is_visible_text(current_node) {
+                    content_text_length += text.chars().filter(|c| !c.is_whitespace()).count();
+                }
+                continue;
+            }
+
+            let element = match current_node.value().as_element() {
+                Some(element) => element,
+                None => continue,
+            };
+
+            // both id and name are valid targets of a #fragment
+            ["id", "name"].iter()
+                .filter_map(|attribute| element.attr(attribute))
+                .for_each(|element_id| { element_ids.insert(element_id.to_string()); });
+
+            if element.name() == "html" {
+                language = element.attr("lang")
+                    .map(|lang| lang.trim().to_string())
+                    .filter(|lang| !lang.is_empty());
+            }
+
+            // titles of inline svgs describe the graphic, not the page
+            if element.name() == "title" && title.is_none() && !is_inside_svg(current_node) {
+                title = collapse_whitespace(&current_node.descendants()
+                    .filter_map(|descendant| descendant.value().as_text())
+                    .map(|text| text.to_string())
+                    .collect::<String>());
+            }
+
+            if element.name() == "meta" && meta_description.is_none() && element.attr("name").map_or(false, |name| name.eq_ignore_ascii_case("description")) {
+                meta_description = element.attr("content").and_then(collapse_whitespace);
+            }
+
+            if element.name() == "meta" && element.attr("name").map_or(false, |name| name.eq_ignore_ascii_case("robots")) {
+                meta_robots = element.attr("content").map(|content| content.to_string());
+            }
+
+            if element.name() == "meta" && element.attr("http-equiv").map_or(false, |http_equiv| http_equiv.eq_ignore_ascii_case("refresh")) {
+                meta_refresh = element.attr("content").and_then(parse_meta_refresh_url);
+            }
+
+            if self.extract_json_ld_links && is_json_ld_script(element) {
+                let json = current_node.descendants()
+                    .filter_map(|descendant| descendant.value().as_text())
+                    .map(|text| text.to_string())
+                    .collect::<String>();
+                let dom_depth = get_dom_depth(current_node);
+                extract_json_ld_urls(&json).into_iter().for_each(|url| links.push(Link {
+                    scope: self.link_type_checker.get_uri_scope(&host, &url),
+                    protocol: self.link_type_checker.get_uri_protocol(&parent_protocol, &url),
+                    source_tag: Some(String::from(JSON_LD_SOURCE_TAG)),
+                    raw_href: Some(url.clone()),
+                    text: None,
+                    alternate: None,
+                    rel: None,
+                    extension: Link::get_extension(&url),
+                    fragment: Link::get_fragment(&url),
+                    dom_order: Some(links.len()),
+                    dom_depth: Some(dom_depth),
+                    uri: url,
+                }));
+            }
+
+            if element.name() == "img" {
+                images_total += 1;
+                if element.attr("alt").map_or(true, |alt| alt.trim().is_empty()) {
+                    images_missing_alt += 1;
+                }
+                if element.attr("width").is_none() || element.attr("height").is_none() {
+                    images_missing_dimensions += 1;
+                }
+            }
+
+            // the url of an itemprop is only reported once, tagged as microdata
+            if let Some(url) = get_itemprop_url(element).filter(|_| self.extract_microdata_links) {
+                links.push(Link {
+                    uri: url.to_string(),
+                    scope: self.link_type_checker.get_uri_scope(&host, url),
+                    protocol: self.link_type_checker.get_uri_protocol(&parent_protocol, url),
+                    source_tag: Some(String::from(MICRODATA_SOURCE_TAG)),
+                    raw_href: Some(url.to_string()),
+                    text: if element.name() == "a" { Some(self.get_link_text(current_node)) } else { None },
+                    alternate: None,
+                    rel: element.attr("rel").map(|rel| rel.to_string()),
+                    extension: Link::get_extension(url),
+                    fragment: Link::get_fragment(url),
+                    dom_order: Some(links.len()),
+                    dom_depth: Some(get_dom_depth(current_node)),
+                });
+                continue;
+            }
+
+            if let Some((_, link)) = element.attrs()
+                .find(|attribute| self.link_attributes.iter().any(|link_attribute| link_attribute == attribute.0)) {
+                links.push(Link {
                     uri: link.trim().to_string(),
                     scope: self.link_type_checker.get_uri_scope(&host, &link),
                     protocol: self.link_type_checker.get_uri_protocol(&parent_protocol, &link),
-                    source_tag: Some(format!("{:?}", current_node.as_element().unwrap())),
-                })
-            })
-            .collect()
+                    source_tag: Some(format!("{:?}", element)),
+                    raw_href: Some(link.to_string()),
+                    text: if element.name() == "a" { Some(self.get_link_text(current_node)) } else { None },
+                    alternate: get_alternate_variant(element),
+                    rel: element.attr("rel").map(|rel| rel.to_string()),
+                    extension: Link::get_extension(link),
+                    fragment: Link::get_fragment(link),
+                    dom_order: Some(links.len()),
+                    dom_depth: Some(get_dom_depth(current_node)),
+                });
+            }
+
+            if self.heuristic_link_extraction {
+                for url in get_heuristic_urls(element, &self.link_attributes) {
+                    links.push(Link {
+                        scope: self.link_type_checker.get_uri_scope(&host, &url),
+                        protocol: self.link_type_checker.get_uri_protocol(&parent_protocol, &url),
+                        source_tag: Some(String::from(HEURISTIC_SOURCE_TAG)),
+                        raw_href: Some(url.clone()),
+                        text: None,
+                        alternate: None,
+                        rel: None,
+                        extension: Link::get_extension(&url),
+                        fragment: Link::get_fragment(&url),
+                        dom_order: Some(links.len()),
+                        dom_depth: Some(get_dom_depth(current_node)),
+                        uri: url,
+                    });
+                }
+            }
+        }
+
+        Some(UriResult {
+            links,
+            parse_complete_time: Utc::now(),
+            images_total,
+            images_missing_alt,
+            images_missing_dimensions,
+            element_ids,
+            meta_robots,
+            content_text_length,
+            language,
+            title,
+            meta_description,
+            meta_refresh,
+        })
     }
+
+    // whitespace is collapsed and long texts are truncated to max_link_text_len characters, marked by an ellipsis
+    fn get_link_text(&self, node: NodeRef<Node>) -> String {
+        let text = node.descendants()
+            .filter_map(|descendant| descendant.value().as_text())
+            .flat_map(|text| text.split_whitespace())
+            .collect::<Vec<&str>>()
+            .join(" ");
+        if text.chars().count() <= self.max_link_text_len {
+            return text;
+        }
+        format!("{}…", text.chars().take(self.max_link_text_len).collect::<String>())
+    }
+}
+
+// html5ever takes quadratic time on deeply nested markup, so with a deadline the document is parsed chunk by chunk
+fn parse_document(body: &str, deadline: Option<Instant>) -> Option<Html> {
+    if deadline.is_none() {
+        return Some(Html::parse_document(body));
+    }
+
+    let mut parser = html5ever::parse_document(Html::new_document(), Default::default());
+    let mut remaining = body;
+    while !remaining.is_empty() {
+        if is_past(deadline) {
+            return None;
+        }
+        let mut chunk_len = remaining.len().min(PARSE_CHUNK_BYTES);
+        while !remaining.is_char_boundary(chunk_len) {
+            chunk_len += 1;
+        }
+        let (chunk, rest) = remaining.split_at(chunk_len);
+        parser.process(StrTendril::from_slice(chunk));
+        remaining = rest;
+    }
+    Some(parser.finish())
+}
+
+fn is_past(deadline: Option<Instant>) -> bool {
+    deadline.map_or(false, |deadline| Instant::now() > deadline)
+}
+
+fn get_alternate_variant(element: &Element) -> Option<AlternateVariant> {
+    if element.name() != "link" {
+        return None;
+    }
+    let rel = element.attr("rel")?;
+    let is_alternate = rel.split_whitespace().any(|it| it.eq_ignore_ascii_case("alternate") || it.eq_ignore_ascii_case("amphtml"));
+    if !is_alternate {
+        return None;
+    }
+    Some(AlternateVariant {
+        rel: rel.to_string(),
+        media: element.attr("media").map(|media| media.to_string()),
+        mime_type: element.attr("type").map(|mime_type| mime_type.to_string()),
+    })
+}
+
+// "5; url=/next" refreshes to /next, a refresh without url only reloads the page itself. Shared with the Refresh header
+pub fn parse_meta_refresh_url(content: &str) -> Option<String> {
+    let (_, target) = content.split_once([';', ','])?;
+    let target = target.trim();
+    let target = match target.get(..3) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("url") => target[3..].trim_start().strip_prefix('=').unwrap_or(target),
+        _ => target,
+    };
+    let target = target.trim().trim_matches(['\'', '"']).trim();
+    if target.is_empty() { None } else { Some(target.to_string()) }
+}
+
+fn is_json_ld_script(element: &Element) -> bool {
+    element.name() == "script" && element.attr("type").map_or(false, |script_type| script_type.trim().eq_ignore_ascii_case("application/ld+json"))
+}
+
+fn is_inside_svg(node: NodeRef<Node>) -> bool {
+    node.ancestors()
+        .filter_map(|ancestor| ancestor.value().as_element())
+        .any(|element| element.name() == "svg")
+}
+
+fn collapse_whitespace(text: &str) -> Option<String> {
+    let collapsed = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if collapsed.is_empty() { None } else { Some(collapsed) }
+}
+
+// the <html> element has a depth of 0
+fn get_dom_depth(node: NodeRef<Node>) -> usize {
+    node.ancestors().filter(|ancestor| ancestor.value().is_element()).count()
+}
+
+// text is visible, unless it's part of the head or of an element that isn't rendered
+fn is_visible_text(node: NodeRef<Node>) -> bool {
+    !node.ancestors()
+        .filter_map(|ancestor| ancestor.value().as_element())
+        .any(|element| INVISIBLE_TEXT_ELEMENTS.contains(&element.name()))
 }
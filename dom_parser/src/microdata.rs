@@ -0,0 +1,19 @@
+use scraper::node::Element;
+
+// marks links found in itemprop elements of schema.org microdata
+pub const MICRODATA_SOURCE_TAG: &str = "microdata";
+
+// <a>, <area> and <link> carry their url in href, <meta> in content. Other itemprops of <meta> are no urls, e.g. dates
+pub fn get_itemprop_url(element: &Element) -> Option<&str> {
+    element.attr("itemprop")?;
+    let url = match element.name() {
+        "a" | "area" | "link" => element.attr("href"),
+        "meta" => element.attr("content").filter(|content| is_url(content.trim())),
+        _ => None,
+    };
+    url.map(|url| url.trim()).filter(|url| !url.is_empty())
+}
+
+fn is_url(content: &str) -> bool {
+    ["http://", "https://", "//", "/"].iter().any(|prefix| content.starts_with(prefix))
+}
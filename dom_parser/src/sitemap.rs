@@ -0,0 +1,24 @@
+use scraper::{Html, Selector};
+
+// marks links found in a sitemap referenced by <link rel="sitemap">
+pub const SITEMAP_SOURCE_TAG: &str = "sitemap";
+
+// the <loc> of every <url>, as well as of every <sitemap> of a sitemap index. Malformed xml yields what could be read
+pub fn extract_sitemap_urls(xml: &str) -> Vec<String> {
+    let document = Html::parse_document(xml);
+    let selector = Selector::parse("loc").unwrap();
+    document.select(&selector)
+        .map(|loc| loc.text().collect::<String>().trim().to_string())
+        .filter(|url| !url.is_empty())
+        .collect()
+}
+
+// only the <loc> of every <sitemap> of a sitemap index, i.e. the sitemaps it refers to
+pub fn extract_sitemap_index_urls(xml: &str) -> Vec<String> {
+    let document = Html::parse_document(xml);
+    let selector = Selector::parse("sitemap > loc").unwrap();
+    document.select(&selector)
+        .map(|loc| loc.text().collect::<String>().trim().to_string())
+        .filter(|url| !url.is_empty())
+        .collect()
+}
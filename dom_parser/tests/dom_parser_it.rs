@@ -1,8 +1,14 @@
 use std::{fs::read_to_string, path::PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use dom_parser::{DomParser, DomParserService};
+use dom_parser::{DEFAULT_MAX_LINK_TEXT_LEN, DomParser, DomParserService};
+use dom_parser::heuristic::HEURISTIC_SOURCE_TAG;
+use dom_parser::json_ld::JSON_LD_SOURCE_TAG;
+use dom_parser::microdata::MICRODATA_SOURCE_TAG;
 use linkresult::link_type_checker::LinkTypeChecker;
+use responses::body_preprocessor::BodyPreprocessor;
+use responses::uri_scope::UriScope;
 
 #[test]
 fn extract_links_returns_correct_links_and_nodes() {
@@ -15,4 +21,271 @@ fn extract_links_returns_correct_links_and_nodes() {
     let result = instance.get_links("https", host, &html_file);
     assert_eq!(result.is_some(), true, "Should have a result");
     assert_eq!(result.unwrap().links.len(), 451 + 79, "Number of links should match"); // href: 451, (data-)?src: 79
-}
\ No newline at end of file
+}
+
+#[test]
+fn get_links_counts_images_with_and_without_alt() {
+    let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    d.push("tests/resources/images.html");
+    let html_file = read_to_string(&d).unwrap();
+
+    let host = "www.example.com";
+    let instance = DomParserService::new(Arc::new(LinkTypeChecker::new(host)));
+    let result = instance.get_links("https", host, &html_file).unwrap();
+    assert_eq!(result.images_total, 5, "Number of images should match");
+    assert_eq!(result.images_missing_alt, 3, "Number of images without alt text should match");
+    assert_eq!(result.images_missing_dimensions, 3, "Number of images without width/height should match");
+    assert_eq!(result.links.len(), 6, "Image sources should still be extracted as links");
+}
+
+#[test]
+fn get_links_applies_body_preprocessors_before_parsing() {
+    let body = String::from("<html><body>\
+        <a href=\"/visible\">visible</a>\
+        <!-- <a href=\"/hidden-in-comment\">hidden</a> -->\
+        <!--<a href=\"/hydration-leftover\">leftover</a>-->\
+        </body></html>");
+    let host = "www.example.com";
+
+    let without_preprocessor = DomParserService::new(Arc::new(LinkTypeChecker::new(host)));
+    let result = without_preprocessor.get_links("https", host, &body).unwrap();
+    assert_eq!(result.links.len(), 1, "Links inside comments should not be found without preprocessor");
+
+    let stripping = DomParserService::new_with_preprocessors(Arc::new(LinkTypeChecker::new(host)), vec![BodyPreprocessor::StripHtmlComments]);
+    let result = stripping.get_links("https", host, &body).unwrap();
+    assert_eq!(result.links.len(), 1, "Links inside comments should not be found when comments are stripped");
+
+    let uncommenting = DomParserService::new_with_preprocessors(Arc::new(LinkTypeChecker::new(host)), vec![BodyPreprocessor::UncommentHtmlComments]);
+    let result = uncommenting.get_links("https", host, &body).unwrap();
+    let uris: Vec<&str> = result.links.iter().map(|link| link.uri.as_str()).collect();
+    assert_eq!(uris, vec!["/hidden-in-comment", "/hydration-leftover", "/visible"], "Links inside comments should be found when comments are uncommented");
+}
+
+#[test]
+fn get_links_gives_up_on_documents_exceeding_max_parse_ms() {
+    // deeply nested, link-heavy markup takes the parser far longer than the budget
+    let body = format!("<html><body>{}</body></html>", "<div><a href=\"/nested\"><span>".repeat(200_000));
+    let host = "www.example.com";
+
+    let guarded = DomParserService::new_with_limits(Arc::new(LinkTypeChecker::new(host)), vec![], Some(10), DEFAULT_MAX_LINK_TEXT_LEN);
+    let start = Instant::now();
+    let result = guarded.get_links("https", host, &body);
+    let elapsed = start.elapsed();
+    assert_eq!(result.is_none(), true, "Should skip parsing once max_parse_ms is exceeded");
+    assert_eq!(elapsed < Duration::from_millis(1000), true, "Should return to the caller shortly after max_parse_ms, took {:?}", elapsed);
+
+    let small_body = String::from("<html><body><a href=\"/within-budget\">ok</a></body></html>");
+    let result = guarded.get_links("https", host, &small_body);
+    assert_eq!(result.is_some(), true, "Should parse documents within max_parse_ms");
+    assert_eq!(result.unwrap().links.len(), 1, "Should contain exactly one link");
+}
+
+#[test]
+fn get_links_with_max_parse_ms_finds_the_same_links_as_without() {
+    // documents spanning several chunks, with multi byte characters on the chunk boundaries
+    let body = format!("<html><body>{}</body></html>", "<p><a href=\"/ü\">ä link</a></p>".repeat(1_000));
+    let host = "www.example.com";
+
+    let unguarded = DomParserService::new(Arc::new(LinkTypeChecker::new(host)));
+    let guarded = DomParserService::new_with_limits(Arc::new(LinkTypeChecker::new(host)), vec![], Some(60_000), DEFAULT_MAX_LINK_TEXT_LEN);
+    let expected = unguarded.get_links("https", host, &body).unwrap();
+    let result = guarded.get_links("https", host, &body);
+
+    assert_eq!(result.is_some(), true, "Should parse documents within max_parse_ms");
+    assert_eq!(format!("{:?}", result.unwrap().links), format!("{:?}", expected.links), "Should find the same links when parsing chunk by chunk");
+}
+
+#[test]
+fn get_links_preserves_raw_href_as_found_in_markup() {
+    let body = String::from("<html><body><a href=\"  /Some%20Path?q=A&amp;b=c \n\">link</a></body></html>");
+    let host = "www.example.com";
+
+    let instance = DomParserService::new(Arc::new(LinkTypeChecker::new(host)));
+    let result = instance.get_links("https", host, &body).unwrap();
+    assert_eq!(result.links.len(), 1, "Should contain exactly one link");
+    assert_eq!(result.links[0].uri, "/Some%20Path?q=A&b=c", "uri should be trimmed");
+    assert_eq!(result.links[0].raw_href.as_ref().unwrap(), "  /Some%20Path?q=A&b=c \n", "raw_href should keep surrounding whitespace and encoding");
+}
+
+#[test]
+fn get_links_truncates_long_link_texts() {
+    let long_text = "lorem ipsum ".repeat(100);
+    let body = format!("<html><body><a href=\"/short\">  Short <b>text</b> </a><a href=\"/long\">{}</a></body></html>", long_text);
+    let host = "www.example.com";
+
+    let instance = DomParserService::new_with_limits(Arc::new(LinkTypeChecker::new(host)), vec![], None, 20);
+    let result = instance.get_links("https", host, &body).unwrap();
+    assert_eq!(result.links.len(), 2, "Should contain exactly two links");
+    assert_eq!(result.links[0].uri, "/long");
+    assert_eq!(result.links[0].text.as_ref().unwrap(), "lorem ipsum lorem ip…", "Long text should be truncated to max_link_text_len");
+    assert_eq!(result.links[1].uri, "/short");
+    assert_eq!(result.links[1].text.as_ref().unwrap(), "Short text", "Short text should be kept, with collapsed whitespace");
+}
+
+#[test]
+fn get_links_tags_alternate_variants() {
+    let body = String::from("<html><head>\
+        <link rel=\"amphtml\" href=\"/amp\">\
+        <link rel=\"alternate\" media=\"print\" href=\"/print\">\
+        <link rel=\"alternate\" hreflang=\"de\" href=\"/de\">\
+        <link rel=\"stylesheet\" href=\"/style.css\">\
+        </head></html>");
+    let host = "www.example.com";
+
+    let instance = DomParserService::new(Arc::new(LinkTypeChecker::new(host)));
+    let result = instance.get_links("https", host, &body).unwrap();
+    let find = |uri: &str| result.links.iter().find(|link| link.uri == uri).unwrap().clone();
+    assert_eq!(find("/amp").alternate.unwrap().is_print_or_amp(), true, "AMP alternate should be tagged");
+    assert_eq!(find("/print").alternate.unwrap().media, Some(String::from("print")), "Print alternate should carry its media");
+    assert_eq!(find("/de").alternate.unwrap().is_print_or_amp(), false, "Language alternates are not print or AMP variants");
+    assert_eq!(find("/style.css").alternate.is_none(), true, "Stylesheets are no alternates");
+}
+
+#[test]
+fn get_links_extracts_json_ld_urls_if_enabled() {
+    let body = String::from("<html><head><script type=\"application/ld+json\">\
+        {\"@context\": \"https://schema.org\", \"@type\": \"Organization\", \"url\": \"https://www.example.com/\",\
+        \"sameAs\": [\"https://twitter.com/example\", \"https://github.com/example\"]}\
+        </script><script type=\"application/ld+json\">{ malformed </script></head></html>");
+    let host = "www.example.com";
+
+    let instance = DomParserService::new_with_options(Arc::new(LinkTypeChecker::new(host)), vec![], None, DEFAULT_MAX_LINK_TEXT_LEN, true, false, false, vec![]);
+    let result = instance.get_links("https", host, &body).unwrap();
+    let uris: Vec<&str> = result.links.iter().map(|link| link.uri.as_str()).collect();
+    assert_eq!(uris, vec!["https://github.com/example", "https://twitter.com/example", "https://www.example.com/"], "Should extract the URLs of the valid block only");
+    assert_eq!(result.links.iter().all(|link| link.source_tag == Some(String::from(JSON_LD_SOURCE_TAG))), true, "Should mark JSON-LD links");
+
+    let instance_without_json_ld = DomParserService::new(Arc::new(LinkTypeChecker::new(host)));
+    let result_without_json_ld = instance_without_json_ld.get_links("https", host, &body).unwrap();
+    assert_eq!(result_without_json_ld.links.is_empty(), true, "Should not extract JSON-LD URLs unless enabled");
+}
+
+#[test]
+fn get_links_extracts_microdata_urls_if_enabled() {
+    let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    d.push("tests/resources/microdata.html");
+    let html_file = read_to_string(&d).unwrap();
+    let host = "www.example.com";
+
+    let instance = DomParserService::new_with_options(Arc::new(LinkTypeChecker::new(host)), vec![], None, DEFAULT_MAX_LINK_TEXT_LEN, false, true, false, vec![]);
+    let result = instance.get_links("https", host, &html_file).unwrap();
+    let microdata_uris: Vec<&str> = result.links.iter()
+        .filter(|link| link.source_tag == Some(String::from(MICRODATA_SOURCE_TAG)))
+        .map(|link| link.uri.as_str())
+        .collect();
+    assert_eq!(microdata_uris, vec!["/brands/example", "https://schema.org/InStock", "https://www.example.com/images/product.jpg", "https://www.example.com/product/offer"], "Should extract href and url content of itemprops, but no other content");
+    assert_eq!(result.links.len(), 5, "Should report each itemprop url once, next to the regular link");
+
+    let instance_without_microdata = DomParserService::new(Arc::new(LinkTypeChecker::new(host)));
+    let result_without_microdata = instance_without_microdata.get_links("https", host, &html_file).unwrap();
+    assert_eq!(result_without_microdata.links.iter().any(|link| link.source_tag == Some(String::from(MICRODATA_SOURCE_TAG))), false, "Should not tag microdata unless enabled");
+    assert_eq!(result_without_microdata.links.iter().any(|link| link.uri == "https://www.example.com/product/offer"), false, "Should not extract meta content unless enabled");
+}
+
+#[test]
+fn get_links_extracts_heuristic_urls_if_enabled() {
+    let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    d.push("tests/resources/heuristic_links.html");
+    let html_file = read_to_string(&d).unwrap();
+    let host = "www.example.com";
+
+    let instance = DomParserService::new_with_options(Arc::new(LinkTypeChecker::new(host)), vec![], None, DEFAULT_MAX_LINK_TEXT_LEN, false, false, true, vec![]);
+    let result = instance.get_links("https", host, &html_file).unwrap();
+    let mut heuristic_uris: Vec<&str> = result.links.iter()
+        .filter(|link| link.source_tag == Some(String::from(HEURISTIC_SOURCE_TAG)))
+        .map(|link| link.uri.as_str())
+        .collect();
+    heuristic_uris.sort();
+    assert_eq!(heuristic_uris, vec!["/blog/latest", "/pricing", "https://www.example.com/contact"], "Should extract urls of event handlers and data attributes, but no other values");
+    assert_eq!(result.links.len(), 4, "Should report the regular link next to the heuristic ones");
+
+    let instance_without_heuristic = DomParserService::new(Arc::new(LinkTypeChecker::new(host)));
+    let result_without_heuristic = instance_without_heuristic.get_links("https", host, &html_file).unwrap();
+    assert_eq!(result_without_heuristic.links.iter().map(|link| link.uri.as_str()).collect::<Vec<&str>>(), vec!["/about"], "Should not extract heuristic urls unless enabled");
+}
+
+#[test]
+fn get_links_captures_in_page_anchors_with_their_fragments() {
+    let body = String::from("<html><body>\
+        <a href=\"#installation\">Installation</a>\
+        <a href=\"#usage\">Usage</a>\
+        <a href=\"/docs#usage\">Docs</a>\
+        </body></html>");
+    let host = "www.example.com";
+
+    let instance = DomParserService::new(Arc::new(LinkTypeChecker::new(host)));
+    let result = instance.get_links("https", host, &body).unwrap();
+    let fragments: Vec<Option<String>> = result.links.iter()
+        .filter(|link| link.scope == Some(UriScope::Anchor))
+        .map(|link| link.fragment.clone())
+        .collect();
+    assert_eq!(fragments, vec![Some(String::from("installation")), Some(String::from("usage"))], "Should capture in-page anchors with their fragments");
+    let docs_link = result.links.iter().find(|link| link.uri == "/docs#usage").unwrap();
+    assert_eq!(docs_link.scope, Some(UriScope::SameDomain), "Links to other pages are no in-page anchors");
+}
+
+#[test]
+fn get_links_extracts_the_meta_refresh_target() {
+    let host = "www.example.com";
+    let instance = DomParserService::new(Arc::new(LinkTypeChecker::new(host)));
+    let get_meta_refresh = |content: &str| {
+        let body = format!("<html><head><meta http-equiv=\"Refresh\" content=\"{}\"></head><body></body></html>", content);
+        instance.get_links("https", host, &body).unwrap().meta_refresh
+    };
+
+    assert_eq!(get_meta_refresh("0; url=/not-found"), Some(String::from("/not-found")), "Should extract the refresh target");
+    assert_eq!(get_meta_refresh("5;URL='https://example.org/'"), Some(String::from("https://example.org/")), "Should extract quoted targets regardless of case");
+    assert_eq!(get_meta_refresh("30"), None, "Should ignore refreshes of the page itself");
+}
+
+#[test]
+fn get_links_extracts_title_and_meta_description() {
+    let body = String::from("<html><head><title>\n  Shop |\n Example </title>        <meta name=\"Description\" content=\" Everything  you need \"></head>        <body><svg><title>Cart icon</title></svg></body></html>");
+    let host = "www.example.com";
+
+    let instance = DomParserService::new(Arc::new(LinkTypeChecker::new(host)));
+    let result = instance.get_links("https", host, &body).unwrap();
+
+    assert_eq!(result.title, Some(String::from("Shop | Example")), "Should collapse the whitespace of the title");
+    assert_eq!(result.meta_description, Some(String::from("Everything you need")), "Should match the meta name regardless of case");
+}
+
+#[test]
+fn get_links_scans_configured_link_attributes_along_with_the_defaults() {
+    let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    d.push("tests/resources/lazy_loading.html");
+    let html_file = read_to_string(&d).unwrap();
+    let host = "www.example.com";
+    let get_uris = |link_attributes: Vec<String>| {
+        let instance = DomParserService::new_with_options(Arc::new(LinkTypeChecker::new(host)), vec![], None, DEFAULT_MAX_LINK_TEXT_LEN, false, false, false, link_attributes);
+        instance.get_links("https", host, &html_file).unwrap().links.into_iter().map(|link| link.uri).collect::<Vec<String>>()
+    };
+
+    let default_uris = get_uris(vec![]);
+    let configured_uris = get_uris(vec![String::from("data-lazy-src")]);
+
+    assert_eq!(default_uris, vec!["/articles", "/images/teaser.jpg"], "Should only scan the default attributes");
+    assert_eq!(configured_uris, vec!["/articles", "/images/hero.jpg", "/images/teaser.jpg"], "Should scan configured attributes along with the defaults");
+}
+
+#[test]
+fn get_links_records_the_dom_position_of_links() {
+    let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    d.push("tests/resources/navigation.html");
+    let html_file = read_to_string(&d).unwrap();
+    let host = "www.example.com";
+
+    let instance = DomParserService::new(Arc::new(LinkTypeChecker::new(host)));
+    let mut links = instance.get_links("https", host, &html_file).unwrap().links;
+
+    let uris: Vec<&str> = links.iter().map(|link| link.uri.as_str()).collect();
+    assert_eq!(uris, vec!["/about", "/imprint", "/middle", "/zoo"], "Links should still be sorted by uri");
+    links.sort_by_key(|link| link.dom_order);
+    let positions: Vec<(&str, Option<usize>, Option<usize>)> = links.iter().map(|link| (link.uri.as_str(), link.dom_order, link.dom_depth)).collect();
+    assert_eq!(positions, vec![
+        ("/zoo", Some(0), Some(5)),
+        ("/about", Some(1), Some(5)),
+        ("/middle", Some(2), Some(3)),
+        ("/imprint", Some(3), Some(3)),
+    ], "dom_order should reflect the document order");
+}
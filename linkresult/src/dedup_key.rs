@@ -0,0 +1,182 @@
+use responses::dedup_key_rules::DedupKeyRules;
+use responses::url_fingerprint::UrlFingerprint;
+use responses::url_normalization::UrlNormalization;
+
+// the key, under which a URL is known or tasked. every check and insert of known links has to use it, so
+// that two URLs canonicalizing identically are deduped, regardless of where they were added.
+// besides the configured rules, scheme and host are lowercased and default ports are removed
+pub fn to_dedup_key(url: &str, rules: &DedupKeyRules) -> String {
+    to_dedup_key_with_normalizations(url, rules).0
+}
+
+// all query variants of a path share the dedup key up to its query
+pub fn to_known_path(dedup_key: &str) -> &str {
+    dedup_key.split('?').next().unwrap_or(dedup_key)
+}
+
+// the dedup key along with the normalizations that changed the url, in the order they were applied
+pub fn to_dedup_key_with_normalizations(url: &str, rules: &DedupKeyRules) -> (String, Vec<UrlNormalization>) {
+    let mut normalizations = vec![];
+    let (url_without_fragment, fragment) = match url.split_once('#') {
+        Some((front, fragment)) => (front, Some(fragment)),
+        None => (url, None),
+    };
+    let (url_without_query, query) = match url_without_fragment.split_once('?') {
+        Some((front, query)) => (front, Some(query)),
+        None => (url_without_fragment, None),
+    };
+    let (origin, original_path) = split_origin(url_without_query);
+
+    let mut dedup_key = strip_default_port(&origin.to_lowercase());
+    if dedup_key != origin {
+        normalizations.push(UrlNormalization::OriginCanonicalized);
+    }
+    let path = if rules.case_insensitive_path { original_path.to_lowercase() } else { original_path.to_string() };
+    if path != original_path {
+        normalizations.push(UrlNormalization::PathLowercased);
+    }
+    if rules.strip_trailing_slash {
+        let stripped_path = path.trim_end_matches('/');
+        if stripped_path != path {
+            normalizations.push(UrlNormalization::TrailingSlashStripped);
+        }
+        dedup_key.push_str(stripped_path);
+    } else {
+        dedup_key.push_str(&path);
+    }
+    match query {
+        Some(_) if rules.ignore_query => normalizations.push(UrlNormalization::QueryIgnored),
+        Some(query) => {
+            let (query, mut query_normalizations) = to_query_fingerprint(query, &rules.fingerprint);
+            normalizations.append(&mut query_normalizations);
+            if let Some(query) = query {
+                dedup_key.push('?');
+                dedup_key.push_str(&query);
+            }
+        }
+        None => {}
+    }
+    match fragment {
+        Some(_) if rules.strip_fragment => normalizations.push(UrlNormalization::FragmentStripped),
+        Some(fragment) => {
+            dedup_key.push('#');
+            dedup_key.push_str(fragment);
+        }
+        None => {}
+    }
+    (dedup_key, normalizations)
+}
+
+// None, if nothing of the query is left
+fn to_query_fingerprint(query: &str, fingerprint: &UrlFingerprint) -> (Option<String>, Vec<UrlNormalization>) {
+    match fingerprint {
+        UrlFingerprint::Exact => (Some(query.to_string()), vec![]),
+        UrlFingerprint::PathOnly => (None, vec![UrlNormalization::QueryIgnored]),
+        UrlFingerprint::PathPlusSortedQueryWhitelist(whitelist) => {
+            let all_params: Vec<&str> = query.split('&').collect();
+            let mut params: Vec<&str> = all_params.iter()
+                .copied()
+                .filter(|param| whitelist.iter().any(|name| name == param.split('=').next().unwrap_or("")))
+                .collect();
+            let mut normalizations = vec![];
+            if params.len() < all_params.len() {
+                normalizations.push(UrlNormalization::QueryParamsDropped);
+            }
+            if !params.windows(2).all(|pair| pair[0] <= pair[1]) {
+                normalizations.push(UrlNormalization::QuerySorted);
+            }
+            params.sort();
+            (if params.is_empty() { None } else { Some(params.join("&")) }, normalizations)
+        }
+    }
+}
+
+// https://example.com/page -> (https://example.com, /page). relative URLs have no origin
+fn split_origin(url: &str) -> (&str, &str) {
+    let authority_start = match url.find("://") {
+        Some(scheme_end) => scheme_end + 3,
+        None => return ("", url),
+    };
+    match url[authority_start..].find('/') {
+        Some(path_start) => url.split_at(authority_start + path_start),
+        None => (url, ""),
+    }
+}
+
+fn strip_default_port(origin: &str) -> String {
+    let default_port_suffix = if origin.starts_with("http://") {
+        ":80"
+    } else if origin.starts_with("https://") {
+        ":443"
+    } else {
+        return origin.to_string();
+    };
+    origin.strip_suffix(default_port_suffix).unwrap_or(origin).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scheme_host_and_default_port_are_always_canonicalized() {
+        let rules = DedupKeyRules::default();
+
+        assert_eq!(to_dedup_key("HTTPS://Example.COM:443/Page", &rules), "https://example.com/Page");
+        assert_eq!(to_dedup_key("http://example.com:80", &rules), "http://example.com");
+        assert_eq!(to_dedup_key("https://example.com:8443/page", &rules), "https://example.com:8443/page");
+    }
+
+    #[test]
+    fn default_rules_only_strip_the_fragment() {
+        let rules = DedupKeyRules::default();
+
+        assert_eq!(to_dedup_key("https://example.com/page/?a=1#top", &rules), "https://example.com/page/?a=1");
+    }
+
+    #[test]
+    fn configured_rules_are_applied() {
+        let rules = DedupKeyRules { strip_fragment: false, ignore_query: true, strip_trailing_slash: true, case_insensitive_path: true, fingerprint: UrlFingerprint::Exact };
+
+        assert_eq!(to_dedup_key("https://example.com/Page/?a=1#top", &rules), "https://example.com/page#top");
+        assert_eq!(to_dedup_key("https://example.com/", &rules), to_dedup_key("https://example.com", &rules));
+    }
+
+    #[test]
+    fn exact_fingerprint_keeps_query_variants_apart() {
+        let rules = DedupKeyRules::default();
+
+        assert_ne!(to_dedup_key("https://example.com/list?b=2&a=1", &rules), to_dedup_key("https://example.com/list?a=1&b=2", &rules));
+        assert_ne!(to_dedup_key("https://example.com/list?utm_source=mail", &rules), to_dedup_key("https://example.com/list", &rules));
+    }
+
+    #[test]
+    fn path_only_fingerprint_collapses_all_query_variants() {
+        let rules = DedupKeyRules { fingerprint: UrlFingerprint::PathOnly, ..DedupKeyRules::default() };
+
+        assert_eq!(to_dedup_key("https://example.com/list?page=2", &rules), "https://example.com/list");
+        assert_eq!(to_dedup_key("https://example.com/list?utm_source=mail", &rules), to_dedup_key("https://example.com/list", &rules));
+        assert_ne!(to_dedup_key("https://example.com/list?page=2", &rules), to_dedup_key("https://example.com/other?page=2", &rules));
+    }
+
+    #[test]
+    fn normalizations_are_recorded_in_the_order_they_are_applied() {
+        let rules = DedupKeyRules { fingerprint: UrlFingerprint::PathPlusSortedQueryWhitelist(vec![String::from("a"), String::from("b")]), ..DedupKeyRules::default() };
+
+        let (dedup_key, normalizations) = to_dedup_key_with_normalizations("https://example.com/list?b=2&a=1#top", &rules);
+
+        assert_eq!(dedup_key, "https://example.com/list?a=1&b=2");
+        assert_eq!(normalizations, vec![UrlNormalization::QuerySorted, UrlNormalization::FragmentStripped]);
+        assert_eq!(to_dedup_key_with_normalizations("https://example.com/list?a=1", &rules).1, vec![], "Should record nothing for canonical urls");
+    }
+
+    #[test]
+    fn whitelist_fingerprint_only_keeps_listed_params_sorted() {
+        let rules = DedupKeyRules { fingerprint: UrlFingerprint::PathPlusSortedQueryWhitelist(vec![String::from("id"), String::from("page")]), ..DedupKeyRules::default() };
+
+        assert_eq!(to_dedup_key("https://example.com/list?page=2&utm_source=mail&id=7", &rules), "https://example.com/list?id=7&page=2");
+        assert_eq!(to_dedup_key("https://example.com/list?id=7&page=2", &rules), to_dedup_key("https://example.com/list?gclid=abc&page=2&id=7", &rules));
+        assert_eq!(to_dedup_key("https://example.com/list?utm_source=mail", &rules), "https://example.com/list", "Should drop queries without listed params");
+        assert_ne!(to_dedup_key("https://example.com/list?page=2", &rules), to_dedup_key("https://example.com/list?page=3", &rules));
+    }
+}
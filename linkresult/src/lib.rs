@@ -1,3 +1,5 @@
+pub mod dedup_key;
 pub mod link_type_checker;
+pub mod registrable_domain;
 pub mod uri_result;
 pub mod uri_service;
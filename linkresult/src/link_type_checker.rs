@@ -4,9 +4,12 @@ use std::sync::Arc;
 use fancy_regex::escape;
 use fancy_regex::Regex;
 
+use responses::domain_scope::DomainScope;
 use responses::uri_protocol::UriProtocol;
 use responses::uri_scope::UriScope;
 
+use crate::registrable_domain::get_registrable_domain;
+
 #[derive(Debug, Eq, PartialEq, Hash)]
 enum RegexType {
     Anchor,
@@ -25,15 +28,36 @@ pub struct LinkTypeChecker {
 
 impl LinkTypeChecker {
     pub fn new(host: &str) -> LinkTypeChecker {
-        let domain_regex = escape(host).replace("-", "\"");
+        LinkTypeChecker::new_with_domain_scope(host, DomainScope::Host)
+    }
+
+    pub fn new_with_domain_scope(host: &str, domain_scope: DomainScope) -> LinkTypeChecker {
+        let host_regex = to_domain_regex(host);
+        let registrable_domain = match domain_scope {
+            DomainScope::Host => None,
+            DomainScope::RegistrableDomain => get_registrable_domain(host).filter(|registrable_domain| registrable_domain != &host.to_lowercase()),
+        };
         let mut hash_map = HashMap::with_capacity(8);
         hash_map.insert(RegexType::Anchor, Regex::new("^/?#").unwrap());
-        hash_map.insert(RegexType::DifferentSubdomain, Regex::new(&format!("^//.+\\.(?:{}).*$", domain_regex)).unwrap());
-        hash_map.insert(RegexType::DifferentSubdomainWithProtocol, Regex::new(&format!("^https?://[^/=?]*\\.{}.*$", domain_regex).to_owned()).unwrap());
-        hash_map.insert(RegexType::EXTERNAL, Regex::new(&format!("^//(?!{}).*$", domain_regex)).unwrap());
+        match registrable_domain {
+            None => {
+                hash_map.insert(RegexType::DifferentSubdomain, Regex::new(&format!("^//.+\\.(?:{}).*$", host_regex)).unwrap());
+                hash_map.insert(RegexType::DifferentSubdomainWithProtocol, Regex::new(&format!("^https?://[^/=?]*\\.{}.*$", host_regex).to_owned()).unwrap());
+                hash_map.insert(RegexType::EXTERNAL, Regex::new(&format!("^//(?!{}).*$", host_regex)).unwrap());
+                hash_map.insert(RegexType::SameDomainWithProtocol, Regex::new(&format!("^https?://{}", host_regex).to_owned()).unwrap());
+            }
+            // the seed host and the apex are the same domain, siblings of the seed are different subdomains
+            Some(registrable_domain) => {
+                let domain_regex = to_domain_regex(&registrable_domain);
+                let not_the_host = format!("(?!(?:{})(?:[/:?#]|$))", host_regex);
+                hash_map.insert(RegexType::DifferentSubdomain, Regex::new(&format!("^//{}.+\\.(?:{}).*$", not_the_host, domain_regex)).unwrap());
+                hash_map.insert(RegexType::DifferentSubdomainWithProtocol, Regex::new(&format!("^https?://{}[^/=?]*\\.{}.*$", not_the_host, domain_regex)).unwrap());
+                hash_map.insert(RegexType::EXTERNAL, Regex::new(&format!("^//(?!(?:{}|{})(?:[/:?#]|$)).*$", host_regex, domain_regex)).unwrap());
+                hash_map.insert(RegexType::SameDomainWithProtocol, Regex::new(&format!("^https?://(?:{}|{})(?:[/:?#]|$)", host_regex, domain_regex)).unwrap());
+            }
+        }
         hash_map.insert(RegexType::ExternalWithProtocol, Regex::new("^https?://.*").unwrap());
         hash_map.insert(RegexType::SameDomain, Regex::new("^(?![a-zA-Z]+://)(?:/?(?:[^#].+))$").unwrap());
-        hash_map.insert(RegexType::SameDomainWithProtocol, Regex::new(&format!("^https?://{}", domain_regex).to_owned()).unwrap());
         hash_map.insert(RegexType::UnknownPrefix, Regex::new("^(?!https?)[a-zA-Z0-9]+:.*").unwrap());
 
         LinkTypeChecker {
@@ -69,6 +93,13 @@ impl LinkTypeChecker {
         }
     }
 
+    // root, regardless of query and fragment, e.g. /?utm=x or https://example.com#top.
+    // a bare ?utm=x is relative to its page, so it isn't root
+    pub fn is_root(&self, host: &str, uri: &str) -> bool {
+        let without_query = uri.split(['?', '#']).next().unwrap_or_default();
+        self.get_uri_scope(host, without_query) == Some(UriScope::Root)
+    }
+
     pub fn get_uri_protocol(&self, parent_protocol: &str, uri: &str) -> Option<UriProtocol> {
         match uri {
             uri if uri.starts_with("https") => Some(UriProtocol::HTTPS),
@@ -91,6 +122,10 @@ impl LinkTypeChecker {
     }
 }
 
+fn to_domain_regex(domain: &str) -> String {
+    escape(domain).replace("-", "\"")
+}
+
 #[cfg(test)]
 mod tests {
     use rand::distributions::Alphanumeric;
@@ -162,6 +197,46 @@ mod tests {
             )
     }
 
+    #[test]
+    fn subdomain_seed_classifies_relative_to_the_registrable_domain() {
+        let input_to_output = vec![
+            ("https://blog.example.com/", Some(UriScope::Root)),
+            ("https://blog.example.com/post", Some(UriScope::SameDomain)),
+            ("//blog.example.com/post", Some(UriScope::SameDomain)),
+            ("/post", Some(UriScope::SameDomain)),
+            ("https://example.com/", Some(UriScope::SameDomain)),
+            ("//example.com/about", Some(UriScope::SameDomain)),
+            ("https://shop.example.com/cart", Some(UriScope::DifferentSubDomain)),
+            ("//shop.example.com/cart", Some(UriScope::DifferentSubDomain)),
+            ("https://en.blog.example.com/", Some(UriScope::DifferentSubDomain)),
+            ("https://example.community/", Some(UriScope::External)),
+            ("//example.org/", Some(UriScope::External)),
+            ("https://www.a-b-c.com", Some(UriScope::External)),
+        ];
+
+        let instance = LinkTypeChecker::new_with_domain_scope("blog.example.com", DomainScope::RegistrableDomain);
+
+        input_to_output
+            .iter()
+            .map(|it| (&it.0, &it.1, instance.get_uri_scope("blog.example.com", it.0)))
+            .for_each(|it|
+                assert_eq!(
+                    it.1, &it.2,
+                    "{} ::> expected: {:?} got: {:?}",
+                    it.0, it.1, it.2
+                )
+            )
+    }
+
+    #[test]
+    fn subdomain_seed_classifies_relative_to_the_host_by_default() {
+        let instance = LinkTypeChecker::new("blog.example.com");
+
+        assert_eq!(instance.get_uri_scope("blog.example.com", "https://example.com/"), Some(UriScope::External));
+        assert_eq!(instance.get_uri_scope("blog.example.com", "https://shop.example.com/"), Some(UriScope::External));
+        assert_eq!(instance.get_uri_scope("blog.example.com", "https://en.blog.example.com/"), Some(UriScope::DifferentSubDomain));
+    }
+
     #[test]
     fn get_uri_protocol_runs_with_different_source_domains() {
         let input_to_output = vec![
@@ -0,0 +1,25 @@
+use std::net::IpAddr;
+
+// eTLD+1 of the host, e.g. example.co.uk for blog.example.co.uk. IP addresses and public suffixes themselves have none.
+// The public suffix list is bundled with the psl crate, updating the crate refreshes it
+pub fn get_registrable_domain(host: &str) -> Option<String> {
+    let host = host.trim_end_matches('.').to_lowercase();
+    if host.parse::<IpAddr>().is_ok() {
+        return None;
+    }
+    psl::domain_str(&host).map(|domain| domain.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registrable_domain_respects_multi_label_suffixes() {
+        assert_eq!(get_registrable_domain("blog.example.com"), Some(String::from("example.com")));
+        assert_eq!(get_registrable_domain("shop.example.co.uk"), Some(String::from("example.co.uk")));
+        assert_eq!(get_registrable_domain("Example.COM."), Some(String::from("example.com")));
+        assert_eq!(get_registrable_domain("co.uk"), None, "Public suffixes have no registrable domain");
+        assert_eq!(get_registrable_domain("127.0.0.1"), None, "IP addresses have no registrable domain");
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+
 use chrono::{DateTime, Utc};
 
 use responses::link::Link;
@@ -6,4 +8,16 @@ use responses::link::Link;
 pub struct UriResult {
     pub parse_complete_time: DateTime<Utc>,
     pub links: Vec<Link>,
+    pub images_total: usize,
+    pub images_missing_alt: usize,
+    pub images_missing_dimensions: usize,
+    pub element_ids: HashSet<String>,
+    pub meta_robots: Option<String>,
+    pub content_text_length: usize,
+    pub language: Option<String>,
+    // whitespace collapsed, None if missing or empty
+    pub title: Option<String>,
+    pub meta_description: Option<String>,
+    // target of a <meta http-equiv="refresh">, as found in the markup
+    pub meta_refresh: Option<String>,
 }
@@ -7,19 +7,49 @@ use tracing::trace;
 
 use crate::link_type_checker::LinkTypeChecker;
 
+pub const DEFAULT_SESSION_QUERY_PARAMS: [&str; 4] = ["jsessionid", "phpsessid", "aspsessionid", "sessionid"];
+
 pub struct UriService {
     link_type_checker: Arc<LinkTypeChecker>,
+    // lowercase names of session parameters, stripped from matrix parameters and query strings
+    session_query_params: Vec<String>,
+    // https://example.com:443/ and https://example.com/ share one crawl key, if enabled
+    strip_default_ports: bool,
+    // /, /?utm=x and /#top share the crawl key of the root, if enabled
+    collapse_root_query: bool,
 }
 
 unsafe impl Send for UriService {}
 
 impl UriService {
     pub fn new(link_type_checker: Arc<LinkTypeChecker>) -> UriService {
-        UriService { link_type_checker }
+        UriService::new_with_session_query_params(link_type_checker, vec![])
+    }
+
+    pub fn new_with_session_query_params(link_type_checker: Arc<LinkTypeChecker>, session_query_params: Vec<String>) -> UriService {
+        UriService::new_with_options(link_type_checker, session_query_params, true, false)
+    }
+
+    pub fn new_with_options(link_type_checker: Arc<LinkTypeChecker>, session_query_params: Vec<String>, strip_default_ports: bool, collapse_root_query: bool) -> UriService {
+        UriService {
+            link_type_checker,
+            session_query_params: session_query_params.iter().map(|it| it.to_lowercase()).collect(),
+            strip_default_ports,
+            collapse_root_query,
+        }
     }
 
     pub fn form_full_url(&self, protocol: &str, uri: &str, host: &str, parent_uri: &Option<String>) -> Uri {
+        let full_url = self.form_full_url_internal(protocol, uri, host, parent_uri);
+        if self.strip_default_ports {
+            return strip_default_port(full_url);
+        }
+        full_url
+    }
+
+    fn form_full_url_internal(&self, protocol: &str, uri: &str, host: &str, parent_uri: &Option<String>) -> Uri {
         trace!("form_full_url {}, {}, {}, {:?}", protocol, uri, host, parent_uri);
+        let uri = &strip_session_ids(uri, &self.session_query_params);
         let pre_cleaned_uri = pre_clean_uri(host, uri);
         let protocol_internal = if pre_cleaned_uri.starts_with("https://") { "https" } else if pre_cleaned_uri.starts_with("http://") { "http" } else { protocol };
         trace!("pre_cleaned uri {}", pre_cleaned_uri);
@@ -35,6 +65,9 @@ impl UriService {
             to_uri(&create_uri_string(protocol_internal, host, &adjusted_uri))
         };
 
+        if self.collapse_root_query && self.link_type_checker.is_root(host, &pre_cleaned_uri) {
+            return to_uri(&create_uri_string(protocol_internal, host, "/"));
+        }
         if let Some(scope) = self.link_type_checker.get_uri_scope(host, &pre_cleaned_uri) {
             return match scope {
                 UriScope::Root => to_uri(&create_uri_string(protocol_internal, host, "/")),
@@ -54,6 +87,142 @@ impl UriService {
     }
 }
 
+// resolves a reference, e.g. a relative Location header, against an absolute base URI as described in RFC 3986, section 5.2
+pub fn resolve_reference(base: &str, reference: &str) -> String {
+    let reference = reference.trim();
+    let has_scheme = reference.split_once(':')
+        .map_or(false, |(scheme, _)| !scheme.is_empty() && !scheme.contains('/') && !scheme.contains('?') && !scheme.contains('#'));
+    if has_scheme {
+        return reference.to_string();
+    }
+    let base_uri = match base.parse::<Uri>() {
+        Ok(base_uri) if base_uri.scheme_str().is_some() && base_uri.authority().is_some() => base_uri,
+        _ => return reference.to_string(),
+    };
+    let scheme = base_uri.scheme_str().unwrap();
+    if reference.starts_with("//") {
+        return format!("{}:{}", scheme, reference);
+    }
+
+    let origin = format!("{}://{}", scheme, base_uri.authority().unwrap());
+    let (reference_without_fragment, fragment) = match reference.split_once('#') {
+        Some((front, fragment)) => (front, Some(fragment)),
+        None => (reference, None),
+    };
+    let (reference_path, reference_query) = match reference_without_fragment.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (reference_without_fragment, None),
+    };
+
+    let (path, query) = if reference_path.is_empty() {
+        (base_uri.path().to_string(), reference_query.or(base_uri.query()))
+    } else if reference_path.starts_with('/') {
+        (remove_dot_segments(reference_path), reference_query)
+    } else {
+        let base_path = base_uri.path();
+        let base_directory = &base_path[..base_path.rfind('/').map_or(0, |index| index + 1)];
+        (remove_dot_segments(&format!("{}{}", base_directory, reference_path)), reference_query)
+    };
+
+    let mut resolved = format!("{}{}", origin, if path.is_empty() { "/" } else { &path });
+    if let Some(query) = query {
+        resolved = format!("{}?{}", resolved, query);
+    }
+    if let Some(fragment) = fragment {
+        resolved = format!("{}#{}", resolved, fragment);
+    }
+    resolved
+}
+
+// RFC 3986, section 5.2.4
+fn remove_dot_segments(path: &str) -> String {
+    let mut output: Vec<&str> = vec![];
+    let segments: Vec<&str> = path.split('/').collect();
+    for (index, segment) in segments.iter().enumerate() {
+        let is_last = index == segments.len() - 1;
+        match *segment {
+            "." => if is_last { output.push("") },
+            ".." => {
+                if output.len() > 1 {
+                    output.pop();
+                }
+                if is_last { output.push("") }
+            }
+            _ => output.push(segment),
+        }
+    }
+    let result = output.join("/");
+    if path.starts_with('/') && !result.starts_with('/') { format!("/{}", result) } else { result }
+}
+
+// removes :80 from http and :443 from https URLs, other ports are kept
+fn strip_default_port(uri: Uri) -> Uri {
+    let default_port = match uri.scheme_str() {
+        Some("http") => 80,
+        Some("https") => 443,
+        _ => return uri,
+    };
+    if uri.port_u16() != Some(default_port) {
+        return uri;
+    }
+
+    let mut parts = uri.into_parts();
+    let authority = parts.authority.as_ref().unwrap().as_str();
+    let authority_without_port = authority[..authority.rfind(':').unwrap()].to_string();
+    parts.authority = Some(authority_without_port.parse().unwrap());
+    Uri::from_parts(parts).unwrap()
+}
+
+fn strip_session_ids(uri: &str, session_query_params: &[String]) -> String {
+    if session_query_params.is_empty() {
+        return uri.to_string();
+    }
+    let is_session_param = |param: &str| {
+        let key = param.split('=').next().unwrap_or("").to_lowercase();
+        session_query_params.contains(&key)
+    };
+
+    let (uri_without_fragment, fragment) = match uri.split_once('#') {
+        Some((front, fragment)) => (front, Some(fragment)),
+        None => (uri, None),
+    };
+    let (path, query) = match uri_without_fragment.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (uri_without_fragment, None),
+    };
+
+    // matrix parameters, e.g. /page;jsessionid=ABC
+    let mut result = path.split('/')
+        .map(|segment| {
+            let mut parts = segment.split(';');
+            let mut cleaned_segment = String::from(parts.next().unwrap_or(""));
+            parts.filter(|param| !is_session_param(param))
+                .for_each(|param| {
+                    cleaned_segment.push(';');
+                    cleaned_segment.push_str(param);
+                });
+            cleaned_segment
+        })
+        .collect::<Vec<String>>()
+        .join("/");
+
+    if let Some(query) = query {
+        let cleaned_query = query.split('&')
+            .filter(|param| !is_session_param(param))
+            .collect::<Vec<&str>>()
+            .join("&");
+        if !cleaned_query.is_empty() {
+            result = format!("{}?{}", result, cleaned_query);
+        }
+    }
+
+    if let Some(fragment) = fragment {
+        result = format!("{}#{}", result, fragment);
+    }
+
+    result
+}
+
 fn prefix_uri_with_forward_slash(uri: &str) -> String {
     if uri.starts_with("/") || uri.starts_with("http://") || uri.starts_with("https://") { uri.to_string() } else { format!("/{}", uri) }
 }
@@ -136,9 +305,7 @@ fn normalize_url(uri: String, parent_uri: &Option<String>) -> String {
 }
 
 fn try_autofix_invalid_url(uri: &str) -> Uri {
-    let autofixed_uri = urlencoding::encode(uri).into_owned()
-        .replace("%3A", ":")
-        .replace("%2F", "/");
+    let autofixed_uri = percent_encode_uri(uri);
 
     match autofixed_uri.parse::<hyper::Uri>() {
         Ok(parse_uri) => parse_uri,
@@ -148,6 +315,24 @@ fn try_autofix_invalid_url(uri: &str) -> Uri {
     }
 }
 
+// percent-encodes every byte that is neither reserved nor unreserved according to RFC 3986, keeping existing escapes
+fn percent_encode_uri(uri: &str) -> String {
+    let is_allowed = |byte: u8| byte.is_ascii_alphanumeric() || b"-._~:/?#@!$&'()*+,;=".contains(&byte);
+    let bytes = uri.as_bytes();
+    let mut encoded = String::with_capacity(bytes.len());
+    for (index, byte) in bytes.iter().enumerate() {
+        let is_escape = *byte == b'%'
+            && bytes.get(index + 1).map_or(false, |it| it.is_ascii_hexdigit())
+            && bytes.get(index + 2).map_or(false, |it| it.is_ascii_hexdigit());
+        if is_allowed(*byte) || is_escape {
+            encoded.push(*byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
 #[cfg(test)]
 mod tests {
     use std::ops::Add;
@@ -188,6 +373,24 @@ mod tests {
             });
     }
 
+    #[test]
+    fn form_full_url_encodes_non_ascii_paths() {
+        let input = vec![
+            ("/straße/café", "https://example.com/stra%C3%9Fe/caf%C3%A9"),
+            ("/café?q=é", "https://example.com/caf%C3%A9?q=%C3%A9"),
+            ("/naïve path/%C3%A9", "https://example.com/na%C3%AFve%20path/%C3%A9"),
+            ("https://example.com/日本語/", "https://example.com/%E6%97%A5%E6%9C%AC%E8%AA%9E/"),
+        ];
+
+        let host = "example.com";
+        let instance = UriService::new(Arc::new(LinkTypeChecker::new(host)));
+        input.iter()
+            .for_each(|(uri, expected)| {
+                let result = instance.form_full_url("https", uri, host, &Some(String::from("")));
+                assert_eq!(&result, expected, "{} should be {}", uri, expected);
+            });
+    }
+
     #[test]
     fn use_protocol_from_full_url() {
         let input = vec![
@@ -230,4 +433,105 @@ mod tests {
                 assert_eq!(&result, expected, "{} should be {}", &result, expected);
             });
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn strip_session_ids_from_crawl_key() {
+        let input = vec![
+            ("/page;jsessionid=ABC", "https://example.com/page"),
+            ("/page;JSESSIONID=ABC?foo=bar", "https://example.com/page?foo=bar"),
+            ("/shop;phpsessid=ABC/item;color=red", "https://example.com/shop/item;color=red"),
+            ("/page?PHPSESSID=xyz", "https://example.com/page"),
+            ("/page?foo=bar&PHPSESSID=xyz", "https://example.com/page?foo=bar"),
+            ("/page?sessionid=xyz#top", "https://example.com/page#top"),
+            ("https://example.com/page;jsessionid=ABC", "https://example.com/page"),
+            ("/page?sid=xyz", "https://example.com/page?sid=xyz"),
+        ];
+
+        let host = "example.com";
+        let link_type_checker = Arc::new(LinkTypeChecker::new(host));
+        let session_query_params = DEFAULT_SESSION_QUERY_PARAMS.iter().map(|it| it.to_string()).collect();
+        let instance = UriService::new_with_session_query_params(link_type_checker, session_query_params);
+        input.iter()
+            .for_each(|(uri, expected)| {
+                let result = instance.form_full_url("https", uri, host, &Some(String::from("")));
+                assert_eq!(&result, expected, "{} should be {}", uri, expected);
+            });
+    }
+
+    #[test]
+    fn keep_session_ids_if_stripping_is_disabled() {
+        let host = "example.com";
+        let instance = UriService::new(Arc::new(LinkTypeChecker::new(host)));
+
+        let result = instance.form_full_url("https", "/page;jsessionid=ABC", host, &Some(String::from("")));
+
+        assert_eq!(&result, "https://example.com/page;jsessionid=ABC");
+    }
+
+    #[test]
+    fn strip_default_ports_from_crawl_key() {
+        let input = vec![
+            ("https", "https://example.com:443/", "https://example.com/"),
+            ("https", "https://example.com:443/foo?bar=baz", "https://example.com/foo?bar=baz"),
+            ("http", "http://example.com:80/foo", "http://example.com/foo"),
+            ("https", "https://example.com:8080/foo", "https://example.com:8080/foo"),
+            ("http", "http://example.com:443/foo", "http://example.com:443/foo"),
+        ];
+
+        let host = "example.com";
+        let instance = UriService::new(Arc::new(LinkTypeChecker::new(host)));
+        input.iter()
+            .for_each(|(protocol, uri, expected)| {
+                let result = instance.form_full_url(protocol, uri, host, &Some(String::from("")));
+                assert_eq!(&result, expected, "{} should be {}", uri, expected);
+            });
+    }
+
+    #[test]
+    fn keep_default_ports_if_stripping_is_disabled() {
+        let host = "example.com";
+        let instance = UriService::new_with_options(Arc::new(LinkTypeChecker::new(host)), vec![], false, false);
+
+        let result = instance.form_full_url("https", "https://example.com:443/", host, &Some(String::from("")));
+
+        assert_eq!(&result, "https://example.com:443/");
+    }
+
+    #[test]
+    fn collapse_root_query_and_fragment_if_enabled() {
+        let input = vec!["/", "/?x=1", "/#a", "https://example.com", "https://example.com/?utm=x", "https://example.com?utm=x#a"];
+
+        let host = "example.com";
+        let collapsing_instance = UriService::new_with_options(Arc::new(LinkTypeChecker::new(host)), vec![], true, true);
+        let instance = UriService::new(Arc::new(LinkTypeChecker::new(host)));
+        input.iter()
+            .for_each(|uri| {
+                let result = collapsing_instance.form_full_url("https", uri, host, &Some(String::from("https://example.com/")));
+                assert_eq!(&result, "https://example.com/", "{} should share the crawl key of the root", uri);
+            });
+        assert_eq!(&instance.form_full_url("https", "/?x=1", host, &Some(String::from(""))), "https://example.com/?x=1", "Should keep the query, if not enabled");
+        assert_eq!(&collapsing_instance.form_full_url("https", "/page?x=1", host, &Some(String::from(""))), "https://example.com/page?x=1", "Should keep the query of other pages");
+    }
+
+    #[test]
+    fn resolve_reference_against_base_uri() {
+        let input = vec![
+            ("https://example.com/a/b/c", "/abs", "https://example.com/abs"),
+            ("https://example.com/a/b/c", "rel", "https://example.com/a/b/rel"),
+            ("https://example.com/a/b/c", "../up", "https://example.com/a/up"),
+            ("https://example.com/a/b/", "../../../up", "https://example.com/up"),
+            ("https://example.com/a/b/c", "./here?x=1", "https://example.com/a/b/here?x=1"),
+            ("https://example.com/a/b/c?q=1", "?x=2", "https://example.com/a/b/c?x=2"),
+            ("https://example.com/a/b/c?q=1", "#top", "https://example.com/a/b/c?q=1#top"),
+            ("https://example.com:8080/a", "b/../c/.", "https://example.com:8080/c/"),
+            ("https://example.com/a", "//other.example.com/x", "https://other.example.com/x"),
+            ("https://example.com/a", "http://other.example.com/x", "http://other.example.com/x"),
+            ("https://example.com", "rel", "https://example.com/rel"),
+        ];
+
+        input.iter()
+            .for_each(|(base, reference, expected)| {
+                assert_eq!(&resolve_reference(base, reference), expected, "{} against {} should be {}", reference, base, expected);
+            });
+    }
+}
@@ -4,18 +4,19 @@ use std::sync::Arc;
 
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
-use hyper::{Body, Response, Uri};
-use hyper::header::HeaderValue;
+use hyper::Uri;
 use responses::head_response::HeadResponse;
 use responses::redirect::Redirect;
 use responses::response_timings::ResponseTimings;
 use responses::status_code::StatusCode;
 use tracing::{debug, info, trace};
 
+use linkresult::uri_service;
 use linkresult::uri_service::UriService;
 
 use crate::http::http_client::HttpClient;
 use crate::http::http_utils;
+use crate::task_context::task_context::TaskConfig;
 
 pub type HeadResponseResult = Result<(HeadResponse, Arc<dyn HttpClient>), String>;
 
@@ -24,11 +25,39 @@ pub trait FetchHeaderCommand: Sync + Send {
     async fn fetch_header(&self, url: String, ignore_redirects: bool, maximum_redirects: u8, uri_service: Arc<UriService>, http_client: Arc<dyn HttpClient>, redirects: Option<Vec<Redirect>>, robots_txt_info_url: Option<String>) -> HeadResponseResult;
 }
 
-pub struct DefaultFetchHeaderCommand {}
+pub struct DefaultFetchHeaderCommand {
+    pub collapse_trailing_slash_redirects: bool,
+    pub max_reported_redirects: Option<usize>,
+    pub retryable_status_codes: Vec<u16>,
+    // stops at the first redirect to the very same url, instead of following it until maximum_redirects
+    pub collapse_identical_redirects: bool,
+    // caps the HEAD requests of all redirect hops and their retries together, the first request is always made
+    pub max_requests_per_url: Option<usize>,
+    // redirects to longer urls end the chain, instead of failing on them
+    pub max_url_length: Option<usize>,
+}
 
 #[async_trait]
 impl FetchHeaderCommand for DefaultFetchHeaderCommand {
     async fn fetch_header(&self, url: String, ignore_redirects: bool, maximum_redirects: u8, uri_service: Arc<UriService>, http_client: Arc<dyn HttpClient>, redirects: Option<Vec<Redirect>>, robots_txt_info_url: Option<String>) -> HeadResponseResult {
+        self.fetch_header_within_budget(url, ignore_redirects, maximum_redirects, uri_service, http_client, redirects, robots_txt_info_url, 0).await
+    }
+}
+
+impl DefaultFetchHeaderCommand {
+    pub fn from_config(config: &TaskConfig) -> DefaultFetchHeaderCommand {
+        DefaultFetchHeaderCommand {
+            collapse_trailing_slash_redirects: config.collapse_trailing_slash_redirects,
+            max_reported_redirects: config.max_reported_redirects,
+            retryable_status_codes: config.retryable_status_codes.clone(),
+            collapse_identical_redirects: config.collapse_identical_redirects,
+            max_requests_per_url: config.max_requests_per_url,
+            max_url_length: config.max_url_length,
+        }
+    }
+
+    // requests_made counts the requests of the previous hops, so redirects and retries can't multiply
+    async fn fetch_header_within_budget(&self, url: String, ignore_redirects: bool, maximum_redirects: u8, uri_service: Arc<UriService>, http_client: Arc<dyn HttpClient>, redirects: Option<Vec<Redirect>>, robots_txt_info_url: Option<String>, mut requests_made: usize) -> HeadResponseResult {
         let start_time = DateTime::from(Utc::now());
         let mut uri = url.clone();
 
@@ -39,80 +68,178 @@ impl FetchHeaderCommand for DefaultFetchHeaderCommand {
             uri = redirects_unwrapped.last().unwrap().destination.clone();
         }
 
-        let response = http_client.head(uri.clone(), robots_txt_info_url.clone()).await;
-        if response.is_err() {
-            return Err(response.unwrap_err().to_string());
-        }
-        let response = response.unwrap();
+        let mut attempt = 0;
+        let response = loop {
+            let response = http_client.head(uri.clone(), robots_txt_info_url.clone()).await;
+            if response.is_err() {
+                return Err(response.unwrap_err().to_string());
+            }
+            let response = response.unwrap();
+            requests_made += 1;
+            if !http_utils::is_retryable_status(&response, &self.retryable_status_codes, attempt) {
+                break response;
+            }
+            if self.is_request_budget_used_up(requests_made) {
+                debug!("Not retrying HEAD for {}, all {} requests for {} are used up", uri, requests_made, url);
+                break response;
+            }
+            attempt += 1;
+            debug!("Retrying HEAD for {} after status {} (attempt {} of {})", uri, response.status(), attempt, http_utils::MAX_STATUS_RETRIES);
+        };
         trace!("HEAD for {}: {:?}", uri, response.headers());
         let headers: HashMap<String, String> = http_utils::response_headers_to_map(&response);
         let can_process_redirects = !ignore_redirects && num_redirects < maximum_redirects && response.status().is_redirection();
-        if can_process_redirects {
+        if can_process_redirects && self.is_request_budget_used_up(requests_made) {
+            debug!("Not following redirect of {}, all {} requests for {} are used up", uri, requests_made, url);
+        } else if can_process_redirects {
             if let Some(location_header) = response.headers().get("location") {
-                let redirects_for_next = DefaultFetchHeaderCommand::append_redirect(uri_service.clone(), redirects, uri, &response, &headers, location_header, start_time);
-                let response = self.fetch_header(url.clone(), false, maximum_redirects, uri_service.clone(), http_client.clone(), Some(redirects_for_next), robots_txt_info_url.clone()).await;
+                let (redirect, is_too_long) = self.to_redirect_within_length(uri_service.clone(), uri, location_header.to_str().unwrap(), http_utils::map_status_code(response.status()), &headers, start_time);
+                let mut redirects_for_next = redirects.unwrap_or(vec![]);
+                if is_too_long {
+                    debug!("Not following redirect of {} to a url of {} characters", redirect.source, redirect.destination.len());
+                    let status_code = redirect.http_response_code.clone();
+                    let source = redirect.source.clone();
+                    redirects_for_next.push(redirect);
+                    return Ok((self.to_head_response(source, status_code, headers, redirects_for_next, start_time), http_client));
+                }
+                if self.collapse_identical_redirects && redirect.is_self_redirect() {
+                    debug!("Not following self redirect of {}", redirect.source);
+                    let status_code = redirect.http_response_code.clone();
+                    let destination = redirect.destination.clone();
+                    redirects_for_next.push(redirect);
+                    return Ok((self.to_head_response(destination, status_code, headers, redirects_for_next, start_time), http_client));
+                }
+                redirects_for_next.push(redirect);
+                let response = Box::pin(self.fetch_header_within_budget(url.clone(), false, maximum_redirects, uri_service.clone(), http_client.clone(), Some(redirects_for_next), robots_txt_info_url.clone(), requests_made)).await;
                 return response;
             }
             let error_message = format!("No valid location found in redirect header {:?}", response);
             info!("{}", &error_message);
         }
 
-        let redirects_result = redirects.unwrap_or(vec![]);
-        let result = HeadResponse {
+        let result = self.to_head_response(uri, http_utils::map_status_code(response.status()), headers, redirects.unwrap_or(vec![]), start_time);
+        Ok((result, http_client))
+    }
+
+    fn is_request_budget_used_up(&self, requests_made: usize) -> bool {
+        self.max_requests_per_url.map_or(false, |max_requests_per_url| requests_made >= max_requests_per_url)
+    }
+
+    // the status and headers of the final hop, along with the redirects that led there
+    pub(crate) fn to_head_response(&self, uri: String, http_response_code: StatusCode, headers: HashMap<String, String>, redirects: Vec<Redirect>, start_time: DateTime<Utc>) -> HeadResponse {
+        let mut redirects_result = redirects;
+        if self.collapse_trailing_slash_redirects {
+            redirects_result = DefaultFetchHeaderCommand::collapse_trailing_slash_redirects(redirects_result);
+        }
+        let permanent_redirect = HeadResponse::is_permanent_redirect_chain(&redirects_result);
+        let x_robots_tag = headers.get("x-robots-tag").cloned();
+        let protocol_downgrade = HeadResponse::has_protocol_downgrade(&redirects_result);
+        let self_redirect = self.collapse_identical_redirects && HeadResponse::has_self_redirect(&redirects_result);
+        let redirect_url_too_long = redirects_result.last().map_or(false, |redirect| self.is_url_too_long(&redirect.destination));
+        let redirect_count = redirects_result.len();
+        if let Some(max_reported_redirects) = self.max_reported_redirects {
+            // the last hop is always kept, as it determines the final uri
+            let keep = max_reported_redirects.max(1);
+            if redirects_result.len() > keep {
+                redirects_result.drain(..redirects_result.len() - keep);
+            }
+        }
+        HeadResponse {
             redirects: redirects_result,
-            http_response_code: http_utils::map_status_code(response.status()),
+            redirect_count,
+            http_response_code,
             headers,
+            headers_of_interest: None,
             requested_url: uri.clone(),
             response_timings: ResponseTimings::from(format!("HeadResponse.{}", uri.clone()), start_time, DateTime::from(Utc::now())),
-        };
-        Ok((result, http_client))
+            permanent_redirect,
+            x_robots_tag,
+            protocol_downgrade,
+            self_redirect,
+            redirect_url_too_long,
+        }
     }
-}
 
-impl DefaultFetchHeaderCommand {
-    fn append_redirect(uri_service: Arc<UriService>, redirects: Option<Vec<Redirect>>, uri: String, response: &Response<Body>, headers: &HashMap<String, String>, location_header: &HeaderValue, redirect_start_time: DateTime<Utc>) -> Vec<Redirect> {
+    fn is_url_too_long(&self, url: &str) -> bool {
+        self.max_url_length.map_or(false, |max_url_length| url.len() > max_url_length)
+    }
+
+    // overlong destinations are reported as resolved, as they may not even be parseable. true, if the destination is too long
+    pub(crate) fn to_redirect_within_length(&self, uri_service: Arc<UriService>, uri: String, location: &str, http_response_code: StatusCode, headers: &HashMap<String, String>, redirect_start_time: DateTime<Utc>) -> (Redirect, bool) {
+        let resolved_location = uri_service::resolve_reference(&uri, location);
+        if self.is_url_too_long(&resolved_location) {
+            return (DefaultFetchHeaderCommand::redirect_to(uri, resolved_location, http_response_code, headers, redirect_start_time), true);
+        }
+        (DefaultFetchHeaderCommand::to_redirect(uri_service, uri, location, http_response_code, headers, redirect_start_time), false)
+    }
+
+    pub(crate) fn to_redirect(uri_service: Arc<UriService>, uri: String, location: &str, http_response_code: StatusCode, headers: &HashMap<String, String>, redirect_start_time: DateTime<Utc>) -> Redirect {
         let uri_object = Uri::from_str(&uri).unwrap();
-        let adjusted_uri = uri_service.form_full_url(uri_object.scheme_str().unwrap(), location_header.to_str().unwrap(), uri_object.host().unwrap(), &Some(uri.clone()));
-        let redirect = Redirect {
+        // relative locations are resolved against the current uri first, so path-relative and ../ locations work, too
+        let location = uri_service::resolve_reference(&uri, location);
+        let adjusted_uri = uri_service.form_full_url(uri_object.scheme_str().unwrap(), &location, uri_object.host().unwrap(), &Some(uri.clone()));
+        debug!("Following redirect {}", adjusted_uri);
+        DefaultFetchHeaderCommand::redirect_to(uri, adjusted_uri.to_string(), http_response_code, headers, redirect_start_time)
+    }
+
+    fn redirect_to(uri: String, destination: String, http_response_code: StatusCode, headers: &HashMap<String, String>, redirect_start_time: DateTime<Utc>) -> Redirect {
+        Redirect {
             source: uri.clone(),
-            destination: adjusted_uri.to_string(),
-            http_response_code: StatusCode { code: response.status().as_u16(), label: response.status().canonical_reason().unwrap().into() },
+            destination,
+            permanent: Redirect::is_permanent_status_code(http_response_code.code),
+            http_response_code,
             headers: headers.clone(),
             response_timings: ResponseTimings::from(format!("Redirect.{}", uri.clone()), redirect_start_time, DateTime::from(Utc::now())),
-        };
-        debug!("Following redirect {}", adjusted_uri);
-        let mut redirects_for_next = vec![];
-        if redirects.is_some() {
-            redirects_for_next.append(&mut redirects.unwrap());
         }
-        redirects_for_next.push(redirect);
-        redirects_for_next
+    }
+
+    // redirects that only add or remove a trailing slash are merged into the previous hop, or dropped if they are the first one.
+    // they are still followed (and counted against maximum_redirects), they are just not reported.
+    fn collapse_trailing_slash_redirects(redirects: Vec<Redirect>) -> Vec<Redirect> {
+        let mut collapsed_redirects: Vec<Redirect> = vec![];
+        for redirect in redirects {
+            let is_trailing_slash_redirect = redirect.source != redirect.destination
+                && redirect.source.trim_end_matches('/') == redirect.destination.trim_end_matches('/');
+            if !is_trailing_slash_redirect {
+                collapsed_redirects.push(redirect);
+            } else if let Some(previous_redirect) = collapsed_redirects.last_mut() {
+                debug!("Collapsing trailing slash redirect {} -> {}", redirect.source, redirect.destination);
+                previous_redirect.destination = redirect.destination;
+                previous_redirect.permanent = previous_redirect.permanent && redirect.permanent;
+            }
+        }
+        collapsed_redirects
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use hyper::{Body, Response};
     use mockall::*;
     use mockall::predicate::eq;
+    use responses::run_config::RunConfig;
 
     use linkresult::link_type_checker::LinkTypeChecker;
     use linkresult::uri_service::UriService;
 
+    use crate::http::http_client::HttpClientResult;
+    use crate::http::http_utils::{DEFAULT_RETRYABLE_STATUS_CODES, MAX_STATUS_RETRIES};
+
     use super::*;
 
     mock! {
         MyHttpClient {}
         #[async_trait]
         impl HttpClient for MyHttpClient{
-            async fn head(&self, uri: String, robots_txt_info_url: Option<String>) -> hyper::Result<Response<Body>>;
-            async fn get(&self, uri: String, robots_txt_info_url: Option<String>) -> hyper::Result<Response<Body>>;
+            async fn head(&self, uri: String, robots_txt_info_url: Option<String>) -> HttpClientResult;
+            async fn get(&self, uri: String, robots_txt_info_url: Option<String>) -> HttpClientResult;
         }
     }
 
     #[tokio::test]
     async fn returns_simple_result_on_simple_request_without_redirect_following() {
         // given: simple fetch command
-        let command = DefaultFetchHeaderCommand {};
+        let command = DefaultFetchHeaderCommand { collapse_trailing_slash_redirects: false, max_reported_redirects: None, retryable_status_codes: vec![], collapse_identical_redirects: false, max_requests_per_url: None, max_url_length: None };
         let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com"))));
         let mut mock_http_client = MockMyHttpClient::new();
         mock_http_client.expect_head().returning(|_, _| Ok(Response::builder()
@@ -135,7 +262,7 @@ mod tests {
         // given: simple fetch command
         let target_domain = "example.com";
         let target_url = String::from(format!("https://{}", target_domain));
-        let command = DefaultFetchHeaderCommand {};
+        let command = DefaultFetchHeaderCommand { collapse_trailing_slash_redirects: false, max_reported_redirects: None, retryable_status_codes: vec![], collapse_identical_redirects: false, max_requests_per_url: None, max_url_length: None };
         let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new(target_domain))));
 
         let mut mock_http_client = MockMyHttpClient::new();
@@ -175,6 +302,7 @@ mod tests {
         assert_eq!(result_unwrapped.redirects.len(), 2, "Should have two redirects");
         assert_eq!(result_unwrapped.headers.get("x-custom").unwrap(), &String::from("Final destination"), "Should have headers embedded");
         assert_eq!(result_unwrapped.response_timings.end_time.is_some(), true, "Should have updated end_time after successful run");
+        assert_eq!(result_unwrapped.permanent_redirect, true, "Chain of 308 redirects should be permanent");
 
         assert_eq!(result_unwrapped.redirects[0].source, target_url, "Source should match");
         assert_eq!(result_unwrapped.redirects[0].destination, String::from("https://first-redirect.example.com/"), "Destination should match");
@@ -186,12 +314,40 @@ mod tests {
         assert_eq!(result_unwrapped.redirects[1].response_timings.end_time.is_some(), true, "Should have updated end_time after successful run - redirect[1]");
     }
 
+    #[tokio::test]
+    async fn truncates_reported_redirects_but_keeps_total_count() {
+        // given: a chain of five redirects and a reporting cap of two
+        let target_domain = "example.com";
+        let target_url = String::from(format!("https://{}/hop0", target_domain));
+        let command = DefaultFetchHeaderCommand { collapse_trailing_slash_redirects: false, max_reported_redirects: Some(2), retryable_status_codes: vec![], collapse_identical_redirects: false, max_requests_per_url: None, max_url_length: None };
+        let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new(target_domain))));
+        let mut mock_http_client = MockMyHttpClient::new();
+        mock_http_client.expect_head().returning(|uri, _| {
+            let hop: usize = uri.rsplit("/hop").next().unwrap().parse().unwrap();
+            if hop < 5 {
+                return Ok(Response::builder().status(301).header("location", format!("/hop{}", hop + 1)).body(Body::from("")).unwrap());
+            }
+            Ok(Response::builder().status(200).body(Body::from("")).unwrap())
+        });
+        let mock_http_client = Arc::new(mock_http_client);
+
+        // when: fetch is invoked
+        let result = command.fetch_header(target_url, false, 10, uri_service, mock_http_client, None, None).await.unwrap().0;
+
+        // then: only the last hops are stored, while the count covers the whole chain
+        assert_eq!(result.redirects.len(), 2, "Should only store the last two hops");
+        assert_eq!(result.redirect_count, 5, "Should count all hops");
+        assert_eq!(result.redirects[0].source, "https://example.com/hop3");
+        assert_eq!(result.get_final_uri(), "https://example.com/hop5", "Should still know the final uri");
+        assert_eq!(result.permanent_redirect, true, "Should derive flags from the whole chain");
+    }
+
     #[tokio::test]
     async fn should_return_no_redirect_if_ignore_redirects_is_true() {
         // given: simple fetch command
         let target_domain = "example.com";
         let target_url = String::from(format!("https://{}", target_domain));
-        let command = DefaultFetchHeaderCommand {};
+        let command = DefaultFetchHeaderCommand { collapse_trailing_slash_redirects: false, max_reported_redirects: None, retryable_status_codes: vec![], collapse_identical_redirects: false, max_requests_per_url: None, max_url_length: None };
         let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new(target_domain))));
 
         let mut mock_http_client = MockMyHttpClient::new();
@@ -223,7 +379,7 @@ mod tests {
         // given: simple fetch command
         let target_domain = "example.com";
         let target_url = String::from(format!("https://{}", target_domain));
-        let command = DefaultFetchHeaderCommand {};
+        let command = DefaultFetchHeaderCommand { collapse_trailing_slash_redirects: false, max_reported_redirects: None, retryable_status_codes: vec![], collapse_identical_redirects: false, max_requests_per_url: None, max_url_length: None };
         let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new(target_domain))));
 
         let mut mock_http_client = MockMyHttpClient::new();
@@ -248,4 +404,271 @@ mod tests {
         assert_eq!(result_unwrapped.redirects.len(), 0, "Should have no redirects");
         assert_eq!(result_unwrapped.response_timings.end_time.is_some(), true, "Should have updated end_time after successful run");
     }
+
+    #[tokio::test]
+    async fn trailing_slash_redirect_is_collapsed_if_enabled() {
+        // given: a url that redirects to itself with a trailing slash
+        let target_domain = "example.com";
+        let target_url = String::from("https://example.com/path");
+        let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new(target_domain))));
+        let create_mock_http_client = || {
+            let mut mock_http_client = MockMyHttpClient::new();
+            mock_http_client.expect_head()
+                .with(eq(String::from("https://example.com/path")), eq(None))
+                .returning(|_, _x: Option<String>| Ok(Response::builder()
+                    .status(301)
+                    .header("location", "/path/")
+                    .body(Body::from(""))
+                    .unwrap()));
+            mock_http_client.expect_head()
+                .with(eq(String::from("https://example.com/path/")), eq(None))
+                .returning(|_, _x: Option<String>| Ok(Response::builder()
+                    .status(200)
+                    .body(Body::from(""))
+                    .unwrap()));
+            Arc::new(mock_http_client)
+        };
+
+        // when: fetch is invoked with and without collapsing
+        let collapsing_command = DefaultFetchHeaderCommand { collapse_trailing_slash_redirects: true, max_reported_redirects: None, retryable_status_codes: vec![], collapse_identical_redirects: false, max_requests_per_url: None, max_url_length: None };
+        let collapsed_result = collapsing_command.fetch_header(target_url.clone(), false, 10, uri_service.clone(), create_mock_http_client(), None, None).await.unwrap().0;
+        let command = DefaultFetchHeaderCommand { collapse_trailing_slash_redirects: false, max_reported_redirects: None, retryable_status_codes: vec![], collapse_identical_redirects: false, max_requests_per_url: None, max_url_length: None };
+        let result = command.fetch_header(target_url.clone(), false, 10, uri_service, create_mock_http_client(), None, None).await.unwrap().0;
+
+        // then: the redirect is followed, but only reported if collapsing is disabled
+        assert_eq!(collapsed_result.redirects.len(), 0, "Should not report trailing slash redirect");
+        assert_eq!(collapsed_result.get_final_uri(), "https://example.com/path/", "Should still follow trailing slash redirect");
+        assert_eq!(result.redirects.len(), 1, "Should report trailing slash redirect");
+        assert_eq!(result.get_final_uri(), "https://example.com/path/");
+    }
+
+    #[tokio::test]
+    async fn stops_at_identical_redirects_if_enabled() {
+        // given: a server redirecting a url to itself over and over again
+        let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com"))));
+        let create_mock_http_client = |times: usize| {
+            let mut mock_http_client = MockMyHttpClient::new();
+            mock_http_client.expect_head()
+                .with(eq(String::from("https://example.com/again")), eq(None))
+                .times(times)
+                .returning(|_, _| Ok(Response::builder()
+                    .status(302)
+                    .header("location", "/again")
+                    .body(Body::from(""))
+                    .unwrap()));
+            Arc::new(mock_http_client)
+        };
+
+        // when: fetch is invoked with and without collapsing identical redirects
+        let collapsing_command = DefaultFetchHeaderCommand { collapse_trailing_slash_redirects: false, max_reported_redirects: None, retryable_status_codes: vec![], collapse_identical_redirects: true, max_requests_per_url: None, max_url_length: None };
+        let collapsed_result = collapsing_command.fetch_header(String::from("https://example.com/again"), false, 10, uri_service.clone(), create_mock_http_client(1), None, None).await.unwrap().0;
+        let command = DefaultFetchHeaderCommand { collapse_trailing_slash_redirects: false, max_reported_redirects: None, retryable_status_codes: vec![], collapse_identical_redirects: false, max_requests_per_url: None, max_url_length: None };
+        let result = command.fetch_header(String::from("https://example.com/again"), false, 10, uri_service, create_mock_http_client(11), None, None).await.unwrap().0;
+
+        // then: collapsing stops after the first hop, otherwise the whole redirect budget is used up
+        assert_eq!(collapsed_result.redirects.len(), 1, "Should stop at the first self redirect");
+        assert_eq!(collapsed_result.self_redirect, true, "Should flag the self redirect");
+        assert_eq!(collapsed_result.http_response_code.code, 302);
+        assert_eq!(result.redirects.len(), 10, "Should follow the self redirect until maximum_redirects");
+        assert_eq!(result.self_redirect, false, "Should only flag self redirects when collapsing");
+    }
+
+    #[tokio::test]
+    async fn retries_configured_status_codes() {
+        // given: a server responding with 429 first, and 429 being retryable
+        let command = DefaultFetchHeaderCommand { collapse_trailing_slash_redirects: false, max_reported_redirects: None, retryable_status_codes: vec![429], collapse_identical_redirects: false, max_requests_per_url: None, max_url_length: None };
+        let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com"))));
+        let mut mock_http_client = MockMyHttpClient::new();
+        let mut sequence = Sequence::new();
+        mock_http_client.expect_head()
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|_, _| Ok(Response::builder()
+                .status(429)
+                .body(Body::from(""))
+                .unwrap()));
+        mock_http_client.expect_head()
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|_, _| Ok(Response::builder()
+                .status(200)
+                .body(Body::from(""))
+                .unwrap()));
+
+        // when: fetch is invoked
+        let result = command.fetch_header(String::from("https://example.com/"), false, 10, uri_service, Arc::new(mock_http_client), None, None).await;
+
+        // then: the retry succeeds
+        assert_eq!(result.unwrap().0.http_response_code.code, 200, "Should have retried the 429");
+    }
+
+    #[tokio::test]
+    async fn gives_up_retrying_after_max_status_retries() {
+        // given: a server that is permanently unavailable
+        let command = DefaultFetchHeaderCommand { collapse_trailing_slash_redirects: false, max_reported_redirects: None, retryable_status_codes: DEFAULT_RETRYABLE_STATUS_CODES.to_vec(), collapse_identical_redirects: false, max_requests_per_url: None, max_url_length: None };
+        let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com"))));
+        let mut mock_http_client = MockMyHttpClient::new();
+        mock_http_client.expect_head()
+            .times(MAX_STATUS_RETRIES + 1)
+            .returning(|_, _| Ok(Response::builder()
+                .status(503)
+                .body(Body::from(""))
+                .unwrap()));
+
+        // when: fetch is invoked
+        let result = command.fetch_header(String::from("https://example.com/"), false, 10, uri_service, Arc::new(mock_http_client), None, None).await;
+
+        // then: the last response is kept
+        assert_eq!(result.unwrap().0.http_response_code.code, 503);
+    }
+
+    #[tokio::test]
+    async fn redirects_and_retries_share_the_request_budget_of_a_url() {
+        // given: a url failing twice before redirecting to a url that keeps failing, and a budget of 4 requests
+        let command = DefaultFetchHeaderCommand { collapse_trailing_slash_redirects: false, max_reported_redirects: None, retryable_status_codes: DEFAULT_RETRYABLE_STATUS_CODES.to_vec(), collapse_identical_redirects: false, max_requests_per_url: Some(4), max_url_length: None };
+        let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com"))));
+        let mut mock_http_client = MockMyHttpClient::new();
+        let mut sequence = Sequence::new();
+        mock_http_client.expect_head()
+            .with(eq(String::from("https://example.com/")), eq(None))
+            .times(2)
+            .in_sequence(&mut sequence)
+            .returning(|_, _| Ok(Response::builder()
+                .status(503)
+                .body(Body::from(""))
+                .unwrap()));
+        mock_http_client.expect_head()
+            .with(eq(String::from("https://example.com/")), eq(None))
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|_, _| Ok(Response::builder()
+                .status(301)
+                .header("location", "/moved")
+                .body(Body::from(""))
+                .unwrap()));
+        mock_http_client.expect_head()
+            .with(eq(String::from("https://example.com/moved")), eq(None))
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|_, _| Ok(Response::builder()
+                .status(503)
+                .body(Body::from(""))
+                .unwrap()));
+
+        // when: fetch is invoked
+        let result = command.fetch_header(String::from("https://example.com/"), false, 10, uri_service, Arc::new(mock_http_client), None, None).await;
+
+        // then: the redirect target isn't retried, as the 4 requests are used up
+        let head_response = result.unwrap().0;
+        assert_eq!(head_response.redirects.len(), 1, "Should have followed the redirect");
+        assert_eq!(head_response.http_response_code.code, 503, "Should keep the last response");
+    }
+
+    #[tokio::test]
+    async fn stops_at_redirects_to_overlong_urls() {
+        // given: a redirect to a url exceeding the maximum url length
+        let command = DefaultFetchHeaderCommand { collapse_trailing_slash_redirects: false, max_reported_redirects: None, retryable_status_codes: vec![], collapse_identical_redirects: false, max_requests_per_url: None, max_url_length: Some(2048) };
+        let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com"))));
+        let overlong_path = format!("/{}", "a".repeat(3000));
+        let mut mock_http_client = MockMyHttpClient::new();
+        mock_http_client.expect_head()
+            .with(eq(String::from("https://example.com/")), eq(None))
+            .times(1)
+            .returning(move |_, _| Ok(Response::builder()
+                .status(301)
+                .header("location", overlong_path.as_str())
+                .body(Body::from(""))
+                .unwrap()));
+
+        // when: fetch is invoked
+        let result = command.fetch_header(String::from("https://example.com/"), false, 10, uri_service, Arc::new(mock_http_client), None, None).await;
+
+        // then: the overlong url is reported, but never requested
+        let head_response = result.unwrap().0;
+        assert_eq!(head_response.redirect_url_too_long, true, "Should flag the overlong redirect");
+        assert_eq!(head_response.redirects.len(), 1, "Should report the redirect");
+        assert_eq!(head_response.redirects[0].destination.len(), "https://example.com/".len() + 3000);
+        assert_eq!(head_response.http_response_code.code, 301, "Should keep the redirect response");
+    }
+
+    #[tokio::test]
+    async fn flags_https_to_http_redirects_as_protocol_downgrade() {
+        // given: an https page redirecting to http
+        let command = DefaultFetchHeaderCommand { collapse_trailing_slash_redirects: false, max_reported_redirects: None, retryable_status_codes: vec![], collapse_identical_redirects: false, max_requests_per_url: None, max_url_length: None };
+        let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com"))));
+        let mut mock_http_client = MockMyHttpClient::new();
+        mock_http_client.expect_head()
+            .with(eq(String::from("https://example.com/")), eq(None))
+            .returning(|_, _| Ok(Response::builder()
+                .status(301)
+                .header("location", "http://example.com/insecure")
+                .body(Body::from(""))
+                .unwrap()));
+        mock_http_client.expect_head()
+            .with(eq(String::from("http://example.com/insecure")), eq(None))
+            .returning(|_, _| Ok(Response::builder()
+                .status(200)
+                .body(Body::from(""))
+                .unwrap()));
+
+        // when: fetch is invoked
+        let result = command.fetch_header(String::from("https://example.com/"), false, 10, uri_service, Arc::new(mock_http_client), None, None).await;
+
+        // then: the downgrade is flagged
+        let head_response = result.unwrap().0;
+        assert_eq!(head_response.redirects.len(), 1, "Should have followed the redirect");
+        assert_eq!(head_response.protocol_downgrade, true, "Should flag the https to http redirect");
+    }
+
+    #[tokio::test]
+    async fn resolves_relative_locations_against_the_current_uri() {
+        let input = vec![
+            ("/abs", "https://example.com/abs"),
+            ("rel", "https://example.com/dir/rel"),
+            ("../up", "https://example.com/up"),
+        ];
+
+        for (location, expected) in input {
+            // given: a redirect with a relative location
+            let command = DefaultFetchHeaderCommand { collapse_trailing_slash_redirects: false, max_reported_redirects: None, retryable_status_codes: vec![], collapse_identical_redirects: false, max_requests_per_url: None, max_url_length: None };
+            let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com"))));
+            let mut mock_http_client = MockMyHttpClient::new();
+            mock_http_client.expect_head()
+                .with(eq(String::from("https://example.com/dir/page")), eq(None))
+                .returning(move |_, _| Ok(Response::builder()
+                    .status(302)
+                    .header("location", location)
+                    .body(Body::from(""))
+                    .unwrap()));
+            mock_http_client.expect_head()
+                .returning(|_, _| Ok(Response::builder()
+                    .status(200)
+                    .body(Body::from(""))
+                    .unwrap()));
+
+            // when: fetch is invoked
+            let result = command.fetch_header(String::from("https://example.com/dir/page"), false, 10, uri_service, Arc::new(mock_http_client), None, None).await;
+
+            // then: the location is resolved relative to the redirecting uri
+            let head_response = result.unwrap().0;
+            assert_eq!(head_response.redirects[0].destination, expected, "Location {} should resolve to {}", location, expected);
+        }
+    }
+
+    #[test]
+    fn from_config_takes_over_the_redirect_settings() {
+        // given: a config with non-default redirect settings
+        let mut run_config = RunConfig::new(String::from("https://example.com"), None);
+        run_config.max_reported_redirects = Some(2);
+        run_config.max_url_length = Some(100);
+        let config = TaskConfig::new(run_config);
+
+        // when: the command is built from it
+        let command = DefaultFetchHeaderCommand::from_config(&config);
+
+        // then: the settings are taken over
+        assert_eq!(command.max_reported_redirects, Some(2));
+        assert_eq!(command.max_url_length, Some(100));
+        assert_eq!(command.retryable_status_codes, config.retryable_status_codes);
+    }
 }
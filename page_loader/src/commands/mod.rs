@@ -1,3 +1,4 @@
 pub mod page_crawl_command;
-pub(crate) mod fetch_header_command;
-pub(crate) mod page_download_command;
\ No newline at end of file
+pub mod fetch_header_command;
+pub(crate) mod page_download_command;
+pub mod sitemap_audit_command;
\ No newline at end of file
@@ -1,24 +1,40 @@
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::Ordering;
 
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use hyper::Error;
-use hyper::header::CONTENT_TYPE;
+use hyper::header::{CONTENT_TYPE, LINK};
 use responses::crawl_status::CrawlStatus;
+use responses::dedup_key_rules::DedupKeyRules;
+use responses::external_link_check::ExternalLinkCheck;
 use responses::get_response::GetResponse;
 use responses::link::Link;
 use responses::page_response::PageResponse;
+use responses::redirect::Redirect;
+use responses::response_timings::ResponseTimings;
 use responses::status_code::StatusCode;
+use responses::uri_scope::UriScope;
+use responses::url_normalization::UrlNormalization;
+use tokio::sync::Semaphore;
 use tracing::debug;
 use uuid::Uuid;
 
-use dom_parser::DomParser;
+use dom_parser::{parse_meta_refresh_url, DomParser};
+use dom_parser::sitemap::{extract_sitemap_urls, SITEMAP_SOURCE_TAG};
+use linkresult::dedup_key::{to_dedup_key, to_dedup_key_with_normalizations, to_known_path};
+use linkresult::link_type_checker::LinkTypeChecker;
+use linkresult::uri_result::UriResult;
+use linkresult::uri_service::UriService;
 
-use crate::commands::fetch_header_command::{FetchHeaderCommand, HeadResponseResult};
+use crate::commands::fetch_header_command::{DefaultFetchHeaderCommand, FetchHeaderCommand, HeadResponseResult};
 use crate::commands::page_download_command::PageDownloadCommand;
-use crate::http::http_client::HttpClient;
+use crate::http::http_client::{HttpClient, HttpClientError, REQUEST_BUDGET_EXHAUSTED_ERROR};
+use crate::http::http_utils;
+use crate::http::resource_kind::ResourceKind;
 use crate::page_request::PageRequest;
+use crate::task_context::lock_recovery::LockRecovering;
 use crate::task_context::task_context::FullTaskContext;
 
 #[async_trait]
@@ -38,6 +54,11 @@ enum Crawlability {
     Crawlable,
     RestrictedByRobotsTxt,
     MaxDepthReached,
+    MaxPathDepthReached,
+    HostLimitReached,
+    RequestBudgetExhausted,
+    ByteBudgetExhausted,
+    QueryVariantSkipped,
 }
 
 pub struct PageCrawlCommand {
@@ -65,125 +86,691 @@ impl PageCrawlCommand {
 
     fn verify_crawlability(&self) -> Crawlability {
         let request_object = self.request_object.clone();
-        let request_object_locked = request_object.lock().unwrap();
+        let request_object_locked = request_object.lock_recovering();
         let task_context = request_object_locked.task_context.clone();
-        let task_context_locked = task_context.lock().unwrap();
+        let task_context_locked = task_context.lock_recovering();
         let config = task_context_locked.get_config().clone();
-        let config_locked = config.lock().unwrap();
+        let config_locked = config.lock_recovering();
         if config_locked.maximum_depth > 0 &&
             self.current_depth >= config_locked.maximum_depth {
             debug!("Dropping requested url: {} -> maximum_depth reached: {}", &request_object_locked.url, config_locked.maximum_depth);
             return Crawlability::MaxDepthReached;
         }
+        if let Some(max_path_depth) = config_locked.max_path_depth {
+            if Self::get_path_depth(&request_object_locked.url) > max_path_depth {
+                debug!("Dropping requested url: {} -> max_path_depth reached: {}", &request_object_locked.url, max_path_depth);
+                return Crawlability::MaxPathDepthReached;
+            }
+        }
+        let max_hosts = config_locked.max_hosts;
+        let max_requests = config_locked.max_requests;
+        let max_total_bytes = config_locked.max_total_bytes;
+        let dedup_key = to_dedup_key(&request_object_locked.url, &config_locked.dedup_key_rules);
         // at this point, the config isn't required anymore and can therefore be dropped
         drop(config_locked);
         drop(config);
 
-        if task_context_locked.get_all_crawled_links().lock().unwrap().contains(&request_object_locked.url) {
+        if let Some(approximate_known_links) = task_context_locked.get_approximate_known_links() {
+            if approximate_known_links.lock_recovering().contains(&dedup_key) {
+                debug!("Dropping requested url: {} -> probably already known", &request_object_locked.url);
+                return Crawlability::AlreadyKnown;
+            }
+        }
+
+        if task_context_locked.get_all_crawled_links().lock_recovering().contains(&dedup_key) {
             debug!("Dropping requested url: {} -> already known", &request_object_locked.url);
             return Crawlability::AlreadyKnown;
         }
 
-        if task_context_locked.get_all_tasked_links().lock().unwrap().contains(&request_object_locked.url) {
+        if task_context_locked.get_all_tasked_links().lock_recovering().contains(&dedup_key) {
             debug!("Dropping requested url: {} -> already tasked", &request_object_locked.url);
             return Crawlability::AlreadyTasked;
         }
 
+        // kept apart from the known links, so it works the same with approximate_dedup
+        if let Some(known_paths) = task_context_locked.get_known_paths() {
+            if dedup_key.contains('?') && known_paths.lock_recovering().contains(to_known_path(&dedup_key)) {
+                debug!("Dropping requested url: {} -> query variant of known path", &request_object_locked.url);
+                return Crawlability::QueryVariantSkipped;
+            }
+        }
+
+        if let Some(max_requests) = max_requests {
+            if task_context_locked.get_request_counter().load(Ordering::SeqCst) >= max_requests {
+                debug!("Dropping requested url: {} -> request budget exhausted: {}", &request_object_locked.url, max_requests);
+                return Crawlability::RequestBudgetExhausted;
+            }
+        }
+
+        if let Some(max_total_bytes) = max_total_bytes {
+            if task_context_locked.get_downloaded_bytes_counter().load(Ordering::SeqCst) >= max_total_bytes {
+                debug!("Dropping requested url: {} -> byte budget exhausted: {}", &request_object_locked.url, max_total_bytes);
+                return Crawlability::ByteBudgetExhausted;
+            }
+        }
+
         if !task_context_locked.can_access(&request_object_locked.url) {
             debug!("Dropping requested url: {} -> can't access (robots.txt)", &request_object_locked.url);
             return Crawlability::RestrictedByRobotsTxt;
         }
 
+        if let Some(max_hosts) = max_hosts {
+            let host = request_object_locked.get_host();
+            let all_seen_hosts = task_context_locked.get_all_seen_hosts();
+            let mut all_seen_hosts_locked = all_seen_hosts.lock_recovering();
+            if !all_seen_hosts_locked.contains(&host) {
+                if all_seen_hosts_locked.len() >= max_hosts {
+                    debug!("Dropping requested url: {} -> maximum number of hosts reached: {}", &request_object_locked.url, max_hosts);
+                    return Crawlability::HostLimitReached;
+                }
+                all_seen_hosts_locked.insert(host);
+            }
+        }
+
         Crawlability::Crawlable
     }
 
     async fn perform_crawl_internal(&self, http_client: Arc<dyn HttpClient>, task_context_uuid: Uuid, robots_txt_info_url: Option<String>) -> Result<Option<PageResponse>, Error> {
         let request_object_cloned = self.request_object.clone();
-        let url = request_object_cloned.lock().unwrap().url.clone();
-        request_object_cloned.lock().unwrap().task_context.lock().unwrap().get_all_tasked_links().lock().unwrap().push(url.clone());
-        let raw_url = request_object_cloned.lock().unwrap().raw_url.clone();
+        let url = request_object_cloned.lock_recovering().url.clone();
+        let task_context = request_object_cloned.lock_recovering().task_context.clone();
+        let dedup_key = to_dedup_key(&url, &task_context.lock_recovering().get_config().lock_recovering().dedup_key_rules);
+        if let Some(known_paths) = task_context.lock_recovering().get_known_paths() {
+            known_paths.lock_recovering().insert(to_known_path(&dedup_key).to_string());
+        }
+        let approximate_known_links = task_context.lock_recovering().get_approximate_known_links();
+        match approximate_known_links {
+            Some(approximate_known_links) => approximate_known_links.lock_recovering().insert(&dedup_key),
+            None => task_context.lock_recovering().get_all_tasked_links().lock_recovering().push(dedup_key),
+        }
+        let raw_url = request_object_cloned.lock_recovering().raw_url.clone();
         let mut page_response = PageResponse::new(url.clone(), raw_url, task_context_uuid);
-        let maximum_redirects = request_object_cloned.lock().unwrap().task_context.lock().unwrap().get_config().lock().unwrap().maximum_redirects;
-        let ignore_redirects = request_object_cloned.lock().unwrap().task_context.lock().unwrap().get_config().lock().unwrap().ignore_redirects;
-        let uri_service = request_object_cloned.lock().unwrap().task_context.lock().unwrap().get_uri_service();
-        let fetch_header_response = self.fetch_header_command.fetch_header(url.clone(), ignore_redirects, maximum_redirects, uri_service, http_client, None, robots_txt_info_url.clone()).await;
-        page_response = self.consume_fetch_header_response(robots_txt_info_url, request_object_cloned, page_response, fetch_header_response).await;
+        page_response.depth = Some(self.current_depth);
+        let record_normalizations = task_context.lock_recovering().get_config().lock_recovering().record_normalizations;
+        if record_normalizations {
+            let dedup_key_rules = task_context.lock_recovering().get_config().lock_recovering().dedup_key_rules.clone();
+            page_response.normalizations = Some(Self::get_normalizations(&page_response.original_requested_url_raw, &url, &dedup_key_rules));
+        }
+        let maximum_redirects = request_object_cloned.lock_recovering().task_context.lock_recovering().get_config().lock_recovering().maximum_redirects;
+        let ignore_redirects = request_object_cloned.lock_recovering().task_context.lock_recovering().get_config().lock_recovering().ignore_redirects;
+        let uri_service = request_object_cloned.lock_recovering().task_context.lock_recovering().get_uri_service();
+        let skip_head_request = request_object_cloned.lock_recovering().task_context.lock_recovering().get_config().lock_recovering().skip_head_request;
+        let follow_meta_refresh = request_object_cloned.lock_recovering().task_context.lock_recovering().get_config().lock_recovering().follow_meta_refresh;
+        let mut redirects = None;
+        let mut meta_refreshes = 0;
+        // a meta refresh is followed like a redirect, e.g. for a 200 refreshing to an error page
+        let mut page_response = loop {
+            let (fetch_header_response, prefetched_get_response) = if skip_head_request {
+                self.fetch_with_get(url.clone(), ignore_redirects, maximum_redirects, uri_service.clone(), http_client.clone(), redirects, robots_txt_info_url.clone()).await
+            } else {
+                (self.fetch_header_command.fetch_header(url.clone(), ignore_redirects, maximum_redirects, uri_service.clone(), http_client.clone(), redirects, robots_txt_info_url.clone()).await, None)
+            };
+            let hop_page_response = self.consume_fetch_header_response(robots_txt_info_url.clone(), request_object_cloned.clone(), page_response.clone(), fetch_header_response, prefetched_get_response).await;
+            redirects = if follow_meta_refresh && meta_refreshes < maximum_redirects { Self::to_meta_refresh_redirects(&hop_page_response, maximum_redirects) } else { None };
+            if redirects.is_none() {
+                break hop_page_response;
+            }
+            meta_refreshes += 1;
+        };
 
+        page_response.content_type = Self::get_content_type(&page_response);
+        Self::apply_headers_of_interest(&task_context, &mut page_response);
         page_response.response_timings.end_time = Some(DateTime::from(Utc::now()));
+        Self::flag_if_slow(&task_context, &mut page_response);
         Ok(Some(page_response))
     }
 
-    async fn consume_fetch_header_response(&self, robots_txt_info_url: Option<String>, request_object: Arc<Mutex<PageRequest>>, mut page_response: PageResponse, fetch_header_response: HeadResponseResult) -> PageResponse {
+    // the response time spans head, redirects and get of the page
+    fn flag_if_slow(task_context: &Arc<Mutex<dyn FullTaskContext>>, page_response: &mut PageResponse) {
+        let slow_threshold_ms = match task_context.lock_recovering().get_config().lock_recovering().slow_threshold_ms {
+            Some(slow_threshold_ms) => slow_threshold_ms,
+            None => return,
+        };
+        let response_time_ms = match (page_response.response_timings.start_time, page_response.response_timings.end_time) {
+            (Some(start_time), Some(end_time)) => (end_time - start_time).num_milliseconds().max(0) as u64,
+            _ => return,
+        };
+        if response_time_ms > slow_threshold_ms {
+            page_response.slow = true;
+            task_context.lock_recovering().get_response_times().lock_recovering().insert(page_response.original_requested_url.clone(), response_time_ms);
+        }
+    }
+
+    // the download knows better, e.g. after sniffing, but pages that aren't downloaded only have their head
+    fn get_content_type(page_response: &PageResponse) -> Option<String> {
+        page_response.get.as_ref().and_then(|get| get.headers.get(CONTENT_TYPE.as_str()))
+            .or_else(|| page_response.head.as_ref().and_then(|head| head.headers.get(CONTENT_TYPE.as_str())))
+            .and_then(|content_type| PageResponse::to_media_type(content_type))
+    }
+
+    // the full header maps are needed while crawling, so they are only reduced once the page is done
+    fn apply_headers_of_interest(task_context: &Arc<Mutex<dyn FullTaskContext>>, page_response: &mut PageResponse) {
+        let config = task_context.lock_recovering().get_config();
+        let headers_of_interest = config.lock_recovering().headers_of_interest.clone();
+        let keep_all_headers = config.lock_recovering().keep_all_headers;
+        if let Some(head) = page_response.head.as_mut() {
+            head.headers_of_interest = headers_of_interest.as_ref().map(|names| http_utils::select_headers(&head.headers, names));
+            if !keep_all_headers { head.headers.clear(); }
+        }
+        if let Some(get) = page_response.get.as_mut() {
+            get.headers_of_interest = headers_of_interest.as_ref().map(|names| http_utils::select_headers(&get.headers, names));
+            if !keep_all_headers { get.headers.clear(); }
+        }
+    }
+
+    // the redirect chain of a page, extended by the hop to its meta refresh target. pages refreshing to themselves aren't followed
+    fn to_meta_refresh_redirects(page_response: &PageResponse, maximum_redirects: u8) -> Option<Vec<Redirect>> {
+        let meta_refresh_url = page_response.meta_refresh_url.as_ref()?;
+        let head = page_response.head.as_ref()?;
+        let final_uri = head.get_final_uri();
+        if meta_refresh_url == &final_uri || head.redirect_count >= maximum_redirects as usize {
+            return None;
+        }
+        let mut redirect = Redirect::from(final_uri, meta_refresh_url.clone());
+        redirect.http_response_code = StatusCode { code: head.http_response_code.code, label: String::from(META_REFRESH_LABEL) };
+        let mut redirects = head.redirects.clone();
+        redirects.push(redirect);
+        Some(redirects)
+    }
+
+    // without HEAD, every hop is a GET. The GET of the final hop provides status and headers, and is kept as the download of the page
+    async fn fetch_with_get(&self, url: String, ignore_redirects: bool, maximum_redirects: u8, uri_service: Arc<UriService>, http_client: Arc<dyn HttpClient>, redirects: Option<Vec<Redirect>>, robots_txt_info_url: Option<String>) -> (HeadResponseResult, Option<GetResponse>) {
+        let task_context = self.request_object.lock_recovering().task_context.clone();
+        let collapse_identical_redirects = task_context.lock_recovering().get_config().lock_recovering().collapse_identical_redirects;
+        // only used to build the responses, the downloads themselves retry
+        let head_response_builder = DefaultFetchHeaderCommand {
+            retryable_status_codes: vec![],
+            max_requests_per_url: None,
+            ..DefaultFetchHeaderCommand::from_config(&task_context.lock_recovering().get_config().lock_recovering())
+        };
+        let start_time = DateTime::from(Utc::now());
+        let mut redirects = redirects.unwrap_or_default();
+        let mut uri = redirects.last().map_or(url, |redirect| redirect.destination.clone());
+        loop {
+            let hop_start_time = DateTime::from(Utc::now());
+            let get_response = match self.page_download_command.download_page(uri.clone(), http_client.clone(), robots_txt_info_url.clone()).await {
+                Ok(get_response) => get_response,
+                Err(error) => return (Err(error), None),
+            };
+            let is_redirect = hyper::StatusCode::from_u16(get_response.http_response_code.code).map_or(false, |status| status.is_redirection());
+            match get_response.headers.get("location") {
+                Some(location) if is_redirect && !ignore_redirects && redirects.len() < maximum_redirects as usize => {
+                    let (redirect, is_too_long) = head_response_builder.to_redirect_within_length(uri_service.clone(), uri, location, get_response.http_response_code.clone(), &get_response.headers, hop_start_time);
+                    uri = redirect.destination.clone();
+                    let is_collapsed = is_too_long || collapse_identical_redirects && redirect.is_self_redirect();
+                    redirects.push(redirect);
+                    if is_collapsed {
+                        let head_response = head_response_builder.to_head_response(uri, get_response.http_response_code.clone(), get_response.headers.clone(), redirects, start_time);
+                        return (Ok((head_response, http_client)), None);
+                    }
+                }
+                _ => {
+                    let head_response = head_response_builder.to_head_response(uri, get_response.http_response_code.clone(), get_response.headers.clone(), redirects, start_time);
+                    return (Ok((head_response, http_client)), Some(get_response));
+                }
+            }
+        }
+    }
+
+    async fn consume_fetch_header_response(&self, robots_txt_info_url: Option<String>, request_object: Arc<Mutex<PageRequest>>, mut page_response: PageResponse, fetch_header_response: HeadResponseResult, prefetched_get_response: Option<GetResponse>) -> PageResponse {
         if let Ok(result) = fetch_header_response {
             let (fetch_header_response, http_client) = result;
             let final_uri = fetch_header_response.get_final_uri();
             page_response.final_url_after_redirects = Some(final_uri.clone());
+            let capture_tls_info = request_object.lock_recovering().task_context.lock_recovering().get_config().lock_recovering().capture_tls_info;
+            if capture_tls_info {
+                page_response.tls_info = final_uri.parse::<hyper::Uri>().ok()
+                    .and_then(|uri| uri.host().map(|host| host.to_lowercase()))
+                    .and_then(|host| http_client.take_tls_info(&host));
+            }
 
             let headers = &fetch_header_response.headers;
-            let should_download = self.should_download_page(headers, &fetch_header_response.http_response_code);
+            let assume_html_when_missing_content_type = Self::get_assume_html_when_missing_content_type(request_object.clone());
+            let mut should_download = self.should_download_page(headers, &fetch_header_response.http_response_code, assume_html_when_missing_content_type);
+            let is_sniffing_required = !should_download
+                && headers.get(CONTENT_TYPE.as_str()).map_or(false, |content_type| is_ambiguous_content_type(content_type))
+                && hyper::StatusCode::from_u16(fetch_header_response.http_response_code.code).unwrap().is_success()
+                && Self::get_sniff_content_type(request_object.clone());
+            Self::apply_robots_directives(&mut page_response, fetch_header_response.x_robots_tag.as_ref());
+            if fetch_header_response.protocol_downgrade {
+                page_response.crawl_status = Some(CrawlStatus::ProtocolDowngrade);
+            }
+            if fetch_header_response.self_redirect {
+                page_response.crawl_status = Some(CrawlStatus::SelfRedirect);
+            }
+            if fetch_header_response.redirect_url_too_long {
+                page_response.crawl_status = Some(CrawlStatus::RedirectUrlTooLong);
+            }
+            page_response.meta_refresh_url = Self::get_refresh_header_url(request_object.clone(), headers);
+            let is_redirect_to_known = !fetch_header_response.redirects.is_empty() && Self::is_known_redirect_target(request_object.clone(), &final_uri);
             page_response.head = Some(fetch_header_response);
+            if is_redirect_to_known {
+                page_response.crawl_status = Some(CrawlStatus::RedirectToKnown);
+                return page_response;
+            }
+
+            if is_sniffing_required {
+                if let Some(get_response) = &prefetched_get_response {
+                    should_download = get_response.body.as_ref().map_or(false, |body| looks_like_html(body));
+                } else if !Self::is_request_budget_exhausted(request_object.clone()) {
+                    should_download = self.sniff_html(final_uri.clone(), http_client.clone(), robots_txt_info_url.clone()).await;
+                }
+            }
 
             if !should_download { return page_response; }
 
-            let page_download_response = self.page_download_command.download_page(final_uri.clone(), http_client, robots_txt_info_url.clone()).await;
-            page_response = self.consume_page_download_response(request_object, page_response, page_download_response);
+            let page_download_response = match prefetched_get_response {
+                Some(get_response) => Ok(get_response),
+                None => {
+                    if Self::is_request_budget_exhausted(request_object.clone()) {
+                        page_response.crawl_status = Some(CrawlStatus::RequestBudgetExhausted);
+                        return page_response;
+                    }
+                    self.page_download_command.download_page(final_uri.clone(), http_client.clone(), robots_txt_info_url.clone()).await
+                }
+            };
+            let download_result = match page_download_response {
+                Ok(download_result) => download_result,
+                // parallel workers may have used up the budget in the meantime, the client doesn't send the request then
+                Err(error) => {
+                    page_response.crawl_status = Some(if error == REQUEST_BUDGET_EXHAUSTED_ERROR { CrawlStatus::RequestBudgetExhausted } else { CrawlStatus::ConnectionError(error) });
+                    return page_response;
+                }
+            };
+            page_response = self.consume_page_download_response(request_object.clone(), page_response, download_result, is_sniffing_required).await;
+
+            let follow_sitemaps = request_object.lock_recovering().task_context.lock_recovering().get_config().lock_recovering().follow_sitemaps;
+            if follow_sitemaps {
+                let mut sitemap_links = self.load_sitemap_links(request_object.clone(), &page_response, http_client.clone(), robots_txt_info_url.clone()).await;
+                if let Some(links) = page_response.links.as_mut() {
+                    links.append(&mut sitemap_links);
+                }
+            }
+
+            let check_external_links = request_object.lock_recovering().task_context.lock_recovering().get_config().lock_recovering().check_external_links;
+            let has_external_check_hosts = request_object.lock_recovering().task_context.lock_recovering().get_config().lock_recovering().external_check_hosts.is_some();
+            if check_external_links || has_external_check_hosts {
+                page_response.external_link_checks = Some(Self::check_external_links(request_object, &page_response, http_client, robots_txt_info_url).await);
+            }
         } else {
-            page_response.crawl_status = Some(CrawlStatus::ConnectionError(fetch_header_response.err().unwrap().to_string()));
+            let error = fetch_header_response.err().unwrap().to_string();
+            page_response.crawl_status = Some(if error == REQUEST_BUDGET_EXHAUSTED_ERROR { CrawlStatus::RequestBudgetExhausted } else { CrawlStatus::ConnectionError(error) });
         }
 
         page_response
     }
 
-    fn consume_page_download_response(&self, request_object: Arc<Mutex<PageRequest>>, mut page_response: PageResponse, page_download_response: Result<GetResponse, String>) -> PageResponse {
-        if let Ok(download_result) = page_download_response {
-            if self.is_html(&download_result.headers) {
-                let request_object_locked = request_object.lock().unwrap();
-                page_response.links = Self::extract_links(
-                    request_object_locked.get_protocol(),
-                    request_object_locked.get_host(),
-                    download_result.body.as_ref(),
-                    request_object_locked.task_context.lock().unwrap().get_dom_parser(),
-                );
+    async fn consume_page_download_response(&self, request_object: Arc<Mutex<PageRequest>>, mut page_response: PageResponse, download_result: GetResponse, is_sniffed_html: bool) -> PageResponse {
+        let assume_html_when_missing_content_type = Self::get_assume_html_when_missing_content_type(request_object.clone());
+        if is_sniffed_html || self.is_html_document(&download_result, assume_html_when_missing_content_type) {
+            let (protocol, host, dom_parser) = {
+                let request_object_locked = request_object.lock_recovering();
+                let dom_parser = request_object_locked.task_context.lock_recovering().get_dom_parser();
+                (request_object_locked.get_protocol(), request_object_locked.get_host(), dom_parser)
+            };
+            let parse_start_time = DateTime::from(Utc::now());
+            let uri_result = Self::extract_links(protocol, host, download_result.body.clone(), dom_parser).await;
+            let request_object_locked = request_object.lock_recovering();
+            let task_context = request_object_locked.task_context.clone();
+            let extract_image_stats = task_context.lock_recovering().get_config().lock_recovering().extract_image_stats;
+            let detect_broken_anchors = task_context.lock_recovering().get_config().lock_recovering().detect_broken_anchors;
+            let detect_canonical_issues = task_context.lock_recovering().get_config().lock_recovering().detect_canonical_issues;
+            let detect_duplicate_content = task_context.lock_recovering().get_config().lock_recovering().detect_duplicate_content;
+            let thin_content_threshold = task_context.lock_recovering().get_config().lock_recovering().thin_content_threshold;
+            let pagination_no_depth = task_context.lock_recovering().get_config().lock_recovering().pagination_no_depth;
+            page_response.parse_timings = Some(ResponseTimings::from(format!("Parse.{}", request_object_locked.url), parse_start_time, DateTime::from(Utc::now())));
+            if let Some(uri_result) = uri_result {
+                Self::apply_robots_directives(&mut page_response, uri_result.meta_robots.as_ref());
+                page_response.content_text_length = Some(uri_result.content_text_length);
+                page_response.thin_content = thin_content_threshold.map_or(false, |threshold| uri_result.content_text_length < threshold);
+                page_response.language = uri_result.language.clone();
+                page_response.title = uri_result.title.clone();
+                page_response.meta_description = uri_result.meta_description.clone();
+                if detect_duplicate_content && hyper::StatusCode::from_u16(download_result.http_response_code.code).map_or(false, |status| status.is_success()) {
+                    Self::register_title_and_description(&request_object_locked, &page_response);
+                }
+                if extract_image_stats {
+                    page_response.images_total = Some(uri_result.images_total);
+                    page_response.images_missing_alt = Some(uri_result.images_missing_alt);
+                    page_response.images_missing_dimensions = Some(uri_result.images_missing_dimensions);
+                }
+                if detect_broken_anchors {
+                    Self::register_anchors(&request_object_locked, &page_response, &uri_result);
+                }
+                let mut links = uri_result.links;
+                page_response.canonical_url = links.iter()
+                    .find(|link| link.is_canonical())
+                    .map(|link| task_context.lock_recovering().get_uri_service().form_full_url(&request_object_locked.get_protocol(), &link.uri, &request_object_locked.get_host(), &Some(request_object_locked.url.clone())).to_string());
+                if detect_canonical_issues {
+                    Self::register_canonical(&request_object_locked, &page_response);
+                }
+                // a meta refresh in the document takes precedence over a Refresh header
+                page_response.meta_refresh_url = uri_result.meta_refresh.as_ref()
+                    .map(|meta_refresh| task_context.lock_recovering().get_uri_service().form_full_url(&request_object_locked.get_protocol(), meta_refresh, &request_object_locked.get_host(), &Some(request_object_locked.url.clone())).to_string())
+                    .or(page_response.meta_refresh_url.take());
+                if pagination_no_depth {
+                    links.append(&mut Self::get_link_header_pagination_links(&request_object_locked.get_host(), &download_result));
+                }
+                page_response.links = Some(links);
+            } else if download_result.body.is_some() {
+                page_response.crawl_status = Some(CrawlStatus::ParseSkipped);
             }
+        }
 
-            page_response.get = Some(download_result);
-        } else {
-            panic!("proper error handling needed")
+        Self::count_downloaded_bytes(request_object.clone(), &download_result);
+
+        // a 2xx without content often is a misconfigured server or a page only rendered by JavaScript
+        page_response.empty_body = hyper::StatusCode::from_u16(download_result.http_response_code.code).map_or(false, |status| status.is_success())
+            && download_result.body.as_ref().map_or(true, |body| body.trim().is_empty());
+        let report_empty_body = Self::get_report_empty_body(request_object.clone());
+        if page_response.empty_body && report_empty_body && page_response.crawl_status.is_none() {
+            page_response.crawl_status = Some(CrawlStatus::EmptyBody);
         }
 
+        Self::apply_robots_directives(&mut page_response, download_result.x_robots_tag.as_ref());
+        page_response.get = Some(download_result);
+
         page_response
     }
 
-    fn should_download_page(&self, headers: &HashMap<String, String>, status_code: &StatusCode) -> bool {
-        (hyper::StatusCode::from_u16(status_code.code).unwrap().is_success()
-            || headers.contains_key("x-cache") && headers.get("x-cache").unwrap().contains("cloudfront")
-        ) && self.is_html(headers)
+    // paginated APIs often announce their next page only via Link: <...>; rel="next"
+    fn get_link_header_pagination_links(host: &str, download_result: &GetResponse) -> Vec<Link> {
+        let link_header = match download_result.headers.get(LINK.as_str()) {
+            Some(link_header) => link_header,
+            None => return vec![],
+        };
+        let link_type_checker = LinkTypeChecker::new(host);
+        http_utils::parse_link_header_next(link_header).into_iter()
+            .map(|uri| {
+                let mut link = Link::from_str_with_scope(&uri, link_type_checker.get_uri_scope(host, &uri));
+                link.source_tag = Some(String::from(LINK_HEADER_SOURCE_TAG));
+                link.rel = Some(String::from("next"));
+                link
+            })
+            .collect()
+    }
+
+    // directives from X-Robots-Tag headers and meta robots are combined, any source may set noindex or nofollow
+    fn apply_robots_directives(page_response: &mut PageResponse, directives: Option<&String>) {
+        if let Some(directives) = directives {
+            let (noindex, nofollow) = http_utils::parse_robots_directives(directives);
+            page_response.noindex = page_response.noindex || noindex;
+            page_response.nofollow = page_response.nofollow || nofollow;
+        }
+    }
+
+    // the urls of sitemaps referenced by <link rel="sitemap"> are seeded like links of the page
+    async fn load_sitemap_links(&self, request_object: Arc<Mutex<PageRequest>>, page_response: &PageResponse, http_client: Arc<dyn HttpClient>, robots_txt_info_url: Option<String>) -> Vec<Link> {
+        let (protocol, host, url, task_context) = {
+            let request_object_locked = request_object.lock_recovering();
+            (request_object_locked.get_protocol(), request_object_locked.get_host(), request_object_locked.url.clone(), request_object_locked.task_context.clone())
+        };
+        let uri_service = task_context.lock_recovering().get_uri_service();
+        let followed_sitemaps = task_context.lock_recovering().get_followed_sitemaps();
+        let sitemap_urls: Vec<String> = page_response.links.iter()
+            .flatten()
+            .filter(|link| link.is_sitemap())
+            .map(|link| uri_service.form_full_url(&protocol, &link.uri, &host, &Some(url.clone())).to_string())
+            .filter(|sitemap_url| followed_sitemaps.lock_recovering().insert(sitemap_url.clone()))
+            .collect();
+
+        let link_type_checker = LinkTypeChecker::new(&host);
+        let mut links = vec![];
+        for sitemap_url in sitemap_urls {
+            if Self::is_request_budget_exhausted(request_object.clone()) { break; }
+            debug!("Loading sitemap {} referenced by {}", sitemap_url, url);
+            let sitemap_body = match self.page_download_command.download_page(sitemap_url.clone(), http_client.clone(), robots_txt_info_url.clone()).await {
+                Ok(get_response) => get_response.body.unwrap_or_default(),
+                Err(error) => {
+                    debug!("Couldn't load sitemap {}: {}", sitemap_url, error);
+                    continue;
+                }
+            };
+            links.extend(extract_sitemap_urls(&sitemap_body).into_iter().map(|uri| {
+                let mut link = Link::from_str_with_scope(&uri, link_type_checker.get_uri_scope(&host, &uri));
+                link.source_tag = Some(String::from(SITEMAP_SOURCE_TAG));
+                link
+            }));
+        }
+        links
+    }
+
+    // error pages and pages excluded from the index may share their title and description without harm
+    fn register_title_and_description(request_object: &PageRequest, page_response: &PageResponse) {
+        if page_response.noindex {
+            return;
+        }
+        let task_context_locked = request_object.task_context.lock_recovering();
+        if let Some(title) = &page_response.title {
+            task_context_locked.get_titles().lock_recovering().insert(request_object.url.clone(), title.clone());
+        }
+        if let Some(meta_description) = &page_response.meta_description {
+            task_context_locked.get_descriptions().lock_recovering().insert(request_object.url.clone(), meta_description.clone());
+        }
+    }
+
+    // canonicals are followed across pages once the crawl is complete, so they are known by requested and final url
+    fn register_canonical(request_object: &PageRequest, page_response: &PageResponse) {
+        let canonical_url = match &page_response.canonical_url {
+            Some(canonical_url) => canonical_url,
+            None => return,
+        };
+        let canonicals = request_object.task_context.lock_recovering().get_canonicals();
+        let mut canonicals_locked = canonicals.lock_recovering();
+        canonicals_locked.insert(request_object.url.clone(), canonical_url.clone());
+        if let Some(final_url) = &page_response.final_url_after_redirects {
+            canonicals_locked.insert(final_url.clone(), canonical_url.clone());
+        }
+    }
+
+    fn register_anchors(request_object: &PageRequest, page_response: &PageResponse, uri_result: &UriResult) {
+        let task_context_locked = request_object.task_context.lock_recovering();
+        let anchor_registry = task_context_locked.get_anchor_registry();
+        let mut anchor_registry_locked = anchor_registry.lock_recovering();
+        anchor_registry_locked.add_page_element_ids(request_object.url.clone(), uri_result.element_ids.clone());
+        if let Some(final_url) = &page_response.final_url_after_redirects {
+            anchor_registry_locked.add_page_element_ids(final_url.clone(), uri_result.element_ids.clone());
+        }
+
+        let uri_service = task_context_locked.get_uri_service();
+        let protocol = request_object.get_protocol();
+        let host = request_object.get_host();
+        for link in uri_result.links.iter() {
+            let is_internal = matches!(link.scope, Some(UriScope::Root) | Some(UriScope::SameDomain) | Some(UriScope::DifferentSubDomain) | Some(UriScope::Anchor));
+            let (target, fragment) = match link.uri.split_once('#') {
+                Some(parts) if is_internal => parts,
+                _ => continue,
+            };
+            // an empty fragment and #top always refer to the top of the page
+            if fragment.is_empty() || fragment.eq_ignore_ascii_case("top") {
+                continue;
+            }
+            let target_url = if target.is_empty() {
+                request_object.url.clone()
+            } else {
+                uri_service.form_full_url(&protocol, target, &host, &Some(request_object.url.clone())).to_string()
+            };
+            anchor_registry_locked.add_anchor_link(request_object.url.clone(), target_url, fragment.to_string());
+        }
+    }
+
+    fn is_request_budget_exhausted(request_object: Arc<Mutex<PageRequest>>) -> bool {
+        let task_context = request_object.lock_recovering().task_context.clone();
+        let task_context_locked = task_context.lock_recovering();
+        let max_requests = task_context_locked.get_config().lock_recovering().max_requests;
+        max_requests.map_or(false, |max_requests| task_context_locked.get_request_counter().load(Ordering::SeqCst) >= max_requests)
     }
 
-    fn is_html(&self, headers: &HashMap<String, String>) -> bool {
-        headers.contains_key(CONTENT_TYPE.as_str()) &&
-            headers.get(CONTENT_TYPE.as_str()).unwrap().contains("text/html")
+    // downloads in flight are still completed, even if they exceed max_total_bytes
+    fn count_downloaded_bytes(request_object: Arc<Mutex<PageRequest>>, download_result: &GetResponse) {
+        let task_context = request_object.lock_recovering().task_context.clone();
+        let task_context_locked = task_context.lock_recovering();
+        if task_context_locked.get_config().lock_recovering().max_total_bytes.is_some() {
+            let downloaded_bytes = download_result.body.as_ref().map_or(0, |body| body.len() as u64);
+            task_context_locked.get_downloaded_bytes_counter().fetch_add(downloaded_bytes, Ordering::SeqCst);
+        }
+    }
+
+    fn get_assume_html_when_missing_content_type(request_object: Arc<Mutex<PageRequest>>) -> bool {
+        let task_context = request_object.lock_recovering().task_context.clone();
+        let config = task_context.lock_recovering().get_config();
+        let assume_html_when_missing_content_type = config.lock_recovering().assume_html_when_missing_content_type;
+        assume_html_when_missing_content_type
     }
 
-    fn extract_links(protocol: String, host: String, body: Option<&String>, dom_parser: Arc<dyn DomParser>) -> Option<Vec<Link>> {
-        if let Some(body_content) = body {
-            let links = dom_parser.get_links(
-                &protocol,
-                &host,
-                body_content);
+    // a single HEAD per external link, without following redirects or crawling any further. Assets are skipped without check_assets.
+    // with external_check_hosts, only links on those hosts are checked, including the ones on other subdomains
+    async fn check_external_links(request_object: Arc<Mutex<PageRequest>>, page_response: &PageResponse, http_client: Arc<dyn HttpClient>, robots_txt_info_url: Option<String>) -> Vec<ExternalLinkCheck> {
+        let protocol = request_object.lock_recovering().get_protocol();
+        let host = request_object.lock_recovering().get_host();
+        let uri_service = request_object.lock_recovering().task_context.lock_recovering().get_uri_service();
+        let external_check_hosts = request_object.lock_recovering().task_context.lock_recovering().get_config().lock_recovering().external_check_hosts.clone();
+        let check_assets = request_object.lock_recovering().task_context.lock_recovering().get_config().lock_recovering().check_assets;
+        let is_checked_scope = |link: &&Link| link.scope == Some(UriScope::External)
+            || (external_check_hosts.is_some() && link.scope == Some(UriScope::DifferentSubDomain));
+        let mut external_uris: Vec<(String, ResourceKind)> = page_response.links.iter().flatten()
+            .filter(is_checked_scope)
+            .filter(|link| check_assets || !ResourceKind::is_asset(link))
+            .map(|link| (uri_service.form_full_url(&protocol, &link.uri, &host, &Some(page_response.original_requested_url.clone())).to_string(), ResourceKind::of(link)))
+            .filter(|(uri, _)| external_check_hosts.as_ref().map_or(true, |external_check_hosts| http_utils::is_on_host(uri, external_check_hosts)))
+            .collect();
+        external_uris.sort_by(|a, b| a.0.cmp(&b.0));
+        external_uris.dedup_by(|a, b| a.0 == b.0);
+
+        // assets have their own politeness settings, so they are checked via the asset client
+        let asset_http_client = if external_uris.iter().any(|(_, resource_kind)| *resource_kind == ResourceKind::Asset) {
+            Some(request_object.lock_recovering().task_context.lock_recovering().get_asset_http_client())
+        } else {
+            None
+        };
 
-            return match links {
-                None => None,
-                Some(links) => Some(links.links)
+        let mut external_link_checks = vec![];
+        for (uri, resource_kind) in external_uris {
+            if Self::is_request_budget_exhausted(request_object.clone()) {
+                break;
+            }
+            let client = match (resource_kind, &asset_http_client) {
+                (ResourceKind::Asset, Some(asset_http_client)) => asset_http_client.clone(),
+                _ => http_client.clone(),
             };
+            let external_link_check = match client.head(uri.clone(), robots_txt_info_url.clone()).await {
+                Ok(response) => ExternalLinkCheck { uri, http_response_code: Some(http_utils::map_status_code(response.status())), error: None },
+                Err(HttpClientError::RequestBudgetExhausted) => break,
+                Err(error) => ExternalLinkCheck { uri, http_response_code: None, error: Some(error.to_string()) },
+            };
+            external_link_checks.push(external_link_check);
+        }
+        external_link_checks
+    }
+
+    fn get_report_empty_body(request_object: Arc<Mutex<PageRequest>>) -> bool {
+        let task_context = request_object.lock_recovering().task_context.clone();
+        let config = task_context.lock_recovering().get_config();
+        let report_empty_body = config.lock_recovering().report_empty_body;
+        report_empty_body
+    }
+
+    fn get_normalizations(raw_url: &str, url: &str, dedup_key_rules: &DedupKeyRules) -> Vec<UrlNormalization> {
+        let mut normalizations = if raw_url != url { vec![UrlNormalization::Resolved] } else { vec![] };
+        normalizations.append(&mut to_dedup_key_with_normalizations(url, dedup_key_rules).1);
+        normalizations
+    }
+
+    // the number of non-empty path segments, e.g. 0 for / and 2 for /blog/post/
+    fn get_path_depth(url: &str) -> usize {
+        url.parse::<hyper::Uri>()
+            .map_or(0, |uri| uri.path().split('/').filter(|segment| !segment.is_empty()).count())
+    }
+
+    // several urls redirecting to the same page would otherwise download it again and again
+    fn is_known_redirect_target(request_object: Arc<Mutex<PageRequest>>, final_uri: &str) -> bool {
+        let task_context = request_object.lock_recovering().task_context.clone();
+        let task_context_locked = task_context.lock_recovering();
+        let config = task_context_locked.get_config();
+        let config_locked = config.lock_recovering();
+        if !config_locked.skip_known_redirect_targets {
+            return false;
+        }
+        let dedup_key = to_dedup_key(final_uri, &config_locked.dedup_key_rules);
+        let is_known = task_context_locked.get_all_crawled_links().lock_recovering().contains(&dedup_key);
+        is_known
+    }
+
+    // "Refresh: 0; url=/target" is the header equivalent of a meta refresh and is followed the same way
+    fn get_refresh_header_url(request_object: Arc<Mutex<PageRequest>>, headers: &HashMap<String, String>) -> Option<String> {
+        let refresh_url = headers.get("refresh").and_then(|refresh| parse_meta_refresh_url(refresh))?;
+        let request_object_locked = request_object.lock_recovering();
+        let uri_service = request_object_locked.task_context.lock_recovering().get_uri_service();
+        Some(uri_service.form_full_url(&request_object_locked.get_protocol(), &refresh_url, &request_object_locked.get_host(), &Some(request_object_locked.url.clone())).to_string())
+    }
+
+    fn get_sniff_content_type(request_object: Arc<Mutex<PageRequest>>) -> bool {
+        let task_context = request_object.lock_recovering().task_context.clone();
+        let config = task_context.lock_recovering().get_config();
+        let sniff_content_type = config.lock_recovering().sniff_content_type;
+        sniff_content_type
+    }
+
+    // some servers declare html as e.g. application/octet-stream, so only the beginning of the body can tell
+    async fn sniff_html(&self, uri: String, http_client: Arc<dyn HttpClient>, robots_txt_info_url: Option<String>) -> bool {
+        self.page_download_command.download_page_prefix(uri, http_client, robots_txt_info_url, SNIFF_PREFIX_BYTES).await
+            .map_or(false, |prefix| prefix.body.as_ref().map_or(false, |body| looks_like_html(body)))
+    }
+
+    fn should_download_page(&self, headers: &HashMap<String, String>, status_code: &StatusCode, assume_html_when_missing_content_type: bool) -> bool {
+        (hyper::StatusCode::from_u16(status_code.code).unwrap().is_success()
+            || headers.contains_key("x-cache") && headers.get("x-cache").unwrap().contains("cloudfront")
+        ) && self.is_html(headers, assume_html_when_missing_content_type)
+    }
+
+    fn is_html(&self, headers: &HashMap<String, String>, assume_html_when_missing_content_type: bool) -> bool {
+        match headers.get(CONTENT_TYPE.as_str()) {
+            Some(content_type) => content_type.contains("text/html"),
+            None => assume_html_when_missing_content_type,
+        }
+    }
+
+    // without a content-type, the body itself has to confirm, that it actually is html
+    fn is_html_document(&self, get_response: &GetResponse, assume_html_when_missing_content_type: bool) -> bool {
+        if get_response.headers.contains_key(CONTENT_TYPE.as_str()) {
+            return self.is_html(&get_response.headers, false);
         }
-        return None;
+        assume_html_when_missing_content_type && get_response.body.as_ref().map_or(false, |body| looks_like_html(body))
     }
+
+    // parsing is cpu bound, so it runs on the blocking pool, with at most one parse per cpu at a time
+    async fn extract_links(protocol: String, host: String, body: Option<String>, dom_parser: Arc<dyn DomParser>) -> Option<UriResult> {
+        let body_content = body?;
+        let _permit = get_parse_limiter().acquire().await.ok()?;
+        tokio::task::spawn_blocking(move || dom_parser.get_links(&protocol, &host, &body_content)).await.ok().flatten()
+    }
+}
+
+static PARSE_LIMITER: OnceLock<Semaphore> = OnceLock::new();
+
+fn get_parse_limiter() -> &'static Semaphore {
+    PARSE_LIMITER.get_or_init(|| Semaphore::new(num_cpus::get()))
+}
+
+const SNIFF_PREFIX_BYTES: usize = 1024;
+const LINK_HEADER_SOURCE_TAG: &str = "link-header";
+// reported as label of the status code of meta refresh hops
+const META_REFRESH_LABEL: &str = "Meta Refresh";
+
+// only these are declared for html by misconfigured servers, any other content-type isn't worth an extra request
+fn is_ambiguous_content_type(content_type: &str) -> bool {
+    let mime_type = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+    mime_type.is_empty() || mime_type == "application/octet-stream" || mime_type == "text/plain"
+}
+
+fn looks_like_html(body: &str) -> bool {
+    let prefix = body.chars().take(SNIFF_PREFIX_BYTES).collect::<String>().to_lowercase();
+    prefix.contains("<!doctype html") || prefix.contains("<html")
 }
 
 #[async_trait]
 impl CrawlCommand for PageCrawlCommand {
-    fn get_url_clone(&self) -> String { self.request_object.lock().unwrap().url.clone() }
+    fn get_url_clone(&self) -> String { self.request_object.lock_recovering().url.clone() }
 
     fn get_page_request(&self) -> Arc<Mutex<PageRequest>> { self.request_object.clone() }
 
@@ -195,19 +782,25 @@ impl CrawlCommand for PageCrawlCommand {
             Crawlability::Crawlable => return self.perform_crawl_internal(http_client, task_context_uuid, robots_txt_info_url).await,
             Crawlability::RestrictedByRobotsTxt => status = Some(CrawlStatus::RestrictedByRobotsTxt),
             Crawlability::MaxDepthReached => status = Some(CrawlStatus::MaximumCrawlDepthReached),
+            Crawlability::MaxPathDepthReached => status = Some(CrawlStatus::MaxPathDepthReached),
+            Crawlability::HostLimitReached => status = Some(CrawlStatus::HostLimitReached),
+            Crawlability::RequestBudgetExhausted => status = Some(CrawlStatus::RequestBudgetExhausted),
+            Crawlability::ByteBudgetExhausted => status = Some(CrawlStatus::ByteBudgetExhausted),
+            Crawlability::QueryVariantSkipped => status = Some(CrawlStatus::QueryVariantSkipped),
         }
 
-        let request_object_locked = self.request_object.lock().unwrap();
+        let request_object_locked = self.request_object.lock_recovering();
         let requested_url = request_object_locked.url.clone();
         let raw_url = request_object_locked.raw_url.clone();
         let mut response = PageResponse::new(requested_url, raw_url, task_context_uuid);
         response.crawl_status = status;
+        response.depth = Some(self.current_depth);
         response.response_timings.end_time = Some(DateTime::from(Utc::now()));
         return Ok(Some(response));
     }
 
     fn get_task_context(&self) -> Arc<Mutex<dyn FullTaskContext>> {
-        self.request_object.lock().unwrap().task_context.clone()
+        self.request_object.lock_recovering().task_context.clone()
     }
 
     fn get_current_depth(&self) -> u16 { self.current_depth }
@@ -218,14 +811,29 @@ impl CrawlCommand for PageCrawlCommand {
 #[cfg(test)]
 mod tests {
     use std::cmp::Ordering;
+    use std::collections::{BTreeMap, HashSet};
     use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicU64, AtomicUsize};
 
     use hyper::{Body, Response};
     use hyper::header::CONTENT_TYPE;
     use mockall::*;
+    use responses::broken_anchor::BrokenAnchor;
+    use responses::canonical_issue::CanonicalIssue;
+    use responses::duplicate_content::DuplicateContentCluster;
+    use responses::slow_page::SlowPage;
+    use responses::dedup_key_rules::DedupKeyRules;
+    use responses::domain_scope::DomainScope;
     use responses::get_response::GetResponse;
     use responses::head_response::HeadResponse;
     use responses::redirect::Redirect;
+    use responses::robots_error_policy::RobotsErrorPolicy;
+    use responses::robots_fetch_timeout_policy::RobotsFetchTimeoutPolicy;
+    use responses::robots_txt_summary::RobotsTxtSummary;
+    use responses::run_config::RunConfig;
+    use responses::soft_limit::SoftLimit;
+    use responses::terminal_reason::TerminalReason;
+    use responses::url_fingerprint::UrlFingerprint;
     use tokio::sync::mpsc::Sender;
     use tokio::time::Instant;
     use uuid::Uuid;
@@ -237,6 +845,10 @@ mod tests {
 
     use crate::commands::page_crawl_command::{CrawlCommand, HeadResponseResult, PageCrawlCommand};
     use crate::events::crawler_event::CrawlerEvent;
+    use crate::http::http_client::{HttpClientResult, REQUEST_BUDGET_EXHAUSTED_ERROR};
+    use crate::task_context::anchor_registry::AnchorRegistry;
+    use crate::task_context::bloom_filter::BloomFilter;
+    use crate::task_context::path_patterns::PathPatterns;
     use crate::task_context::robots_service::RobotsTxt;
     use crate::task_context::task_context::*;
 
@@ -252,19 +864,44 @@ mod tests {
             fn set_last_command_received(&mut self, instant: Instant);
             fn can_be_garbage_collected(&self, gc_timeout_ms: u64) -> bool;
             fn get_response_channel(&self) -> &Sender<CrawlerEvent>;
+            fn get_request_counter(&self) -> Arc<AtomicUsize>;
+            fn get_downloaded_bytes_counter(&self) -> Arc<AtomicU64>;
+            fn get_page_counter(&self) -> Arc<AtomicUsize>;
+            fn get_broken_anchors(&self) -> Option<Vec<BrokenAnchor>>;
+            fn get_canonical_issues(&self) -> Option<Vec<CanonicalIssue>>;
+            fn get_duplicate_content(&self) -> Option<Vec<DuplicateContentCluster>>;
+            fn get_slowest_pages(&self) -> Option<Vec<SlowPage>>;
+            fn is_first_soft_limit_warning(&self, soft_limit: SoftLimit) -> bool;
+            fn get_robots_txt_summary(&self) -> Option<RobotsTxtSummary>;
+            fn cancel(&self);
+            fn set_crawl_delay_ms(&self, crawl_delay_ms: usize);
+            fn get_terminal_reason(&self) -> TerminalReason;
+            fn get_crawl_started_at(&self) -> DateTime<Utc>;
+            fn get_effective_config(&self) -> Option<RunConfig>;
         }
         impl TaskContextServices for MyTaskContext{
             fn get_uri_service(&self) -> Arc<UriService>;
             fn get_dom_parser(&self) ->Arc<dyn DomParser>;
             fn get_http_client(&self) -> Arc<dyn HttpClient>;
+            fn get_asset_http_client(&self) -> Arc<dyn HttpClient>;
         }
         impl KnownLinks for MyTaskContext{
             fn get_all_crawled_links(&self) -> Arc<Mutex<Vec<String>>>;
             fn get_all_tasked_links(&self) -> Arc<Mutex<Vec<String>>>;
             fn add_crawled_link(&self, link: String);
+            fn get_all_seen_hosts(&self) -> Arc<Mutex<HashSet<String>>>;
+            fn get_anchor_registry(&self) -> Arc<Mutex<AnchorRegistry>>;
+            fn get_canonicals(&self) -> Arc<Mutex<BTreeMap<String, String>>>;
+            fn get_followed_sitemaps(&self) -> Arc<Mutex<HashSet<String>>>;
+            fn get_titles(&self) -> Arc<Mutex<BTreeMap<String, String>>>;
+            fn get_descriptions(&self) -> Arc<Mutex<BTreeMap<String, String>>>;
+            fn get_response_times(&self) -> Arc<Mutex<BTreeMap<String, u64>>>;
+            fn get_approximate_known_links(&self) -> Option<Arc<Mutex<BloomFilter>>>;
+            fn get_known_paths(&self) -> Option<Arc<Mutex<HashSet<String>>>>;
         }
         impl RobotsTxt for MyTaskContext{
             fn can_access(&self, item_uri: &str) -> bool;
+            fn get_summary(&self) -> RobotsTxtSummary;
         }
         impl Registrar for MyTaskContext {
             fn register_crawl_command(&self, uuid:Uuid, url:String);
@@ -284,8 +921,8 @@ mod tests {
         MyHttpClient {}
         #[async_trait]
         impl HttpClient for MyHttpClient{
-            async fn head(&self, uri: String, robots_txt_info_url: Option<String>) -> hyper::Result<Response<Body>>;
-            async fn get(&self, uri: String, robots_txt_info_url: Option<String>) -> hyper::Result<Response<Body>>;
+            async fn head(&self, uri: String, robots_txt_info_url: Option<String>) -> HttpClientResult;
+            async fn get(&self, uri: String, robots_txt_info_url: Option<String>) -> HttpClientResult;
         }
     }
     mock! {
@@ -302,6 +939,7 @@ mod tests {
         #[async_trait]
         impl PageDownloadCommand for MyPageDownloadCommand{
                 async fn download_page(&self, uri: String, http_client: Arc<dyn HttpClient>, robots_txt_info_url: Option<String>) -> Result<GetResponse, String>;
+                async fn download_page_prefix(&self, uri: String, http_client: Arc<dyn HttpClient>, robots_txt_info_url: Option<String>, max_bytes: usize) -> Result<GetResponse, String>;
         }
     }
 
@@ -316,6 +954,89 @@ mod tests {
             user_agent: "".to_string(),
             robots_txt_info_url: None,
             crawl_delay_ms: 1,
+            extract_image_stats: false,
+            seed_retries: 0,
+            seed_retry_backoff_ms: 0,
+            max_hosts: None,
+            body_preprocessors: vec![],
+            strip_session_ids: true,
+            session_query_params: vec![],
+            max_requests: None,
+            max_total_bytes: None,
+            detect_broken_anchors: false,
+            ignore_query_variants_of_known_paths: false,
+            rng_seed: None,
+            assume_html_when_missing_content_type: false,
+            sniff_content_type: false,
+            check_external_links: false,
+            robots_fetch_timeout_ms: 0,
+            robots_fetch_timeout_policy: RobotsFetchTimeoutPolicy::AllowAll,
+            emit_link_events: false,
+            deterministic: false,
+            max_parse_ms: None,
+            accept_encoding: String::from("gzip, deflate, br"),
+            soft_limit_percent: None,
+            emit_soft_limit_events: false,
+            pool_idle_timeout_ms: 90000,
+            pool_max_idle_per_host: 32,
+            report_robots_txt: false,
+            approximate_dedup: false,
+            collapse_trailing_slash_redirects: false,
+            max_link_text_len: 256,
+            respect_nofollow: false,
+            global_requests_per_sec: None,
+            seed_known_links: vec![],
+            recrawl_only_failed: None,
+            strip_default_ports: true,
+            max_duration_ms: None,
+            max_page_duration_ms: None,
+            robots_on_error: RobotsErrorPolicy::AllowAll,
+            skip_alternate_variants: false,
+            asset_crawl_delay_ms: 0,
+            asset_max_concurrent_requests: None,
+            include_effective_config: false,
+            extract_json_ld_links: false,
+            thin_content_threshold: None,
+            pagination_no_depth: false,
+            max_reported_redirects: None,
+            skip_extensions: vec![],
+            only_extensions: None,
+            allowed_subdomains: None,
+            external_check_hosts: None,
+            warmup: false,
+            capture_tls_info: false,
+            max_stored_links: None,
+            domain_scope: DomainScope::Host,
+            skip_head_request: false,
+            dedup_by_canonical: false,
+            dedup_key_rules: DedupKeyRules::default(),
+            report_empty_body: false,
+            follow_meta_refresh: false,
+            link_attributes: vec![],
+            path_patterns: PathPatterns::default(),
+            check_assets: true,
+            retryable_status_codes: vec![],
+            collapse_identical_redirects: false,
+            homepage_plus_one: false,
+            detect_canonical_issues: false,
+            headers_of_interest: None,
+            keep_all_headers: true,
+            follow_sitemaps: false,
+            detect_duplicate_content: false,
+            jitter_window: None,
+            max_requests_per_url: None,
+            max_url_length: None,
+            device_profile: None,
+            extract_microdata_links: false,
+            slow_threshold_ms: None,
+            report_content_types: None,
+            strict_rate_limit: false,
+            collapse_root_query: false,
+            skip_known_redirect_targets: false,
+            heuristic_link_extraction: false,
+            max_path_depth: None,
+            record_normalizations: false,
+            robots_sitemap_audit: false,
         }))
     }
 
@@ -328,6 +1049,8 @@ mod tests {
         // given: a task context with maximum_depth > 0
         let url = String::from("https://example.com");
         let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
         let url_clone = url.clone();
         mock_task_context.expect_get_url().return_const(url_clone);
         let config = get_default_task_config();
@@ -364,6 +1087,8 @@ mod tests {
         let url = String::from("https://example.com");
         let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com"))));
         let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
         mock_task_context.expect_get_uri_service().return_const(uri_service.clone());
         mock_task_context.expect_get_url().return_const(url.clone());
         mock_task_context.expect_get_all_crawled_links().return_const(Arc::new(Mutex::new(vec![])));
@@ -405,6 +1130,8 @@ mod tests {
         // given: a task context with a known link
         let url = String::from("https://example.com");
         let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
         mock_task_context.expect_get_url().return_const(url.clone());
         let config = get_default_task_config();
         mock_task_context.expect_get_config().return_const(config.clone());
@@ -436,6 +1163,8 @@ mod tests {
         let url = String::from("https://example.com");
         let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com"))));
         let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
         mock_task_context.expect_get_uri_service().return_const(uri_service.clone());
         mock_task_context.expect_get_url().return_const(url.clone());
         let config = get_default_task_config();
@@ -476,6 +1205,8 @@ mod tests {
         // given: a task context with a known link
         let url = String::from("https://example.com");
         let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
         mock_task_context.expect_get_url().return_const(url.clone());
         let config = get_default_task_config();
         mock_task_context.expect_get_config().return_const(config.clone());
@@ -508,6 +1239,8 @@ mod tests {
         let url = String::from("https://example.com");
         let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com"))));
         let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
         mock_task_context.expect_get_uri_service().return_const(uri_service.clone());
         mock_task_context.expect_get_url().return_const(url.clone());
         let config = get_default_task_config();
@@ -552,6 +1285,8 @@ mod tests {
         // given: a task context with robots_txt disallowing crawling
         let url = String::from("https://example.com");
         let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
         mock_task_context.expect_get_url().return_const(url.clone());
         let config = get_default_task_config();
         mock_task_context.expect_get_config().return_const(config.clone());
@@ -589,6 +1324,8 @@ mod tests {
         let url = String::from("https://example.com");
         let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com"))));
         let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
         mock_task_context.expect_get_uri_service().return_const(uri_service.clone());
         mock_task_context.expect_get_url().return_const(url.clone());
         let config = get_default_task_config();
@@ -629,6 +1366,8 @@ mod tests {
         let url = String::from("https://example.com");
         let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com"))));
         let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
         mock_task_context.expect_get_uri_service().return_const(uri_service.clone());
         mock_task_context.expect_get_url().return_const(url.clone());
         let config = get_default_task_config();
@@ -676,6 +1415,8 @@ mod tests {
         let url = String::from("https://example.com");
         let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com"))));
         let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
         mock_task_context.expect_get_uri_service().return_const(uri_service.clone());
         mock_task_context.expect_get_url().return_const(url.clone());
         let config = get_default_task_config();
@@ -720,6 +1461,8 @@ mod tests {
 
         let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com"))));
         let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
         mock_task_context.expect_get_uri_service().return_const(uri_service.clone());
         mock_task_context.expect_get_url().return_const(url.clone());
 
@@ -787,18 +1530,18 @@ mod tests {
         assert_eq!(crawl_result_unwrapped.final_url_after_redirects.as_ref().unwrap(), "https://final-redirection.example.com", "Should have final_url_after_redirects set to requested url");
     }
 
-    #[test]
-    fn extract_links_invokes_dom_parser() {
+    #[tokio::test]
+    async fn extract_links_invokes_dom_parser() {
         // given: a test body
         let body = String::from("<a href=\"https://www.example.com\">");
         let dom_parser = Arc::new(DomParserService::new(Arc::new(LinkTypeChecker::new("example.com"))));
 
         // when: extract_links is invoked
-        let result = PageCrawlCommand::extract_links("https".into(), "example.com".into(), Some(&body), dom_parser);
+        let result = PageCrawlCommand::extract_links("https".into(), "example.com".into(), Some(body), dom_parser).await;
 
         // then: result contains 1 link
         assert_eq!(result.is_some(), true, "Should contain a result");
-        assert_eq!(result.unwrap().len(), 1, "Should contain exactly one link");
+        assert_eq!(result.unwrap().links.len(), 1, "Should contain exactly one link");
     }
 
     #[tokio::test]
@@ -807,6 +1550,8 @@ mod tests {
         let url = String::from("https://example.com");
         let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com"))));
         let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
         mock_task_context.expect_get_uri_service().return_const(uri_service.clone());
         mock_task_context.expect_get_url().return_const(url.clone());
         let config = get_default_task_config();
@@ -851,6 +1596,8 @@ mod tests {
         let url = format!("https://{}", domain);
         let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new(domain))));
         let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
         mock_task_context.expect_get_uri_service().return_const(uri_service.clone());
         mock_task_context.expect_get_url().return_const(url.clone());
         let config = get_default_task_config();
@@ -889,4 +1636,1407 @@ mod tests {
         assert_eq!(crawl_result_unwrapped.crawl_status.is_some(), true, "Should have crawl_status, if error occurred");
         assert_eq!(crawl_result_unwrapped.crawl_status.unwrap(), CrawlStatus::ConnectionError(String::from("Some nasty shit happened.")), "Should have crawl_status == ConnectionError, if error occurred");
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn crawl_status_is_connection_error_if_download_fails_after_head() {
+        // given: a page, whose head succeeds, but whose download fails
+        let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
+        mock_task_context.expect_get_config().return_const(get_default_task_config());
+        mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_can_access().returning(|_| true);
+        mock_task_context.expect_get_uri_service().returning(|| Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+        mock_fetch_header_command.expect_fetch_header().returning(|_, _, _, _, _, _, _| {
+            let mut head_response = HeadResponse::new(String::from("https://example.com"), StatusCode { code: 200, label: String::from("OK") });
+            head_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            Ok((head_response, get_mock_http_client()))
+        });
+        let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+        mock_page_download_command.expect_download_page().returning(|_, _, _| Err(String::from("connection reset by peer")));
+        let page_crawl_command = PageCrawlCommand::new(
+            String::from("https://example.com"),
+            String::from("https://example.com"),
+            Arc::new(Mutex::new(mock_task_context)),
+            1,
+            Box::new(mock_fetch_header_command),
+            Box::new(mock_page_download_command),
+        );
+
+        // when: the page is crawled
+        let crawl_result = page_crawl_command.crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap();
+
+        // then: the error is reported instead of failing the crawl
+        assert_eq!(crawl_result.head.is_some(), true, "Should keep the head response");
+        assert_eq!(crawl_result.get.is_none(), true, "Should not have a get response");
+        assert_eq!(crawl_result.crawl_status.unwrap(), CrawlStatus::ConnectionError(String::from("connection reset by peer")), "Should have crawl_status == ConnectionError, if the download failed");
+    }
+
+    #[tokio::test]
+    async fn crawl_status_is_request_budget_exhausted_if_download_is_refused_by_the_client() {
+        // given: a page, whose download is refused, because parallel pages used up the request budget
+        let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
+        mock_task_context.expect_get_config().return_const(get_default_task_config());
+        mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_can_access().returning(|_| true);
+        mock_task_context.expect_get_uri_service().returning(|| Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+        mock_fetch_header_command.expect_fetch_header().returning(|_, _, _, _, _, _, _| {
+            let mut head_response = HeadResponse::new(String::from("https://example.com"), StatusCode { code: 200, label: String::from("OK") });
+            head_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            Ok((head_response, get_mock_http_client()))
+        });
+        let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+        mock_page_download_command.expect_download_page().returning(|_, _, _| Err(String::from(REQUEST_BUDGET_EXHAUSTED_ERROR)));
+        let page_crawl_command = PageCrawlCommand::new(
+            String::from("https://example.com"),
+            String::from("https://example.com"),
+            Arc::new(Mutex::new(mock_task_context)),
+            1,
+            Box::new(mock_fetch_header_command),
+            Box::new(mock_page_download_command),
+        );
+
+        // when: the page is crawled
+        let crawl_result = page_crawl_command.crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap();
+
+        // then: the exhausted budget is reported
+        assert_eq!(crawl_result.head.is_some(), true, "Should keep the head response");
+        assert_eq!(crawl_result.get.is_none(), true, "Should not have a get response");
+        assert_eq!(crawl_result.crawl_status.unwrap(), CrawlStatus::RequestBudgetExhausted, "Should have crawl_status == RequestBudgetExhausted, if the download was refused");
+    }
+
+    #[tokio::test]
+    async fn will_not_crawl_new_hosts_if_host_limit_reached() {
+        // given: a task context with a host cap of 2 and one host already seen
+        let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
+        let config = get_default_task_config();
+        config.lock().unwrap().max_hosts = Some(2);
+        mock_task_context.expect_get_config().return_const(config.clone());
+        mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_can_access().returning(|_| true);
+        let all_seen_hosts = Arc::new(Mutex::new(HashSet::from([String::from("a.example.com")])));
+        mock_task_context.expect_get_all_seen_hosts().return_const(all_seen_hosts.clone());
+        let task_context: Arc<Mutex<dyn FullTaskContext>> = Arc::new(Mutex::new(mock_task_context));
+        let create_command = |url: &str| PageCrawlCommand::new(
+            String::from(url),
+            String::from(url),
+            task_context.clone(),
+            1,
+            Box::new(MockMyFetchHeaderCommand::new()),
+            Box::new(MockMyPageDownloadCommand::new()),
+        );
+
+        // when: links to several hosts are verified
+        let second_host = create_command("https://b.example.com/");
+        let third_host = create_command("https://c.example.com/");
+        let known_host = create_command("https://a.example.com/deeplink");
+
+        // then: new hosts beyond the cap are skipped, while known hosts continue
+        assert_eq!(second_host.verify_crawlability(), Crawlability::Crawlable, "Second host should still be within the cap");
+        assert_eq!(third_host.verify_crawlability(), Crawlability::HostLimitReached, "Third host should exceed the cap");
+        assert_eq!(known_host.verify_crawlability(), Crawlability::Crawlable, "Known hosts should still be crawled");
+        assert_eq!(all_seen_hosts.lock().unwrap().len(), 2, "Skipped host should not be recorded");
+        let crawl_result = third_host.crawl(get_mock_http_client(), Uuid::new_v4(), None).await;
+        assert_eq!(crawl_result.unwrap().unwrap().crawl_status.unwrap(), CrawlStatus::HostLimitReached, "Should have crawl status HostLimitReached for skipped hosts");
+    }
+
+    #[tokio::test]
+    async fn will_stop_crawling_once_request_budget_is_exhausted() {
+        // given: a task context with a budget of a single request
+        let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
+        let config = get_default_task_config();
+        config.lock().unwrap().max_requests = Some(1);
+        mock_task_context.expect_get_config().return_const(config.clone());
+        mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_can_access().returning(|_| true);
+        mock_task_context.expect_get_uri_service().returning(|| Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        let request_counter = Arc::new(AtomicUsize::new(0));
+        mock_task_context.expect_get_request_counter().return_const(request_counter.clone());
+        let task_context: Arc<Mutex<dyn FullTaskContext>> = Arc::new(Mutex::new(mock_task_context));
+        let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+        let request_counter_clone = request_counter.clone();
+        mock_fetch_header_command.expect_fetch_header().times(1).returning(move |_, _, _, _, _, _, _| {
+            request_counter_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let mut head_response = HeadResponse::new(String::from("https://example.com"), StatusCode { code: 200, label: String::from("OK") });
+            head_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            Ok((head_response, get_mock_http_client()))
+        });
+        let first_page = PageCrawlCommand::new(
+            String::from("https://example.com"),
+            String::from("https://example.com"),
+            task_context.clone(),
+            1,
+            Box::new(mock_fetch_header_command),
+            Box::new(MockMyPageDownloadCommand::new()),
+        );
+        let second_page = PageCrawlCommand::new(
+            String::from("https://example.com/second"),
+            String::from("https://example.com/second"),
+            task_context.clone(),
+            1,
+            Box::new(MockMyFetchHeaderCommand::new()),
+            Box::new(MockMyPageDownloadCommand::new()),
+        );
+
+        // when: both pages are crawled
+        let first_result = first_page.crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap();
+        let second_result = second_page.crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap();
+
+        // then: no further requests are made once the budget is used up
+        assert_eq!(request_counter.load(std::sync::atomic::Ordering::SeqCst), 1, "Should not exceed the request budget");
+        assert_eq!(first_result.head.is_some(), true, "Should have fetched the header within budget");
+        assert_eq!(first_result.get.is_none(), true, "Should not download the page once budget is exhausted");
+        assert_eq!(first_result.crawl_status.unwrap(), CrawlStatus::RequestBudgetExhausted, "Should have crawl status RequestBudgetExhausted for the first page");
+        assert_eq!(second_result.crawl_status.unwrap(), CrawlStatus::RequestBudgetExhausted, "Should have crawl status RequestBudgetExhausted for subsequent pages");
+    }
+
+    #[tokio::test]
+    async fn will_stop_crawling_once_byte_budget_is_exhausted() {
+        // given: a task context with a byte budget below the size of two pages
+        let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
+        let config = get_default_task_config();
+        config.lock().unwrap().max_total_bytes = Some(1000);
+        mock_task_context.expect_get_config().return_const(config.clone());
+        mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_can_access().returning(|_| true);
+        mock_task_context.expect_get_uri_service().returning(|| Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        mock_task_context.expect_get_dom_parser().returning(|| Arc::new(DomParserService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        let downloaded_bytes_counter = Arc::new(AtomicU64::new(0));
+        mock_task_context.expect_get_downloaded_bytes_counter().return_const(downloaded_bytes_counter.clone());
+        let task_context: Arc<Mutex<dyn FullTaskContext>> = Arc::new(Mutex::new(mock_task_context));
+        let create_command = |url: &str| {
+            let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+            mock_fetch_header_command.expect_fetch_header().returning(|url, _, _, _, _, _, _| {
+                let mut head_response = HeadResponse::new(url, StatusCode { code: 200, label: String::from("OK") });
+                head_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+                Ok((head_response, get_mock_http_client()))
+            });
+            let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+            mock_page_download_command.expect_download_page().returning(|uri, _, _| {
+                let mut download_response = GetResponse::new(uri, StatusCode { code: 200, label: String::from("OK") });
+                download_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+                download_response.body = Some(format!("<html><body><p>{}</p></body></html>", "a".repeat(600)));
+                Ok(download_response)
+            });
+            PageCrawlCommand::new(
+                String::from(url),
+                String::from(url),
+                task_context.clone(),
+                1,
+                Box::new(mock_fetch_header_command),
+                Box::new(mock_page_download_command),
+            )
+        };
+
+        // when: three sizeable pages are crawled
+        let mut crawl_results = vec![];
+        for url in ["https://example.com/first", "https://example.com/second", "https://example.com/third"] {
+            crawl_results.push(create_command(url).crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap());
+        }
+
+        // then: the page exceeding the budget is still completed, but no further page is downloaded
+        assert_eq!(crawl_results[0].get.is_some() && crawl_results[1].get.is_some(), true, "Should download pages until the budget is exceeded");
+        assert_eq!(crawl_results[2].get.is_none(), true, "Should not download pages once the budget is exhausted");
+        assert_eq!(crawl_results[2].crawl_status, Some(CrawlStatus::ByteBudgetExhausted), "Should have crawl status ByteBudgetExhausted");
+        assert_eq!(downloaded_bytes_counter.load(std::sync::atomic::Ordering::SeqCst) >= 1000, true, "Should have counted the downloaded bytes");
+    }
+
+    #[tokio::test]
+    async fn registers_anchors_for_broken_anchor_detection() {
+        // given: a task context with broken anchor detection enabled and an already crawled target page
+        let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
+        let config = get_default_task_config();
+        config.lock().unwrap().detect_broken_anchors = true;
+        mock_task_context.expect_get_config().return_const(config.clone());
+        mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_can_access().returning(|_| true);
+        let link_type_checker = Arc::new(LinkTypeChecker::new("example.com"));
+        mock_task_context.expect_get_uri_service().return_const(Arc::new(UriService::new(link_type_checker.clone())));
+        let dom_parser: Arc<dyn DomParser> = Arc::new(DomParserService::new(link_type_checker.clone()));
+        mock_task_context.expect_get_dom_parser().return_const(dom_parser);
+        let anchor_registry = Arc::new(Mutex::new(AnchorRegistry::new()));
+        anchor_registry.lock().unwrap().add_page_element_ids(String::from("https://example.com/page"), HashSet::from([String::from("present")]));
+        mock_task_context.expect_get_anchor_registry().return_const(anchor_registry.clone());
+        let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+        mock_fetch_header_command.expect_fetch_header().returning(|_, _, _, _, _, _, _| {
+            let mut head_response = HeadResponse::new(String::from("https://example.com/"), StatusCode { code: 200, label: String::from("OK") });
+            head_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            Ok((head_response, get_mock_http_client()))
+        });
+        let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+        mock_page_download_command.expect_download_page().returning(|uri, _, _| {
+            let mut download_response = GetResponse::new(uri, StatusCode { code: 200, label: String::from("OK") });
+            download_response.headers.insert(String::from("content-type"), String::from("text/html"));
+            download_response.body = Some(String::from("<html><h1 id=\"intro\">Intro</h1><a href=\"#intro\">intro</a><a href=\"#top\">top</a><a href=\"/page#present\">present</a><a href=\"/page#missing\">missing</a></html>"));
+            Ok(download_response)
+        });
+
+        // when: the linking page is crawled
+        let page_crawl_command = PageCrawlCommand::new(
+            String::from("https://example.com/"),
+            String::from("https://example.com/"),
+            Arc::new(Mutex::new(mock_task_context)),
+            1,
+            Box::new(mock_fetch_header_command),
+            Box::new(mock_page_download_command),
+        );
+        let _ = page_crawl_command.crawl(get_mock_http_client(), Uuid::new_v4(), None).await;
+
+        // then: only the fragment without a matching element is reported
+        assert_eq!(anchor_registry.lock().unwrap().get_broken_anchors(), vec![BrokenAnchor {
+            source_url: String::from("https://example.com/"),
+            target_url: String::from("https://example.com/page"),
+            fragment: String::from("missing"),
+        }], "Should report page#missing as broken anchor");
+    }
+
+    #[tokio::test]
+    async fn will_skip_query_variants_of_known_paths_if_enabled() {
+        // given: a task context where /list has already been crawled
+        let create_task_context = |ignore_query_variants_of_known_paths: bool| {
+            let mut mock_task_context = MockMyTaskContext::new();
+            mock_task_context.expect_get_approximate_known_links().returning(|| None);
+            let known_paths = if ignore_query_variants_of_known_paths { Some(Arc::new(Mutex::new(HashSet::from([String::from("https://example.com/list")])))) } else { None };
+            mock_task_context.expect_get_known_paths().returning(move || known_paths.clone());
+            let config = get_default_task_config();
+            config.lock().unwrap().ignore_query_variants_of_known_paths = ignore_query_variants_of_known_paths;
+            mock_task_context.expect_get_config().return_const(config.clone());
+            mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![String::from("https://example.com/list")])));
+            mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+            mock_task_context.expect_can_access().returning(|_| true);
+            let task_context: Arc<Mutex<dyn FullTaskContext>> = Arc::new(Mutex::new(mock_task_context));
+            task_context
+        };
+        let create_command = |url: &str, task_context: Arc<Mutex<dyn FullTaskContext>>| PageCrawlCommand::new(
+            String::from(url),
+            String::from(url),
+            task_context,
+            1,
+            Box::new(MockMyFetchHeaderCommand::new()),
+            Box::new(MockMyPageDownloadCommand::new()),
+        );
+        let enabled_task_context = create_task_context(true);
+
+        // when: query variants and new paths are verified
+        let query_variant = create_command("https://example.com/list?sort=asc", enabled_task_context.clone());
+        let new_path = create_command("https://example.com/other?sort=asc", enabled_task_context.clone());
+        let query_variant_disabled = create_command("https://example.com/list?sort=asc", create_task_context(false));
+
+        // then: only query variants of known paths are skipped, and only if enabled
+        assert_eq!(query_variant.verify_crawlability(), Crawlability::QueryVariantSkipped, "Query variant of a crawled path should be skipped");
+        assert_eq!(new_path.verify_crawlability(), Crawlability::Crawlable, "New paths should still be crawled");
+        assert_eq!(query_variant_disabled.verify_crawlability(), Crawlability::Crawlable, "Query variants should be crawled if disabled");
+        let crawl_result = query_variant.crawl(get_mock_http_client(), Uuid::new_v4(), None).await;
+        assert_eq!(crawl_result.unwrap().unwrap().crawl_status.unwrap(), CrawlStatus::QueryVariantSkipped, "Should have crawl status QueryVariantSkipped for skipped query variants");
+    }
+
+    #[tokio::test]
+    async fn will_skip_query_variants_of_tasked_paths_in_both_dedup_modes() {
+        for approximate_dedup in [false, true] {
+            // given: a task context ignoring query variants, deduplicating exactly or approximately
+            let mut mock_task_context = MockMyTaskContext::new();
+            let approximate_known_links = if approximate_dedup { Some(Arc::new(Mutex::new(BloomFilter::new(1000, 0.01)))) } else { None };
+            mock_task_context.expect_get_approximate_known_links().returning(move || approximate_known_links.clone());
+            mock_task_context.expect_get_known_paths().return_const(Some(Arc::new(Mutex::new(HashSet::new()))));
+            let config = get_default_task_config();
+            config.lock().unwrap().ignore_query_variants_of_known_paths = true;
+            mock_task_context.expect_get_config().return_const(config.clone());
+            mock_task_context.expect_get_uri_service().return_const(Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+            mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+            mock_task_context.expect_get_all_tasked_links().return_const(Arc::new(Mutex::new(vec![])));
+            mock_task_context.expect_can_access().returning(|_| true);
+            let task_context: Arc<Mutex<dyn FullTaskContext>> = Arc::new(Mutex::new(mock_task_context));
+            let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+            mock_fetch_header_command.expect_fetch_header().returning(|_, _, _, _, _, _, _| Ok((HeadResponse::new(String::from("https://example.com/list"), StatusCode { code: hyper::StatusCode::IM_A_TEAPOT.as_u16(), label: hyper::StatusCode::IM_A_TEAPOT.canonical_reason().unwrap().into() }), get_mock_http_client())));
+            let create_command = |url: &str, fetch_header_command: MockMyFetchHeaderCommand| PageCrawlCommand::new(
+                String::from(url),
+                String::from(url),
+                task_context.clone(),
+                1,
+                Box::new(fetch_header_command),
+                Box::new(MockMyPageDownloadCommand::new()),
+            );
+
+            // when: /list is crawled, before a query variant of it is verified
+            let _ = create_command("https://example.com/list", mock_fetch_header_command).crawl(get_mock_http_client(), Uuid::new_v4(), None).await;
+            let query_variant = create_command("https://example.com/list?sort=asc", MockMyFetchHeaderCommand::new());
+
+            // then: the query variant is skipped, regardless of the dedup mode
+            assert_eq!(query_variant.verify_crawlability(), Crawlability::QueryVariantSkipped, "Should skip the query variant with approximate_dedup: {}", approximate_dedup);
+        }
+    }
+
+    #[tokio::test]
+    async fn will_skip_urls_beyond_max_path_depth() {
+        // given: a task context allowing two path segments
+        let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
+        let config = get_default_task_config();
+        config.lock().unwrap().max_path_depth = Some(2);
+        mock_task_context.expect_get_config().return_const(config.clone());
+        mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_can_access().returning(|_| true);
+        let task_context: Arc<Mutex<dyn FullTaskContext>> = Arc::new(Mutex::new(mock_task_context));
+        let create_command = |url: &str| PageCrawlCommand::new(
+            String::from(url),
+            String::from(url),
+            task_context.clone(),
+            1,
+            Box::new(MockMyFetchHeaderCommand::new()),
+            Box::new(MockMyPageDownloadCommand::new()),
+        );
+
+        // when: links of increasing path depth are verified
+        let shallow = create_command("https://example.com/blog/");
+        let at_cap = create_command("https://example.com/blog/post?page=2");
+        let nested = create_command("https://example.com/blog/2024/01/post");
+
+        // then: only paths beyond the cap are skipped, independent of the click distance
+        assert_eq!(shallow.verify_crawlability(), Crawlability::Crawlable, "Should crawl /blog/");
+        assert_eq!(at_cap.verify_crawlability(), Crawlability::Crawlable, "Should crawl paths at the cap, regardless of the query");
+        assert_eq!(nested.verify_crawlability(), Crawlability::MaxPathDepthReached, "Should skip deeply nested paths");
+        let crawl_result = nested.crawl(get_mock_http_client(), Uuid::new_v4(), None).await;
+        assert_eq!(crawl_result.unwrap().unwrap().crawl_status.unwrap(), CrawlStatus::MaxPathDepthReached, "Should have crawl status MaxPathDepthReached");
+    }
+
+    #[test]
+    fn known_and_tasked_links_are_compared_by_their_dedup_key() {
+        // given: a task context with a known and a tasked link, ignoring trailing slashes
+        let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
+        let config = get_default_task_config();
+        config.lock().unwrap().dedup_key_rules.strip_trailing_slash = true;
+        let dedup_key_rules = config.lock().unwrap().dedup_key_rules.clone();
+        mock_task_context.expect_get_config().return_const(config.clone());
+        let all_crawled_links = Arc::new(Mutex::new(vec![to_dedup_key("https://example.com/docs/", &dedup_key_rules)]));
+        mock_task_context.expect_get_all_crawled_links().return_const(all_crawled_links);
+        let all_tasked_links = Arc::new(Mutex::new(vec![to_dedup_key("https://example.com/guide", &dedup_key_rules)]));
+        mock_task_context.expect_get_all_tasked_links().return_const(all_tasked_links);
+        mock_task_context.expect_can_access().returning(|_| true);
+        let task_context: Arc<Mutex<dyn FullTaskContext>> = Arc::new(Mutex::new(mock_task_context));
+        let create_command = |url: &str| PageCrawlCommand::new(
+            String::from(url),
+            String::from(url),
+            task_context.clone(),
+            1,
+            Box::new(MockMyFetchHeaderCommand::new()),
+            Box::new(MockMyPageDownloadCommand::new()),
+        );
+
+        // when: differently spelled variants of them are verified
+        let known_variant = create_command("https://EXAMPLE.com:443/docs#top").verify_crawlability();
+        let tasked_variant = create_command("https://example.com/guide/").verify_crawlability();
+        let new_page = create_command("https://example.com/blog").verify_crawlability();
+
+        // then: variants are deduped, no matter whether the original is known or tasked
+        assert_eq!(known_variant, Crawlability::AlreadyKnown, "Variant of a known link should be known");
+        assert_eq!(tasked_variant, Crawlability::AlreadyTasked, "Variant of a tasked link should be tasked");
+        assert_eq!(new_page, Crawlability::Crawlable, "New pages should still be crawled");
+    }
+
+    #[tokio::test]
+    async fn downloads_page_without_content_type_only_if_html_is_assumed() {
+        // given: a server that omits the content-type
+        let crawl = |assume_html_when_missing_content_type: bool| async move {
+            let mut mock_task_context = MockMyTaskContext::new();
+            mock_task_context.expect_get_approximate_known_links().returning(|| None);
+            mock_task_context.expect_get_known_paths().returning(|| None);
+            let config = get_default_task_config();
+            config.lock().unwrap().assume_html_when_missing_content_type = assume_html_when_missing_content_type;
+            mock_task_context.expect_get_config().return_const(config.clone());
+            mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+            mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+            mock_task_context.expect_can_access().returning(|_| true);
+            mock_task_context.expect_get_uri_service().returning(|| Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+            mock_task_context.expect_get_dom_parser().returning(|| Arc::new(DomParserService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+            let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+            mock_fetch_header_command.expect_fetch_header().returning(|_, _, _, _, _, _, _| {
+                Ok((HeadResponse::new(String::from("https://example.com"), StatusCode { code: 200, label: String::from("OK") }), get_mock_http_client()))
+            });
+            let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+            mock_page_download_command.expect_download_page().returning(|uri, _, _| {
+                let mut download_response = GetResponse::new(uri, StatusCode { code: 200, label: String::from("OK") });
+                download_response.body = Some(String::from("<!DOCTYPE html><html><a href=\"/subpage\">sub</a></html>"));
+                Ok(download_response)
+            });
+            let page_crawl_command = PageCrawlCommand::new(
+                String::from("https://example.com"),
+                String::from("https://example.com"),
+                Arc::new(Mutex::new(mock_task_context)),
+                1,
+                Box::new(mock_fetch_header_command),
+                Box::new(mock_page_download_command),
+            );
+            page_crawl_command.crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap()
+        };
+
+        // when: the page is crawled with and without assuming html
+        let result_without_assumption = crawl(false).await;
+        let result_with_assumption = crawl(true).await;
+
+        // then: the page is only downloaded and parsed, if html is assumed
+        assert_eq!(result_without_assumption.get.is_none(), true, "Should not download page without content-type by default");
+        assert_eq!(result_with_assumption.get.is_some(), true, "Should download page without content-type if html is assumed");
+        assert_eq!(result_with_assumption.links.unwrap().len(), 1, "Should extract links if body looks like html");
+    }
+
+    #[tokio::test]
+    async fn extracts_links_of_mislabeled_html_if_content_type_is_sniffed() {
+        // given: a server that declares html as application/octet-stream
+        let crawl = |sniff_content_type: bool| async move {
+            let mut mock_task_context = MockMyTaskContext::new();
+            mock_task_context.expect_get_approximate_known_links().returning(|| None);
+            mock_task_context.expect_get_known_paths().returning(|| None);
+            let config = get_default_task_config();
+            config.lock().unwrap().sniff_content_type = sniff_content_type;
+            mock_task_context.expect_get_config().return_const(config.clone());
+            mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+            mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+            mock_task_context.expect_can_access().returning(|_| true);
+            mock_task_context.expect_get_uri_service().returning(|| Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+            mock_task_context.expect_get_dom_parser().returning(|| Arc::new(DomParserService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+            let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+            mock_fetch_header_command.expect_fetch_header().returning(|_, _, _, _, _, _, _| {
+                let mut head_response = HeadResponse::new(String::from("https://example.com"), StatusCode { code: 200, label: String::from("OK") });
+                head_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("application/octet-stream"));
+                Ok((head_response, get_mock_http_client()))
+            });
+            let body = "<html><a href=\"/subpage\">sub</a></html>";
+            let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+            mock_page_download_command.expect_download_page_prefix().returning(move |uri, _, _, max_bytes| {
+                let mut download_response = GetResponse::new(uri, StatusCode { code: 200, label: String::from("OK") });
+                download_response.body = Some(body.chars().take(max_bytes).collect());
+                Ok(download_response)
+            });
+            mock_page_download_command.expect_download_page().returning(move |uri, _, _| {
+                let mut download_response = GetResponse::new(uri, StatusCode { code: 200, label: String::from("OK") });
+                download_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("application/octet-stream"));
+                download_response.body = Some(String::from(body));
+                Ok(download_response)
+            });
+            let page_crawl_command = PageCrawlCommand::new(
+                String::from("https://example.com"),
+                String::from("https://example.com"),
+                Arc::new(Mutex::new(mock_task_context)),
+                1,
+                Box::new(mock_fetch_header_command),
+                Box::new(mock_page_download_command),
+            );
+            page_crawl_command.crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap()
+        };
+
+        // when: the page is crawled with and without sniffing
+        let result_without_sniffing = crawl(false).await;
+        let result_with_sniffing = crawl(true).await;
+
+        // then: links are only extracted, if sniffing is on
+        assert_eq!(result_without_sniffing.get.is_none(), true, "Should not download mislabeled html without sniffing");
+        assert_eq!(result_with_sniffing.get.is_some(), true, "Should download mislabeled html with sniffing");
+        assert_eq!(result_with_sniffing.links.unwrap().len(), 1, "Should extract links of sniffed html");
+    }
+
+    #[tokio::test]
+    async fn sniffs_only_ambiguous_content_types() {
+        // given: servers declaring different content-types, with sniffing on
+        let crawl = |content_type: &'static str| async move {
+            let mut mock_task_context = MockMyTaskContext::new();
+            mock_task_context.expect_get_approximate_known_links().returning(|| None);
+            mock_task_context.expect_get_known_paths().returning(|| None);
+            let config = get_default_task_config();
+            config.lock().unwrap().sniff_content_type = true;
+            mock_task_context.expect_get_config().return_const(config.clone());
+            mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+            mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+            mock_task_context.expect_can_access().returning(|_| true);
+            mock_task_context.expect_get_uri_service().returning(|| Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+            mock_task_context.expect_get_dom_parser().returning(|| Arc::new(DomParserService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+            let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+            mock_fetch_header_command.expect_fetch_header().returning(move |_, _, _, _, _, _, _| {
+                let mut head_response = HeadResponse::new(String::from("https://example.com"), StatusCode { code: 200, label: String::from("OK") });
+                head_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from(content_type));
+                Ok((head_response, get_mock_http_client()))
+            });
+            let sniff_count = Arc::new(AtomicUsize::new(0));
+            let sniff_count_clone = sniff_count.clone();
+            let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+            mock_page_download_command.expect_download_page_prefix().returning(move |uri, _, _, _| {
+                sniff_count_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                let mut download_response = GetResponse::new(uri, StatusCode { code: 200, label: String::from("OK") });
+                download_response.body = Some(String::from("no html"));
+                Ok(download_response)
+            });
+            let page_crawl_command = PageCrawlCommand::new(
+                String::from("https://example.com"),
+                String::from("https://example.com"),
+                Arc::new(Mutex::new(mock_task_context)),
+                1,
+                Box::new(mock_fetch_header_command),
+                Box::new(mock_page_download_command),
+            );
+            page_crawl_command.crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap();
+            sniff_count.load(std::sync::atomic::Ordering::SeqCst)
+        };
+
+        // when: the pages are crawled
+        let sniffs_of_octet_stream = crawl("application/octet-stream").await;
+        let sniffs_of_text_plain = crawl("text/plain; charset=utf-8").await;
+        let sniffs_of_empty = crawl("").await;
+        let sniffs_of_image = crawl("image/png").await;
+        let sniffs_of_pdf = crawl("application/pdf").await;
+
+        // then: only the ambiguous content-types are sniffed
+        assert_eq!(sniffs_of_octet_stream, 1, "Should sniff application/octet-stream");
+        assert_eq!(sniffs_of_text_plain, 1, "Should sniff text/plain");
+        assert_eq!(sniffs_of_empty, 1, "Should sniff empty content-type");
+        assert_eq!(sniffs_of_image, 0, "Should not sniff image/png");
+        assert_eq!(sniffs_of_pdf, 0, "Should not sniff application/pdf");
+    }
+
+    #[tokio::test]
+    async fn records_status_of_external_links_if_enabled() {
+        // given: a page with an internal and an external link
+        let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
+        let config = get_default_task_config();
+        config.lock().unwrap().check_external_links = true;
+        mock_task_context.expect_get_config().return_const(config.clone());
+        mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_can_access().returning(|_| true);
+        mock_task_context.expect_get_uri_service().returning(|| Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        mock_task_context.expect_get_dom_parser().returning(|| Arc::new(DomParserService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        let mut mock_http_client = MockMyHttpClient::new();
+        mock_http_client.expect_head().times(1).returning(|uri, _| {
+            assert_eq!(uri, "https://external.org/gone", "Should only check external links");
+            Ok(Response::builder().status(404).body(Body::from("")).unwrap())
+        });
+        let mock_http_client = Arc::new(mock_http_client);
+        let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+        mock_fetch_header_command.expect_fetch_header().returning(move |_, _, _, _, _, _, _| {
+            let mut head_response = HeadResponse::new(String::from("https://example.com"), StatusCode { code: 200, label: String::from("OK") });
+            head_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            Ok((head_response, mock_http_client.clone()))
+        });
+        let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+        mock_page_download_command.expect_download_page().returning(|uri, _, _| {
+            let mut download_response = GetResponse::new(uri, StatusCode { code: 200, label: String::from("OK") });
+            download_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            download_response.body = Some(String::from("<html><a href=\"/internal\">internal</a><a href=\"https://external.org/gone\">external</a></html>"));
+            Ok(download_response)
+        });
+
+        // when: the page is crawled
+        let page_crawl_command = PageCrawlCommand::new(
+            String::from("https://example.com"),
+            String::from("https://example.com"),
+            Arc::new(Mutex::new(mock_task_context)),
+            1,
+            Box::new(mock_fetch_header_command),
+            Box::new(mock_page_download_command),
+        );
+        let crawl_result = page_crawl_command.crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap();
+
+        // then: external links are reported together with their status
+        assert_eq!(crawl_result.external_links().len(), 1, "Should report the external link");
+        assert_eq!(crawl_result.external_links()[0].uri, "https://external.org/gone");
+        let external_link_checks = crawl_result.external_link_checks.unwrap();
+        assert_eq!(external_link_checks.len(), 1, "Should have checked the external link");
+        assert_eq!(external_link_checks[0].http_response_code.as_ref().unwrap().code, 404, "Should have recorded the status of the external link");
+    }
+
+    #[tokio::test]
+    async fn external_assets_are_checked_with_the_asset_client() {
+        // given: a page with an external image and an external page, and separate clients for pages and assets
+        let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
+        let config = get_default_task_config();
+        config.lock().unwrap().check_external_links = true;
+        mock_task_context.expect_get_config().return_const(config.clone());
+        mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_can_access().returning(|_| true);
+        mock_task_context.expect_get_uri_service().returning(|| Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        mock_task_context.expect_get_dom_parser().returning(|| Arc::new(DomParserService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        let mut mock_asset_http_client = MockMyHttpClient::new();
+        mock_asset_http_client.expect_head().times(1).returning(|uri, _| {
+            assert_eq!(uri, "https://cdn.org/image.png", "Should only check assets with the asset client");
+            Ok(Response::builder().status(200).body(Body::from("")).unwrap())
+        });
+        let mock_asset_http_client = Arc::new(mock_asset_http_client);
+        mock_task_context.expect_get_asset_http_client().returning(move || mock_asset_http_client.clone());
+        let mut mock_http_client = MockMyHttpClient::new();
+        mock_http_client.expect_head().times(1).returning(|uri, _| {
+            assert_eq!(uri, "https://external.org/page", "Should only check pages with the page client");
+            Ok(Response::builder().status(200).body(Body::from("")).unwrap())
+        });
+        let mock_http_client = Arc::new(mock_http_client);
+        let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+        mock_fetch_header_command.expect_fetch_header().returning(move |_, _, _, _, _, _, _| {
+            let mut head_response = HeadResponse::new(String::from("https://example.com"), StatusCode { code: 200, label: String::from("OK") });
+            head_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            Ok((head_response, mock_http_client.clone()))
+        });
+        let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+        mock_page_download_command.expect_download_page().returning(|uri, _, _| {
+            let mut download_response = GetResponse::new(uri, StatusCode { code: 200, label: String::from("OK") });
+            download_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            download_response.body = Some(String::from("<html><img src=\"https://cdn.org/image.png\"><a href=\"https://external.org/page\">external</a></html>"));
+            Ok(download_response)
+        });
+
+        // when: the page is crawled
+        let page_crawl_command = PageCrawlCommand::new(
+            String::from("https://example.com"),
+            String::from("https://example.com"),
+            Arc::new(Mutex::new(mock_task_context)),
+            1,
+            Box::new(mock_fetch_header_command),
+            Box::new(mock_page_download_command),
+        );
+        let crawl_result = page_crawl_command.crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap();
+
+        // then: both links are checked, each with the client of its resource kind
+        assert_eq!(crawl_result.external_link_checks.unwrap().len(), 2, "Should have checked both external links");
+    }
+
+    #[tokio::test]
+    async fn only_links_on_external_check_hosts_are_checked() {
+        // given: a page linking to a partner site, another external site and a subdomain, with only the first and the last being check hosts
+        let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
+        let config = get_default_task_config();
+        config.lock().unwrap().external_check_hosts = Some(vec![String::from("partner.org"), String::from("blog.example.com")]);
+        mock_task_context.expect_get_config().return_const(config.clone());
+        mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_can_access().returning(|_| true);
+        mock_task_context.expect_get_uri_service().returning(|| Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        mock_task_context.expect_get_dom_parser().returning(|| Arc::new(DomParserService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        let mut mock_http_client = MockMyHttpClient::new();
+        mock_http_client.expect_head().times(2).returning(|uri, _| {
+            assert_eq!(uri == "https://partner.org/page" || uri == "https://blog.example.com/post", true, "Should only check links on check hosts, got {}", uri);
+            Ok(Response::builder().status(200).body(Body::from("")).unwrap())
+        });
+        let mock_http_client = Arc::new(mock_http_client);
+        let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+        mock_fetch_header_command.expect_fetch_header().returning(move |_, _, _, _, _, _, _| {
+            let mut head_response = HeadResponse::new(String::from("https://example.com"), StatusCode { code: 200, label: String::from("OK") });
+            head_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            Ok((head_response, mock_http_client.clone()))
+        });
+        let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+        mock_page_download_command.expect_download_page().returning(|uri, _, _| {
+            let mut download_response = GetResponse::new(uri, StatusCode { code: 200, label: String::from("OK") });
+            download_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            download_response.body = Some(String::from("<html><a href=\"https://partner.org/page\">partner</a><a href=\"https://other.org/page\">other</a><a href=\"https://blog.example.com/post\">blog</a></html>"));
+            Ok(download_response)
+        });
+
+        // when: the page is crawled
+        let page_crawl_command = PageCrawlCommand::new(
+            String::from("https://example.com"),
+            String::from("https://example.com"),
+            Arc::new(Mutex::new(mock_task_context)),
+            1,
+            Box::new(mock_fetch_header_command),
+            Box::new(mock_page_download_command),
+        );
+        let crawl_result = page_crawl_command.crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap();
+
+        // then: only links on check hosts are checked, the other external link is still listed
+        assert_eq!(crawl_result.external_link_checks.as_ref().unwrap().len(), 2, "Should have checked the links on check hosts");
+        assert_eq!(crawl_result.external_links().len(), 2, "Should still list all external links");
+    }
+
+    #[tokio::test]
+    async fn asset_links_are_not_checked_without_check_assets() {
+        // given: a page linking to an external page, an external image and an external script, with check_assets disabled
+        let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
+        let config = get_default_task_config();
+        config.lock().unwrap().check_external_links = true;
+        config.lock().unwrap().check_assets = false;
+        mock_task_context.expect_get_config().return_const(config.clone());
+        mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_can_access().returning(|_| true);
+        mock_task_context.expect_get_uri_service().returning(|| Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        mock_task_context.expect_get_dom_parser().returning(|| Arc::new(DomParserService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        let mut mock_http_client = MockMyHttpClient::new();
+        mock_http_client.expect_head().times(1).returning(|uri, _| {
+            assert_eq!(uri, "https://other.org/page", "Should only check the page link");
+            Ok(Response::builder().status(200).body(Body::from("")).unwrap())
+        });
+        let mock_http_client = Arc::new(mock_http_client);
+        let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+        mock_fetch_header_command.expect_fetch_header().returning(move |_, _, _, _, _, _, _| {
+            let mut head_response = HeadResponse::new(String::from("https://example.com"), StatusCode { code: 200, label: String::from("OK") });
+            head_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            Ok((head_response, mock_http_client.clone()))
+        });
+        let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+        mock_page_download_command.expect_download_page().returning(|uri, _, _| {
+            let mut download_response = GetResponse::new(uri, StatusCode { code: 200, label: String::from("OK") });
+            download_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            download_response.body = Some(String::from("<html><a href=\"https://other.org/page\">other</a><img src=\"https://cdn.org/logo\"><a href=\"https://cdn.org/photo.jpg\">photo</a></html>"));
+            Ok(download_response)
+        });
+
+        // when: the page is crawled
+        let page_crawl_command = PageCrawlCommand::new(
+            String::from("https://example.com"),
+            String::from("https://example.com"),
+            Arc::new(Mutex::new(mock_task_context)),
+            1,
+            Box::new(mock_fetch_header_command),
+            Box::new(mock_page_download_command),
+        );
+        let crawl_result = page_crawl_command.crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap();
+
+        // then: the assets are listed, but not requested
+        assert_eq!(crawl_result.external_link_checks.as_ref().unwrap().len(), 1, "Should only check the page link");
+        assert_eq!(crawl_result.external_links().len(), 3, "Should still list the assets");
+    }
+
+    #[tokio::test]
+    async fn flags_pages_below_thin_content_threshold() {
+        // given: a near-empty and a content-rich page
+        let crawl = |body: &'static str| async move {
+            let mut mock_task_context = MockMyTaskContext::new();
+            mock_task_context.expect_get_approximate_known_links().returning(|| None);
+            mock_task_context.expect_get_known_paths().returning(|| None);
+            let config = get_default_task_config();
+            config.lock().unwrap().thin_content_threshold = Some(100);
+            mock_task_context.expect_get_config().return_const(config.clone());
+            mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+            mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+            mock_task_context.expect_can_access().returning(|_| true);
+            mock_task_context.expect_get_uri_service().returning(|| Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+            mock_task_context.expect_get_dom_parser().returning(|| Arc::new(DomParserService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+            let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+            mock_fetch_header_command.expect_fetch_header().returning(|_, _, _, _, _, _, _| {
+                let mut head_response = HeadResponse::new(String::from("https://example.com"), StatusCode { code: 200, label: String::from("OK") });
+                head_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+                Ok((head_response, get_mock_http_client()))
+            });
+            let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+            mock_page_download_command.expect_download_page().returning(move |uri, _, _| {
+                let mut download_response = GetResponse::new(uri, StatusCode { code: 200, label: String::from("OK") });
+                download_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+                download_response.body = Some(String::from(body));
+                Ok(download_response)
+            });
+            let page_crawl_command = PageCrawlCommand::new(
+                String::from("https://example.com"),
+                String::from("https://example.com"),
+                Arc::new(Mutex::new(mock_task_context)),
+                1,
+                Box::new(mock_fetch_header_command),
+                Box::new(mock_page_download_command),
+            );
+            page_crawl_command.crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap()
+        };
+
+        // when: both pages are crawled
+        let thin_page = crawl("<html><head><title>A long title that does not count as content</title><script>var invisible = 'script content is not visible either';</script></head><body><p>Hi</p></body></html>").await;
+        let rich_page = crawl("<html><body><p>Tarantula crawls websites and reports their links, redirects and status codes, along with some statistics for each page it visits.</p></body></html>").await;
+
+        // then: only the near-empty page is flagged
+        assert_eq!(thin_page.content_text_length, Some(2), "Should only count visible text");
+        assert_eq!(thin_page.thin_content, true, "Near-empty page should be flagged as thin");
+        assert_eq!(rich_page.content_text_length.unwrap() >= 100, true, "Should count the paragraph");
+        assert_eq!(rich_page.thin_content, false, "Content-rich page should not be flagged as thin");
+    }
+
+    #[tokio::test]
+    async fn flags_successful_responses_with_an_empty_body() {
+        // given: an empty and a regular page, reporting empty bodies as crawl status
+        let crawl = |body: &'static str| async move {
+            let mut mock_task_context = MockMyTaskContext::new();
+            mock_task_context.expect_get_approximate_known_links().returning(|| None);
+            mock_task_context.expect_get_known_paths().returning(|| None);
+            let config = get_default_task_config();
+            config.lock().unwrap().report_empty_body = true;
+            mock_task_context.expect_get_config().return_const(config);
+            mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+            mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+            mock_task_context.expect_can_access().returning(|_| true);
+            mock_task_context.expect_get_uri_service().returning(|| Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+            mock_task_context.expect_get_dom_parser().returning(|| Arc::new(DomParserService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+            let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+            mock_fetch_header_command.expect_fetch_header().returning(|_, _, _, _, _, _, _| {
+                let mut head_response = HeadResponse::new(String::from("https://example.com"), StatusCode { code: 200, label: String::from("OK") });
+                head_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+                Ok((head_response, get_mock_http_client()))
+            });
+            let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+            mock_page_download_command.expect_download_page().returning(move |uri, _, _| {
+                let mut download_response = GetResponse::new(uri, StatusCode { code: 200, label: String::from("OK") });
+                download_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+                download_response.body = Some(String::from(body));
+                Ok(download_response)
+            });
+            let page_crawl_command = PageCrawlCommand::new(
+                String::from("https://example.com"),
+                String::from("https://example.com"),
+                Arc::new(Mutex::new(mock_task_context)),
+                1,
+                Box::new(mock_fetch_header_command),
+                Box::new(mock_page_download_command),
+            );
+            page_crawl_command.crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap()
+        };
+
+        // when: both pages are crawled
+        let empty_page = crawl("").await;
+        let regular_page = crawl("<html><body><p>Hello</p></body></html>").await;
+
+        // then: only the empty page is flagged
+        assert_eq!(empty_page.empty_body, true, "Should flag a 200 with an empty body");
+        assert_eq!(empty_page.crawl_status, Some(CrawlStatus::EmptyBody), "Should report the empty body as crawl status");
+        assert_eq!(regular_page.empty_body, false, "Should not flag a page with content");
+        assert_eq!(regular_page.crawl_status, None, "Should not have a crawl status for a page with content");
+    }
+
+    #[tokio::test]
+    async fn reports_the_language_of_the_html_element() {
+        // given: a page with and a page without a lang attribute
+        let crawl = |body: &'static str| async move {
+            let mut mock_task_context = MockMyTaskContext::new();
+            mock_task_context.expect_get_approximate_known_links().returning(|| None);
+            mock_task_context.expect_get_known_paths().returning(|| None);
+            mock_task_context.expect_get_config().return_const(get_default_task_config());
+            mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+            mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+            mock_task_context.expect_can_access().returning(|_| true);
+            mock_task_context.expect_get_uri_service().returning(|| Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+            mock_task_context.expect_get_dom_parser().returning(|| Arc::new(DomParserService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+            let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+            mock_fetch_header_command.expect_fetch_header().returning(|_, _, _, _, _, _, _| {
+                let mut head_response = HeadResponse::new(String::from("https://example.com"), StatusCode { code: 200, label: String::from("OK") });
+                head_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+                Ok((head_response, get_mock_http_client()))
+            });
+            let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+            mock_page_download_command.expect_download_page().returning(move |uri, _, _| {
+                let mut download_response = GetResponse::new(uri, StatusCode { code: 200, label: String::from("OK") });
+                download_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+                download_response.body = Some(String::from(body));
+                Ok(download_response)
+            });
+            let page_crawl_command = PageCrawlCommand::new(
+                String::from("https://example.com"),
+                String::from("https://example.com"),
+                Arc::new(Mutex::new(mock_task_context)),
+                1,
+                Box::new(mock_fetch_header_command),
+                Box::new(mock_page_download_command),
+            );
+            page_crawl_command.crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap()
+        };
+
+        // when: both pages are crawled
+        let german_page = crawl("<html lang=\"de\"><body><p>Hallo</p></body></html>").await;
+        let unspecified_page = crawl("<html><body><p>Hello</p></body></html>").await;
+
+        // then: only the page with a lang attribute reports a language
+        assert_eq!(german_page.language, Some(String::from("de")), "Should report the lang attribute");
+        assert_eq!(unspecified_page.language, None, "Should not report a language without lang attribute");
+    }
+
+    #[tokio::test]
+    async fn follows_meta_refresh_as_redirect_if_enabled() {
+        // given: a 200 page refreshing to a missing page
+        let crawl = |follow_meta_refresh: bool| async move {
+            let mut mock_task_context = MockMyTaskContext::new();
+            mock_task_context.expect_get_approximate_known_links().returning(|| None);
+            mock_task_context.expect_get_known_paths().returning(|| None);
+            let config = get_default_task_config();
+            config.lock().unwrap().follow_meta_refresh = follow_meta_refresh;
+            config.lock().unwrap().maximum_redirects = 3;
+            mock_task_context.expect_get_config().return_const(config);
+            mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+            mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+            mock_task_context.expect_can_access().returning(|_| true);
+            mock_task_context.expect_get_uri_service().returning(|| Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+            mock_task_context.expect_get_dom_parser().returning(|| Arc::new(DomParserService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+            let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+            mock_fetch_header_command.expect_fetch_header().returning(|url, _, _, _, _, redirects, _| {
+                let redirects = redirects.unwrap_or_default();
+                let (uri, code) = match redirects.last() {
+                    Some(redirect) => (redirect.destination.clone(), 404),
+                    None => (url, 200),
+                };
+                let mut head_response = HeadResponse::new(uri, StatusCode { code, label: String::from("") });
+                head_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+                head_response.redirect_count = redirects.len();
+                head_response.redirects = redirects;
+                Ok((head_response, get_mock_http_client()))
+            });
+            let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+            mock_page_download_command.expect_download_page().returning(|uri, _, _| {
+                let mut download_response = GetResponse::new(uri, StatusCode { code: 200, label: String::from("OK") });
+                download_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+                download_response.body = Some(String::from("<html><head><meta http-equiv=\"refresh\" content=\"0; url=/not-found\"></head></html>"));
+                Ok(download_response)
+            });
+            let page_crawl_command = PageCrawlCommand::new(
+                String::from("https://example.com"),
+                String::from("https://example.com"),
+                Arc::new(Mutex::new(mock_task_context)),
+                1,
+                Box::new(mock_fetch_header_command),
+                Box::new(mock_page_download_command),
+            );
+            page_crawl_command.crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap()
+        };
+
+        // when: the page is crawled, with and without following meta refreshes
+        let followed = crawl(true).await;
+        let not_followed = crawl(false).await;
+
+        // then: the refresh target becomes the final url, recorded as a meta refresh hop
+        assert_eq!(followed.final_url_after_redirects, Some(String::from("https://example.com/not-found")), "Should end at the refresh target");
+        assert_eq!(followed.head.as_ref().unwrap().http_response_code.code, 404, "Should report the status of the refresh target");
+        let redirects = &followed.head.as_ref().unwrap().redirects;
+        assert_eq!(redirects.len(), 1, "Should record the meta refresh in the redirect chain");
+        assert_eq!(redirects[0].http_response_code.label, "Meta Refresh", "Should mark the hop as meta refresh");
+        assert_eq!(not_followed.final_url_after_redirects, Some(String::from("https://example.com")), "Should stay on the page, if not enabled");
+        assert_eq!(not_followed.meta_refresh_url, Some(String::from("https://example.com/not-found")), "Should still report the refresh target");
+    }
+
+    #[tokio::test]
+    async fn skips_download_of_known_redirect_targets_if_enabled() {
+        // given: a page redirecting to an already crawled page
+        let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
+        let config = get_default_task_config();
+        config.lock().unwrap().skip_known_redirect_targets = true;
+        mock_task_context.expect_get_config().return_const(config);
+        mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![String::from("https://example.com/known")])));
+        mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_can_access().returning(|_| true);
+        mock_task_context.expect_get_uri_service().returning(|| Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+        mock_fetch_header_command.expect_fetch_header().returning(|url, _, _, _, _, _, _| {
+            let mut head_response = HeadResponse::new(String::from("https://example.com/known"), StatusCode { code: 200, label: String::from("OK") });
+            head_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            head_response.redirects = vec![Redirect::from(url, String::from("https://example.com/known"))];
+            head_response.redirect_count = 1;
+            Ok((head_response, get_mock_http_client()))
+        });
+        let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+        mock_page_download_command.expect_download_page().times(0);
+        let page_crawl_command = PageCrawlCommand::new(
+            String::from("https://example.com/moved"),
+            String::from("https://example.com/moved"),
+            Arc::new(Mutex::new(mock_task_context)),
+            1,
+            Box::new(mock_fetch_header_command),
+            Box::new(mock_page_download_command),
+        );
+
+        // when: the redirecting page is crawled
+        let result = page_crawl_command.crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap();
+
+        // then: the known destination isn't downloaded again
+        assert_eq!(result.crawl_status, Some(CrawlStatus::RedirectToKnown), "Should flag the redirect to a known page");
+        assert_eq!(result.final_url_after_redirects, Some(String::from("https://example.com/known")));
+        assert_eq!(result.get.is_none(), true, "Should not download the known page");
+    }
+
+    #[tokio::test]
+    async fn records_the_normalizations_of_the_crawled_url_if_enabled() {
+        // given: a task context sorting whitelisted query params and stripping fragments
+        let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
+        let config = get_default_task_config();
+        config.lock().unwrap().record_normalizations = true;
+        config.lock().unwrap().dedup_key_rules = DedupKeyRules { fingerprint: UrlFingerprint::PathPlusSortedQueryWhitelist(vec![String::from("a"), String::from("b")]), ..DedupKeyRules::default() };
+        mock_task_context.expect_get_config().return_const(config);
+        mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_can_access().returning(|_| true);
+        mock_task_context.expect_get_uri_service().returning(|| Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+        mock_fetch_header_command.expect_fetch_header().returning(|url, _, _, _, _, _, _| Ok((HeadResponse::new(url, StatusCode { code: 404, label: String::from("Not Found") }), get_mock_http_client())));
+        let page_crawl_command = PageCrawlCommand::new(
+            String::from("https://example.com/list?b=2&a=1#top"),
+            String::from("/list?b=2&a=1#top"),
+            Arc::new(Mutex::new(mock_task_context)),
+            1,
+            Box::new(mock_fetch_header_command),
+            Box::new(MockMyPageDownloadCommand::new()),
+        );
+
+        // when: the page is crawled
+        let result = page_crawl_command.crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap();
+
+        // then: resolving the href, sorting the query and stripping the fragment are recorded
+        assert_eq!(result.normalizations, Some(vec![UrlNormalization::Resolved, UrlNormalization::QuerySorted, UrlNormalization::FragmentStripped]));
+    }
+
+    #[tokio::test]
+    async fn follows_refresh_header_as_redirect_if_enabled() {
+        // given: a 200 page announcing a refresh via response header
+        let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
+        let config = get_default_task_config();
+        config.lock().unwrap().follow_meta_refresh = true;
+        config.lock().unwrap().maximum_redirects = 3;
+        mock_task_context.expect_get_config().return_const(config);
+        mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_can_access().returning(|_| true);
+        mock_task_context.expect_get_uri_service().returning(|| Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        mock_task_context.expect_get_dom_parser().returning(|| Arc::new(DomParserService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+        mock_fetch_header_command.expect_fetch_header().returning(|url, _, _, _, _, redirects, _| {
+            let redirects = redirects.unwrap_or_default();
+            let uri = redirects.last().map_or(url, |redirect| redirect.destination.clone());
+            let mut head_response = HeadResponse::new(uri, StatusCode { code: 200, label: String::from("") });
+            head_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            if redirects.is_empty() {
+                head_response.headers.insert(String::from("refresh"), String::from("0; url=/target"));
+            }
+            head_response.redirect_count = redirects.len();
+            head_response.redirects = redirects;
+            Ok((head_response, get_mock_http_client()))
+        });
+        let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+        mock_page_download_command.expect_download_page().returning(|uri, _, _| {
+            let mut download_response = GetResponse::new(uri, StatusCode { code: 200, label: String::from("OK") });
+            download_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            download_response.body = Some(String::from("<html><head><title>No meta refresh</title></head></html>"));
+            Ok(download_response)
+        });
+        let page_crawl_command = PageCrawlCommand::new(
+            String::from("https://example.com"),
+            String::from("https://example.com"),
+            Arc::new(Mutex::new(mock_task_context)),
+            1,
+            Box::new(mock_fetch_header_command),
+            Box::new(mock_page_download_command),
+        );
+
+        // when: the page is crawled
+        let result = page_crawl_command.crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap();
+
+        // then: the refresh target becomes the final url
+        assert_eq!(result.final_url_after_redirects, Some(String::from("https://example.com/target")), "Should end at the refresh target");
+        assert_eq!(result.head.as_ref().unwrap().redirects.len(), 1, "Should record the refresh in the redirect chain");
+    }
+
+    #[tokio::test]
+    async fn skipping_head_requests_uses_the_get_response() {
+        // given: a task context skipping HEAD and a page redirecting once
+        let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
+        let config = get_default_task_config();
+        config.lock().unwrap().skip_head_request = true;
+        config.lock().unwrap().maximum_redirects = 3;
+        mock_task_context.expect_get_config().return_const(config.clone());
+        mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_can_access().returning(|_| true);
+        mock_task_context.expect_get_uri_service().returning(|| Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        mock_task_context.expect_get_dom_parser().returning(|| Arc::new(DomParserService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+        mock_fetch_header_command.expect_fetch_header().never();
+        let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+        mock_page_download_command.expect_download_page().times(2).returning(|uri, _, _| {
+            if uri == "https://example.com" {
+                let mut download_response = GetResponse::new(uri, StatusCode { code: 301, label: String::from("Moved Permanently") });
+                download_response.headers.insert(String::from("location"), String::from("/start"));
+                return Ok(download_response);
+            }
+            assert_eq!(uri, "https://example.com/start", "Should follow the redirect with GET");
+            let mut download_response = GetResponse::new(uri, StatusCode { code: 200, label: String::from("OK") });
+            download_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            download_response.body = Some(String::from("<html><a href=\"/next\">next</a></html>"));
+            Ok(download_response)
+        });
+
+        // when: the page is crawled
+        let page_crawl_command = PageCrawlCommand::new(
+            String::from("https://example.com"),
+            String::from("https://example.com"),
+            Arc::new(Mutex::new(mock_task_context)),
+            1,
+            Box::new(mock_fetch_header_command),
+            Box::new(mock_page_download_command),
+        );
+        let crawl_result = page_crawl_command.crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap();
+
+        // then: the GET responses drive the page response, without any HEAD
+        let head = crawl_result.head.unwrap();
+        assert_eq!(head.http_response_code.code, 200, "Status should come from the final GET");
+        assert_eq!(head.redirects.len(), 1, "Should report the redirect");
+        assert_eq!(crawl_result.final_url_after_redirects, Some(String::from("https://example.com/start")));
+        assert_eq!(crawl_result.get.unwrap().http_response_code.code, 200, "Should keep the final GET as download");
+        assert_eq!(crawl_result.links.unwrap().len(), 1, "Should parse the body of the final GET");
+    }
+
+    #[tokio::test]
+    async fn derives_noindex_and_nofollow_from_x_robots_tag() {
+        // given: a page served with an X-Robots-Tag header
+        let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
+        mock_task_context.expect_get_config().return_const(get_default_task_config());
+        mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_can_access().returning(|_| true);
+        mock_task_context.expect_get_uri_service().returning(|| Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        mock_task_context.expect_get_dom_parser().returning(|| Arc::new(DomParserService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+        mock_fetch_header_command.expect_fetch_header().returning(|_, _, _, _, _, _, _| {
+            let mut head_response = HeadResponse::new(String::from("https://example.com"), StatusCode { code: 200, label: String::from("OK") });
+            head_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            head_response.x_robots_tag = Some(String::from("noindex,nofollow"));
+            Ok((head_response, get_mock_http_client()))
+        });
+        let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+        mock_page_download_command.expect_download_page().returning(|uri, _, _| {
+            let mut download_response = GetResponse::new(uri, StatusCode { code: 200, label: String::from("OK") });
+            download_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            download_response.body = Some(String::from("<html><a href=\"/internal\">internal</a></html>"));
+            Ok(download_response)
+        });
+
+        // when: the page is crawled
+        let page_crawl_command = PageCrawlCommand::new(
+            String::from("https://example.com"),
+            String::from("https://example.com"),
+            Arc::new(Mutex::new(mock_task_context)),
+            1,
+            Box::new(mock_fetch_header_command),
+            Box::new(mock_page_download_command),
+        );
+        let crawl_result = page_crawl_command.crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap();
+
+        // then: both flags are set
+        assert_eq!(crawl_result.noindex, true, "Should be noindex due to X-Robots-Tag");
+        assert_eq!(crawl_result.nofollow, true, "Should be nofollow due to X-Robots-Tag");
+    }
+
+    #[tokio::test]
+    async fn only_retains_headers_of_interest_if_configured() {
+        // given: a task context only interested in two headers and a page served with more
+        let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
+        let config = get_default_task_config();
+        config.lock().unwrap().headers_of_interest = Some(vec![String::from("cache-control"), String::from("server")]);
+        config.lock().unwrap().keep_all_headers = false;
+        mock_task_context.expect_get_config().return_const(config.clone());
+        mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_can_access().returning(|_| true);
+        mock_task_context.expect_get_uri_service().returning(|| Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        mock_task_context.expect_get_dom_parser().returning(|| Arc::new(DomParserService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+        mock_fetch_header_command.expect_fetch_header().returning(|_, _, _, _, _, _, _| {
+            let mut head_response = HeadResponse::new(String::from("https://example.com"), StatusCode { code: 200, label: String::from("OK") });
+            head_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            head_response.headers.insert(String::from("server"), String::from("nginx"));
+            head_response.headers.insert(String::from("x-powered-by"), String::from("PHP"));
+            Ok((head_response, get_mock_http_client()))
+        });
+        let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+        mock_page_download_command.expect_download_page().returning(|uri, _, _| {
+            let mut download_response = GetResponse::new(uri, StatusCode { code: 200, label: String::from("OK") });
+            download_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            download_response.headers.insert(String::from("cache-control"), String::from("no-cache"));
+            download_response.body = Some(String::from("<html><a href=\"/internal\">internal</a></html>"));
+            Ok(download_response)
+        });
+
+        // when: the page is crawled
+        let page_crawl_command = PageCrawlCommand::new(
+            String::from("https://example.com"),
+            String::from("https://example.com"),
+            Arc::new(Mutex::new(mock_task_context)),
+            1,
+            Box::new(mock_fetch_header_command),
+            Box::new(mock_page_download_command),
+        );
+        let crawl_result = page_crawl_command.crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap();
+
+        // then: only the requested headers are retained, while the content-type was still used for the download
+        let head = crawl_result.head.unwrap();
+        let get = crawl_result.get.unwrap();
+        let mut expected_head_headers = HashMap::new();
+        expected_head_headers.insert(String::from("server"), String::from("nginx"));
+        let mut expected_get_headers = HashMap::new();
+        expected_get_headers.insert(String::from("cache-control"), String::from("no-cache"));
+        assert_eq!(head.headers_of_interest, Some(expected_head_headers));
+        assert_eq!(get.headers_of_interest, Some(expected_get_headers));
+        assert_eq!(head.headers.is_empty(), true, "Should drop the full header map");
+        assert_eq!(get.headers.is_empty(), true, "Should drop the full header map");
+        assert_eq!(crawl_result.links.unwrap().len(), 1, "Should still download and parse the page");
+    }
+
+    #[tokio::test]
+    async fn seeds_the_urls_of_sitemaps_referenced_by_link_rel_sitemap() {
+        // given: a task context following sitemaps and a page referencing its sitemap in the head
+        let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
+        let config = get_default_task_config();
+        config.lock().unwrap().follow_sitemaps = true;
+        mock_task_context.expect_get_config().return_const(config.clone());
+        mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+        let followed_sitemaps = Arc::new(Mutex::new(HashSet::new()));
+        let followed_sitemaps_cloned = followed_sitemaps.clone();
+        mock_task_context.expect_get_followed_sitemaps().returning(move || followed_sitemaps_cloned.clone());
+        mock_task_context.expect_can_access().returning(|_| true);
+        mock_task_context.expect_get_uri_service().returning(|| Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        mock_task_context.expect_get_dom_parser().returning(|| Arc::new(DomParserService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+        mock_fetch_header_command.expect_fetch_header().returning(|_, _, _, _, _, _, _| {
+            let mut head_response = HeadResponse::new(String::from("https://example.com"), StatusCode { code: 200, label: String::from("OK") });
+            head_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            Ok((head_response, get_mock_http_client()))
+        });
+        let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+        mock_page_download_command.expect_download_page().times(2).returning(|uri, _, _| {
+            let mut download_response = GetResponse::new(uri.clone(), StatusCode { code: 200, label: String::from("OK") });
+            if uri == "https://example.com/sitemap.xml" {
+                download_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("application/xml"));
+                download_response.body = Some(String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?><urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\"><url><loc>https://example.com/hidden</loc></url><url><loc> https://example.com/orphan?a=1&amp;b=2 </loc></url></urlset>"));
+                return Ok(download_response);
+            }
+            download_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            download_response.body = Some(String::from("<html><head><link rel=\"sitemap\" type=\"application/xml\" href=\"/sitemap.xml\"></head><body><a href=\"/internal\">internal</a></body></html>"));
+            Ok(download_response)
+        });
+
+        // when: the page is crawled
+        let page_crawl_command = PageCrawlCommand::new(
+            String::from("https://example.com"),
+            String::from("https://example.com"),
+            Arc::new(Mutex::new(mock_task_context)),
+            1,
+            Box::new(mock_fetch_header_command),
+            Box::new(mock_page_download_command),
+        );
+        let crawl_result = page_crawl_command.crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap();
+
+        // then: the sitemap is discovered and its urls are seeded as links of the page
+        let sitemap_links: Vec<Link> = crawl_result.links.unwrap().into_iter()
+            .filter(|link| link.source_tag == Some(String::from("sitemap")))
+            .collect();
+        assert_eq!(sitemap_links.iter().map(|link| link.uri.clone()).collect::<Vec<String>>(), vec![String::from("https://example.com/hidden"), String::from("https://example.com/orphan?a=1&b=2")]);
+        assert_eq!(sitemap_links[0].scope, Some(UriScope::SameDomain));
+        assert_eq!(followed_sitemaps.lock().unwrap().contains("https://example.com/sitemap.xml"), true, "Should remember the sitemap, so it's only loaded once");
+    }
+
+    #[tokio::test]
+    async fn flags_pages_responding_slower_than_the_threshold() {
+        // given: a task context flagging pages slower than 10ms, and a head request taking longer
+        let mut mock_task_context = MockMyTaskContext::new();
+        mock_task_context.expect_get_approximate_known_links().returning(|| None);
+        mock_task_context.expect_get_known_paths().returning(|| None);
+        let config = get_default_task_config();
+        config.lock().unwrap().slow_threshold_ms = Some(10);
+        mock_task_context.expect_get_config().return_const(config.clone());
+        mock_task_context.expect_get_all_crawled_links().returning(|| Arc::new(Mutex::new(vec![])));
+        mock_task_context.expect_get_all_tasked_links().returning(|| Arc::new(Mutex::new(vec![])));
+        let response_times = Arc::new(Mutex::new(BTreeMap::new()));
+        let response_times_cloned = response_times.clone();
+        mock_task_context.expect_get_response_times().returning(move || response_times_cloned.clone());
+        mock_task_context.expect_can_access().returning(|_| true);
+        mock_task_context.expect_get_uri_service().returning(|| Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        mock_task_context.expect_get_dom_parser().returning(|| Arc::new(DomParserService::new(Arc::new(LinkTypeChecker::new("example.com")))));
+        let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+        mock_fetch_header_command.expect_fetch_header().returning(|_, _, _, _, _, _, _| {
+            std::thread::sleep(std::time::Duration::from_millis(30));
+            let mut head_response = HeadResponse::new(String::from("https://example.com"), StatusCode { code: 200, label: String::from("OK") });
+            head_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            Ok((head_response, get_mock_http_client()))
+        });
+        let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+        mock_page_download_command.expect_download_page().returning(|uri, _, _| {
+            let mut download_response = GetResponse::new(uri, StatusCode { code: 200, label: String::from("OK") });
+            download_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("text/html"));
+            download_response.body = Some(String::from("<html><body>slow</body></html>"));
+            Ok(download_response)
+        });
+
+        // when: the page is crawled
+        let page_crawl_command = PageCrawlCommand::new(
+            String::from("https://example.com"),
+            String::from("https://example.com"),
+            Arc::new(Mutex::new(mock_task_context)),
+            1,
+            Box::new(mock_fetch_header_command),
+            Box::new(mock_page_download_command),
+        );
+        let crawl_result = page_crawl_command.crawl(get_mock_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap();
+
+        // then: the page is flagged and its response time is kept for the summary
+        assert_eq!(crawl_result.slow, true, "Should flag the slow page");
+        assert_eq!(response_times.lock().unwrap().get("https://example.com").map_or(false, |response_time_ms| *response_time_ms >= 30), true, "Should register the response time of the page");
+    }
+
+    #[test]
+    fn reads_pagination_links_from_link_header() {
+        // given: a response announcing its neighbours via Link header
+        let mut download_result = GetResponse::new(String::from("https://example.com/api"), StatusCode { code: 200, label: String::from("OK") });
+        download_result.headers.insert(String::from("link"), String::from("<https://example.com/api?page=1>; rel=\"prev\", <https://example.com/api?page=3>; rel=\"next\""));
+
+        // when: the pagination links are read
+        let links = PageCrawlCommand::get_link_header_pagination_links("example.com", &download_result);
+
+        // then: only the next page is returned, marked as pagination link
+        assert_eq!(links.len(), 1, "Should only return rel=next");
+        assert_eq!(links[0].uri, "https://example.com/api?page=3");
+        assert_eq!(links[0].is_pagination_next(), true, "Should be marked as pagination link");
+        assert_eq!(links[0].scope, Some(UriScope::SameDomain));
+    }
+
+    #[test]
+    fn combines_meta_robots_with_x_robots_tag() {
+        // given: a page without X-Robots-Tag
+        let mut page_response = PageResponse::new(String::from("https://example.com"), String::from("/"), Uuid::new_v4());
+
+        // when: directives of both sources are applied
+        PageCrawlCommand::apply_robots_directives(&mut page_response, None);
+        PageCrawlCommand::apply_robots_directives(&mut page_response, Some(&String::from("googlebot: NOFOLLOW")));
+
+        // then: only nofollow is set
+        assert_eq!(page_response.noindex, false, "Should not be noindex");
+        assert_eq!(page_response.nofollow, true, "Should be nofollow");
+    }
+}
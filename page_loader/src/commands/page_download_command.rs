@@ -3,9 +3,10 @@ use std::sync::Arc;
 
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
+use hyper::body::HttpBody;
 use responses::get_response::GetResponse;
 use responses::response_timings::ResponseTimings;
-use tracing::trace;
+use tracing::{debug, trace};
 
 use crate::http::http_client::HttpClient;
 use crate::http::http_utils;
@@ -13,27 +14,77 @@ use crate::http::http_utils;
 #[async_trait]
 pub trait PageDownloadCommand: Sync + Send {
     async fn download_page(&self, uri: String, http_client: Arc<dyn HttpClient>, robots_txt_info_url: Option<String>) -> Result<GetResponse, String>;
+    async fn download_page_prefix(&self, uri: String, http_client: Arc<dyn HttpClient>, robots_txt_info_url: Option<String>, max_bytes: usize) -> Result<GetResponse, String>;
 }
 
-pub struct DefaultPageDownloadCommand {}
+pub struct DefaultPageDownloadCommand {
+    pub retryable_status_codes: Vec<u16>,
+}
 
 #[async_trait]
 impl PageDownloadCommand for DefaultPageDownloadCommand {
     async fn download_page(&self, uri: String, http_client: Arc<dyn HttpClient>, robots_txt_info_url: Option<String>) -> Result<GetResponse, String> {
         let start_time = DateTime::from(Utc::now());
 
-        let response = http_client.get(uri.clone(), robots_txt_info_url).await.unwrap();
+        let mut attempt = 0;
+        let response = loop {
+            let response = http_client.get(uri.clone(), robots_txt_info_url.clone()).await.map_err(|error| error.to_string())?;
+            if !http_utils::is_retryable_status(&response, &self.retryable_status_codes, attempt) {
+                break response;
+            }
+            attempt += 1;
+            debug!("Retrying GET for {} after status {} (attempt {} of {})", uri, response.status(), attempt, http_utils::MAX_STATUS_RETRIES);
+        };
         trace!("GET for {}: {:?}", uri, response.headers());
         let headers: HashMap<String, String> = http_utils::response_headers_to_map(&response);
         let http_response_code = http_utils::map_status_code(response.status());
-        let body: String = String::from_utf8_lossy(hyper::body::to_bytes(response.into_body()).await.unwrap().as_ref())
+        let content_encoding = headers.get("content-encoding").cloned();
+        let x_robots_tag = headers.get("x-robots-tag").cloned();
+        let raw_body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: String = String::from_utf8_lossy(&http_utils::decode_body(raw_body.as_ref(), content_encoding.as_ref()))
             .to_string();
         let result = GetResponse {
             http_response_code,
             headers,
+            headers_of_interest: None,
             requested_url: uri.clone(),
             response_timings: ResponseTimings::from(uri.clone(), start_time, DateTime::from(Utc::now())),
             body: Some(body),
+            content_encoding,
+            x_robots_tag,
+        };
+        Ok(result)
+    }
+
+    async fn download_page_prefix(&self, uri: String, http_client: Arc<dyn HttpClient>, robots_txt_info_url: Option<String>, max_bytes: usize) -> Result<GetResponse, String> {
+        let start_time = DateTime::from(Utc::now());
+
+        let response = http_client.get(uri.clone(), robots_txt_info_url).await.map_err(|error| error.to_string())?;
+        let headers: HashMap<String, String> = http_utils::response_headers_to_map(&response);
+        let http_response_code = http_utils::map_status_code(response.status());
+        let mut body = response.into_body();
+        let mut prefix: Vec<u8> = vec![];
+        // the remaining body is dropped together with the connection, once enough bytes have been read
+        while prefix.len() < max_bytes {
+            match body.data().await {
+                Some(Ok(chunk)) => prefix.extend_from_slice(&chunk),
+                Some(Err(error)) => return Err(error.to_string()),
+                None => break,
+            }
+        }
+        let content_encoding = headers.get("content-encoding").cloned();
+        let x_robots_tag = headers.get("x-robots-tag").cloned();
+        let mut prefix = http_utils::decode_body(&prefix, content_encoding.as_ref());
+        prefix.truncate(max_bytes);
+        let result = GetResponse {
+            http_response_code,
+            headers,
+            headers_of_interest: None,
+            requested_url: uri.clone(),
+            response_timings: ResponseTimings::from(uri.clone(), start_time, DateTime::from(Utc::now())),
+            body: Some(String::from_utf8_lossy(&prefix).to_string()),
+            content_encoding,
+            x_robots_tag,
         };
         Ok(result)
     }
@@ -41,24 +92,30 @@ impl PageDownloadCommand for DefaultPageDownloadCommand {
 
 #[cfg(test)]
 mod tests {
+    use std::io::Write;
+
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
     use hyper::{Body, Response};
     use mockall::*;
 
+    use crate::http::http_client::HttpClientResult;
+
     use super::*;
 
     mock! {
         MyHttpClient {}
         #[async_trait]
         impl HttpClient for MyHttpClient{
-            async fn head(&self, uri: String, robots_txt_info_url: Option<String>) -> hyper::Result<Response<Body>>;
-            async fn get(&self, uri: String, robots_txt_info_url: Option<String>) -> hyper::Result<Response<Body>>;
+            async fn head(&self, uri: String, robots_txt_info_url: Option<String>) -> HttpClientResult;
+            async fn get(&self, uri: String, robots_txt_info_url: Option<String>) -> HttpClientResult;
         }
     }
 
     #[tokio::test]
     async fn returns_simple_result_on_simple_request() {
         // given: simple download command
-        let command = DefaultPageDownloadCommand {};
+        let command = DefaultPageDownloadCommand { retryable_status_codes: vec![] };
         let mut mock_http_client = MockMyHttpClient::new();
         mock_http_client.expect_get().returning(|_, _| Ok(Response::builder()
             .status(200)
@@ -75,4 +132,68 @@ mod tests {
         assert_eq!(result.as_ref().unwrap().body.as_ref().unwrap(), "Hello World", "Should have body");
         assert_eq!(result.as_ref().unwrap().response_timings.end_time.is_some(), true, "Should have updated end_time after successful run");
     }
+
+    #[tokio::test]
+    async fn retries_configured_status_codes() {
+        // given: a server responding with 429 first, and 429 being retryable
+        let command = DefaultPageDownloadCommand { retryable_status_codes: vec![429] };
+        let mut mock_http_client = MockMyHttpClient::new();
+        let mut sequence = Sequence::new();
+        mock_http_client.expect_get().times(1).in_sequence(&mut sequence).returning(|_, _| Ok(Response::builder()
+            .status(429)
+            .body(Body::from(""))
+            .unwrap()));
+        mock_http_client.expect_get().times(1).in_sequence(&mut sequence).returning(|_, _| Ok(Response::builder()
+            .status(200)
+            .body(Body::from("Hello World"))
+            .unwrap()));
+
+        // when: fetch is invoked
+        let result = command.download_page("https://example.com".into(), Arc::new(mock_http_client), None).await.unwrap();
+
+        // then: the retried response is returned
+        assert_eq!(result.http_response_code.code, 200, "Should have retried the 429");
+        assert_eq!(result.body.unwrap(), "Hello World");
+    }
+
+    #[tokio::test]
+    async fn download_page_prefix_is_capped() {
+        // given: simple download command
+        let command = DefaultPageDownloadCommand { retryable_status_codes: vec![] };
+        let mut mock_http_client = MockMyHttpClient::new();
+        mock_http_client.expect_get().returning(|_, _| Ok(Response::builder()
+            .status(200)
+            .body(Body::from("<html>Hello World</html>"))
+            .unwrap()));
+        let mock_http_client = Arc::new(mock_http_client);
+
+        // when: prefix download is invoked
+        let result = command.download_page_prefix("https://example.com".into(), mock_http_client, None, 6).await;
+
+        // then: only the prefix is returned
+        assert_eq!(result.unwrap().body.unwrap(), "<html>", "Should only contain the first bytes");
+    }
+
+    #[tokio::test]
+    async fn gzip_encoded_body_is_decoded_and_encoding_is_recorded() {
+        // given: a server responding with a gzip encoded body
+        let command = DefaultPageDownloadCommand { retryable_status_codes: vec![] };
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(b"<html>Hello World</html>").unwrap();
+        let gzipped_body = encoder.finish().unwrap();
+        let mut mock_http_client = MockMyHttpClient::new();
+        mock_http_client.expect_get().returning(move |_, _| Ok(Response::builder()
+            .status(200)
+            .header("content-encoding", "gzip")
+            .body(Body::from(gzipped_body.clone()))
+            .unwrap()));
+        let mock_http_client = Arc::new(mock_http_client);
+
+        // when: fetch is invoked
+        let result = command.download_page("https://example.com".into(), mock_http_client, None).await.unwrap();
+
+        // then: body is decoded and the used encoding is recorded
+        assert_eq!(result.body.unwrap(), "<html>Hello World</html>", "Should have decoded body");
+        assert_eq!(result.content_encoding, Some(String::from("gzip")), "Should record the used encoding");
+    }
 }
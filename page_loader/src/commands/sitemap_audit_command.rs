@@ -0,0 +1,320 @@
+use std::collections::{BTreeSet, HashSet, VecDeque};
+use std::sync::Arc;
+
+use hyper::Uri;
+use responses::dedup_key_rules::DedupKeyRules;
+use responses::sitemap_audit::SitemapAudit;
+use responses::sitemap_summary::SitemapSummary;
+use responses::uri_scope::UriScope;
+use tracing::debug;
+
+use dom_parser::DomParser;
+use dom_parser::sitemap::{extract_sitemap_index_urls, extract_sitemap_urls};
+use linkresult::dedup_key::to_dedup_key;
+use linkresult::uri_service::UriService;
+
+use crate::commands::fetch_header_command::FetchHeaderCommand;
+use crate::commands::page_download_command::PageDownloadCommand;
+use crate::http::http_client::HttpClient;
+use crate::http::http_utils;
+use crate::task_context::robots_service::RobotsTxt;
+
+pub const MAX_AUDITED_SITEMAPS: usize = 100;
+pub const MAX_AUDITED_SITEMAP_URLS: usize = 10_000;
+
+// cross-checks robots.txt, the sitemaps and the links of the seed, without crawling any further.
+// sitemap urls allowed by robots.txt get a HEAD request each, disallowed ones aren't requested at all.
+// Only the host of the seed and its robots.txt is requested, so a sitemap can't point the audit to other servers
+pub struct SitemapAuditCommand {
+    pub fetch_header_command: Box<dyn FetchHeaderCommand>,
+    pub page_download_command: Box<dyn PageDownloadCommand>,
+    pub uri_service: Arc<UriService>,
+    pub dom_parser: Arc<dyn DomParser>,
+    pub http_client: Arc<dyn HttpClient>,
+    pub maximum_redirects: u8,
+    pub robots_txt_info_url: Option<String>,
+    pub max_sitemaps: usize,
+    pub max_sitemap_urls: usize,
+}
+
+impl SitemapAuditCommand {
+    pub async fn audit(&self, seed: &str, robots_txt: &dyn RobotsTxt) -> SitemapAudit {
+        let robots_txt_summary = robots_txt.get_summary();
+        let audited_hosts: Vec<String> = [Some(seed), robots_txt_summary.url.as_deref()].into_iter().flatten()
+            .filter_map(|url| url.parse::<Uri>().ok().and_then(|uri| uri.host().map(|host| host.to_lowercase())))
+            .collect();
+        let mut sitemaps: VecDeque<String> = robots_txt_summary.sitemaps.iter().map(|sitemap| sitemap.url.clone()).collect();
+        // without a Sitemap line in robots.txt, the conventional location is checked
+        if sitemaps.is_empty() {
+            sitemaps.extend(get_default_sitemap_url(seed));
+        }
+
+        let mut sitemap_summaries = vec![];
+        let mut loaded_sitemaps = HashSet::new();
+        let mut sitemap_urls = BTreeSet::new();
+        let mut foreign_urls = BTreeSet::new();
+        let mut truncated = false;
+        while let Some(sitemap_url) = sitemaps.pop_front() {
+            if !loaded_sitemaps.insert(sitemap_url.clone()) { continue; }
+            if !http_utils::is_on_host(&sitemap_url, &audited_hosts) {
+                if foreign_urls.len() < self.max_sitemap_urls {
+                    foreign_urls.insert(sitemap_url);
+                }
+                continue;
+            }
+            if sitemap_summaries.len() >= self.max_sitemaps {
+                debug!("Not auditing sitemap {}, reached {} sitemaps", sitemap_url, self.max_sitemaps);
+                truncated = true;
+                break;
+            }
+            let sitemap_body = match self.page_download_command.download_page(sitemap_url.clone(), self.http_client.clone(), self.robots_txt_info_url.clone()).await {
+                Ok(get_response) if get_response.http_response_code.code < 400 => get_response.body.unwrap_or_default(),
+                Ok(get_response) => {
+                    debug!("Couldn't load sitemap {}: {}", sitemap_url, get_response.http_response_code.code);
+                    sitemap_summaries.push(SitemapSummary { url: sitemap_url, url_count: None });
+                    continue;
+                }
+                Err(error) => {
+                    debug!("Couldn't load sitemap {}: {}", sitemap_url, error);
+                    sitemap_summaries.push(SitemapSummary { url: sitemap_url, url_count: None });
+                    continue;
+                }
+            };
+            // a sitemap index refers to further sitemaps, they are audited as well
+            let index_urls = extract_sitemap_index_urls(&sitemap_body);
+            let page_urls: Vec<String> = extract_sitemap_urls(&sitemap_body).into_iter()
+                .filter(|url| !index_urls.contains(url))
+                .collect();
+            sitemaps.extend(index_urls);
+            sitemap_summaries.push(SitemapSummary { url: sitemap_url, url_count: Some(page_urls.len()) });
+            for page_url in page_urls {
+                if sitemap_urls.len() >= self.max_sitemap_urls {
+                    truncated = true;
+                    break;
+                }
+                if http_utils::is_on_host(&page_url, &audited_hosts) {
+                    sitemap_urls.insert(page_url);
+                } else if foreign_urls.len() < self.max_sitemap_urls {
+                    foreign_urls.insert(page_url);
+                }
+            }
+        }
+
+        let mut disallowed_sitemap_urls = vec![];
+        let mut not_found_sitemap_urls = vec![];
+        for sitemap_url in sitemap_urls.iter() {
+            if !robots_txt.can_access(sitemap_url) {
+                disallowed_sitemap_urls.push(sitemap_url.clone());
+                continue;
+            }
+            let head_response = self.fetch_header_command.fetch_header(sitemap_url.clone(), false, self.maximum_redirects, self.uri_service.clone(), self.http_client.clone(), None, self.robots_txt_info_url.clone()).await;
+            if let Ok((head_response, _)) = head_response {
+                if head_response.http_response_code.code == 404 || head_response.http_response_code.code == 410 {
+                    not_found_sitemap_urls.push(sitemap_url.clone());
+                }
+            }
+        }
+
+        let unlisted_urls = self.get_unlisted_urls(seed, robots_txt, &sitemap_urls).await;
+
+        SitemapAudit {
+            robots_txt_url: robots_txt_summary.url,
+            sitemaps: sitemap_summaries,
+            disallowed_sitemap_urls,
+            not_found_sitemap_urls,
+            unlisted_urls,
+            foreign_urls: foreign_urls.into_iter().collect(),
+            truncated,
+        }
+    }
+
+    // urls are compared by their dedup key, so fragments don't make a difference
+    async fn get_unlisted_urls(&self, seed: &str, robots_txt: &dyn RobotsTxt, sitemap_urls: &BTreeSet<String>) -> Vec<String> {
+        let seed_uri = match seed.parse::<Uri>() {
+            Ok(seed_uri) if seed_uri.scheme_str().is_some() && seed_uri.host().is_some() => seed_uri,
+            _ => return vec![],
+        };
+        let body = match self.page_download_command.download_page(seed.to_string(), self.http_client.clone(), self.robots_txt_info_url.clone()).await {
+            Ok(get_response) => get_response.body.unwrap_or_default(),
+            Err(error) => {
+                debug!("Couldn't load seed {}: {}", seed, error);
+                return vec![];
+            }
+        };
+        let protocol = seed_uri.scheme_str().unwrap();
+        let host = seed_uri.host().unwrap();
+        let dedup_key_rules = DedupKeyRules::default();
+        let listed_keys: HashSet<String> = sitemap_urls.iter().map(|url| to_dedup_key(url, &dedup_key_rules)).collect();
+        let links = self.dom_parser.get_links(protocol, host, &body).map_or(vec![], |uri_result| uri_result.links);
+        links.iter()
+            .filter(|link| link.scope == Some(UriScope::SameDomain) || link.scope == Some(UriScope::Root))
+            .map(|link| self.uri_service.form_full_url(protocol, &link.uri, host, &Some(seed.to_string())).to_string())
+            .map(|url| to_dedup_key(&url, &dedup_key_rules))
+            .filter(|url| !listed_keys.contains(url) && robots_txt.can_access(url))
+            .collect::<BTreeSet<String>>()
+            .into_iter()
+            .collect()
+    }
+}
+
+fn get_default_sitemap_url(seed: &str) -> Option<String> {
+    let seed_uri = seed.parse::<Uri>().ok()?;
+    Some(format!("{}://{}/sitemap.xml", seed_uri.scheme_str()?, seed_uri.authority()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use mockall::*;
+    use responses::get_response::GetResponse;
+    use responses::head_response::HeadResponse;
+    use responses::redirect::Redirect;
+    use responses::robots_txt_summary::{RobotsTxtStatus, RobotsTxtSummary};
+    use responses::status_code::StatusCode;
+
+    use dom_parser::DomParserService;
+    use linkresult::link_type_checker::LinkTypeChecker;
+
+    use crate::commands::fetch_header_command::HeadResponseResult;
+    use crate::http::http_client::HttpClientResult;
+
+    use super::*;
+
+    mock! {
+        #[async_trait]
+        MyHttpClient {}
+        #[async_trait]
+        impl HttpClient for MyHttpClient{
+            async fn head(&self, uri: String, robots_txt_info_url: Option<String>) -> HttpClientResult;
+            async fn get(&self, uri: String, robots_txt_info_url: Option<String>) -> HttpClientResult;
+        }
+    }
+    mock! {
+        #[async_trait]
+        MyFetchHeaderCommand {}
+        #[async_trait]
+        impl FetchHeaderCommand for MyFetchHeaderCommand{
+            async fn fetch_header(&self, url: String, ignore_redirects:bool, maximum_redirects: u8, uri_service: Arc<UriService>, http_client: Arc<dyn HttpClient>, redirects: Option<Vec<Redirect>>, robots_txt_info_url: Option<String>) -> HeadResponseResult;
+        }
+    }
+    mock! {
+        #[async_trait]
+        MyPageDownloadCommand {}
+        #[async_trait]
+        impl PageDownloadCommand for MyPageDownloadCommand{
+                async fn download_page(&self, uri: String, http_client: Arc<dyn HttpClient>, robots_txt_info_url: Option<String>) -> Result<GetResponse, String>;
+                async fn download_page_prefix(&self, uri: String, http_client: Arc<dyn HttpClient>, robots_txt_info_url: Option<String>, max_bytes: usize) -> Result<GetResponse, String>;
+        }
+    }
+
+    fn create_sitemap_audit_command(fetch_header_command: MockMyFetchHeaderCommand, page_download_command: MockMyPageDownloadCommand, max_sitemap_urls: usize) -> SitemapAuditCommand {
+        let link_type_checker = Arc::new(LinkTypeChecker::new("example.com"));
+        SitemapAuditCommand {
+            fetch_header_command: Box::new(fetch_header_command),
+            page_download_command: Box::new(page_download_command),
+            uri_service: Arc::new(UriService::new(link_type_checker.clone())),
+            dom_parser: Arc::new(DomParserService::new(link_type_checker)),
+            http_client: Arc::new(MockMyHttpClient::new()),
+            maximum_redirects: 3,
+            robots_txt_info_url: None,
+            max_sitemaps: MAX_AUDITED_SITEMAPS,
+            max_sitemap_urls,
+        }
+    }
+
+    // disallows everything below /private, like "Disallow: /private" would
+    struct PrivateDisallowingRobotsTxt {}
+
+    impl RobotsTxt for PrivateDisallowingRobotsTxt {
+        fn can_access(&self, item_uri: &str) -> bool {
+            !item_uri.starts_with("https://example.com/private")
+        }
+
+        fn get_summary(&self) -> RobotsTxtSummary {
+            RobotsTxtSummary {
+                url: Some(String::from("https://example.com/robots.txt")),
+                status: RobotsTxtStatus::Parsed,
+                sitemaps: vec![SitemapSummary { url: String::from("https://example.com/sitemap.xml"), url_count: None }],
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_inconsistencies_of_robots_txt_and_sitemap() {
+        // given: a sitemap listing a disallowed, a missing and a regular page, and a seed linking to an unlisted page
+        let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+        mock_page_download_command.expect_download_page().returning(|uri, _, _| {
+            let mut get_response = GetResponse::new(uri.clone(), StatusCode { code: 200, label: String::from("OK") });
+            get_response.body = Some(match uri.as_str() {
+                "https://example.com/sitemap.xml" => String::from("<urlset>\
+                    <url><loc>https://example.com/</loc></url>\
+                    <url><loc>https://example.com/private/report</loc></url>\
+                    <url><loc>https://example.com/gone</loc></url>\
+                    </urlset>"),
+                _ => String::from("<html><body><a href=\"/\">Home</a><a href=\"/unlisted#top\">Unlisted</a><a href=\"/private/draft\">Draft</a></body></html>"),
+            });
+            Ok(get_response)
+        });
+        let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+        mock_fetch_header_command.expect_fetch_header()
+            .withf(|url, _, _, _, _, _, _| url.starts_with("https://example.com/private"))
+            .times(0)
+            .returning(|_, _, _, _, _, _, _| Err(String::from("Should not be requested")));
+        mock_fetch_header_command.expect_fetch_header().returning(|url, _, _, _, _, _, _| {
+            let code = if url.ends_with("/gone") { 404 } else { 200 };
+            Ok((HeadResponse::new(url, StatusCode { code, label: String::from("") }), Arc::new(MockMyHttpClient::new())))
+        });
+        let sitemap_audit_command = create_sitemap_audit_command(mock_fetch_header_command, mock_page_download_command, MAX_AUDITED_SITEMAP_URLS);
+
+        // when: the seed is audited
+        let sitemap_audit = sitemap_audit_command.audit("https://example.com", &PrivateDisallowingRobotsTxt {}).await;
+
+        // then: each inconsistency is reported
+        assert_eq!(sitemap_audit.disallowed_sitemap_urls, vec![String::from("https://example.com/private/report")], "Should report sitemap urls disallowed by robots.txt");
+        assert_eq!(sitemap_audit.not_found_sitemap_urls, vec![String::from("https://example.com/gone")], "Should report missing sitemap urls");
+        assert_eq!(sitemap_audit.unlisted_urls, vec![String::from("https://example.com/unlisted")], "Should report allowed links missing in the sitemap");
+        assert_eq!(sitemap_audit.sitemaps, vec![SitemapSummary { url: String::from("https://example.com/sitemap.xml"), url_count: Some(3) }]);
+        assert_eq!(sitemap_audit.robots_txt_url, Some(String::from("https://example.com/robots.txt")));
+    }
+
+    #[tokio::test]
+    async fn only_audits_the_host_of_the_seed_up_to_the_maximum_of_urls() {
+        // given: a sitemap index referring to a foreign sitemap, and a sitemap with more urls than audited, one of them foreign
+        let mut mock_page_download_command = MockMyPageDownloadCommand::new();
+        mock_page_download_command.expect_download_page().returning(|uri, _, _| {
+            assert_eq!(uri.starts_with("https://example.com"), true, "Should not request {}", uri);
+            let mut get_response = GetResponse::new(uri.clone(), StatusCode { code: 200, label: String::from("OK") });
+            get_response.body = Some(match uri.as_str() {
+                "https://example.com/sitemap.xml" => String::from("<sitemapindex>\
+                    <sitemap><loc>https://internal.example.org/sitemap.xml</loc></sitemap>\
+                    <sitemap><loc>https://example.com/sitemap-pages.xml</loc></sitemap>\
+                    </sitemapindex>"),
+                "https://example.com/sitemap-pages.xml" => String::from("<urlset>\
+                    <url><loc>https://example.com/a</loc></url>\
+                    <url><loc>https://internal.example.org/admin</loc></url>\
+                    <url><loc>https://example.com/b</loc></url>\
+                    <url><loc>https://example.com/c</loc></url>\
+                    </urlset>"),
+                _ => String::from("<html></html>"),
+            });
+            Ok(get_response)
+        });
+        let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+        mock_fetch_header_command.expect_fetch_header()
+            .withf(|url, _, _, _, _, _, _| !url.starts_with("https://example.com"))
+            .times(0)
+            .returning(|_, _, _, _, _, _, _| Err(String::from("Should not be requested")));
+        mock_fetch_header_command.expect_fetch_header()
+            .times(2)
+            .returning(|url, _, _, _, _, _, _| Ok((HeadResponse::new(url, StatusCode { code: 200, label: String::from("") }), Arc::new(MockMyHttpClient::new()))));
+        let sitemap_audit_command = create_sitemap_audit_command(mock_fetch_header_command, mock_page_download_command, 2);
+
+        // when: the seed is audited
+        let sitemap_audit = sitemap_audit_command.audit("https://example.com", &PrivateDisallowingRobotsTxt {}).await;
+
+        // then: foreign urls are reported, but not requested, and the audit stops at its maximum
+        assert_eq!(sitemap_audit.foreign_urls, vec![String::from("https://internal.example.org/admin"), String::from("https://internal.example.org/sitemap.xml")]);
+        assert_eq!(sitemap_audit.truncated, true, "Should report the audit as truncated");
+        assert_eq!(sitemap_audit.sitemaps.len(), 2, "Should only load the sitemaps of the seed's host");
+    }
+}
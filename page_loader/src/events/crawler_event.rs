@@ -1,13 +1,47 @@
 use uuid::Uuid;
 
+use responses::broken_anchor::BrokenAnchor;
+use responses::canonical_issue::CanonicalIssue;
+use responses::crawl_status::CrawlStatus;
+use responses::crawl_times::CrawlTimes;
+use responses::duplicate_content::DuplicateContentCluster;
+use responses::link::Link;
 use responses::page_response::PageResponse;
+use responses::robots_txt_summary::RobotsTxtSummary;
+use responses::run_config::RunConfig;
+use responses::sitemap_audit::SitemapAudit;
+use responses::slow_page::SlowPage;
+use responses::soft_limit::SoftLimit;
+use responses::terminal_reason::TerminalReason;
 
 #[derive(Debug)]
 pub enum CrawlerEvent {
     CompleteEvent {
         uuid: Uuid,
+        crawl_status: Option<CrawlStatus>,
+        broken_anchors: Option<Vec<BrokenAnchor>>,
+        canonical_issues: Option<Vec<CanonicalIssue>>,
+        duplicate_content: Option<Vec<DuplicateContentCluster>>,
+        slowest_pages: Option<Vec<SlowPage>>,
+        robots_txt: Option<RobotsTxtSummary>,
+        sitemap_audit: Option<SitemapAudit>,
+        terminal_reason: TerminalReason,
+        page_count: usize,
+        crawl_times: CrawlTimes,
+        effective_config: Option<RunConfig>,
     },
     PageEvent {
         page_response: PageResponse,
-    }
+    },
+    LinkDiscoveredEvent {
+        uuid: Uuid,
+        source_url: String,
+        link: Link,
+    },
+    SoftLimitEvent {
+        uuid: Uuid,
+        soft_limit: SoftLimit,
+        current: usize,
+        maximum: usize,
+    },
 }
\ No newline at end of file
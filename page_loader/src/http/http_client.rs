@@ -1,101 +1,308 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Formatter;
 use std::ops::Sub;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
 use hyper::{Body, Client, Request, Response};
-use hyper::client::HttpConnector;
 use hyper_tls::HttpsConnector;
-use rand::random;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use responses::tls_info::TlsInfo;
+use tokio::sync::Semaphore;
 use tracing::debug;
 
+use crate::http::tls_info_connector::TlsInfoConnector;
+use crate::http::token_bucket::TokenBucket;
+use crate::task_context::lock_recovery::LockRecovering;
+
+pub const REQUEST_BUDGET_EXHAUSTED_ERROR: &str = "Request budget exhausted";
+
+#[derive(Debug)]
+pub enum HttpClientError {
+    Hyper(hyper::Error),
+    // the request wasn't sent, as all max_requests of the crawl are used up
+    RequestBudgetExhausted,
+}
+
+impl fmt::Display for HttpClientError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpClientError::Hyper(error) => error.fmt(f),
+            HttpClientError::RequestBudgetExhausted => f.write_str(REQUEST_BUDGET_EXHAUSTED_ERROR),
+        }
+    }
+}
+
+impl std::error::Error for HttpClientError {}
+
+impl From<hyper::Error> for HttpClientError {
+    fn from(error: hyper::Error) -> Self {
+        HttpClientError::Hyper(error)
+    }
+}
+
+pub type HttpClientResult = Result<Response<Body>, HttpClientError>;
+
 #[async_trait]
 pub trait HttpClient: Sync + Send {
-    async fn head(&self, uri: String, robots_txt_info_url: Option<String>) -> hyper::Result<Response<Body>>;
-    async fn get(&self, uri: String, robots_txt_info_url: Option<String>) -> hyper::Result<Response<Body>>;
+    async fn head(&self, uri: String, robots_txt_info_url: Option<String>) -> HttpClientResult;
+    async fn get(&self, uri: String, robots_txt_info_url: Option<String>) -> HttpClientResult;
+
+    // clients, that don't capture TLS details, have nothing to report
+    fn take_tls_info(&self, _host: &str) -> Option<TlsInfo> {
+        None
+    }
+
+    // clients, that don't rate limit, have nothing to adjust
+    fn set_rate_limiting_ms(&self, _rate_limiting_ms: usize) {}
 }
 
 pub struct HttpClientImpl {
     user_agent: String,
-    client: Client<HttpsConnector<HttpConnector>>,
-    rate_limiting_ms: usize,
+    accept_encoding: String,
+    client: Client<TlsInfoConnector>,
+    tls_info_connector: TlsInfoConnector,
+    // adjustable while crawling
+    rate_limiting_ms: AtomicUsize,
     last_request_timestamp: Arc<Mutex<Option<Instant>>>,
+    request_counter: Arc<AtomicUsize>,
+    max_requests: Option<usize>,
+    rng: Mutex<StdRng>,
+    // if set, the delay after each request is drawn from this window, instead of always being rate_limiting_ms
+    jitter_window: Option<(u64, u64)>,
+    next_delay_ms: AtomicU64,
+    // if set, requests to the same host start exactly rate_limiting_ms apart, requests to other hosts don't wait for them
+    strict_rate_limit: bool,
+    last_request_start_by_host: Mutex<HashMap<String, Instant>>,
+    // sent with every request, e.g. the client hints of a device profile
+    headers: Vec<(String, String)>,
+    global_rate_limiter: Option<Arc<Mutex<TokenBucket>>>,
+    concurrency_limiter: Option<Semaphore>,
 }
 
 impl HttpClientImpl {
-    pub fn new(user_agent: String, rate_limiting_ms: usize) -> HttpClientImpl {
-        let connector = HttpsConnector::new();
-        HttpClientImpl::new_(connector, user_agent, rate_limiting_ms)
+    pub fn new(user_agent: String, accept_encoding: String, rate_limiting_ms: usize, request_counter: Arc<AtomicUsize>, pool_idle_timeout_ms: u64, pool_max_idle_per_host: usize, capture_tls_info: bool) -> HttpClientImpl {
+        let connector = TlsInfoConnector::new(HttpsConnector::new(), capture_tls_info);
+        // keeping connections alive lets bursts of requests to the same host reuse them
+        let client = Client::builder()
+            .pool_idle_timeout(Duration::from_millis(pool_idle_timeout_ms))
+            .pool_max_idle_per_host(pool_max_idle_per_host)
+            .build::<_, hyper::Body>(connector.clone());
+        HttpClientImpl::new_(client, connector, user_agent, accept_encoding, rate_limiting_ms, request_counter)
     }
 
     #[cfg(test)]
     pub fn new_with_timeout(user_agent: String, rate_limiting_ms: usize, timeout_ms: usize) -> HttpClientImpl {
-        let mut http_connector = HttpConnector::new();
+        let mut http_connector = hyper::client::HttpConnector::new();
         http_connector.set_connect_timeout(Some(Duration::from_millis(timeout_ms as u64)));
-        let https_connector = HttpsConnector::new_with_connector(http_connector);
-        HttpClientImpl::new_(https_connector, user_agent, rate_limiting_ms)
+        let connector = TlsInfoConnector::new(HttpsConnector::new_with_connector(http_connector), false);
+        let client = Client::builder().build::<_, hyper::Body>(connector.clone());
+        HttpClientImpl::new_(client, connector, user_agent, String::from("gzip, deflate, br"), rate_limiting_ms, Arc::new(AtomicUsize::new(0)))
     }
 
-    fn new_(connector: HttpsConnector<HttpConnector>, user_agent: String, rate_limiting_ms: usize) -> HttpClientImpl {
+    fn new_(client: Client<TlsInfoConnector>, tls_info_connector: TlsInfoConnector, user_agent: String, accept_encoding: String, rate_limiting_ms: usize, request_counter: Arc<AtomicUsize>) -> HttpClientImpl {
         HttpClientImpl {
             user_agent,
-            client: Client::builder().build::<_, hyper::Body>(connector),
-            rate_limiting_ms,
+            accept_encoding,
+            client,
+            tls_info_connector,
+            rate_limiting_ms: AtomicUsize::new(rate_limiting_ms),
             last_request_timestamp: Arc::new(Mutex::new(Some(Instant::now().sub(Duration::from_millis(rate_limiting_ms as u64))))),
+            request_counter,
+            max_requests: None,
+            rng: Mutex::new(StdRng::from_entropy()),
+            jitter_window: None,
+            next_delay_ms: AtomicU64::new(rate_limiting_ms as u64),
+            strict_rate_limit: false,
+            last_request_start_by_host: Mutex::new(HashMap::new()),
+            headers: vec![],
+            global_rate_limiter: None,
+            concurrency_limiter: None,
         }
     }
 
-    async fn send_request(&self, method: &str, uri: String, robots_txt_info_url: Option<String>) -> hyper::Result<Response<Body>> {
-        while self.is_blocked() {
-            let sleep_duration = (random::<f64>() * self.rate_limiting_ms as f64) as u64 + self.rate_limiting_ms as u64;
-            debug!("Rate limiting request {}. Random limit: {}ms; Config Setting: {}ms", uri, sleep_duration, self.rate_limiting_ms);
-            // tokio::time::sleep(Duration::from_millis(sleep_duration)).await;
-            tokio::task::yield_now().await;
+    // a given seed makes the delays drawn from the jitter window reproducible. Without window, nothing is drawn
+    pub fn with_rng_seed(mut self, rng_seed: u64) -> HttpClientImpl {
+        self.rng = Mutex::new(StdRng::seed_from_u64(rng_seed));
+        self
+    }
+
+    // shared by all clients of a crawl, so pages and assets draw from the same budget
+    pub fn with_global_rate_limiter(mut self, global_rate_limiter: Arc<Mutex<TokenBucket>>) -> HttpClientImpl {
+        self.global_rate_limiter = Some(global_rate_limiter);
+        self
+    }
+
+    // the budget is shared by all clients counting into the same request_counter
+    pub fn with_max_requests(mut self, max_requests: usize) -> HttpClientImpl {
+        self.max_requests = Some(max_requests);
+        self
+    }
+
+    pub fn with_max_concurrent_requests(mut self, max_concurrent_requests: usize) -> HttpClientImpl {
+        self.concurrency_limiter = Some(Semaphore::new(max_concurrent_requests.max(1)));
+        self
+    }
+
+    // e.g. 500-2000ms regardless of rate_limiting_ms. A max below min is raised to min
+    pub fn with_jitter_window(mut self, jitter_min_ms: usize, jitter_max_ms: usize) -> HttpClientImpl {
+        self.jitter_window = Some((jitter_min_ms as u64, jitter_max_ms.max(jitter_min_ms) as u64));
+        self
+    }
+
+    // replaces the jitter and the spacing across hosts by exactly rate_limiting_ms per host
+    pub fn with_strict_rate_limit(mut self) -> HttpClientImpl {
+        self.strict_rate_limit = true;
+        self
+    }
+
+    pub fn with_headers(mut self, headers: Vec<(String, String)>) -> HttpClientImpl {
+        self.headers = headers;
+        self
+    }
+
+    async fn send_request(&self, method: &str, uri: String, robots_txt_info_url: Option<String>) -> HttpClientResult {
+        if !claim_request(&self.request_counter, self.max_requests) {
+            debug!("Not requesting {}, all {:?} requests are used up", uri, self.max_requests);
+            return Err(HttpClientError::RequestBudgetExhausted);
+        }
+        if self.strict_rate_limit {
+            let host = uri.parse::<hyper::Uri>().ok().and_then(|it| it.authority().map(|authority| authority.to_string())).unwrap_or_default();
+            while let Some(remaining_delay) = self.claim_host_slot(&host) {
+                debug!("Strictly rate limiting request {} for {:?}. Config Setting: {}ms", uri, remaining_delay, self.rate_limiting_ms.load(Ordering::SeqCst));
+                tokio::time::sleep(remaining_delay).await;
+            }
+        } else {
+            // the remaining delay is checked again after sleeping, as other requests or a new rate may have moved it
+            while let Some(remaining_delay) = self.get_remaining_delay() {
+                debug!("Rate limiting request {} for {:?}. Drawn delay: {}ms; Config Setting: {}ms", uri, remaining_delay, self.next_delay_ms.load(Ordering::SeqCst), self.rate_limiting_ms.load(Ordering::SeqCst));
+                tokio::time::sleep(remaining_delay).await;
+            }
         }
+        self.acquire_global_token().await;
 
         let user_agent_string = format!("{}{}", self.user_agent,
                                         robots_txt_info_url
                                             .map_or("".into(),
                                                     |it| format!(" +{}", it)));
 
-        let req = Request::builder()
-            .header("user-agent", user_agent_string)
+        let mut request_builder = Request::builder()
+            .header("user-agent", user_agent_string);
+        if !self.accept_encoding.is_empty() {
+            request_builder = request_builder.header("accept-encoding", self.accept_encoding.clone());
+        }
+        for (name, value) in &self.headers {
+            request_builder = request_builder.header(name.as_str(), value.as_str());
+        }
+        let req = request_builder
             .method(method)
             .uri(uri.clone())
             .body(Body::from(""))
             .expect(&format!("{} request builder", method));
 
         debug!("request {}", uri);
+        let permit = match &self.concurrency_limiter {
+            Some(concurrency_limiter) => Some(concurrency_limiter.acquire().await.expect("concurrency limiter is never closed")),
+            None => None,
+        };
         let result = self.client.request(req).await;
-        let instant = self.last_request_timestamp.lock().unwrap().unwrap();
+        drop(permit);
+        let instant = self.last_request_timestamp.lock_recovering().unwrap();
         debug!("request end {}, last_request_timestamp {:?}", uri,instant);
-        self.last_request_timestamp.lock().unwrap().replace(Instant::now());
-        let instant = self.last_request_timestamp.lock().unwrap().unwrap();
+        self.last_request_timestamp.lock_recovering().replace(Instant::now());
+        self.next_delay_ms.store(self.compute_delay_ms(), Ordering::SeqCst);
+        let instant = self.last_request_timestamp.lock_recovering().unwrap();
         debug!("request end {}, last_request_timestamp {:?}", uri,instant);
 
-        result
+        Ok(result?)
+    }
+
+    // the global limiter is shared by all clients of a crawl, on top of the rate limiting of this client
+    async fn acquire_global_token(&self) {
+        if let Some(global_rate_limiter) = &self.global_rate_limiter {
+            loop {
+                let acquire_result = global_rate_limiter.lock_recovering().try_acquire();
+                match acquire_result {
+                    Ok(_) => break,
+                    Err(wait_duration) => tokio::time::sleep(wait_duration).await,
+                }
+            }
+        }
+    }
+
+    // without a jitter window, requests are spaced by exactly rate_limiting_ms
+    fn compute_delay_ms(&self) -> u64 {
+        match self.jitter_window {
+            Some(jitter_window) => self.next_jitter_ms(jitter_window),
+            None => self.rate_limiting_ms.load(Ordering::SeqCst) as u64,
+        }
+    }
+
+    fn next_jitter_ms(&self, (jitter_min_ms, jitter_max_ms): (u64, u64)) -> u64 {
+        self.rng.lock_recovering().gen_range(jitter_min_ms..=jitter_max_ms)
+    }
+
+    // claims the start of the next request to the host, if rate_limiting_ms passed since the last one started.
+    // Otherwise the time left is returned. Claiming right away makes parallel requests to the host queue up
+    fn claim_host_slot(&self, host: &str) -> Option<Duration> {
+        let rate_limiting = Duration::from_millis(self.rate_limiting_ms.load(Ordering::SeqCst) as u64);
+        let mut last_request_start_by_host = self.last_request_start_by_host.lock_recovering();
+        let now = Instant::now();
+        let remaining_delay = last_request_start_by_host.get(host)
+            .and_then(|last_request_start| (*last_request_start + rate_limiting).checked_duration_since(now))
+            .filter(|remaining_delay| !remaining_delay.is_zero());
+        if remaining_delay.is_none() {
+            last_request_start_by_host.insert(host.to_string(), now);
+        }
+        remaining_delay
     }
 
-    fn is_blocked(&self) -> bool {
-        debug!("is_blocked: elapsed {}", self.last_request_timestamp.lock().unwrap().unwrap().elapsed().as_millis());
-        self.last_request_timestamp.lock().unwrap().unwrap()
-            .elapsed().as_millis() <= self.rate_limiting_ms as u128
+    // the time left of the delay drawn after the last request, None once it has passed
+    fn get_remaining_delay(&self) -> Option<Duration> {
+        let elapsed = self.last_request_timestamp.lock_recovering().unwrap().elapsed();
+        Duration::from_millis(self.next_delay_ms.load(Ordering::SeqCst))
+            .checked_sub(elapsed)
+            .filter(|remaining_delay| !remaining_delay.is_zero())
     }
 }
 
+// checking and counting is a single step, so parallel workers can't claim more requests than the budget allows
+pub(crate) fn claim_request(request_counter: &AtomicUsize, max_requests: Option<usize>) -> bool {
+    let max_requests = max_requests.unwrap_or(usize::MAX);
+    request_counter
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |request_count| if request_count < max_requests { Some(request_count + 1) } else { None })
+        .is_ok()
+}
+
 #[async_trait]
 impl HttpClient for HttpClientImpl {
-    async fn head(&self, uri: String, robots_txt_info_url: Option<String>) -> hyper::Result<Response<Body>> {
+    async fn head(&self, uri: String, robots_txt_info_url: Option<String>) -> HttpClientResult {
         self.send_request("HEAD", uri, robots_txt_info_url).await
     }
 
-    async fn get(&self, uri: String, robots_txt_info_url: Option<String>) -> hyper::Result<Response<Body>> {
+    async fn get(&self, uri: String, robots_txt_info_url: Option<String>) -> HttpClientResult {
         self.send_request("GET", uri, robots_txt_info_url).await
     }
+
+    // the delay before the next request is recomputed, so the new rate applies right away
+    fn set_rate_limiting_ms(&self, rate_limiting_ms: usize) {
+        self.rate_limiting_ms.store(rate_limiting_ms, Ordering::SeqCst);
+        self.next_delay_ms.store(self.compute_delay_ms(), Ordering::SeqCst);
+    }
+
+    fn take_tls_info(&self, host: &str) -> Option<TlsInfo> {
+        self.tls_info_connector.take_tls_info(host)
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
     use super::*;
 
     #[tokio::test]
@@ -138,4 +345,275 @@ mod tests {
         assert_eq!(second_first_diff >= rate_limit as u128, true);
         assert_eq!(third_second_diff >= rate_limit as u128, true);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn updated_rate_limit_applies_to_subsequent_requests() {
+        // given: a client without rate limit, that already sent a request
+        let client = HttpClientImpl::new_with_timeout("test-client".into(), 0, 10);
+        let _ = client.send_request("GET", String::from("https://localhost:12345"), None).await;
+        let first_request_end = client.last_request_timestamp.lock().unwrap().unwrap();
+
+        // when: the rate limit is raised before the next request
+        client.set_rate_limiting_ms(200);
+        let _ = client.send_request("GET", String::from("https://localhost:12345"), None).await;
+
+        // then: the next request waits for the new rate limit
+        assert_eq!(first_request_end.elapsed().as_millis() >= 200, true, "Should space the requests by the updated rate limit");
+        assert_eq!(client.compute_delay_ms(), 200);
+    }
+
+    #[test]
+    fn same_rng_seed_produces_same_delay_sequence() {
+        // given: two clients with the same seed and jitter window
+        let create_client = |rng_seed: u64| HttpClientImpl::new("test-client".into(), "gzip, deflate, br".into(), 1000, Arc::new(AtomicUsize::new(0)), 90000, 32, false)
+            .with_rng_seed(rng_seed)
+            .with_jitter_window(0, 1000);
+        let first_client = create_client(42);
+        let second_client = create_client(42);
+        let differently_seeded_client = create_client(7);
+
+        // when: delays are computed several times
+        let first_sequence: Vec<u64> = (0..10).map(|_| first_client.compute_delay_ms()).collect();
+        let second_sequence: Vec<u64> = (0..10).map(|_| second_client.compute_delay_ms()).collect();
+        let different_sequence: Vec<u64> = (0..10).map(|_| differently_seeded_client.compute_delay_ms()).collect();
+
+        // then: the sequences are identical for the same seed
+        assert_eq!(first_sequence, second_sequence);
+        assert_ne!(first_sequence, different_sequence);
+        assert_eq!(first_sequence.iter().all(|delay| *delay <= 1000), true, "Delays should stay within the jitter window");
+    }
+
+    #[tokio::test]
+    async fn requests_are_spaced_by_the_seeded_jitter() {
+        // given: a server recording its requests and a client with seeded jitter
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let uri = format!("http://{}/", listener.local_addr().unwrap());
+        let request_times = Arc::new(Mutex::new(vec![]));
+        let request_times_server = request_times.clone();
+        tokio::spawn(async move {
+            while let Ok((mut connection, _)) = listener.accept().await {
+                let request_times = request_times_server.clone();
+                tokio::spawn(async move {
+                    let mut buffer = vec![0u8; 4096];
+                    while let Ok(read) = connection.read(&mut buffer).await {
+                        if read == 0 {
+                            break;
+                        }
+                        request_times.lock().unwrap().push(Instant::now());
+                        if connection.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n").await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        let create_client = || HttpClientImpl::new("test-client".into(), "gzip, deflate, br".into(), 0, Arc::new(AtomicUsize::new(0)), 90000, 32, false)
+            .with_rng_seed(42)
+            .with_jitter_window(200, 400);
+        let client = create_client();
+        let equally_seeded_client = create_client();
+        let expected_delays: Vec<u64> = (0..3).map(|_| equally_seeded_client.compute_delay_ms()).collect();
+
+        // when: several requests are sent one after another
+        for _ in 0..4 {
+            let response = client.get(uri.clone(), None).await.unwrap();
+            hyper::body::to_bytes(response.into_body()).await.unwrap();
+        }
+
+        // then: each request waits for the delay drawn after its predecessor
+        let request_times = request_times.lock().unwrap().clone();
+        assert_eq!(request_times.len(), 4);
+        for (index, expected_delay) in expected_delays.iter().enumerate() {
+            let gap = request_times[index + 1].duration_since(request_times[index]).as_millis() as u64;
+            assert_eq!(gap >= *expected_delay && gap < *expected_delay + 150, true, "Request {} should follow after about {}ms, but took {}ms", index + 1, expected_delay, gap);
+        }
+    }
+
+    #[test]
+    fn delays_stay_within_the_jitter_window() {
+        // given: a client with a base delay outside of its jitter window
+        let client = HttpClientImpl::new("test-client".into(), "gzip, deflate, br".into(), 100, Arc::new(AtomicUsize::new(0)), 90000, 32, false).with_rng_seed(42)
+            .with_jitter_window(500, 2000);
+        let client_without_window = HttpClientImpl::new("test-client".into(), "gzip, deflate, br".into(), 100, Arc::new(AtomicUsize::new(0)), 90000, 32, false).with_rng_seed(42);
+
+        // when: delays are computed several times
+        let delays: Vec<u64> = (0..100).map(|_| client.compute_delay_ms()).collect();
+
+        // then: they are spread within the window, regardless of the base delay
+        assert_eq!(delays.iter().all(|delay| (500..=2000).contains(delay)), true, "Delays should stay within 500-2000ms, got {:?}", delays);
+        assert_ne!(delays.iter().min(), delays.iter().max(), "Delays should be randomized");
+        assert_eq!(client_without_window.compute_delay_ms(), 100, "Should keep the base delay without window");
+    }
+
+    #[tokio::test]
+    async fn strict_rate_limit_spaces_requests_per_host() {
+        // given: a server reachable via two host names and a strict client
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut connection, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buffer = vec![0u8; 4096];
+                    while let Ok(read) = connection.read(&mut buffer).await {
+                        if read == 0 || connection.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n").await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        let client = HttpClientImpl::new("test-client".into(), "".into(), 300, Arc::new(AtomicUsize::new(0)), 5000, 4, false).with_strict_rate_limit();
+        let first_host = format!("http://127.0.0.1:{}/", port);
+        let second_host = format!("http://localhost:{}/", port);
+
+        // when: the first host is requested twice, with a request to the second host in between
+        let start = Instant::now();
+        let _ = client.get(first_host.clone(), None).await.unwrap();
+        let _ = client.get(second_host, None).await.unwrap();
+        let second_host_elapsed_ms = start.elapsed().as_millis();
+        let _ = client.get(first_host, None).await.unwrap();
+        let first_host_elapsed_ms = start.elapsed().as_millis();
+
+        // then: only the request to the same host waits, for exactly the rate limit
+        assert_eq!(second_host_elapsed_ms < 100, true, "Should not delay another host, but took {}ms", second_host_elapsed_ms);
+        assert_eq!((300..330).contains(&first_host_elapsed_ms), true, "Should start the next request to the host 300ms after the first, but took {}ms", first_host_elapsed_ms);
+    }
+
+    #[tokio::test]
+    async fn every_request_is_counted() {
+        // given: a client
+        let client = HttpClientImpl::new_with_timeout("test-client".into(), 0, 10);
+
+        // when: client is invoked several times
+        let _ = client.send_request("HEAD", String::from("https://localhost:12345"), None).await;
+        let _ = client.send_request("GET", String::from("https://localhost:12345"), None).await;
+
+        // then: all requests are counted, regardless of their outcome
+        assert_eq!(client.request_counter.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn requests_beyond_max_requests_are_not_sent() {
+        // given: a client with a budget of 3 requests
+        let client = Arc::new(HttpClientImpl::new_with_timeout("test-client".into(), 0, 10).with_max_requests(3));
+
+        // when: more requests are sent in parallel
+        let requests: Vec<_> = (0..10).map(|_| {
+            let client = client.clone();
+            tokio::spawn(async move { client.send_request("GET", String::from("https://localhost:12345"), None).await })
+        }).collect();
+        let mut budget_exhausted_count = 0;
+        for request in requests {
+            if let Err(HttpClientError::RequestBudgetExhausted) = request.await.unwrap() {
+                budget_exhausted_count += 1;
+            }
+        }
+
+        // then: only the budget is requested, the others are refused
+        assert_eq!(client.request_counter.load(Ordering::SeqCst), 3);
+        assert_eq!(budget_exhausted_count, 7, "Should refuse all requests beyond the budget");
+    }
+
+    #[tokio::test]
+    async fn accept_encoding_header_is_sent() {
+        // given: a server capturing the incoming request
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let uri = format!("http://{}/", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut connection, _) = listener.accept().await.unwrap();
+            let mut buffer = vec![0u8; 4096];
+            let read = connection.read(&mut buffer).await.unwrap();
+            connection.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n").await.unwrap();
+            String::from_utf8_lossy(&buffer[..read]).to_lowercase()
+        });
+        let client = HttpClientImpl::new("test-client".into(), "gzip, br".into(), 0, Arc::new(AtomicUsize::new(0)), 90000, 32, false);
+
+        // when: a request is sent
+        let _ = client.get(uri, None).await;
+
+        // then: the configured accept-encoding header is part of the request
+        let request = server.await.unwrap();
+        assert_eq!(request.contains("accept-encoding: gzip, br\r\n"), true, "Should send accept-encoding header, got {}", request);
+    }
+
+    #[tokio::test]
+    async fn connections_are_reused_with_custom_pool_settings() {
+        // given: a keep-alive server counting its connections and a client with custom pool settings
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let uri = format!("http://{}/", listener.local_addr().unwrap());
+        let accepted_connections = Arc::new(AtomicUsize::new(0));
+        let accepted_connections_clone = accepted_connections.clone();
+        tokio::spawn(async move {
+            while let Ok((mut connection, _)) = listener.accept().await {
+                accepted_connections_clone.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut buffer = vec![0u8; 4096];
+                    while let Ok(read) = connection.read(&mut buffer).await {
+                        if read == 0 || connection.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n").await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        let client = HttpClientImpl::new("test-client".into(), "gzip, deflate, br".into(), 0, Arc::new(AtomicUsize::new(0)), 5000, 4, false);
+
+        // when: several requests are sent one after another
+        for _ in 0..3 {
+            let response = client.get(uri.clone(), None).await.unwrap();
+            hyper::body::to_bytes(response.into_body()).await.unwrap();
+        }
+
+        // then: a single connection is used for all of them
+        assert_eq!(accepted_connections.load(Ordering::SeqCst), 1, "Should reuse the pooled connection");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn global_rate_limit_caps_requests_across_hosts() {
+        // given: a server reachable via two host names and clients sharing a global limit of 20 requests per second
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let request_times = Arc::new(Mutex::new(vec![]));
+        let request_times_server = request_times.clone();
+        tokio::spawn(async move {
+            while let Ok((mut connection, _)) = listener.accept().await {
+                let request_times = request_times_server.clone();
+                tokio::spawn(async move {
+                    let mut buffer = vec![0u8; 4096];
+                    while let Ok(read) = connection.read(&mut buffer).await {
+                        request_times.lock().unwrap().push(Instant::now());
+                        if read == 0 || connection.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n").await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        let requests_per_sec = 20.0;
+        let global_rate_limiter = Arc::new(Mutex::new(TokenBucket::new(requests_per_sec)));
+        let create_client = || Arc::new(HttpClientImpl::new("test-client".into(), "".into(), 0, Arc::new(AtomicUsize::new(0)), 5000, 4, false).with_global_rate_limiter(global_rate_limiter.clone()));
+        let hosts = vec![format!("http://127.0.0.1:{}/", port), format!("http://localhost:{}/", port)];
+
+        // when: both clients send requests concurrently
+        let start = Instant::now();
+        let mut handles = vec![];
+        for host in hosts {
+            let client = create_client();
+            handles.push(tokio::spawn(async move {
+                for _ in 0..10 {
+                    let response = client.get(host.clone(), None).await.unwrap();
+                    hyper::body::to_bytes(response.into_body()).await.unwrap();
+                }
+            }));
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        let elapsed = start.elapsed();
+
+        // then: 20 requests at 20 per second take at least 19 token intervals
+        let request_count = request_times.lock().unwrap().len();
+        assert_eq!(request_count >= 20, true, "Should have received all requests");
+        assert_eq!(elapsed >= Duration::from_millis(950), true, "Aggregate rate should stay under the ceiling, but took {:?}", elapsed);
+    }
+}
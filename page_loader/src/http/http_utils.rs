@@ -1,9 +1,17 @@
 use std::collections::HashMap;
+use std::io::Read;
 
+use brotli_decompressor::Decompressor;
+use flate2::read::{GzDecoder, ZlibDecoder};
 use hyper::{Body, Response};
+use tracing::debug;
 
 use responses::status_code::StatusCode;
 
+pub const DEFAULT_RETRYABLE_STATUS_CODES: [u16; 3] = [502, 503, 504];
+// the crawl delay of the http client spaces out the retries
+pub const MAX_STATUS_RETRIES: usize = 2;
+
 pub fn response_headers_to_map(response: &Response<Body>) -> HashMap<String, String> {
     response.headers().iter()
         .map(|(key, value)| {
@@ -11,6 +19,84 @@ pub fn response_headers_to_map(response: &Response<Body>) -> HashMap<String, Str
         }).collect()
 }
 
+// names are expected in lowercase, like the keys of the header map
+pub fn select_headers(headers: &HashMap<String, String>, names: &[String]) -> HashMap<String, String> {
+    headers.iter()
+        .filter(|(key, _)| names.contains(key))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+// the last response is returned as is, once retries are used up
+pub fn is_retryable_status(response: &Response<Body>, retryable_status_codes: &[u16], attempt: usize) -> bool {
+    attempt < MAX_STATUS_RETRIES && retryable_status_codes.contains(&response.status().as_u16())
+}
+
+// undoes the content-encoding, bodies with unknown encodings are passed through unchanged
+pub fn decode_body(body: &[u8], content_encoding: Option<&String>) -> Vec<u8> {
+    let mut decoded = vec![];
+    let result = match content_encoding.map(|encoding| encoding.trim().to_lowercase()).as_deref() {
+        Some("gzip") | Some("x-gzip") => GzDecoder::new(body).read_to_end(&mut decoded),
+        Some("deflate") => ZlibDecoder::new(body).read_to_end(&mut decoded),
+        Some("br") => Decompressor::new(body, 4096).read_to_end(&mut decoded),
+        _ => return body.to_vec(),
+    };
+    // truncated bodies, e.g. downloaded prefixes, keep whatever could be decoded
+    if let Err(error) = result {
+        debug!("decoding {:?} body stopped early: {}", content_encoding, error);
+    }
+    decoded
+}
+
+// parses X-Robots-Tag and meta robots values into (noindex, nofollow), user agent prefixes like "googlebot: noindex" are ignored
+pub fn parse_robots_directives(directives: &str) -> (bool, bool) {
+    let mut noindex = false;
+    let mut nofollow = false;
+    for directive in directives.split(',') {
+        match directive.rsplit(':').next().unwrap_or("").trim().to_lowercase().as_str() {
+            "noindex" => noindex = true,
+            "nofollow" => nofollow = true,
+            "none" => {
+                noindex = true;
+                nofollow = true;
+            }
+            _ => {}
+        }
+    }
+    (noindex, nofollow)
+}
+
+// returns the targets of all rel="next" entries of a Link header, e.g. <https://example.com/?page=2>; rel="next"
+pub fn parse_link_header_next(link_header: &str) -> Vec<String> {
+    let mut next_uris = vec![];
+    let mut remainder = link_header;
+    while let Some(start) = remainder.find('<') {
+        let end = match remainder[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let uri = remainder[start + 1..end].trim();
+        let params_end = remainder[end..].find('<').map_or(remainder.len(), |next_start| end + next_start);
+        let is_next = remainder[end + 1..params_end].split(';')
+            .filter_map(|param| param.split_once('='))
+            .filter(|(name, _)| name.trim().eq_ignore_ascii_case("rel"))
+            .any(|(_, value)| value.trim().trim_end_matches(',').trim().trim_matches('"').split_whitespace().any(|rel| rel.eq_ignore_ascii_case("next")));
+        if is_next && !uri.is_empty() {
+            next_uris.push(uri.to_string());
+        }
+        remainder = &remainder[params_end..];
+    }
+    next_uris
+}
+
+// whether the host of an absolute or protocol relative uri is one of the given, lowercased hosts
+pub fn is_on_host(uri: &str, hosts: &[String]) -> bool {
+    let absolute_uri = if uri.starts_with("//") { format!("https:{}", uri) } else { uri.to_string() };
+    absolute_uri.parse::<hyper::Uri>().ok()
+        .and_then(|uri| uri.host().map(|host| host.to_lowercase()))
+        .map_or(false, |host| hosts.contains(&host))
+}
+
 fn build_status_codes() -> HashMap<u16, &'static str> {
     let mut status_codes = HashMap::new();
     status_codes.insert(520u16, "[CLOUDFLARE] Web Server Returned an Unknown Error");
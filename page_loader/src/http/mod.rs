@@ -1,2 +1,5 @@
 pub mod http_client;
-pub(crate) mod http_utils;
\ No newline at end of file
+pub(crate) mod http_utils;
+pub mod resource_kind;
+pub mod tls_info_connector;
+pub mod token_bucket;
\ No newline at end of file
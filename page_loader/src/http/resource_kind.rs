@@ -0,0 +1,74 @@
+use responses::link::Link;
+
+const ASSET_ELEMENTS: [&str; 9] = ["img", "script", "link", "source", "video", "audio", "track", "embed", "object"];
+const ASSET_EXTENSIONS: [&str; 21] = [
+    "png", "jpg", "jpeg", "gif", "svg", "webp", "avif", "ico", "bmp",
+    "css", "js", "mjs", "map",
+    "woff", "woff2", "ttf", "otf", "eot",
+    "mp4", "webm", "mp3",
+];
+
+// selects the http client (and with it the politeness settings) used for a request
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ResourceKind {
+    Page,
+    Asset,
+}
+
+impl ResourceKind {
+    // alternate versions of a page are pages, even though they are referenced via <link>
+    pub fn of(link: &Link) -> ResourceKind {
+        let element_name = link.source_tag.as_ref().and_then(|source_tag| get_element_name(source_tag));
+        match element_name {
+            Some(element_name) if ASSET_ELEMENTS.contains(&element_name.as_str()) && link.alternate.is_none() => ResourceKind::Asset,
+            _ => ResourceKind::Page,
+        }
+    }
+
+    // links from asset elements, or e.g. to images from an <a>, which still are no html
+    pub fn is_asset(link: &Link) -> bool {
+        ResourceKind::of(link) == ResourceKind::Asset
+            || link.extension.as_ref().map_or(false, |extension| ASSET_EXTENSIONS.contains(&extension.as_str()))
+    }
+}
+
+// source tags are formatted like <img src="...">
+fn get_element_name(source_tag: &str) -> Option<String> {
+    let start = source_tag.find('<')? + 1;
+    source_tag[start..].split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .next()
+        .filter(|element_name| !element_name.is_empty())
+        .map(|element_name| element_name.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_resource_kind_from_source_tag() {
+        // given: links from different elements
+        let create_link = |source_tag: &str| {
+            let mut link = Link::from_str("https://example.com/resource");
+            link.source_tag = Some(String::from(source_tag));
+            link
+        };
+
+        // when / then: only asset elements are assets
+        assert_eq!(ResourceKind::of(&create_link("<img src=\"/a.png\">")), ResourceKind::Asset);
+        assert_eq!(ResourceKind::of(&create_link("<link rel=\"stylesheet\" href=\"/a.css\">")), ResourceKind::Asset);
+        assert_eq!(ResourceKind::of(&create_link("<a href=\"/page\">")), ResourceKind::Page);
+        assert_eq!(ResourceKind::of(&Link::from_str("https://example.com/unknown")), ResourceKind::Page, "Links without source tag are pages");
+    }
+
+    #[test]
+    fn identifies_assets_by_element_or_extension() {
+        let mut image = Link::from_str("https://example.com/image");
+        image.source_tag = Some(String::from("<img src=\"/image\">"));
+
+        assert_eq!(ResourceKind::is_asset(&image), true, "Should identify assets by their element");
+        assert_eq!(ResourceKind::is_asset(&Link::from_str("https://example.com/photo.JPG")), true, "Should identify assets by their extension");
+        assert_eq!(ResourceKind::is_asset(&Link::from_str("https://example.com/report.pdf")), false, "Documents are no assets");
+        assert_eq!(ResourceKind::is_asset(&Link::from_str("https://example.com/about")), false, "Pages are no assets");
+    }
+}
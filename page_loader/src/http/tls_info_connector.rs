@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use hyper::client::HttpConnector;
+use hyper::service::Service;
+use hyper::Uri;
+use hyper_tls::{HttpsConnector, MaybeHttpsStream};
+use openssl::x509::{X509, X509NameRef};
+use responses::tls_info::TlsInfo;
+use tokio::net::TcpStream;
+use tracing::debug;
+
+use crate::task_context::lock_recovery::LockRecovering;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+// wraps the https connector, to read the peer certificate of each new TLS connection.
+// Only the first certificate per host is kept, a reported host keeps its entry with None, so it isn't captured again
+#[derive(Clone)]
+pub struct TlsInfoConnector {
+    https_connector: HttpsConnector<HttpConnector>,
+    tls_infos: Option<Arc<Mutex<HashMap<String, Option<TlsInfo>>>>>,
+}
+
+impl TlsInfoConnector {
+    pub fn new(https_connector: HttpsConnector<HttpConnector>, capture_tls_info: bool) -> TlsInfoConnector {
+        TlsInfoConnector {
+            https_connector,
+            tls_infos: if capture_tls_info { Some(Arc::new(Mutex::new(HashMap::new()))) } else { None },
+        }
+    }
+
+    // the details are reported once per host
+    pub fn take_tls_info(&self, host: &str) -> Option<TlsInfo> {
+        self.tls_infos.as_ref()
+            .and_then(|tls_infos| tls_infos.lock_recovering().get_mut(host).and_then(|tls_info| tls_info.take()))
+    }
+}
+
+impl Service<Uri> for TlsInfoConnector {
+    type Response = MaybeHttpsStream<TcpStream>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output=Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.https_connector.poll_ready(cx)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let host = uri.host().map(|host| host.to_lowercase());
+        let tls_infos = self.tls_infos.clone();
+        let connecting = self.https_connector.call(uri);
+        Box::pin(async move {
+            let stream = connecting.await?;
+            if let (Some(tls_infos), Some(host), MaybeHttpsStream::Https(tls_stream)) = (tls_infos, host, &stream) {
+                if !tls_infos.lock_recovering().contains_key(&host) {
+                    let tls_info = match tls_stream.get_ref().peer_certificate() {
+                        Ok(Some(certificate)) => certificate.to_der().ok().and_then(|der| read_tls_info(&der)),
+                        _ => None,
+                    };
+                    debug!("Captured TLS info of {}: {:?}", host, tls_info);
+                    tls_infos.lock_recovering().entry(host).or_insert(tls_info);
+                }
+            }
+            Ok(stream)
+        })
+    }
+}
+
+fn read_tls_info(der: &[u8]) -> Option<TlsInfo> {
+    let certificate = X509::from_der(der).ok()?;
+    Some(TlsInfo {
+        subject: format_name(certificate.subject_name()),
+        issuer: format_name(certificate.issuer_name()),
+        not_after: Some(certificate.not_after().to_string()),
+    })
+}
+
+fn format_name(name: &X509NameRef) -> Option<String> {
+    let entries: Vec<String> = name.entries()
+        .filter_map(|entry| {
+            let key = entry.object().nid().short_name().ok()?;
+            Some(format!("{}={}", key, String::from_utf8_lossy(entry.data().as_slice())))
+        })
+        .collect();
+    if entries.is_empty() { None } else { Some(entries.join(", ")) }
+}
+
+#[cfg(test)]
+mod tests {
+    use openssl::asn1::Asn1Time;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use openssl::x509::X509NameBuilder;
+
+    use super::*;
+
+    #[test]
+    fn reads_subject_issuer_and_expiry_of_a_certificate() {
+        // given: a self-signed certificate
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("O", "Example Org").unwrap();
+        name.append_entry_by_text("CN", "example.com").unwrap();
+        let name = name.build();
+        let mut builder = X509::builder().unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::from_str("20301231235959Z").unwrap()).unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+        let der = builder.build().to_der().unwrap();
+
+        // when: the details are read
+        let tls_info = read_tls_info(&der).unwrap();
+
+        // then: all fields are populated
+        assert_eq!(tls_info.subject, Some(String::from("O=Example Org, CN=example.com")));
+        assert_eq!(tls_info.issuer, Some(String::from("O=Example Org, CN=example.com")));
+        assert_eq!(tls_info.not_after.unwrap().contains("2030"), true, "Should contain the expiry year");
+    }
+
+    #[test]
+    fn nothing_is_reported_without_capturing() {
+        // given: connectors with and without capturing
+        let capturing_connector = TlsInfoConnector::new(HttpsConnector::new(), true);
+        let non_capturing_connector = TlsInfoConnector::new(HttpsConnector::new(), false);
+        capturing_connector.tls_infos.as_ref().unwrap().lock().unwrap()
+            .insert(String::from("example.com"), Some(TlsInfo { subject: None, issuer: None, not_after: None }));
+
+        // when: the details are taken twice
+        let first = capturing_connector.take_tls_info("example.com");
+        let second = capturing_connector.take_tls_info("example.com");
+
+        // then: they are reported once, and never without capturing
+        assert_eq!(first.is_some(), true, "Should report the details of the host");
+        assert_eq!(second.is_none(), true, "Should only report the details once per host");
+        assert_eq!(non_capturing_connector.take_tls_info("example.com").is_none(), true, "Should not report anything without capturing");
+    }
+}
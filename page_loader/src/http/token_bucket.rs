@@ -0,0 +1,68 @@
+use std::time::{Duration, Instant};
+
+// Crawl-wide request rate limit. Tokens are refilled continuously at requests_per_sec, the bucket holds
+// a single token, so requests can't burst above the configured rate after idle periods.
+#[derive(Debug)]
+pub struct TokenBucket {
+    requests_per_sec: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(requests_per_sec: f64) -> TokenBucket {
+        TokenBucket {
+            requests_per_sec,
+            tokens: 1.0,
+            last_refill: Instant::now(),
+        }
+    }
+
+    // takes a token, if one is available. Otherwise returns how long to wait for the next one
+    pub fn try_acquire(&mut self) -> Result<(), Duration> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.requests_per_sec).min(1.0);
+        self.last_refill = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+        // a rate of 0 never refills, that wait can't be represented
+        Err(Duration::try_from_secs_f64((1.0 - self.tokens) / self.requests_per_sec).unwrap_or(Duration::MAX))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hands_out_a_single_token_until_refilled() {
+        // given: a bucket with 10 tokens per second
+        let mut token_bucket = TokenBucket::new(10.0);
+
+        // when: tokens are acquired back to back
+        let first = token_bucket.try_acquire();
+        let second = token_bucket.try_acquire();
+
+        // then: the second one has to wait for about 100ms
+        assert_eq!(first.is_ok(), true, "Should hand out the initial token");
+        let wait = second.unwrap_err();
+        assert_eq!(wait > Duration::from_millis(90) && wait <= Duration::from_millis(100), true, "Should wait for the next token, but waited {:?}", wait);
+    }
+
+    #[test]
+    fn waits_without_panicking_for_a_rate_of_0() {
+        // given: a bucket, that is never refilled
+        let mut token_bucket = TokenBucket::new(0.0);
+
+        // when: tokens are acquired back to back
+        let first = token_bucket.try_acquire();
+        let second = token_bucket.try_acquire();
+
+        // then: the second one waits indefinitely
+        assert_eq!(first.is_ok(), true, "Should hand out the initial token");
+        assert_eq!(second, Err(Duration::MAX));
+    }
+}
@@ -2,7 +2,7 @@
 
 // Event-driven page loader
 
-mod commands;
+pub mod commands;
 pub mod events;
 mod http;
 pub mod page_request;
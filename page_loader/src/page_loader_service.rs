@@ -1,27 +1,53 @@
 use std::{fmt, thread};
 use std::cmp::max;
+use std::collections::BTreeSet;
 use std::fmt::Formatter;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::Ordering;
 
+use hyper::header::CONTENT_TYPE;
+use hyper::Uri;
+use responses::crawl_status::CrawlStatus;
+use responses::crawl_times::CrawlTimes;
+use responses::dedup_key_rules::DedupKeyRules;
+use responses::domain_scope::DomainScope;
 use responses::link::Link;
 use responses::page_response::PageResponse;
 use responses::run_config::RunConfig;
+use responses::sitemap_audit::SitemapAudit;
+use responses::soft_limit::SoftLimit;
+use responses::terminal_reason::TerminalReason;
 use responses::uri_scope::UriScope;
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::Sender;
-use tokio::time::Instant;
-use tracing::{debug, error, warn};
+use tokio::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
-use crate::commands::fetch_header_command::DefaultFetchHeaderCommand;
+use linkresult::dedup_key::{to_dedup_key, to_known_path};
+use linkresult::registrable_domain::get_registrable_domain;
+use linkresult::uri_service::UriService;
+
+use crate::commands::fetch_header_command::{DefaultFetchHeaderCommand, FetchHeaderCommand};
 use crate::commands::page_crawl_command::{CrawlCommand, PageCrawlCommand};
 use crate::commands::page_download_command::DefaultPageDownloadCommand;
+use crate::commands::sitemap_audit_command::{MAX_AUDITED_SITEMAP_URLS, MAX_AUDITED_SITEMAPS, SitemapAuditCommand};
 use crate::events::crawler_event::CrawlerEvent;
-use crate::events::crawler_event::CrawlerEvent::PageEvent;
+use crate::events::crawler_event::CrawlerEvent::{LinkDiscoveredEvent, PageEvent, SoftLimitEvent};
+use crate::http::http_client::{claim_request, HttpClient};
+use crate::http::http_utils;
+use crate::http::resource_kind::ResourceKind;
 use crate::page_loader_service::PageLoaderServiceCommand::LoadPageCommand;
-use crate::task_context::task_context::{DefaultTaskContext, FullTaskContext, TaskContextInit};
+use crate::task_context::bloom_filter::BloomFilter;
+use crate::task_context::lock_recovery::LockRecovering;
+use crate::task_context::path_patterns::PathPatterns;
+use crate::task_context::robots_service::{RobotsService, RobotsTxtInit};
+use crate::task_context::task_context::{DefaultTaskContext, FullTaskContext, Registrar, TaskConfig, TaskContext, TaskContextInit};
 use crate::task_context_manager::{DefaultTaskManager, TaskManager};
 
+const MAX_SEED_RETRY_BACKOFF_MS: u64 = 60_000;
+const DRAIN_POLL_INTERVAL_MS: u64 = 50;
+
 pub trait CommandFactory: Sync + Send {
     fn create_page_crawl_command(&self, url: String, raw_url: String, task_context: Arc<Mutex<dyn FullTaskContext>>, current_depth: u16) -> Box<dyn CrawlCommand>;
 }
@@ -36,12 +62,14 @@ impl PageCrawlCommandFactory {
 
 impl CommandFactory for PageCrawlCommandFactory {
     fn create_page_crawl_command(&self, url: String, raw_url: String, task_context: Arc<Mutex<dyn FullTaskContext>>, current_depth: u16) -> Box<dyn CrawlCommand> {
+        let fetch_header_command = DefaultFetchHeaderCommand::from_config(&task_context.lock_recovering().get_config().lock_recovering());
+        let retryable_status_codes = task_context.lock_recovering().get_config().lock_recovering().retryable_status_codes.clone();
         Box::new(PageCrawlCommand::new(url,
                                        raw_url,
                                        task_context,
                                        current_depth,
-                                       Box::new(DefaultFetchHeaderCommand {}),
-                                       Box::new(DefaultPageDownloadCommand {})))
+                                       Box::new(fetch_header_command),
+                                       Box::new(DefaultPageDownloadCommand { retryable_status_codes })))
     }
 }
 
@@ -75,7 +103,13 @@ impl PageLoaderService {
                         PageLoaderService::handle_load_page_command(&tx_clone, arc_command_factory.clone(), url, raw_url, response_channel, task_context, current_depth);
                     }
                     PageLoaderServiceCommand::CrawlDomainCommand { run_config, response_channel, task_context_uuid, .. } => {
-                        PageLoaderService::handle_crawl_domain_command(&tx_clone, &page_loader_service, run_config, response_channel, task_context_uuid).await;
+                        PageLoaderService::handle_crawl_domain_command(&tx_clone, &page_loader_service, arc_command_factory.clone(), run_config, response_channel, task_context_uuid);
+                    }
+                    PageLoaderServiceCommand::CancelCrawlCommand { task_context_uuid } => {
+                        PageLoaderService::handle_cancel_crawl_command(&page_loader_service, task_context_uuid);
+                    }
+                    PageLoaderServiceCommand::UpdateCrawlDelayCommand { task_context_uuid, crawl_delay_ms } => {
+                        PageLoaderService::handle_update_crawl_delay_command(&page_loader_service, task_context_uuid, crawl_delay_ms);
                     }
                 }
             }
@@ -85,38 +119,311 @@ impl PageLoaderService {
         tx
     }
 
-    async fn handle_crawl_domain_command(tx_clone: &Sender<PageLoaderServiceCommand>, page_loader_service: &PageLoaderService, run_config: RunConfig, response_channel: Sender<CrawlerEvent>, task_context_uuid: Uuid) {
+    // seed checks may retry and back off, so the crawl is set up in its own task, keeping the dispatcher free for other crawls.
+    // The task is known right away though, so it can be cancelled during its setup
+    fn handle_crawl_domain_command(tx_clone: &Sender<PageLoaderServiceCommand>, page_loader_service: &PageLoaderService, arc_command_factory: Arc<Box<dyn CommandFactory>>, run_config: RunConfig, response_channel: Sender<CrawlerEvent>, task_context_uuid: Uuid) {
         debug!("received CrawlDomainCommand with run_config: {:?} and uuid: {} on thread {:?}", run_config, task_context_uuid, thread::current().name());
         let default_task_context = DefaultTaskContext::init(run_config.clone(), task_context_uuid, response_channel.clone());
         let task_context = Arc::new(Mutex::new(default_task_context));
-        tx_clone.send(LoadPageCommand { url: run_config.url.clone(), raw_url: run_config.url.clone(), response_channel, task_context: task_context.clone(), current_depth: 0 }).await.expect("Problem with spawned worker thread for CrawlDomainCommand");
-        page_loader_service.task_manager.lock().unwrap().add_task(task_context);
+        // the setup counts as a running crawl command, so garbage collection doesn't complete the task while backing off
+        let setup_uuid = Uuid::new_v4();
+        task_context.lock_recovering().register_crawl_command(setup_uuid, run_config.url.clone());
+        page_loader_service.task_manager.lock_recovering().add_task(task_context.clone());
+        let task_manager = page_loader_service.task_manager.as_ref().clone();
+        if let Some(max_duration_ms) = run_config.max_duration_ms {
+            tokio::spawn(time_out_crawl(task_manager.clone(), task_context_uuid, max_duration_ms));
+        }
+        let tx_setup = tx_clone.clone();
+        tokio::spawn(async move {
+            set_up_crawl(tx_setup, task_manager, arc_command_factory, run_config, response_channel, task_context.clone()).await;
+            task_context.lock_recovering().unregister_crawl_command(setup_uuid);
+        });
+    }
+
+    // requests already waiting for their turn pick up the new delay as well
+    fn handle_update_crawl_delay_command(page_loader_service: &PageLoaderService, task_context_uuid: Uuid, crawl_delay_ms: usize) {
+        match page_loader_service.task_manager.lock_recovering().get_task(&task_context_uuid) {
+            Some(task_context) => {
+                info!("Changing crawl delay of task {} to {}ms", task_context_uuid, crawl_delay_ms);
+                task_context.lock_recovering().set_crawl_delay_ms(crawl_delay_ms);
+            }
+            None => warn!("Can't change the crawl delay of unknown task {}", task_context_uuid),
+        }
+    }
+
+    // the task is removed from garbage collection and no new pages are tasked. Pages in flight are still reported,
+    // the CompleteEvent follows once they are done
+    fn handle_cancel_crawl_command(page_loader_service: &PageLoaderService, task_context_uuid: Uuid) {
+        let task_context = page_loader_service.task_manager.lock_recovering().remove_task(&task_context_uuid);
+        let task_context = match task_context {
+            Some(task_context) => task_context,
+            None => {
+                warn!("Can't cancel unknown task {}", task_context_uuid);
+                return;
+            }
+        };
+        task_context.lock_recovering().cancel();
+        tokio::spawn(complete_when_drained(task_context));
     }
 
     fn handle_load_page_command(tx_clone: &Sender<PageLoaderServiceCommand>, arc_command_factory: Arc<Box<dyn CommandFactory>>, url: String, raw_url: String, response_channel: Sender<CrawlerEvent>, task_context: Arc<Mutex<dyn FullTaskContext>>, current_depth: u16) {
         debug!("received LoadPage command with url: {} (raw_url: {}) on thread {:?}, depth: {}", url, raw_url, thread::current().name(), current_depth);
+        // pages still queued when the crawl stopped aren't loaded anymore, its CompleteEvent may have been sent already
+        let terminal_reason = task_context.lock_recovering().get_terminal_reason();
+        if terminal_reason == TerminalReason::Cancelled || terminal_reason == TerminalReason::TimedOut {
+            debug!("Not loading {}, crawl stopped: {:?}", url, terminal_reason);
+            return;
+        }
         let tx_task = tx_clone.clone();
         let local_command_factory = arc_command_factory.clone();
         tokio::spawn(async move {
-            let robots_txt_info_url = task_context.lock().unwrap().get_config().lock().unwrap().robots_txt_info_url.clone();
+            let robots_txt_info_url = task_context.lock_recovering().get_config().lock_recovering().robots_txt_info_url.clone();
             let page_crawl_command = local_command_factory.create_page_crawl_command(url.clone(), raw_url, task_context.clone(), current_depth);
             let uuid = page_crawl_command.get_uuid_clone();
-            task_context.lock().unwrap().register_crawl_command(uuid, url.clone());
+            task_context.lock_recovering().register_crawl_command(uuid, url.clone());
             do_load(response_channel, page_crawl_command, tx_task, robots_txt_info_url).await;
-            task_context.lock().unwrap().unregister_crawl_command(uuid);
+            task_context.lock_recovering().unregister_crawl_command(uuid);
         });// Don't await here. Otherwise all processes might hang indefinitely
     }
 }
 
+async fn set_up_crawl(tx: Sender<PageLoaderServiceCommand>, task_manager: Arc<Mutex<dyn TaskManager>>, arc_command_factory: Arc<Box<dyn CommandFactory>>, run_config: RunConfig, response_channel: Sender<CrawlerEvent>, task_context: Arc<Mutex<dyn FullTaskContext>>) {
+    let task_context_uuid = task_context.lock_recovering().get_uuid();
+    let config = task_context.lock_recovering().get_config().lock_recovering().clone();
+    if config.robots_sitemap_audit {
+        let sitemap_audit = audit_robots_txt_and_sitemap(run_config.url.clone(), &config, task_context.clone()).await;
+        complete_setup_without_crawling(&task_manager, &response_channel, task_context.clone(), None, Some(sitemap_audit)).await;
+        return;
+    }
+
+    if config.seed_retries > 0 {
+        let uri_service = task_context.lock_recovering().get_uri_service();
+        let http_client = task_context.lock_recovering().get_http_client();
+        let is_reachable = is_seed_reachable(&DefaultFetchHeaderCommand::from_config(&config), run_config.url.clone(), &config, uri_service, http_client).await;
+        if !is_reachable {
+            warn!("Seed {} of task {} unreachable after {} retries, giving up", &run_config.url, task_context_uuid, config.seed_retries);
+            complete_setup_without_crawling(&task_manager, &response_channel, task_context.clone(), Some(CrawlStatus::SeedUnreachable), None).await;
+            return;
+        }
+    }
+
+    if config.warmup {
+        let fetch_header_command = DefaultFetchHeaderCommand::from_config(&config);
+        if let Err(reason) = check_seed_crawlable(&fetch_header_command, run_config.url.clone(), &config, task_context.clone()).await {
+            warn!("Seed {} of task {} is not crawlable, giving up: {}", &run_config.url, task_context_uuid, reason);
+            complete_setup_without_crawling(&task_manager, &response_channel, task_context.clone(), Some(CrawlStatus::SeedUncrawlable(reason)), None).await;
+            return;
+        }
+    }
+
+    let terminal_reason = task_context.lock_recovering().get_terminal_reason();
+    if terminal_reason == TerminalReason::Cancelled || terminal_reason == TerminalReason::TimedOut {
+        debug!("Not starting task {}, it was stopped during its setup: {:?}", task_context_uuid, terminal_reason);
+        return;
+    }
+
+    // a repair crawl only starts from the previously failed URLs, the regular seed is treated as known
+    let seeds = match &config.recrawl_only_failed {
+        Some(failed_urls) => failed_urls.clone(),
+        None => vec![run_config.url.clone()],
+    };
+    let mut known_links = config.seed_known_links.clone();
+    if config.recrawl_only_failed.is_some() {
+        known_links.push(run_config.url.clone());
+    }
+    let seed_keys: Vec<String> = seeds.iter().map(|seed| to_dedup_key(seed, &config.dedup_key_rules)).collect();
+    let mut known_links: Vec<String> = known_links.iter().map(|known_link| to_dedup_key(known_link, &config.dedup_key_rules)).collect();
+    known_links.retain(|known_link| !seed_keys.contains(known_link));
+    add_seed_known_links(task_context.clone(), known_links);
+
+    if config.deterministic {
+        tokio::spawn(crawl_deterministically(arc_command_factory, seeds, response_channel, task_context));
+    } else {
+        // this runs outside of the dispatcher, so waiting for room in its channel can't block it
+        for seed in seeds {
+            tx.send(LoadPageCommand { url: seed.clone(), raw_url: seed, response_channel: response_channel.clone(), task_context: task_context.clone(), current_depth: 0 }).await.expect("Problem with spawned worker thread for CrawlDomainCommand");
+        }
+    }
+}
+
+// a single worker crawls breadth-first, URL-sorted per depth, so repeated crawls of a static site produce identical ordering.
+// It stays registered until the frontier is done, so a stopped crawl only completes once its last page is reported
+async fn crawl_deterministically(command_factory: Arc<Box<dyn CommandFactory>>, seeds: Vec<String>, response_channel: Sender<CrawlerEvent>, task_context: Arc<Mutex<dyn FullTaskContext>>) {
+    let worker_uuid = Uuid::new_v4();
+    let task_url = task_context.lock_recovering().get_url();
+    task_context.lock_recovering().register_crawl_command(worker_uuid, task_url);
+    let mut frontier = BTreeSet::new();
+    for seed in seeds {
+        frontier.insert((0u16, seed.clone(), seed));
+    }
+    while let Some(next) = frontier.iter().next().cloned() {
+        let terminal_reason = task_context.lock_recovering().get_terminal_reason();
+        if terminal_reason != TerminalReason::Completed {
+            debug!("Not crawling the remaining {} pages, crawl stopped: {:?}", frontier.len(), terminal_reason);
+            break;
+        }
+        frontier.remove(&next);
+        let (current_depth, url, raw_url) = next;
+        let robots_txt_info_url = task_context.lock_recovering().get_config().lock_recovering().robots_txt_info_url.clone();
+        let page_crawl_command = command_factory.create_page_crawl_command(url.clone(), raw_url, task_context.clone(), current_depth);
+        task_context.lock_recovering().set_last_command_received(Instant::now());
+        let http_client = task_context.lock_recovering().get_http_client();
+        let task_context_uuid = task_context.lock_recovering().get_uuid();
+        if let Ok(Some(crawl_result)) = crawl_within_page_deadline(&page_crawl_command, http_client, task_context_uuid, robots_txt_info_url).await {
+            for (_, load_page_command) in prepare_subpage_load_commands(&response_channel, &page_crawl_command, &crawl_result).await {
+                if let LoadPageCommand { url, raw_url, current_depth, .. } = load_page_command {
+                    frontier.insert((current_depth, url, raw_url));
+                }
+            }
+            warn_on_soft_limits(&response_channel, &page_crawl_command).await;
+            send_page_event(&response_channel, &page_crawl_command, crawl_result).await;
+        }
+    }
+    task_context.lock_recovering().unregister_crawl_command(worker_uuid);
+}
+
+// known_links are expected as dedup keys already
+fn add_seed_known_links(task_context: Arc<Mutex<dyn FullTaskContext>>, known_links: Vec<String>) {
+    if let Some(known_paths) = task_context.lock_recovering().get_known_paths() {
+        known_paths.lock_recovering().extend(known_links.iter().map(|known_link| to_known_path(known_link).to_string()));
+    }
+    let approximate_known_links = task_context.lock_recovering().get_approximate_known_links();
+    match approximate_known_links {
+        Some(approximate_known_links) => known_links.iter()
+            .for_each(|known_link| approximate_known_links.lock_recovering().insert(known_link)),
+        None => task_context.lock_recovering().get_all_crawled_links().lock_recovering().extend(known_links),
+    }
+}
+
+async fn is_seed_reachable(fetch_header_command: &dyn FetchHeaderCommand, url: String, config: &TaskConfig, uri_service: Arc<UriService>, http_client: Arc<dyn HttpClient>) -> bool {
+    for attempt in 0..=config.seed_retries {
+        if attempt > 0 {
+            let backoff_ms = get_seed_retry_backoff_ms(config.seed_retry_backoff_ms, attempt);
+            debug!("Retrying seed {} in {}ms (attempt {} of {})", &url, backoff_ms, attempt, config.seed_retries);
+            tokio::time::sleep(Duration::from_millis(backoff_ms)).await;
+        }
+
+        let fetch_header_response = fetch_header_command.fetch_header(url.clone(), config.ignore_redirects, config.maximum_redirects, uri_service.clone(), http_client.clone(), None, config.robots_txt_info_url.clone()).await;
+        match fetch_header_response {
+            Ok(_) => return true,
+            Err(error) => warn!("Seed {} unreachable: {}", &url, error),
+        }
+    }
+
+    false
+}
+
+// doubles with every attempt, up to MAX_SEED_RETRY_BACKOFF_MS
+fn get_seed_retry_backoff_ms(seed_retry_backoff_ms: u64, attempt: u8) -> u64 {
+    2u64.checked_pow(attempt.saturating_sub(1) as u32)
+        .map_or(u64::MAX, |factor| seed_retry_backoff_ms.saturating_mul(factor))
+        .min(MAX_SEED_RETRY_BACKOFF_MS)
+}
+
+// the seed is checked up front, so a misconfigured crawl fails right away instead of completing without any page.
+// Only called from the setup task of the crawl, a slow seed must not hold up the dispatcher
+async fn check_seed_crawlable(fetch_header_command: &dyn FetchHeaderCommand, url: String, config: &TaskConfig, task_context: Arc<Mutex<dyn FullTaskContext>>) -> Result<(), String> {
+    if !task_context.lock_recovering().can_access(&url) {
+        return Err(format!("Seed {} is disallowed by robots.txt", url));
+    }
+
+    let uri_service = task_context.lock_recovering().get_uri_service();
+    let http_client = task_context.lock_recovering().get_http_client();
+    let (head_response, _) = fetch_header_command.fetch_header(url.clone(), config.ignore_redirects, config.maximum_redirects, uri_service, http_client, None, config.robots_txt_info_url.clone()).await
+        .map_err(|error| format!("Seed {} is unreachable: {}", url, error))?;
+    let status_code = head_response.http_response_code.code;
+    if status_code >= 400 {
+        return Err(format!("Seed {} responded with status code {}", url, status_code));
+    }
+    match head_response.headers.get(CONTENT_TYPE.as_str()) {
+        Some(content_type) if !content_type.contains("text/html") => Err(format!("Seed {} is no html document, but {}", url, content_type)),
+        None if !config.assume_html_when_missing_content_type => Err(format!("Seed {} has no content type", url)),
+        _ => Ok(()),
+    }
+}
+
+// the audit mode fetches robots.txt and the sitemaps, but doesn't crawl any pages
+async fn audit_robots_txt_and_sitemap(url: String, config: &TaskConfig, task_context: Arc<Mutex<dyn FullTaskContext>>) -> SitemapAudit {
+    let mut robots_service = RobotsService::new_with_policies(config.user_agent.clone(), config.robots_fetch_timeout_ms, config.robots_fetch_timeout_policy.clone(), config.robots_on_error.clone());
+    let request_counter = task_context.lock_recovering().get_request_counter();
+    // robots.txt is fetched with its own client, but counts into the request budget as well
+    if let Some(robots_txt_uri) = url.parse::<Uri>().ok().and_then(|uri| RobotsService::get_robots_txt_uri(&uri)).filter(|_| claim_request(&request_counter, config.max_requests)) {
+        robots_service.init(robots_txt_uri).await;
+    }
+    let sitemap_audit_command = SitemapAuditCommand {
+        fetch_header_command: Box::new(DefaultFetchHeaderCommand::from_config(config)),
+        page_download_command: Box::new(DefaultPageDownloadCommand { retryable_status_codes: config.retryable_status_codes.clone() }),
+        uri_service: task_context.lock_recovering().get_uri_service(),
+        dom_parser: task_context.lock_recovering().get_dom_parser(),
+        http_client: task_context.lock_recovering().get_http_client(),
+        maximum_redirects: config.maximum_redirects,
+        robots_txt_info_url: config.robots_txt_info_url.clone(),
+        max_sitemaps: MAX_AUDITED_SITEMAPS,
+        max_sitemap_urls: MAX_AUDITED_SITEMAP_URLS,
+    };
+    sitemap_audit_command.audit(&url, &robots_service).await
+}
+
+// a task cancelled meanwhile was completed by the cancellation already
+async fn complete_setup_without_crawling(task_manager: &Arc<Mutex<dyn TaskManager>>, response_channel: &Sender<CrawlerEvent>, task_context: Arc<Mutex<dyn FullTaskContext>>, crawl_status: Option<CrawlStatus>, sitemap_audit: Option<SitemapAudit>) {
+    let task_context_uuid = task_context.lock_recovering().get_uuid();
+    if task_manager.lock_recovering().remove_task(&task_context_uuid).is_some() {
+        complete_without_crawling(response_channel, task_context, crawl_status, sitemap_audit).await;
+    }
+}
+
+async fn complete_without_crawling(response_channel: &Sender<CrawlerEvent>, task_context: Arc<Mutex<dyn FullTaskContext>>, crawl_status: Option<CrawlStatus>, sitemap_audit: Option<SitemapAudit>) {
+    let task_context_uuid = task_context.lock_recovering().get_uuid();
+    let effective_config = task_context.lock_recovering().get_effective_config();
+    let crawl_times = CrawlTimes::finish(task_context.lock_recovering().get_crawl_started_at());
+    let complete_event = CrawlerEvent::CompleteEvent { uuid: task_context_uuid, crawl_status, broken_anchors: None, canonical_issues: None, duplicate_content: None, slowest_pages: None, robots_txt: None, sitemap_audit, terminal_reason: TerminalReason::Completed, page_count: 0, crawl_times, effective_config };
+    if let Err(error) = response_channel.send(complete_event).await {
+        error!("Error while sending CompleteEvent to channel of task {}, error: {}", task_context_uuid, error);
+    }
+}
+
+// a crawl reaching max_duration_ms doesn't wait for garbage collection, it completes once its pages in flight are done
+async fn time_out_crawl(task_manager: Arc<Mutex<dyn TaskManager>>, task_context_uuid: Uuid, max_duration_ms: u64) {
+    tokio::time::sleep(Duration::from_millis(max_duration_ms)).await;
+    let task_context = task_manager.lock_recovering().remove_task(&task_context_uuid);
+    if let Some(task_context) = task_context {
+        info!("Task {} reached its max_duration_ms of {}ms", task_context_uuid, max_duration_ms);
+        complete_when_drained(task_context).await;
+    }
+}
+
+// the task has to be removed from the task manager already, so garbage collection doesn't complete it a second time.
+// Pages in flight are registered by the dispatcher, a deterministic crawl keeps its worker registered instead
+async fn complete_when_drained(task_context: Arc<Mutex<dyn TaskContext>>) {
+    while task_context.lock_recovering().get_registered_tasks() > 0 {
+        tokio::time::sleep(Duration::from_millis(DRAIN_POLL_INTERVAL_MS)).await;
+    }
+    let task_context_uuid = task_context.lock_recovering().get_uuid();
+    let broken_anchors = task_context.lock_recovering().get_broken_anchors();
+    let canonical_issues = task_context.lock_recovering().get_canonical_issues();
+    let duplicate_content = task_context.lock_recovering().get_duplicate_content();
+    let slowest_pages = task_context.lock_recovering().get_slowest_pages();
+    let robots_txt = task_context.lock_recovering().get_robots_txt_summary();
+    let terminal_reason = task_context.lock_recovering().get_terminal_reason();
+    let page_count = task_context.lock_recovering().get_page_counter().load(Ordering::SeqCst);
+    let effective_config = task_context.lock_recovering().get_effective_config();
+    let crawl_times = CrawlTimes::finish(task_context.lock_recovering().get_crawl_started_at());
+    let response_channel = task_context.lock_recovering().get_response_channel().clone();
+    let complete_event = CrawlerEvent::CompleteEvent { uuid: task_context_uuid, crawl_status: None, broken_anchors, canonical_issues, duplicate_content, slowest_pages, robots_txt, sitemap_audit: None, terminal_reason, page_count, crawl_times, effective_config };
+    if let Err(error) = response_channel.send(complete_event).await {
+        error!("Error while sending CompleteEvent to channel of task {}, error: {}", task_context_uuid, error);
+    }
+}
+
 async fn do_load(response_channel: Sender<CrawlerEvent>, page_crawl_command: Box<dyn CrawlCommand>, tx: Sender<PageLoaderServiceCommand>, robots_txt_info_url: Option<String>) {
     let url = page_crawl_command.get_url_clone();
     debug!("got url: {:?}", &url);
 
     // updated last_command_received for garbage collection handling
-    page_crawl_command.get_task_context().lock().unwrap().set_last_command_received(Instant::now());
-    let http_client = page_crawl_command.get_task_context().lock().unwrap().get_http_client();
-    let task_context_uuid = page_crawl_command.get_task_context().lock().unwrap().get_uuid();
-    let page_response = page_crawl_command.crawl(http_client, task_context_uuid, robots_txt_info_url).await;
+    page_crawl_command.get_task_context().lock_recovering().set_last_command_received(Instant::now());
+    let http_client = page_crawl_command.get_task_context().lock_recovering().get_http_client();
+    let task_context_uuid = page_crawl_command.get_task_context().lock_recovering().get_uuid();
+    let page_response = crawl_within_page_deadline(&page_crawl_command, http_client, task_context_uuid, robots_txt_info_url).await;
     if let Ok(page_response_result) = page_response {
         if let Some(crawl_result) = page_response_result {
             consume_crawl_result(&response_channel, &page_crawl_command, &tx, crawl_result).await;
@@ -133,71 +440,298 @@ async fn do_load(response_channel: Sender<CrawlerEvent>, page_crawl_command: Box
     drop(response_channel);
 }
 
+// unlike connect and request timeouts, this limits everything done for a single page, from the download to parsing it
+async fn crawl_within_page_deadline(page_crawl_command: &Box<dyn CrawlCommand>, http_client: Arc<dyn HttpClient>, task_context_uuid: Uuid, robots_txt_info_url: Option<String>) -> Result<Option<PageResponse>, hyper::Error> {
+    let max_page_duration_ms = page_crawl_command.get_task_context().lock_recovering().get_config().lock_recovering().max_page_duration_ms;
+    let max_page_duration_ms = match max_page_duration_ms {
+        Some(max_page_duration_ms) => max_page_duration_ms,
+        None => return page_crawl_command.crawl(http_client, task_context_uuid, robots_txt_info_url).await,
+    };
+    match tokio::time::timeout(Duration::from_millis(max_page_duration_ms as u64), page_crawl_command.crawl(http_client, task_context_uuid, robots_txt_info_url)).await {
+        Ok(page_response) => page_response,
+        Err(_) => {
+            let page_request = page_crawl_command.get_page_request();
+            let page_request = page_request.lock_recovering();
+            warn!("Crawling {} took longer than {}ms", page_request.url, max_page_duration_ms);
+            let mut page_response = PageResponse::new(page_request.url.clone(), page_request.raw_url.clone(), task_context_uuid);
+            page_response.crawl_status = Some(CrawlStatus::PageTimeout);
+            Ok(Some(page_response))
+        }
+    }
+}
+
 async fn consume_crawl_result(response_channel: &Sender<CrawlerEvent>, page_crawl_command: &Box<dyn CrawlCommand>, tx: &Sender<PageLoaderServiceCommand>, crawl_result: PageResponse) {
+    for (url, load_page_command) in prepare_subpage_load_commands(response_channel, page_crawl_command, &crawl_result).await {
+        tx.send(load_page_command).await.expect(&format!("Issue sending LoadPage command to tx: {:?}", url.clone()));
+    }
+    warn_on_soft_limits(response_channel, page_crawl_command).await;
+    send_page_event(response_channel, page_crawl_command, crawl_result).await;
+}
+
+async fn prepare_subpage_load_commands(response_channel: &Sender<CrawlerEvent>, page_crawl_command: &Box<dyn CrawlCommand>, crawl_result: &PageResponse) -> Vec<(String, PageLoaderServiceCommand)> {
+    let mut load_page_commands = vec![];
     let task_context = page_crawl_command.get_task_context();
-    add_links_to_known_list(&mut task_context.lock().unwrap()
-        .get_all_crawled_links().lock().unwrap(), &crawl_result);
-    let links = crawl_result.links.clone();
-    let max_crawl_depth = task_context.lock().unwrap().get_config().lock().unwrap().maximum_depth;
+    let dedup_key_rules = task_context.lock_recovering().get_config().lock_recovering().dedup_key_rules.clone();
+    let approximate_known_links = task_context.lock_recovering().get_approximate_known_links();
+    match &approximate_known_links {
+        Some(approximate_known_links) => add_links_to_approximate_known_links(&mut approximate_known_links.lock_recovering(), &crawl_result, &dedup_key_rules),
+        None => add_links_to_known_list(&mut task_context.lock_recovering()
+            .get_all_crawled_links().lock_recovering(), &crawl_result, &dedup_key_rules),
+    }
+    if let Some(known_paths) = task_context.lock_recovering().get_known_paths() {
+        known_paths.lock_recovering().extend(get_dedup_keys(crawl_result, &dedup_key_rules).iter().map(|dedup_key| to_known_path(dedup_key).to_string()));
+    }
+    let mut links = crawl_result.links.clone();
+    let max_crawl_depth = task_context.lock_recovering().get_config().lock_recovering().maximum_depth;
+    let emit_link_events = task_context.lock_recovering().get_config().lock_recovering().emit_link_events;
+    if emit_link_events {
+        send_link_discovered_events(response_channel, task_context.clone(), crawl_result).await;
+    }
+    let terminal_reason = task_context.lock_recovering().get_terminal_reason();
+    if terminal_reason == TerminalReason::Cancelled || terminal_reason == TerminalReason::TimedOut {
+        debug!("Not following links of {}, crawl stopped: {:?}", crawl_result.original_requested_url, terminal_reason);
+        return load_page_commands;
+    }
+    let max_total_bytes = task_context.lock_recovering().get_config().lock_recovering().max_total_bytes;
+    let downloaded_bytes = task_context.lock_recovering().get_downloaded_bytes_counter().load(Ordering::SeqCst);
+    if max_total_bytes.map_or(false, |max_total_bytes| downloaded_bytes >= max_total_bytes) {
+        debug!("Not following links of {}, byte budget exhausted: {}", crawl_result.original_requested_url, downloaded_bytes);
+        return load_page_commands;
+    }
+    let respect_nofollow = task_context.lock_recovering().get_config().lock_recovering().respect_nofollow;
+    if respect_nofollow && crawl_result.nofollow {
+        debug!("Not following links of {}, due to nofollow", crawl_result.original_requested_url);
+        return load_page_commands;
+    }
+    let homepage_plus_one = task_context.lock_recovering().get_config().lock_recovering().homepage_plus_one;
+    if homepage_plus_one && page_crawl_command.get_current_depth() > 0 {
+        // the links of the seed are the last pages to crawl, their own links aren't even reported as too deep
+        debug!("Not following links of {}, due to homepage_plus_one", crawl_result.original_requested_url);
+        return load_page_commands;
+    }
+    let dedup_by_canonical = task_context.lock_recovering().get_config().lock_recovering().dedup_by_canonical;
+    if dedup_by_canonical && crawl_result.is_duplicate_of_canonical() {
+        // duplicates aren't crawled any further, only their canonical is
+        debug!("Only following the canonical of {}: {:?}", crawl_result.original_requested_url, crawl_result.canonical_url);
+        links = links.map(|links| links.into_iter().filter(|link| link.is_canonical()).take(1).collect());
+    }
     if links.is_some() && page_crawl_command.get_current_depth() <= max_crawl_depth {
         let mut links_deduped = links.unwrap();
         links_deduped.dedup_by(|a, b| a.uri.eq(&b.uri));
-        let mut all_tasked_links = task_context.lock().unwrap().get_all_tasked_links().lock().unwrap().clone();
-        let mut all_crawled_and_tasked_links = task_context.lock().unwrap().get_all_crawled_links().lock().unwrap().clone();
+        let mut all_tasked_links = task_context.lock_recovering().get_all_tasked_links().lock_recovering().clone();
+        let mut all_crawled_and_tasked_links = task_context.lock_recovering().get_all_crawled_links().lock_recovering().clone();
         all_crawled_and_tasked_links.append(&mut all_tasked_links);
         all_crawled_and_tasked_links.dedup();
         links_deduped.retain(|it| it.scope.is_some());
+        let skip_alternate_variants = task_context.lock_recovering().get_config().lock_recovering().skip_alternate_variants;
+        if skip_alternate_variants {
+            // still reported with the page, just not crawled
+            links_deduped.retain(|it| !it.alternate.as_ref().map_or(false, |alternate| alternate.is_print_or_amp()));
+        }
+        let skip_extensions = task_context.lock_recovering().get_config().lock_recovering().skip_extensions.clone();
+        let only_extensions = task_context.lock_recovering().get_config().lock_recovering().only_extensions.clone();
+        links_deduped.retain(|it| is_enqueueable_extension(it.extension.as_ref(), &skip_extensions, only_extensions.as_ref()));
+        let check_assets = task_context.lock_recovering().get_config().lock_recovering().check_assets;
+        if !check_assets {
+            // still reported with the page, just neither requested nor given a status
+            links_deduped.retain(|it| !ResourceKind::is_asset(it));
+        }
+        let allowed_subdomains = task_context.lock_recovering().get_config().lock_recovering().allowed_subdomains.clone();
+        if let Some(allowed_subdomains) = allowed_subdomains {
+            let crawl_host = get_crawl_domain(&task_context.lock_recovering().get_config().lock_recovering());
+            links_deduped.retain(|it| it.scope != Some(UriScope::DifferentSubDomain) || is_allowed_subdomain(&it.uri, &crawl_host, &allowed_subdomains));
+        }
+        let external_check_hosts = task_context.lock_recovering().get_config().lock_recovering().external_check_hosts.clone();
+        if let Some(external_check_hosts) = external_check_hosts {
+            // these are only checked for their status, not crawled
+            links_deduped.retain(|it| it.scope != Some(UriScope::DifferentSubDomain) || !http_utils::is_on_host(&it.uri, &external_check_hosts));
+        }
+        let path_patterns = task_context.lock_recovering().get_config().lock_recovering().path_patterns.clone();
         for link in links_deduped {
             match link.scope.as_ref().unwrap() {
                 UriScope::Root |
                 UriScope::SameDomain |
                 UriScope::DifferentSubDomain => {
                     let (url, load_page_command) = prepare_load_command(response_channel, &page_crawl_command, task_context.clone(), &link);
+                    if !path_patterns.is_empty() && !is_allowed_path(&url, &path_patterns) {
+                        debug!("Not following {}, due to path patterns", url);
+                        continue;
+                    }
 
-                    if !all_crawled_and_tasked_links.contains(&url) {
-                        tx.send(load_page_command).await.expect(&format!("Issue sending LoadPage command to tx: {:?}", url.clone()));
+                    let dedup_key = to_dedup_key(&url, &dedup_key_rules);
+                    let is_approximately_known = approximate_known_links.as_ref()
+                        .map_or(false, |approximate_known_links| approximate_known_links.lock_recovering().contains(&dedup_key));
+                    if !all_crawled_and_tasked_links.contains(&dedup_key) && !is_approximately_known {
+                        load_page_commands.push((url, load_page_command));
                     }
                 }
                 _ => { continue; }
             }
         }
     }
+    load_page_commands
+}
+
+// with only_extensions, links without extension aren't followed either
+fn is_enqueueable_extension(extension: Option<&String>, skip_extensions: &Vec<String>, only_extensions: Option<&Vec<String>>) -> bool {
+    if extension.map_or(false, |extension| skip_extensions.contains(extension)) {
+        return false;
+    }
+    only_extensions.map_or(true, |only_extensions| extension.map_or(false, |extension| only_extensions.contains(extension)))
+}
+
+fn is_allowed_path(url: &str, path_patterns: &PathPatterns) -> bool {
+    url.parse::<Uri>().map_or(true, |uri| path_patterns.is_allowed(uri.path()))
+}
+
+// subdomains are relative to the host of the seed, or to its registrable domain
+fn get_crawl_domain(config: &TaskConfig) -> String {
+    let host = config.uri.host().unwrap_or_default().to_lowercase();
+    match config.domain_scope {
+        DomainScope::Host => host,
+        DomainScope::RegistrableDomain => get_registrable_domain(&host).unwrap_or(host),
+    }
+}
+
+// an allowed subdomain also allows its own subdomains, e.g. "blog" allows "en.blog"
+fn is_allowed_subdomain(uri: &str, crawl_host: &str, allowed_subdomains: &Vec<String>) -> bool {
+    let absolute_uri = if uri.starts_with("//") { format!("https:{}", uri) } else { uri.to_string() };
+    let link_host = match absolute_uri.parse::<Uri>().ok().and_then(|uri| uri.host().map(|host| host.to_lowercase())) {
+        Some(link_host) => link_host,
+        None => return false,
+    };
+    match link_host.strip_suffix(&format!(".{}", crawl_host)) {
+        Some(subdomain) => allowed_subdomains.iter()
+            .any(|allowed| subdomain == allowed || subdomain.ends_with(&format!(".{}", allowed))),
+        None => false,
+    }
+}
+
+// with report_content_types, other pages were only crawled to discover links
+async fn send_page_event(response_channel: &Sender<CrawlerEvent>, page_crawl_command: &Box<dyn CrawlCommand>, mut crawl_result: PageResponse) {
+    let report_content_types = page_crawl_command.get_task_context().lock_recovering().get_config().lock_recovering().report_content_types.clone();
+    if report_content_types.map_or(false, |report_content_types| !crawl_result.has_content_type_of(&report_content_types)) {
+        debug!("Not reporting {}, due to its content type {:?}", crawl_result.original_requested_url, crawl_result.content_type);
+        return;
+    }
+    let dedup_by_canonical = page_crawl_command.get_task_context().lock_recovering().get_config().lock_recovering().dedup_by_canonical;
+    if dedup_by_canonical && crawl_result.is_duplicate_of_canonical() {
+        // still reported with its links, but only its canonical counts as a page
+        crawl_result.crawl_status = Some(CrawlStatus::CanonicalDuplicate);
+    } else {
+        page_crawl_command.get_task_context().lock_recovering().get_page_counter().fetch_add(1, Ordering::SeqCst);
+    }
+    let max_stored_links = page_crawl_command.get_task_context().lock_recovering().get_config().lock_recovering().max_stored_links;
+    truncate_stored_links(&mut crawl_result, max_stored_links);
     let send_result = response_channel.send(PageEvent { page_response: crawl_result }).await;
     if send_result.is_err() {
         warn!("Couldn't send PageResponse for TaskContext {}, PageCrawlCommand id {}, requested_url: {}",
-            &page_crawl_command.get_task_context().as_ref().lock().unwrap().get_uuid(),
+            &page_crawl_command.get_task_context().as_ref().lock_recovering().get_uuid(),
             page_crawl_command.get_uuid_clone(), page_crawl_command.get_url_clone());
     } else {
-        debug!("all_known_links: {}", page_crawl_command.get_task_context().lock().unwrap().get_all_crawled_links().lock().unwrap().len());
-        debug!("all_tasked_links: {}", page_crawl_command.get_task_context().lock().unwrap().get_all_tasked_links().lock().unwrap().len());
+        debug!("all_known_links: {}", page_crawl_command.get_task_context().lock_recovering().get_all_crawled_links().lock_recovering().len());
+        debug!("all_tasked_links: {}", page_crawl_command.get_task_context().lock_recovering().get_all_tasked_links().lock_recovering().len());
+    }
+}
+
+// links are only truncated for the response, after all of them were enqueued. links_total keeps the actual count
+fn truncate_stored_links(crawl_result: &mut PageResponse, max_stored_links: Option<usize>) {
+    if let Some(links) = crawl_result.links.as_mut() {
+        crawl_result.links_total = Some(links.len());
+        if let Some(max_stored_links) = max_stored_links {
+            links.truncate(max_stored_links);
+        }
+    }
+}
+
+// warns once per limit, as soon as the crawl reaches soft_limit_percent of one of its hard caps
+async fn warn_on_soft_limits(response_channel: &Sender<CrawlerEvent>, page_crawl_command: &Box<dyn CrawlCommand>) {
+    let task_context = page_crawl_command.get_task_context();
+    let config = task_context.lock_recovering().get_config().lock_recovering().clone();
+    let soft_limit_percent = match config.soft_limit_percent {
+        Some(soft_limit_percent) => soft_limit_percent as usize,
+        None => return,
+    };
+    let mut limits = vec![(SoftLimit::MaximumDepth, page_crawl_command.get_current_depth() as usize, config.maximum_depth as usize)];
+    if let Some(max_requests) = config.max_requests {
+        let request_count = task_context.lock_recovering().get_request_counter().load(Ordering::SeqCst);
+        limits.push((SoftLimit::MaxRequests, request_count, max_requests));
+    }
+
+    let uuid = task_context.lock_recovering().get_uuid();
+    for (soft_limit, current, maximum) in limits {
+        // a maximum of 0 means unlimited
+        if maximum == 0 || current * 100 < maximum * soft_limit_percent {
+            continue;
+        }
+        if !task_context.lock_recovering().is_first_soft_limit_warning(soft_limit) {
+            continue;
+        }
+        warn!("TaskContext {} reached {} of {} ({}%) for {:?}", uuid, current, maximum, soft_limit_percent, soft_limit);
+        if config.emit_soft_limit_events {
+            if let Err(error) = response_channel.send(SoftLimitEvent { uuid, soft_limit, current, maximum }).await {
+                warn!("Couldn't send SoftLimitEvent for TaskContext {}, error: {}", uuid, error);
+            }
+        }
+    }
+}
+
+// links are sent ahead of the PageEvent, so consumers can show them before the page is complete
+async fn send_link_discovered_events(response_channel: &Sender<CrawlerEvent>, task_context: Arc<Mutex<dyn FullTaskContext>>, crawl_result: &PageResponse) {
+    let uuid = task_context.lock_recovering().get_uuid();
+    for link in crawl_result.links.iter().flatten() {
+        let link_discovered_event = LinkDiscoveredEvent { uuid, source_url: crawl_result.original_requested_url.clone(), link: link.clone() };
+        if let Err(error) = response_channel.send(link_discovered_event).await {
+            warn!("Couldn't send LinkDiscoveredEvent for TaskContext {}, error: {}", uuid, error);
+            return;
+        }
     }
 }
 
 fn prepare_load_command(response_channel: &Sender<CrawlerEvent>, page_crawl_command: &Box<dyn CrawlCommand>, task_context: Arc<Mutex<dyn FullTaskContext>>, link: &Link) -> (String, PageLoaderServiceCommand) {
     let request = page_crawl_command.get_page_request();
-    let protocol = request.lock().unwrap().get_protocol();
-    let host = request.lock().unwrap().get_host();
-    let url = task_context.lock().unwrap().get_uri_service().form_full_url(
+    let protocol = request.lock_recovering().get_protocol();
+    let host = request.lock_recovering().get_host();
+    let url = task_context.lock_recovering().get_uri_service().form_full_url(
         &protocol,
         &link.uri,
         &host,
         &Some(page_crawl_command.get_url_clone()),
     ).to_string();
 
+    // all pages of a paginated listing share one depth, so pagination doesn't eat up the depth budget
+    let pagination_no_depth = task_context.lock_recovering().get_config().lock_recovering().pagination_no_depth;
+    let current_depth = if pagination_no_depth && link.is_pagination_next() {
+        page_crawl_command.get_current_depth()
+    } else {
+        page_crawl_command.get_current_depth() + 1
+    };
     let resp = response_channel.clone();
-    let load_page_command = LoadPageCommand { url: url.clone(), raw_url: link.uri.clone(), response_channel: resp, task_context: task_context.clone(), current_depth: page_crawl_command.get_current_depth() + 1 };
+    let load_page_command = LoadPageCommand { url: url.clone(), raw_url: link.raw_href.clone().unwrap_or_else(|| link.uri.clone()), response_channel: resp, task_context: task_context.clone(), current_depth };
     (url, load_page_command)
 }
 
-fn add_links_to_known_list(all_known_links: &mut Vec<String>, crawl_result: &PageResponse) {
-    if !all_known_links.contains(&crawl_result.original_requested_url) {
-        all_known_links.push(crawl_result.original_requested_url.clone());
+fn add_links_to_known_list(all_known_links: &mut Vec<String>, crawl_result: &PageResponse, dedup_key_rules: &DedupKeyRules) {
+    for dedup_key in get_dedup_keys(crawl_result, dedup_key_rules) {
+        if !all_known_links.contains(&dedup_key) {
+            all_known_links.push(dedup_key);
+        }
+    }
+}
+
+fn add_links_to_approximate_known_links(approximate_known_links: &mut BloomFilter, crawl_result: &PageResponse, dedup_key_rules: &DedupKeyRules) {
+    for dedup_key in get_dedup_keys(crawl_result, dedup_key_rules) {
+        approximate_known_links.insert(&dedup_key);
     }
+}
+
+// the requested and the final URL of a page are both known afterwards
+fn get_dedup_keys(crawl_result: &PageResponse, dedup_key_rules: &DedupKeyRules) -> Vec<String> {
+    let mut dedup_keys = vec![to_dedup_key(&crawl_result.original_requested_url, dedup_key_rules)];
     if let Some(final_url) = &crawl_result.final_url_after_redirects {
-        if !all_known_links.contains(&final_url) {
-            all_known_links.push(final_url.clone());
-        }
+        dedup_keys.push(to_dedup_key(final_url, dedup_key_rules));
     }
+    dedup_keys
 }
 
 #[derive(Clone)]
@@ -215,6 +749,13 @@ pub enum PageLoaderServiceCommand {
         task_context_uuid: Uuid,
         last_crawled_timestamp: u64,
     },
+    CancelCrawlCommand {
+        task_context_uuid: Uuid,
+    },
+    UpdateCrawlDelayCommand {
+        task_context_uuid: Uuid,
+        crawl_delay_ms: usize,
+    },
 }
 
 impl fmt::Debug for PageLoaderServiceCommand {
@@ -232,6 +773,13 @@ impl fmt::Debug for PageLoaderServiceCommand {
                 .field("task_context_uuid", &task_context_uuid)
                 .field("last_crawled_timestamp", &last_crawled_timestamp)
                 .finish(),
+            PageLoaderServiceCommand::CancelCrawlCommand { task_context_uuid } => f.debug_struct("CancelCrawlCommand")
+                .field("task_context_uuid", &task_context_uuid)
+                .finish(),
+            PageLoaderServiceCommand::UpdateCrawlDelayCommand { task_context_uuid, crawl_delay_ms } => f.debug_struct("UpdateCrawlDelayCommand")
+                .field("task_context_uuid", &task_context_uuid)
+                .field("crawl_delay_ms", &crawl_delay_ms)
+                .finish(),
         }
     }
 }
@@ -240,13 +788,22 @@ impl fmt::Debug for PageLoaderServiceCommand {
 mod tests {
     use async_trait::async_trait;
     use hyper::Error;
+    use mockall::*;
+    use responses::alternate_variant::AlternateVariant;
+    use responses::head_response::HeadResponse;
     use responses::link::Link;
+    use responses::redirect::Redirect;
+    use responses::status_code::StatusCode;
+    use tokio::sync::Notify;
     use uuid::Uuid;
 
+    use linkresult::link_type_checker::LinkTypeChecker;
+
+    use crate::commands::fetch_header_command::HeadResponseResult;
     use crate::http::http_client::HttpClient;
-    use crate::page_loader_service::PageLoaderServiceCommand::{CrawlDomainCommand, LoadPageCommand};
+    use crate::page_loader_service::PageLoaderServiceCommand::{CancelCrawlCommand, CrawlDomainCommand, LoadPageCommand};
     use crate::page_request::PageRequest;
-    use crate::task_context::task_context::{DefaultTaskContext, TaskContext, TaskContextInit};
+    use crate::task_context::task_context::{DefaultTaskContext, TaskContextInit, TaskContextServices};
 
     use super::*;
 
@@ -305,6 +862,160 @@ mod tests {
         fn get_uuid_clone(&self) -> Uuid { self.uuid.clone() }
     }
 
+    // every page links to a page one level deeper
+    struct LinkingPageCrawlCommand {
+        inner: StubPageCrawlCommand,
+        current_depth: u16,
+    }
+
+    #[async_trait]
+    impl CrawlCommand for LinkingPageCrawlCommand {
+        fn get_url_clone(&self) -> String {
+            self.inner.get_url_clone()
+        }
+
+        fn get_page_request(&self) -> Arc<Mutex<PageRequest>> {
+            self.inner.get_page_request()
+        }
+
+        #[allow(unused_variables)]
+        async fn crawl(&self, http_client: Arc<dyn HttpClient>, task_context_uuid: Uuid, robots_txt_info_url: Option<String>) -> std::result::Result<Option<PageResponse>, Error> {
+            let mut response = PageResponse::new(self.inner.url.clone(), self.inner.url.clone(), Uuid::new_v4());
+            response.depth = Some(self.current_depth);
+            response.links = Some(vec![Link::from_str_with_scope(&format!("https://example.com/depth-{}", self.current_depth + 1), Some(UriScope::SameDomain))]);
+            Ok(Some(response))
+        }
+
+        fn get_task_context(&self) -> Arc<Mutex<dyn FullTaskContext>> {
+            self.inner.get_task_context()
+        }
+
+        fn get_current_depth(&self) -> u16 { self.current_depth }
+
+        fn get_uuid_clone(&self) -> Uuid { self.inner.get_uuid_clone() }
+    }
+
+    // html pages link to a pdf and to another html page one level deeper
+    struct ContentTypePageCrawlCommand {
+        inner: StubPageCrawlCommand,
+        current_depth: u16,
+    }
+
+    #[async_trait]
+    impl CrawlCommand for ContentTypePageCrawlCommand {
+        fn get_url_clone(&self) -> String {
+            self.inner.get_url_clone()
+        }
+
+        fn get_page_request(&self) -> Arc<Mutex<PageRequest>> {
+            self.inner.get_page_request()
+        }
+
+        #[allow(unused_variables)]
+        async fn crawl(&self, http_client: Arc<dyn HttpClient>, task_context_uuid: Uuid, robots_txt_info_url: Option<String>) -> std::result::Result<Option<PageResponse>, Error> {
+            let mut response = PageResponse::new(self.inner.url.clone(), self.inner.url.clone(), Uuid::new_v4());
+            if self.inner.url.ends_with(".pdf") {
+                response.content_type = Some(String::from("application/pdf"));
+                return Ok(Some(response));
+            }
+            response.content_type = Some(String::from("text/html"));
+            if self.current_depth < 2 {
+                response.links = Some(vec![
+                    Link::from_str_with_scope(&format!("https://example.com/report-{}.pdf", self.current_depth), Some(UriScope::SameDomain)),
+                    Link::from_str_with_scope(&format!("https://example.com/page-{}", self.current_depth + 1), Some(UriScope::SameDomain)),
+                ]);
+            }
+            Ok(Some(response))
+        }
+
+        fn get_task_context(&self) -> Arc<Mutex<dyn FullTaskContext>> {
+            self.inner.get_task_context()
+        }
+
+        fn get_current_depth(&self) -> u16 { self.current_depth }
+
+        fn get_uuid_clone(&self) -> Uuid { self.inner.get_uuid_clone() }
+    }
+
+    struct ContentTypeStubFactory;
+
+    impl CommandFactory for ContentTypeStubFactory {
+        #[allow(unused)]
+        fn create_page_crawl_command(&self, url: String, raw_url: String, task_context: Arc<Mutex<dyn FullTaskContext>>, current_depth: u16) -> Box<dyn CrawlCommand> {
+            let response_channel = task_context.lock().unwrap().get_response_channel().clone();
+            let mut inner = StubPageCrawlCommand::new(url, response_channel);
+            inner.task_context = task_context;
+            Box::new(ContentTypePageCrawlCommand { inner, current_depth })
+        }
+    }
+
+    struct LinkingStubFactory;
+
+    impl CommandFactory for LinkingStubFactory {
+        #[allow(unused)]
+        fn create_page_crawl_command(&self, url: String, raw_url: String, task_context: Arc<Mutex<dyn FullTaskContext>>, current_depth: u16) -> Box<dyn CrawlCommand> {
+            let response_channel = task_context.lock().unwrap().get_response_channel().clone();
+            let mut inner = StubPageCrawlCommand::new(url, response_channel);
+            inner.task_context = task_context;
+            Box::new(LinkingPageCrawlCommand { inner, current_depth })
+        }
+    }
+
+    // takes a second per page, to run into page deadlines
+    struct SlowPageCrawlCommand {
+        inner: StubPageCrawlCommand,
+        crawl_started: Option<Arc<Notify>>,
+    }
+
+    #[async_trait]
+    impl CrawlCommand for SlowPageCrawlCommand {
+        fn get_url_clone(&self) -> String {
+            self.inner.get_url_clone()
+        }
+
+        fn get_page_request(&self) -> Arc<Mutex<PageRequest>> {
+            self.inner.get_page_request()
+        }
+
+        async fn crawl(&self, http_client: Arc<dyn HttpClient>, task_context_uuid: Uuid, robots_txt_info_url: Option<String>) -> std::result::Result<Option<PageResponse>, Error> {
+            if let Some(crawl_started) = self.crawl_started.as_ref() {
+                crawl_started.notify_one();
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            self.inner.crawl(http_client, task_context_uuid, robots_txt_info_url).await
+        }
+
+        fn get_task_context(&self) -> Arc<Mutex<dyn FullTaskContext>> {
+            self.inner.get_task_context()
+        }
+
+        fn get_current_depth(&self) -> u16 { 1 }
+
+        fn get_uuid_clone(&self) -> Uuid { self.inner.get_uuid_clone() }
+    }
+
+    struct SlowStubFactory {
+        crawl_started: Arc<Notify>,
+    }
+
+    impl CommandFactory for SlowStubFactory {
+        #[allow(unused)]
+        fn create_page_crawl_command(&self, url: String, raw_url: String, task_context: Arc<Mutex<dyn FullTaskContext>>, current_depth: u16) -> Box<dyn CrawlCommand> {
+            let response_channel = task_context.lock().unwrap().get_response_channel().clone();
+            let mut inner = StubPageCrawlCommand::new(url, response_channel);
+            inner.task_context = task_context;
+            Box::new(SlowPageCrawlCommand { inner, crawl_started: Some(self.crawl_started.clone()) })
+        }
+    }
+
+    mock! {
+        MyFetchHeaderCommand {}
+        #[async_trait]
+        impl FetchHeaderCommand for MyFetchHeaderCommand {
+            async fn fetch_header(&self, url: String, ignore_redirects: bool, maximum_redirects: u8, uri_service: Arc<UriService>, http_client: Arc<dyn HttpClient>, redirects: Option<Vec<Redirect>>, robots_txt_info_url: Option<String>) -> HeadResponseResult;
+        }
+    }
+
     struct StubFactory;
 
     impl CommandFactory for StubFactory {
@@ -418,4 +1129,895 @@ mod tests {
 
         assert_eq!(expected_results.len(), 0);
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn link_discovered_events_precede_page_event() {
+        // given: a task context that emits link events
+        let stub_page_crawl_command_factory = StubFactory {};
+        let tx = PageLoaderService::init_with_factory(Box::new(stub_page_crawl_command_factory));
+        let (resp_tx, mut resp_rx) = mpsc::channel(2);
+        let mut run_config = RunConfig::new(String::from("https://example.com"), None);
+        run_config.emit_link_events = Some(true);
+        let task_context = Arc::new(Mutex::new(DefaultTaskContext::init(run_config, Uuid::new_v4(), resp_tx.clone())));
+
+        // when: a page with several links is loaded
+        let send_result = tx.send(LoadPageCommand { url: String::from("https://example.com"), raw_url: String::from("/"), response_channel: resp_tx.clone(), task_context: task_context.clone(), current_depth: 0 }).await;
+
+        // then: all links of the page are sent before the page itself
+        assert_eq!(true, send_result.is_ok());
+        let mut discovered_links = vec![];
+        loop {
+            match resp_rx.recv().await.unwrap() {
+                CrawlerEvent::LinkDiscoveredEvent { source_url, link, .. } if source_url == "https://example.com" => discovered_links.push(link.uri),
+                CrawlerEvent::PageEvent { page_response } if page_response.original_requested_url == "https://example.com" => break,
+                _ => continue,
+            }
+        }
+        assert_eq!(discovered_links.len(), 10, "Should have sent all links before the PageEvent");
+        assert_eq!(discovered_links[0], "https://example.com/inner1");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn soft_limit_event_fires_once_when_crossing_threshold() {
+        // given: a task context that has already used 90% of its request budget
+        let stub_page_crawl_command_factory = StubFactory {};
+        let tx = PageLoaderService::init_with_factory(Box::new(stub_page_crawl_command_factory));
+        let (resp_tx, mut resp_rx) = mpsc::channel(2);
+        let mut run_config = RunConfig::new(String::from("https://example.com"), None);
+        run_config.max_requests = Some(100);
+        run_config.soft_limit_percent = Some(90);
+        run_config.emit_soft_limit_events = Some(true);
+        let task_context = Arc::new(Mutex::new(DefaultTaskContext::init(run_config, Uuid::new_v4(), resp_tx.clone())));
+        task_context.lock().unwrap().get_request_counter().store(90, Ordering::SeqCst);
+
+        // when: a page and all of its subpages are loaded
+        let send_result = tx.send(LoadPageCommand { url: String::from("https://example.com"), raw_url: String::from("/"), response_channel: resp_tx.clone(), task_context: task_context.clone(), current_depth: 0 }).await;
+
+        // then: the warning event is sent exactly once
+        assert_eq!(true, send_result.is_ok());
+        let mut soft_limit_events = vec![];
+        let mut page_events = 0;
+        while page_events < 11 {
+            match resp_rx.recv().await.unwrap() {
+                CrawlerEvent::SoftLimitEvent { soft_limit, current, maximum, .. } => soft_limit_events.push((soft_limit, current, maximum)),
+                CrawlerEvent::PageEvent { .. } => page_events += 1,
+                _ => continue,
+            }
+        }
+        assert_eq!(soft_limit_events, vec![(SoftLimit::MaxRequests, 90, 100)], "Should warn exactly once");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn deterministic_crawls_produce_identical_ordering() {
+        // given: a stubbed static site, crawled in deterministic mode
+        let crawl = || async {
+            let stub_page_crawl_command_factory = StubFactory {};
+            let tx = PageLoaderService::init_with_factory(Box::new(stub_page_crawl_command_factory));
+            let (resp_tx, mut resp_rx) = mpsc::channel(2);
+            let mut run_config = RunConfig::new(String::from("https://example.com"), None);
+            run_config.deterministic = Some(true);
+            tx.send(CrawlDomainCommand { run_config, response_channel: resp_tx, task_context_uuid: Uuid::new_v4(), last_crawled_timestamp: 0 }).await.unwrap();
+
+            let mut crawled_urls = vec![];
+            while crawled_urls.len() < 11 {
+                if let CrawlerEvent::PageEvent { page_response } = resp_rx.recv().await.unwrap() {
+                    crawled_urls.push(page_response.original_requested_url);
+                }
+            }
+            crawled_urls
+        };
+
+        // when: the site is crawled twice
+        let first_crawl = crawl().await;
+        let second_crawl = crawl().await;
+
+        // then: both crawls have the same, URL-sorted ordering
+        assert_eq!(first_crawl, second_crawl);
+        let mut expected_inner_urls = first_crawl[1..].to_vec();
+        expected_inner_urls.sort();
+        assert_eq!(first_crawl[0], "https://example.com");
+        assert_eq!(first_crawl[1..].to_vec(), expected_inner_urls, "Pages of the same depth should be crawled URL-sorted");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn only_pages_of_report_content_types_are_reported() {
+        // given: html pages linking to pdfs, crawled for pdfs only
+        let tx = PageLoaderService::init_with_factory(Box::new(ContentTypeStubFactory {}));
+        let (resp_tx, mut resp_rx) = mpsc::channel(2);
+        let mut run_config = RunConfig::new(String::from("https://example.com"), None);
+        run_config.deterministic = Some(true);
+        run_config.report_content_types = Some(vec![String::from("Application/PDF")]);
+
+        // when: the site is crawled
+        tx.send(CrawlDomainCommand { run_config, response_channel: resp_tx, task_context_uuid: Uuid::new_v4(), last_crawled_timestamp: 0 }).await.unwrap();
+
+        // then: only the pdfs are reported, including the one linked by a deeper html page
+        let mut reported_urls = vec![];
+        while reported_urls.len() < 2 {
+            if let CrawlerEvent::PageEvent { page_response } = tokio::time::timeout(Duration::from_secs(5), resp_rx.recv()).await.expect("Should report both pdfs").unwrap() {
+                reported_urls.push(page_response.original_requested_url);
+            }
+        }
+        assert_eq!(reported_urls, vec![String::from("https://example.com/report-0.pdf"), String::from("https://example.com/report-1.pdf")], "Should crawl html pages without reporting them");
+    }
+
+    #[tokio::test]
+    async fn seed_is_retried_until_reachable() {
+        // given: a seed that fails twice, then succeeds
+        let http_client = get_http_client();
+        let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com"))));
+        let mut config = TaskConfig::new(RunConfig::new(String::from("https://example.com"), None));
+        config.seed_retries = 3;
+        config.seed_retry_backoff_ms = 1;
+        let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+        let mut sequence = Sequence::new();
+        mock_fetch_header_command.expect_fetch_header()
+            .times(2)
+            .in_sequence(&mut sequence)
+            .returning(|_, _, _, _, _, _, _| Err(String::from("error trying to connect")));
+        mock_fetch_header_command.expect_fetch_header()
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(move |url, _, _, _, _, _, _| Ok((HeadResponse::new(url, StatusCode { code: 200, label: "OK".into() }), http_client.clone())));
+
+        // when: the seed is checked
+        let is_reachable = is_seed_reachable(&mock_fetch_header_command, String::from("https://example.com"), &config, uri_service, get_http_client()).await;
+
+        // then: the crawl proceeds after the third attempt
+        assert_eq!(is_reachable, true, "Seed should be reachable after two failed attempts");
+    }
+
+    #[tokio::test]
+    async fn seed_is_unreachable_after_all_retries_failed() {
+        // given: a seed that always fails
+        let uri_service = Arc::new(UriService::new(Arc::new(LinkTypeChecker::new("example.com"))));
+        let mut config = TaskConfig::new(RunConfig::new(String::from("https://example.com"), None));
+        config.seed_retries = 2;
+        config.seed_retry_backoff_ms = 1;
+        let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+        mock_fetch_header_command.expect_fetch_header()
+            .times(3)
+            .returning(|_, _, _, _, _, _, _| Err(String::from("error trying to connect")));
+
+        // when: the seed is checked
+        let is_reachable = is_seed_reachable(&mock_fetch_header_command, String::from("https://example.com"), &config, uri_service, get_http_client()).await;
+
+        // then: the seed is reported as unreachable
+        assert_eq!(is_reachable, false, "Seed should be unreachable, if all retries failed");
+    }
+
+    #[test]
+    fn seed_retry_backoff_doubles_up_to_its_cap() {
+        assert_eq!(get_seed_retry_backoff_ms(1000, 1), 1000);
+        assert_eq!(get_seed_retry_backoff_ms(1000, 3), 4000);
+        assert_eq!(get_seed_retry_backoff_ms(1000, 70), MAX_SEED_RETRY_BACKOFF_MS, "Should not overflow for many retries");
+        assert_eq!(get_seed_retry_backoff_ms(u64::MAX, 2), MAX_SEED_RETRY_BACKOFF_MS, "Should not overflow for large backoffs");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn backing_off_seed_does_not_block_other_crawls() {
+        // given: a crawl backing off from its unreachable seed
+        let tx = PageLoaderService::init_with_factory(Box::new(StubFactory {}));
+        let (unreachable_resp_tx, _unreachable_resp_rx) = mpsc::channel(1);
+        let mut run_config = RunConfig::new(String::from("https://unreachable-domain.no"), None);
+        run_config.seed_retries = Some(3);
+        run_config.seed_retry_backoff_ms = Some(10_000);
+        let _ = tx.send(CrawlDomainCommand { run_config, response_channel: unreachable_resp_tx, task_context_uuid: Uuid::new_v4(), last_crawled_timestamp: 0 }).await;
+
+        // when: another crawl is requested meanwhile
+        let (resp_tx, mut resp_rx) = mpsc::channel(1);
+        // NOTE: use "/inner" in the url to trick the StubPageCrawlCommand
+        let _ = tx.send(CrawlDomainCommand { run_config: RunConfig::new(String::from("https://example.com/inner"), None), response_channel: resp_tx, task_context_uuid: Uuid::new_v4(), last_crawled_timestamp: 0 }).await;
+
+        // then: it is crawled without waiting for the backoff
+        let event = tokio::time::timeout(Duration::from_secs(5), resp_rx.recv()).await.expect("Should not wait for the other crawl's backoff");
+        assert_eq!(matches!(event, Some(CrawlerEvent::PageEvent { .. })), true, "Should receive the page of the other crawl");
+    }
+
+    #[tokio::test]
+    async fn crawl_domain_command_completes_with_seed_unreachable() {
+        // given: a seed that can't be resolved and a single retry
+        let tx = PageLoaderService::init_with_factory(Box::new(StubFactory {}));
+        let (resp_tx, mut resp_rx) = mpsc::channel(1);
+        let mut run_config = RunConfig::new(String::from("https://unreachable-domain.no"), None);
+        run_config.seed_retries = Some(1);
+        run_config.seed_retry_backoff_ms = Some(1);
+        let task_context_uuid = Uuid::new_v4();
+
+        // when: the crawl is requested
+        let _ = tx.send(CrawlDomainCommand { run_config, response_channel: resp_tx.clone(), task_context_uuid, last_crawled_timestamp: 0 }).await;
+
+        // then: the crawl completes without any page, reporting SeedUnreachable
+        if let CrawlerEvent::CompleteEvent { uuid, crawl_status, .. } = resp_rx.recv().await.unwrap() {
+            assert_eq!(uuid, task_context_uuid);
+            assert_eq!(crawl_status, Some(CrawlStatus::SeedUnreachable));
+        } else {
+            panic!("Wrong type");
+        }
+    }
+
+    #[tokio::test]
+    async fn seed_without_html_is_not_crawlable() {
+        // given: a seed serving a pdf
+        let (resp_tx, _resp_rx) = mpsc::channel(1);
+        let task_context = create_default_task_context(resp_tx);
+        let config = TaskConfig::new(RunConfig::new(String::from("https://example.com"), None));
+        let mut mock_fetch_header_command = MockMyFetchHeaderCommand::new();
+        mock_fetch_header_command.expect_fetch_header()
+            .times(1)
+            .returning(|url, _, _, _, _, _, _| {
+                let mut head_response = HeadResponse::new(url, StatusCode { code: 200, label: "OK".into() });
+                head_response.headers.insert(String::from(CONTENT_TYPE.as_str()), String::from("application/pdf"));
+                Ok((head_response, get_http_client()))
+            });
+
+        // when: the seed is checked
+        let result = check_seed_crawlable(&mock_fetch_header_command, String::from("https://example.com"), &config, task_context).await;
+
+        // then: the reason is reported
+        assert_eq!(result.unwrap_err().contains("application/pdf"), true, "Should name the content type of the seed");
+    }
+
+    #[tokio::test]
+    async fn crawl_domain_command_completes_with_seed_uncrawlable_if_warmup_fails() {
+        // given: a seed that can't be resolved, checked during warmup
+        let tx = PageLoaderService::init_with_factory(Box::new(StubFactory {}));
+        let (resp_tx, mut resp_rx) = mpsc::channel(1);
+        let mut run_config = RunConfig::new(String::from("https://unreachable-domain.no"), None);
+        run_config.warmup = Some(true);
+        let task_context_uuid = Uuid::new_v4();
+
+        // when: the crawl is requested
+        let _ = tx.send(CrawlDomainCommand { run_config, response_channel: resp_tx.clone(), task_context_uuid, last_crawled_timestamp: 0 }).await;
+
+        // then: the crawl completes right away, with a summary instead of any page
+        if let CrawlerEvent::CompleteEvent { uuid, crawl_status, .. } = resp_rx.recv().await.unwrap() {
+            assert_eq!(uuid, task_context_uuid);
+            match crawl_status {
+                Some(CrawlStatus::SeedUncrawlable(reason)) => assert_eq!(reason.contains("unreachable"), true, "Should explain why, got {}", reason),
+                other => panic!("Expected SeedUncrawlable, got {:?}", other),
+            }
+        } else {
+            panic!("Wrong type");
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn warmup_of_a_hanging_seed_does_not_block_other_crawls() {
+        // given: a crawl warming up on a seed, that accepts connections but never responds
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let hanging_seed = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut connections = vec![];
+            while let Ok((connection, _)) = listener.accept().await {
+                connections.push(connection);
+            }
+        });
+        let tx = PageLoaderService::init_with_factory(Box::new(StubFactory {}));
+        let (hanging_resp_tx, _hanging_resp_rx) = mpsc::channel(1);
+        let mut run_config = RunConfig::new(hanging_seed, None);
+        run_config.warmup = Some(true);
+        let _ = tx.send(CrawlDomainCommand { run_config, response_channel: hanging_resp_tx, task_context_uuid: Uuid::new_v4(), last_crawled_timestamp: 0 }).await;
+
+        // when: another crawl is requested meanwhile
+        let (resp_tx, mut resp_rx) = mpsc::channel(1);
+        // NOTE: use "/inner" in the url to trick the StubPageCrawlCommand
+        let _ = tx.send(CrawlDomainCommand { run_config: RunConfig::new(String::from("https://example.com/inner"), None), response_channel: resp_tx, task_context_uuid: Uuid::new_v4(), last_crawled_timestamp: 0 }).await;
+
+        // then: it is crawled without waiting for the warmup
+        let event = tokio::time::timeout(Duration::from_secs(5), resp_rx.recv()).await.expect("Should not wait for the other crawl's warmup");
+        assert_eq!(matches!(event, Some(CrawlerEvent::PageEvent { .. })), true, "Should receive the page of the other crawl");
+    }
+
+    #[tokio::test]
+    async fn does_not_follow_links_once_byte_budget_is_exhausted() {
+        // given: a page with links and a task context, whose byte budget is used up
+        let (resp_tx, _resp_rx) = mpsc::channel(1);
+        let page_crawl_command: Box<dyn CrawlCommand> = Box::new(StubPageCrawlCommand::new(String::from("https://example.com"), resp_tx.clone()));
+        let crawl_result = page_crawl_command.crawl(get_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap();
+        page_crawl_command.get_task_context().lock().unwrap().get_config().lock().unwrap().max_total_bytes = Some(1000);
+        page_crawl_command.get_task_context().lock().unwrap().get_downloaded_bytes_counter().store(1000, Ordering::SeqCst);
+
+        // when: subpage commands are prepared
+        let load_page_commands = prepare_subpage_load_commands(&resp_tx, &page_crawl_command, &crawl_result).await;
+
+        // then: no links are followed
+        assert_eq!(load_page_commands.len(), 0, "Should not follow links once the byte budget is exhausted");
+        assert_eq!(page_crawl_command.get_task_context().lock().unwrap().get_terminal_reason(), TerminalReason::BudgetExhausted, "Should end with BudgetExhausted");
+    }
+
+    #[tokio::test]
+    async fn follows_links_of_a_task_context_poisoned_by_a_panicked_worker() {
+        // given: a page with links and a task context, whose lock was poisoned by a panic
+        let (resp_tx, _resp_rx) = mpsc::channel(1);
+        let page_crawl_command: Box<dyn CrawlCommand> = Box::new(StubPageCrawlCommand::new(String::from("https://example.com"), resp_tx.clone()));
+        let crawl_result = page_crawl_command.crawl(get_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap();
+        let task_context = page_crawl_command.get_task_context();
+        let _ = thread::spawn(move || {
+            let _locked = task_context.lock().unwrap();
+            panic!("deliberate panic while holding the lock");
+        }).join();
+        assert_eq!(page_crawl_command.get_task_context().is_poisoned(), true, "The lock should be poisoned");
+
+        // when: subpage commands are prepared
+        let load_page_commands = prepare_subpage_load_commands(&resp_tx, &page_crawl_command, &crawl_result).await;
+
+        // then: the crawl goes on
+        assert_eq!(load_page_commands.len(), 10, "Should follow links despite the poisoned lock");
+    }
+
+    #[tokio::test]
+    async fn does_not_follow_links_of_nofollow_pages_if_respected() {
+        // given: a nofollow page with links and a task context respecting nofollow
+        let (resp_tx, _resp_rx) = mpsc::channel(1);
+        let page_crawl_command: Box<dyn CrawlCommand> = Box::new(StubPageCrawlCommand::new(String::from("https://example.com"), resp_tx.clone()));
+        let mut crawl_result = page_crawl_command.crawl(get_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap();
+        crawl_result.nofollow = true;
+
+        // when: subpage commands are prepared, with and without respecting nofollow
+        let commands_ignoring_nofollow = prepare_subpage_load_commands(&resp_tx, &page_crawl_command, &crawl_result).await;
+        page_crawl_command.get_task_context().lock().unwrap().get_config().lock().unwrap().respect_nofollow = true;
+        page_crawl_command.get_task_context().lock().unwrap().get_all_crawled_links().lock().unwrap().clear();
+        let commands_respecting_nofollow = prepare_subpage_load_commands(&resp_tx, &page_crawl_command, &crawl_result).await;
+
+        // then: links are only followed, if nofollow is ignored
+        assert_eq!(commands_ignoring_nofollow.len(), 10, "Should follow links, if nofollow is not respected");
+        assert_eq!(commands_respecting_nofollow.len(), 0, "Should not follow links, if nofollow is respected");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn recrawl_only_failed_crawls_failed_urls_and_their_new_children() {
+        // given: a repair run for a single failed URL, with the seed and one child already known
+        let tx = PageLoaderService::init_with_factory(Box::new(StubFactory {}));
+        let (resp_tx, mut resp_rx) = mpsc::channel(2);
+        let mut run_config = RunConfig::new(String::from("https://example.com"), None);
+        run_config.deterministic = Some(true);
+        run_config.seed_known_links = Some(vec![String::from("https://example.com/inner1")]);
+        run_config.recrawl_only_failed = Some(vec![String::from("https://example.com/failed")]);
+
+        // when: the crawl is requested
+        let _ = tx.send(CrawlDomainCommand { run_config, response_channel: resp_tx.clone(), task_context_uuid: Uuid::new_v4(), last_crawled_timestamp: 0 }).await;
+
+        // then: only the failed URL and its unknown children are crawled
+        let mut crawled_urls = vec![];
+        while crawled_urls.len() < 10 {
+            if let CrawlerEvent::PageEvent { page_response } = resp_rx.recv().await.unwrap() {
+                crawled_urls.push(page_response.original_requested_url);
+            }
+        }
+        crawled_urls.sort();
+        let mut expected_urls: Vec<String> = (2..=10).map(|i| format!("https://example.com/inner{}", i)).collect();
+        expected_urls.push(String::from("https://example.com/failed"));
+        expected_urls.sort();
+        assert_eq!(crawled_urls, expected_urls, "Should only crawl the failed URL and its unknown children");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn recrawl_only_failed_with_more_urls_than_the_dispatcher_buffers() {
+        // given: a repair run for more failed URLs than the dispatcher's channel holds
+        let tx = PageLoaderService::init_with_factory(Box::new(StubFactory {}));
+        let (resp_tx, mut resp_rx) = mpsc::channel(2);
+        let failed_url_count = num_cpus::get() * 10 + 1;
+        let failed_urls: Vec<String> = (0..failed_url_count).map(|i| format!("https://example.com/inner-failed-{}", i)).collect();
+        let mut run_config = RunConfig::new(String::from("https://example.com"), None);
+        run_config.recrawl_only_failed = Some(failed_urls);
+
+        // when: the crawl is requested
+        let _ = tx.send(CrawlDomainCommand { run_config, response_channel: resp_tx.clone(), task_context_uuid: Uuid::new_v4(), last_crawled_timestamp: 0 }).await;
+
+        // then: every failed URL is crawled, the dispatcher doesn't block on its own channel
+        let mut crawled_count = 0;
+        while crawled_count < failed_url_count {
+            let event = tokio::time::timeout(Duration::from_secs(10), resp_rx.recv()).await.expect("Dispatcher should not deadlock");
+            if let Some(CrawlerEvent::PageEvent { .. }) = event {
+                crawled_count += 1;
+            }
+        }
+        assert_eq!(crawled_count, failed_url_count, "Should crawl all failed URLs");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn cancelled_crawl_emits_complete_event_with_reason_cancelled() {
+        // given: a running crawl
+        let tx = PageLoaderService::init_with_factory(Box::new(StubFactory {}));
+        let (resp_tx, mut resp_rx) = mpsc::channel(2);
+        let task_context_uuid = Uuid::new_v4();
+        let _ = tx.send(CrawlDomainCommand { run_config: RunConfig::new(String::from("https://example.com"), None), response_channel: resp_tx.clone(), task_context_uuid, last_crawled_timestamp: 0 }).await;
+
+        // when: the crawl is cancelled
+        let _ = tx.send(CancelCrawlCommand { task_context_uuid }).await;
+
+        // then: a CompleteEvent is sent without waiting for garbage collection
+        loop {
+            match tokio::time::timeout(Duration::from_secs(5), resp_rx.recv()).await.expect("Should complete before garbage collection").unwrap() {
+                CrawlerEvent::CompleteEvent { uuid, terminal_reason, .. } => {
+                    assert_eq!(uuid, task_context_uuid);
+                    assert_eq!(terminal_reason, TerminalReason::Cancelled);
+                    break;
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn cancelled_crawl_reports_pages_in_flight_before_completing() {
+        // given: a crawl with its seed in flight
+        let crawl_started = Arc::new(Notify::new());
+        let tx = PageLoaderService::init_with_factory(Box::new(SlowStubFactory { crawl_started: crawl_started.clone() }));
+        let (resp_tx, mut resp_rx) = mpsc::channel(2);
+        let task_context_uuid = Uuid::new_v4();
+        let _ = tx.send(CrawlDomainCommand { run_config: RunConfig::new(String::from("https://example.com"), None), response_channel: resp_tx.clone(), task_context_uuid, last_crawled_timestamp: 0 }).await;
+        crawl_started.notified().await;
+
+        // when: the crawl is cancelled
+        let _ = tx.send(CancelCrawlCommand { task_context_uuid }).await;
+
+        // then: the seed is reported first, its links aren't followed, the crawl completes as cancelled
+        let mut page_urls = vec![];
+        loop {
+            match tokio::time::timeout(Duration::from_secs(5), resp_rx.recv()).await.expect("Should complete before garbage collection").unwrap() {
+                CrawlerEvent::PageEvent { page_response } => page_urls.push(page_response.original_requested_url),
+                CrawlerEvent::CompleteEvent { terminal_reason, .. } => {
+                    assert_eq!(terminal_reason, TerminalReason::Cancelled);
+                    break;
+                }
+                _ => continue,
+            }
+        }
+        assert_eq!(page_urls, vec![String::from("https://example.com")], "Should report the page in flight before completing");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn cancelled_deterministic_crawl_stops_after_the_page_in_flight() {
+        // given: a deterministic crawl with the first link of its seed in flight
+        let crawl_started = Arc::new(Notify::new());
+        let tx = PageLoaderService::init_with_factory(Box::new(SlowStubFactory { crawl_started: crawl_started.clone() }));
+        let (resp_tx, mut resp_rx) = mpsc::channel(2);
+        let task_context_uuid = Uuid::new_v4();
+        let mut run_config = RunConfig::new(String::from("https://example.com"), None);
+        run_config.deterministic = Some(true);
+        let _ = tx.send(CrawlDomainCommand { run_config, response_channel: resp_tx.clone(), task_context_uuid, last_crawled_timestamp: 0 }).await;
+        crawl_started.notified().await;
+        crawl_started.notified().await;
+
+        // when: the crawl is cancelled
+        let _ = tx.send(CancelCrawlCommand { task_context_uuid }).await;
+
+        // then: the page in flight is reported before the crawl completes as cancelled, the remaining links aren't crawled
+        let mut page_urls = vec![];
+        loop {
+            match tokio::time::timeout(Duration::from_secs(5), resp_rx.recv()).await.expect("Should complete before garbage collection").unwrap() {
+                CrawlerEvent::PageEvent { page_response } => page_urls.push(page_response.original_requested_url),
+                CrawlerEvent::CompleteEvent { terminal_reason, .. } => {
+                    assert_eq!(terminal_reason, TerminalReason::Cancelled);
+                    break;
+                }
+                _ => continue,
+            }
+        }
+        assert_eq!(page_urls, vec![String::from("https://example.com"), String::from("https://example.com/inner1")], "Should report the page in flight before completing");
+        let event_after_completion = tokio::time::timeout(Duration::from_millis(1500), resp_rx.recv()).await;
+        assert_eq!(event_after_completion.is_err(), true, "Should not crawl the remaining links after completing");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn crawl_reaching_max_duration_ms_completes_as_timed_out() {
+        // given: a crawl limited to a duration shorter than its pages take
+        let tx = PageLoaderService::init_with_factory(Box::new(SlowStubFactory { crawl_started: Arc::new(Notify::new()) }));
+        let (resp_tx, mut resp_rx) = mpsc::channel(2);
+        let task_context_uuid = Uuid::new_v4();
+        let mut run_config = RunConfig::new(String::from("https://example.com"), None);
+        run_config.max_duration_ms = Some(200);
+
+        // when: the crawl is started
+        let _ = tx.send(CrawlDomainCommand { run_config, response_channel: resp_tx.clone(), task_context_uuid, last_crawled_timestamp: 0 }).await;
+
+        // then: the crawl completes as timed out, without waiting for garbage collection
+        let mut page_events = 0;
+        loop {
+            match tokio::time::timeout(Duration::from_secs(5), resp_rx.recv()).await.expect("Should complete before garbage collection").unwrap() {
+                CrawlerEvent::PageEvent { .. } => page_events += 1,
+                CrawlerEvent::CompleteEvent { uuid, terminal_reason, .. } => {
+                    assert_eq!(uuid, task_context_uuid);
+                    assert_eq!(terminal_reason, TerminalReason::TimedOut);
+                    break;
+                }
+                _ => continue,
+            }
+        }
+        assert_eq!(page_events <= 1, true, "Should at most report the seed in flight, but not follow its links");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn deterministic_crawl_reaching_max_duration_ms_completes_after_its_page_in_flight() {
+        // given: a deterministic crawl limited to a duration shorter than its pages take
+        let tx = PageLoaderService::init_with_factory(Box::new(SlowStubFactory { crawl_started: Arc::new(Notify::new()) }));
+        let (resp_tx, mut resp_rx) = mpsc::channel(2);
+        let task_context_uuid = Uuid::new_v4();
+        let mut run_config = RunConfig::new(String::from("https://example.com"), None);
+        run_config.deterministic = Some(true);
+        run_config.max_duration_ms = Some(1500);
+
+        // when: the crawl is started
+        let _ = tx.send(CrawlDomainCommand { run_config, response_channel: resp_tx.clone(), task_context_uuid, last_crawled_timestamp: 0 }).await;
+
+        // then: the crawl completes as timed out, once the page in flight is reported
+        let mut page_events = 0;
+        loop {
+            match tokio::time::timeout(Duration::from_secs(5), resp_rx.recv()).await.expect("Should complete before garbage collection").unwrap() {
+                CrawlerEvent::PageEvent { .. } => page_events += 1,
+                CrawlerEvent::CompleteEvent { uuid, terminal_reason, .. } => {
+                    assert_eq!(uuid, task_context_uuid);
+                    assert_eq!(terminal_reason, TerminalReason::TimedOut);
+                    break;
+                }
+                _ => continue,
+            }
+        }
+        assert_eq!(page_events >= 1 && page_events < 11, true, "Should report the crawled pages, but not all of them");
+        let event_after_completion = tokio::time::timeout(Duration::from_millis(1500), resp_rx.recv()).await;
+        assert_eq!(event_after_completion.is_err(), true, "Should not report pages after completing");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn complete_event_carries_the_crawl_times() {
+        // given: a running crawl
+        let tx = PageLoaderService::init_with_factory(Box::new(StubFactory {}));
+        let (resp_tx, mut resp_rx) = mpsc::channel(2);
+        let task_context_uuid = Uuid::new_v4();
+        let before_crawl = chrono::Utc::now();
+        let _ = tx.send(CrawlDomainCommand { run_config: RunConfig::new(String::from("https://example.com"), None), response_channel: resp_tx.clone(), task_context_uuid, last_crawled_timestamp: 0 }).await;
+
+        // when: the crawl completes
+        let _ = tx.send(CancelCrawlCommand { task_context_uuid }).await;
+
+        // then: start and end of the crawl are reported, along with its duration
+        loop {
+            match tokio::time::timeout(Duration::from_secs(5), resp_rx.recv()).await.expect("Should complete").unwrap() {
+                CrawlerEvent::CompleteEvent { crawl_times, .. } => {
+                    assert_eq!(crawl_times.crawl_started_at >= before_crawl, true, "Should start with the CrawlDomainCommand");
+                    assert_eq!(crawl_times.crawl_finished_at >= crawl_times.crawl_started_at, true, "Should finish after the start");
+                    assert_eq!(crawl_times.total_duration_ms, (crawl_times.crawl_finished_at - crawl_times.crawl_started_at).num_milliseconds() as u64);
+                    break;
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn amp_alternates_are_not_enqueued_if_skipping_is_enabled() {
+        // given: a page with a regular link and an AMP alternate
+        let (resp_tx, _resp_rx) = mpsc::channel(1);
+        let page_crawl_command: Box<dyn CrawlCommand> = Box::new(StubPageCrawlCommand::new(String::from("https://example.com/inner"), resp_tx.clone()));
+        let mut crawl_result = PageResponse::new(String::from("https://example.com/inner"), String::from("/inner"), Uuid::new_v4());
+        let mut amp_link = Link::from_str_with_scope("https://example.com/amp", Some(UriScope::SameDomain));
+        amp_link.alternate = Some(AlternateVariant { rel: String::from("amphtml"), media: None, mime_type: None });
+        crawl_result.links = Some(vec![amp_link, Link::from_str_with_scope("https://example.com/regular", Some(UriScope::SameDomain))]);
+        page_crawl_command.get_task_context().lock().unwrap().get_config().lock().unwrap().skip_alternate_variants = true;
+
+        // when: subpage commands are prepared
+        let load_page_commands = prepare_subpage_load_commands(&resp_tx, &page_crawl_command, &crawl_result).await;
+
+        // then: the AMP alternate is reported, but not enqueued
+        assert_eq!(crawl_result.links.as_ref().unwrap().len(), 2, "Should still report the AMP alternate");
+        assert_eq!(load_page_commands.len(), 1, "Should only enqueue the regular link");
+        assert_eq!(load_page_commands[0].0, "https://example.com/regular");
+    }
+
+    #[tokio::test]
+    async fn rel_next_children_inherit_the_parents_depth_if_enabled() {
+        // given: a page with a pagination link and a regular link
+        let (resp_tx, _resp_rx) = mpsc::channel(1);
+        let page_crawl_command: Box<dyn CrawlCommand> = Box::new(StubPageCrawlCommand::new(String::from("https://example.com/inner"), resp_tx.clone()));
+        let mut crawl_result = PageResponse::new(String::from("https://example.com/inner"), String::from("/inner"), Uuid::new_v4());
+        let mut next_link = Link::from_str_with_scope("https://example.com/inner?page=2", Some(UriScope::SameDomain));
+        next_link.rel = Some(String::from("next"));
+        crawl_result.links = Some(vec![next_link, Link::from_str_with_scope("https://example.com/regular", Some(UriScope::SameDomain))]);
+        page_crawl_command.get_task_context().lock().unwrap().get_config().lock().unwrap().pagination_no_depth = true;
+
+        // when: subpage commands are prepared
+        let load_page_commands = prepare_subpage_load_commands(&resp_tx, &page_crawl_command, &crawl_result).await;
+
+        // then: the next page stays at the parent's depth, while other links go one level deeper
+        let depth_of = |url: &str| load_page_commands.iter()
+            .find_map(|(command_url, command)| match command {
+                LoadPageCommand { current_depth, .. } if command_url == url => Some(*current_depth),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(depth_of("https://example.com/inner?page=2"), page_crawl_command.get_current_depth(), "Pagination should inherit the depth");
+        assert_eq!(depth_of("https://example.com/regular"), page_crawl_command.get_current_depth() + 1, "Regular links should increment the depth");
+    }
+
+    #[tokio::test]
+    async fn links_with_skipped_extensions_are_not_enqueued() {
+        // given: a page linking a pdf and an html page, skipping pdfs
+        let (resp_tx, _resp_rx) = mpsc::channel(1);
+        let page_crawl_command: Box<dyn CrawlCommand> = Box::new(StubPageCrawlCommand::new(String::from("https://example.com/inner"), resp_tx.clone()));
+        let mut crawl_result = PageResponse::new(String::from("https://example.com/inner"), String::from("/inner"), Uuid::new_v4());
+        crawl_result.links = Some(vec![
+            Link::from_str_with_scope("https://example.com/report.pdf?download=1", Some(UriScope::SameDomain)),
+            Link::from_str_with_scope("https://example.com/page.html", Some(UriScope::SameDomain)),
+        ]);
+        page_crawl_command.get_task_context().lock().unwrap().get_config().lock().unwrap().skip_extensions = vec![String::from("pdf")];
+
+        // when: subpage commands are prepared
+        let load_page_commands = prepare_subpage_load_commands(&resp_tx, &page_crawl_command, &crawl_result).await;
+
+        // then: the pdf is skipped
+        assert_eq!(load_page_commands.len(), 1, "Should skip the pdf");
+        assert_eq!(load_page_commands[0].0, "https://example.com/page.html");
+    }
+
+    #[tokio::test]
+    async fn only_links_with_given_extensions_are_enqueued() {
+        // given: a page linking html, css and extensionless pages, only following html
+        let (resp_tx, _resp_rx) = mpsc::channel(1);
+        let page_crawl_command: Box<dyn CrawlCommand> = Box::new(StubPageCrawlCommand::new(String::from("https://example.com/inner"), resp_tx.clone()));
+        let mut crawl_result = PageResponse::new(String::from("https://example.com/inner"), String::from("/inner"), Uuid::new_v4());
+        crawl_result.links = Some(vec![
+            Link::from_str_with_scope("https://example.com/page.html#top", Some(UriScope::SameDomain)),
+            Link::from_str_with_scope("https://example.com/main.css", Some(UriScope::SameDomain)),
+            Link::from_str_with_scope("https://example.com/about", Some(UriScope::SameDomain)),
+        ]);
+        page_crawl_command.get_task_context().lock().unwrap().get_config().lock().unwrap().only_extensions = Some(vec![String::from("html")]);
+
+        // when: subpage commands are prepared
+        let load_page_commands = prepare_subpage_load_commands(&resp_tx, &page_crawl_command, &crawl_result).await;
+
+        // then: only the html page is followed
+        assert_eq!(load_page_commands.len(), 1, "Should only follow html");
+        assert_eq!(load_page_commands[0].0.starts_with("https://example.com/page.html"), true, "Should follow the html page, got {}", load_page_commands[0].0);
+    }
+
+    #[tokio::test]
+    async fn only_allowlisted_subdomains_are_enqueued() {
+        // given: a page linking several subdomains, only allowing blog and shop
+        let (resp_tx, _resp_rx) = mpsc::channel(1);
+        let page_crawl_command: Box<dyn CrawlCommand> = Box::new(StubPageCrawlCommand::new(String::from("https://example.com/inner"), resp_tx.clone()));
+        let mut crawl_result = PageResponse::new(String::from("https://example.com/inner"), String::from("/inner"), Uuid::new_v4());
+        crawl_result.links = Some(vec![
+            Link::from_str_with_scope("https://blog.example.com/post", Some(UriScope::DifferentSubDomain)),
+            Link::from_str_with_scope("//Shop.example.com/cart", Some(UriScope::DifferentSubDomain)),
+            Link::from_str_with_scope("https://en.blog.example.com/post", Some(UriScope::DifferentSubDomain)),
+            Link::from_str_with_scope("https://forum.example.com/thread", Some(UriScope::DifferentSubDomain)),
+            Link::from_str_with_scope("https://example.com/same", Some(UriScope::SameDomain)),
+        ]);
+        page_crawl_command.get_task_context().lock().unwrap().get_config().lock().unwrap().allowed_subdomains = Some(vec![String::from("blog"), String::from("shop")]);
+
+        // when: subpage commands are prepared
+        let load_page_commands = prepare_subpage_load_commands(&resp_tx, &page_crawl_command, &crawl_result).await;
+
+        // then: the forum is skipped, links of the crawled host are unaffected
+        let mut urls: Vec<&str> = load_page_commands.iter().map(|(url, _)| url.as_str()).collect();
+        urls.sort();
+        assert_eq!(urls.len(), 4, "Should skip the forum, got {:?}", urls);
+        assert_eq!(urls.iter().any(|url| url.contains("forum")), false, "Should not follow the forum");
+        assert_eq!(urls.iter().any(|url| url.ends_with("/same")), true, "Should still follow links of the crawled host");
+    }
+
+    #[tokio::test]
+    async fn stored_links_are_truncated_after_enqueuing() {
+        // given: a link-heavy page and a task context storing 10 links at most
+        let (resp_tx, mut resp_rx) = mpsc::channel(10);
+        let (tx, mut rx) = mpsc::channel(100);
+        let page_crawl_command: Box<dyn CrawlCommand> = Box::new(StubPageCrawlCommand::new(String::from("https://example.com/inner"), resp_tx.clone()));
+        let mut crawl_result = PageResponse::new(String::from("https://example.com/inner"), String::from("/inner"), Uuid::new_v4());
+        crawl_result.links = Some((0..50)
+            .map(|index| Link::from_str_with_scope(&format!("https://example.com/page-{:02}", index), Some(UriScope::SameDomain)))
+            .collect());
+        page_crawl_command.get_task_context().lock().unwrap().get_config().lock().unwrap().max_stored_links = Some(10);
+
+        // when: the crawl result is consumed
+        consume_crawl_result(&resp_tx, &page_crawl_command, &tx, crawl_result).await;
+
+        // then: all links are enqueued, but only 10 are stored with the page
+        drop(tx);
+        let mut enqueued = 0;
+        while rx.recv().await.is_some() {
+            enqueued += 1;
+        }
+        assert_eq!(enqueued, 50, "Should enqueue all links");
+        if let CrawlerEvent::PageEvent { page_response } = resp_rx.recv().await.unwrap() {
+            assert_eq!(page_response.links.unwrap().len(), 10, "Should only store 10 links");
+            assert_eq!(page_response.links_total, Some(50), "Should report the actual number of links");
+        } else {
+            panic!("Wrong type");
+        }
+    }
+
+    #[tokio::test]
+    async fn subdomains_on_external_check_hosts_are_not_enqueued() {
+        // given: a page linking to two subdomains, one of them being an external check host
+        let (resp_tx, _resp_rx) = mpsc::channel(1);
+        let page_crawl_command: Box<dyn CrawlCommand> = Box::new(StubPageCrawlCommand::new(String::from("https://example.com/inner"), resp_tx.clone()));
+        page_crawl_command.get_task_context().lock().unwrap().get_config().lock().unwrap().external_check_hosts = Some(vec![String::from("status.example.com")]);
+        let mut crawl_result = PageResponse::new(String::from("https://example.com/page"), String::from("/page"), Uuid::new_v4());
+        crawl_result.links = Some(vec![
+            Link::from_str_with_scope("https://status.example.com/", Some(UriScope::DifferentSubDomain)),
+            Link::from_str_with_scope("https://blog.example.com/", Some(UriScope::DifferentSubDomain)),
+        ]);
+
+        // when: subpage commands are prepared
+        let load_page_commands = prepare_subpage_load_commands(&resp_tx, &page_crawl_command, &crawl_result).await;
+
+        // then: the check host is not crawled into
+        let urls: Vec<&str> = load_page_commands.iter().map(|(url, _)| url.as_str()).collect();
+        assert_eq!(urls, vec!["https://blog.example.com/"], "Should not crawl into external check hosts");
+    }
+
+    #[tokio::test]
+    async fn homepage_plus_one_only_crawls_the_seed_and_its_links() {
+        // given: a site, that is deeper than one level, and a homepage_plus_one crawl of it
+        let (resp_tx, mut resp_rx) = mpsc::channel(10);
+        let mut run_config = RunConfig::new(String::from("https://example.com"), None);
+        run_config.homepage_plus_one = Some(true);
+        run_config.maximum_depth = Some(0);
+        let task_context: Arc<Mutex<dyn FullTaskContext>> = Arc::new(Mutex::new(DefaultTaskContext::init(run_config, Uuid::new_v4(), resp_tx.clone())));
+        let command_factory: Arc<Box<dyn CommandFactory>> = Arc::new(Box::new(LinkingStubFactory {}));
+
+        // when: it is crawled
+        crawl_deterministically(command_factory, vec![String::from("https://example.com/")], resp_tx, task_context).await;
+
+        // then: exactly the pages at depth 0 and 1 are crawled, nothing deeper is even reported
+        let mut crawled = vec![];
+        while let Ok(event) = resp_rx.try_recv() {
+            if let CrawlerEvent::PageEvent { page_response } = event {
+                crawled.push((page_response.original_requested_url, page_response.depth));
+            }
+        }
+        assert_eq!(crawled, vec![
+            (String::from("https://example.com/"), Some(0)),
+            (String::from("https://example.com/depth-1"), Some(1)),
+        ], "Should only crawl the seed and its links");
+    }
+
+    #[tokio::test]
+    async fn pages_exceeding_max_page_duration_ms_time_out() {
+        // given: a slow page and a page deadline of 50ms
+        let (resp_tx, _resp_rx) = mpsc::channel(1);
+        let page_crawl_command: Box<dyn CrawlCommand> = Box::new(SlowPageCrawlCommand { inner: StubPageCrawlCommand::new(String::from("https://example.com/slow"), resp_tx.clone()), crawl_started: None });
+        page_crawl_command.get_task_context().lock().unwrap().get_config().lock().unwrap().max_page_duration_ms = Some(50);
+
+        // when: the page is crawled
+        let started_at = Instant::now();
+        let crawl_result = crawl_within_page_deadline(&page_crawl_command, get_http_client(), Uuid::new_v4(), None).await.unwrap().unwrap();
+
+        // then: the crawl is given up in time and reported as timed out
+        assert_eq!(started_at.elapsed() < Duration::from_secs(1), true, "Should not wait for the slow page");
+        assert_eq!(crawl_result.original_requested_url, "https://example.com/slow");
+        assert_eq!(crawl_result.crawl_status, Some(CrawlStatus::PageTimeout));
+    }
+
+    #[tokio::test]
+    async fn asset_links_are_not_enqueued_without_check_assets() {
+        // given: a page linking to a page, an image and a stylesheet, with check_assets disabled
+        let (resp_tx, _resp_rx) = mpsc::channel(1);
+        let page_crawl_command: Box<dyn CrawlCommand> = Box::new(StubPageCrawlCommand::new(String::from("https://example.com/inner"), resp_tx.clone()));
+        page_crawl_command.get_task_context().lock().unwrap().get_config().lock().unwrap().check_assets = false;
+        let mut crawl_result = PageResponse::new(String::from("https://example.com/page"), String::from("/page"), Uuid::new_v4());
+        let mut stylesheet = Link::from_str_with_scope("/styles", Some(UriScope::SameDomain));
+        stylesheet.source_tag = Some(String::from("<link rel=\"stylesheet\" href=\"/styles\">"));
+        crawl_result.links = Some(vec![
+            Link::from_str_with_scope("/about", Some(UriScope::SameDomain)),
+            Link::from_str_with_scope("/logo.png", Some(UriScope::SameDomain)),
+            stylesheet,
+        ]);
+
+        // when: subpage commands are prepared
+        let load_page_commands = prepare_subpage_load_commands(&resp_tx, &page_crawl_command, &crawl_result).await;
+
+        // then: only the page is requested
+        let urls: Vec<&str> = load_page_commands.iter().map(|(url, _)| url.as_str()).collect();
+        assert_eq!(urls, vec!["https://example.com/about"], "Should not request assets");
+    }
+
+    #[tokio::test]
+    async fn only_links_matching_the_path_patterns_are_enqueued() {
+        // given: a page linking to blog posts, drafts and the shop, and patterns only allowing published blog posts
+        let (resp_tx, _resp_rx) = mpsc::channel(1);
+        let page_crawl_command: Box<dyn CrawlCommand> = Box::new(StubPageCrawlCommand::new(String::from("https://example.com/inner"), resp_tx.clone()));
+        page_crawl_command.get_task_context().lock().unwrap().get_config().lock().unwrap().path_patterns =
+            PathPatterns::new(vec![String::from("^/blog/")], vec![String::from("^/blog/drafts$")], true, true);
+        let mut crawl_result = PageResponse::new(String::from("https://example.com/page"), String::from("/page"), Uuid::new_v4());
+        crawl_result.links = Some(vec![
+            Link::from_str_with_scope("/Blog", Some(UriScope::SameDomain)),
+            Link::from_str_with_scope("/blog/drafts/", Some(UriScope::SameDomain)),
+            Link::from_str_with_scope("/shop", Some(UriScope::SameDomain)),
+        ]);
+
+        // when: subpage commands are prepared
+        let load_page_commands = prepare_subpage_load_commands(&resp_tx, &page_crawl_command, &crawl_result).await;
+
+        // then: only the blog is followed, regardless of case and trailing slash
+        let urls: Vec<&str> = load_page_commands.iter().map(|(url, _)| url.as_str()).collect();
+        assert_eq!(urls, vec!["https://example.com/Blog"], "Should only follow links matching the path patterns");
+    }
+
+    #[tokio::test]
+    async fn links_canonicalizing_to_a_known_page_are_not_enqueued() {
+        // given: a redirected page, whose requested and final URL become known, ignoring trailing slashes
+        let (resp_tx, _resp_rx) = mpsc::channel(1);
+        let page_crawl_command: Box<dyn CrawlCommand> = Box::new(StubPageCrawlCommand::new(String::from("https://example.com/inner"), resp_tx.clone()));
+        page_crawl_command.get_task_context().lock().unwrap().get_config().lock().unwrap().dedup_key_rules.strip_trailing_slash = true;
+        let mut crawl_result = PageResponse::new(String::from("https://Example.com:443/moved"), String::from("/moved"), Uuid::new_v4());
+        crawl_result.final_url_after_redirects = Some(String::from("https://example.com/docs/"));
+        crawl_result.links = Some(vec![
+            Link::from_str_with_scope("/moved/", Some(UriScope::SameDomain)),
+            Link::from_str_with_scope("/docs", Some(UriScope::SameDomain)),
+            Link::from_str_with_scope("/docs/#intro", Some(UriScope::SameDomain)),
+            Link::from_str_with_scope("/blog", Some(UriScope::SameDomain)),
+        ]);
+
+        // when: subpage commands are prepared
+        let load_page_commands = prepare_subpage_load_commands(&resp_tx, &page_crawl_command, &crawl_result).await;
+
+        // then: only the link to an unknown page is followed
+        let urls: Vec<&str> = load_page_commands.iter().map(|(url, _)| url.as_str()).collect();
+        assert_eq!(urls, vec!["https://example.com/blog"], "Should only follow links to unknown pages");
+    }
+
+    #[tokio::test]
+    async fn only_the_canonical_of_duplicates_is_enqueued_and_counted_if_deduplicating_by_canonical() {
+        // given: two pages sharing a canonical, each with a link of its own
+        let (resp_tx, mut resp_rx) = mpsc::channel(10);
+        let (load_page_tx, mut load_page_rx) = mpsc::channel(10);
+        let page_crawl_command: Box<dyn CrawlCommand> = Box::new(StubPageCrawlCommand::new(String::from("https://example.com/inner"), resp_tx.clone()));
+        page_crawl_command.get_task_context().lock().unwrap().get_config().lock().unwrap().dedup_by_canonical = true;
+        let create_duplicate = |path: &str| {
+            let mut crawl_result = PageResponse::new(format!("https://example.com{}", path), String::from(path), Uuid::new_v4());
+            let mut canonical = Link::from_str_with_scope("/shared", Some(UriScope::SameDomain));
+            canonical.rel = Some(String::from("canonical"));
+            crawl_result.links = Some(vec![canonical, Link::from_str_with_scope(&format!("{}/details", path), Some(UriScope::SameDomain))]);
+            crawl_result.canonical_url = Some(String::from("https://example.com/shared"));
+            crawl_result
+        };
+
+        // when: both pages are consumed
+        consume_crawl_result(&resp_tx, &page_crawl_command, &load_page_tx, create_duplicate("/first")).await;
+        consume_crawl_result(&resp_tx, &page_crawl_command, &load_page_tx, create_duplicate("/second")).await;
+
+        // then: only the canonical is followed
+        drop(load_page_tx);
+        let mut followed_urls = vec![];
+        while let Some(LoadPageCommand { url, .. }) = load_page_rx.recv().await {
+            followed_urls.push(url);
+        }
+        // the canonical is enqueued for both duplicates, the crawl drops it as already tasked the second time
+        followed_urls.dedup();
+        assert_eq!(followed_urls, vec!["https://example.com/shared"], "Should only follow the canonical");
+        // and: both duplicates are reported as such, without being counted as pages
+        for _ in 0..2 {
+            if let CrawlerEvent::PageEvent { page_response } = resp_rx.recv().await.unwrap() {
+                assert_eq!(page_response.crawl_status, Some(CrawlStatus::CanonicalDuplicate), "Should mark {} as canonical duplicate", page_response.original_requested_url);
+            } else {
+                panic!("Should report the duplicates");
+            }
+        }
+        assert_eq!(page_crawl_command.get_task_context().lock().unwrap().get_page_counter().load(Ordering::SeqCst), 0, "Should not count canonical duplicates");
+    }
+
+    #[tokio::test]
+    async fn links_of_the_canonical_page_itself_are_followed() {
+        // given: a page declaring itself as canonical
+        let (resp_tx, _resp_rx) = mpsc::channel(1);
+        let page_crawl_command: Box<dyn CrawlCommand> = Box::new(StubPageCrawlCommand::new(String::from("https://example.com/inner"), resp_tx.clone()));
+        page_crawl_command.get_task_context().lock().unwrap().get_config().lock().unwrap().dedup_by_canonical = true;
+        let mut crawl_result = PageResponse::new(String::from("https://example.com/shared"), String::from("/shared"), Uuid::new_v4());
+        crawl_result.links = Some(vec![Link::from_str_with_scope("/shared/details", Some(UriScope::SameDomain))]);
+        crawl_result.canonical_url = Some(String::from("https://example.com/shared"));
+
+        // when: subpage commands are prepared
+        let load_page_commands = prepare_subpage_load_commands(&resp_tx, &page_crawl_command, &crawl_result).await;
+
+        // then: its links are followed as usual
+        assert_eq!(load_page_commands.len(), 1, "Should follow the links of the canonical page");
+    }
+
+    fn get_http_client() -> Arc<dyn HttpClient> {
+        let (resp_tx, _) = mpsc::channel(1);
+        create_default_task_context(resp_tx).lock().unwrap().get_http_client()
+    }
 }
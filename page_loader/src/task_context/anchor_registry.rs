@@ -0,0 +1,65 @@
+use std::collections::{HashMap, HashSet};
+
+use responses::broken_anchor::BrokenAnchor;
+
+// Collects element ids per page and all links pointing to a #fragment, so that fragments
+// without a matching element can be reported once the crawl is complete.
+#[derive(Debug, Default)]
+pub struct AnchorRegistry {
+    element_ids_by_page: HashMap<String, HashSet<String>>,
+    anchor_links: Vec<BrokenAnchor>,
+}
+
+impl AnchorRegistry {
+    pub fn new() -> AnchorRegistry {
+        AnchorRegistry::default()
+    }
+
+    pub fn add_page_element_ids(&mut self, url: String, element_ids: HashSet<String>) {
+        self.element_ids_by_page.entry(url).or_insert_with(HashSet::new).extend(element_ids);
+    }
+
+    pub fn add_anchor_link(&mut self, source_url: String, target_url: String, fragment: String) {
+        let anchor_link = BrokenAnchor { source_url, target_url, fragment };
+        if !self.anchor_links.contains(&anchor_link) {
+            self.anchor_links.push(anchor_link);
+        }
+    }
+
+    // only fragments pointing to pages that have been crawled (and therefore have known element ids) are verified.
+    pub fn get_broken_anchors(&self) -> Vec<BrokenAnchor> {
+        self.anchor_links.iter()
+            .filter(|anchor_link| {
+                self.element_ids_by_page.get(&anchor_link.target_url)
+                    .map_or(false, |element_ids| !element_ids.contains(&anchor_link.fragment))
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_fragments_without_matching_element_id() {
+        // given: a registry with two crawled pages
+        let mut registry = AnchorRegistry::new();
+        registry.add_page_element_ids(String::from("https://example.com/"), HashSet::from([String::from("intro")]));
+        registry.add_page_element_ids(String::from("https://example.com/page"), HashSet::from([String::from("present")]));
+
+        // when: fragments to existing, missing and uncrawled targets are added
+        registry.add_anchor_link(String::from("https://example.com/"), String::from("https://example.com/"), String::from("intro"));
+        registry.add_anchor_link(String::from("https://example.com/"), String::from("https://example.com/page"), String::from("present"));
+        registry.add_anchor_link(String::from("https://example.com/"), String::from("https://example.com/page"), String::from("missing"));
+        registry.add_anchor_link(String::from("https://example.com/"), String::from("https://example.com/uncrawled"), String::from("missing"));
+
+        // then: only the missing fragment on a crawled page is reported
+        assert_eq!(registry.get_broken_anchors(), vec![BrokenAnchor {
+            source_url: String::from("https://example.com/"),
+            target_url: String::from("https://example.com/page"),
+            fragment: String::from("missing"),
+        }]);
+    }
+}
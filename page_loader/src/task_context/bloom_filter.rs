@@ -0,0 +1,74 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+// Approximate set of known links. Lookups may report an unknown link as known (false positive),
+// but never report a known link as unknown. For the crawler this means a false positive only
+// causes a new URL to be skipped, it can never cause a URL to be crawled twice.
+#[derive(Debug)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    number_of_bits: u64,
+    number_of_hashes: u32,
+}
+
+impl BloomFilter {
+    pub fn new(expected_items: usize, false_positive_rate: f64) -> BloomFilter {
+        let ln_2 = std::f64::consts::LN_2;
+        let number_of_bits = (-(expected_items.max(1) as f64) * false_positive_rate.ln() / (ln_2 * ln_2)).ceil().max(64.0) as u64;
+        let number_of_hashes = ((number_of_bits as f64 / expected_items.max(1) as f64) * ln_2).round().max(1.0) as u32;
+        BloomFilter {
+            bits: vec![0; ((number_of_bits + 63) / 64) as usize],
+            number_of_bits,
+            number_of_hashes,
+        }
+    }
+
+    pub fn insert(&mut self, item: &str) {
+        for bit in self.bit_positions(item) {
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+    }
+
+    pub fn contains(&self, item: &str) -> bool {
+        self.bit_positions(item)
+            .all(|bit| self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+
+    // double hashing: the i-th position is h1 + i * h2
+    fn bit_positions(&self, item: &str) -> impl Iterator<Item=u64> {
+        let first_hash = BloomFilter::hash_with_seed(item, 0);
+        let second_hash = BloomFilter::hash_with_seed(item, 1) | 1;
+        let number_of_bits = self.number_of_bits;
+        (0..self.number_of_hashes as u64)
+            .map(move |i| first_hash.wrapping_add(i.wrapping_mul(second_hash)) % number_of_bits)
+    }
+
+    fn hash_with_seed(item: &str, seed: u64) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        seed.hash(&mut hasher);
+        item.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_inserted_urls_as_seen() {
+        // given: a filter with some inserted urls
+        let mut bloom_filter = BloomFilter::new(10_000, 0.01);
+        let urls: Vec<String> = (0..10_000).map(|i| format!("https://example.com/page-{}", i)).collect();
+
+        // when: urls are inserted
+        urls.iter().for_each(|url| bloom_filter.insert(url));
+
+        // then: all of them are reported as seen and unseen urls rarely are
+        assert_eq!(urls.iter().all(|url| bloom_filter.contains(url)), true, "Should never report a seen url as unseen");
+        let false_positives = (0..10_000)
+            .filter(|i| bloom_filter.contains(&format!("https://example.com/other-{}", i)))
+            .count();
+        assert_eq!(false_positives < 300, true, "False positive rate should be close to 1%, got {} of 10000", false_positives);
+    }
+}
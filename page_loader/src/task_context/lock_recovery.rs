@@ -0,0 +1,50 @@
+use std::sync::{Mutex, MutexGuard};
+
+use tracing::warn;
+
+// A panic while a lock is held poisons the mutex, and every later lock().unwrap() would panic as well.
+// The shared crawl state only consists of collections and settings, which stay usable after a panicked
+// worker, so the guard is recovered instead of taking down the whole crawl.
+pub trait LockRecovering<T: ?Sized> {
+    fn lock_recovering(&self) -> MutexGuard<'_, T>;
+}
+
+impl<T: ?Sized> LockRecovering<T> for Mutex<T> {
+    fn lock_recovering(&self) -> MutexGuard<'_, T> {
+        self.lock().unwrap_or_else(|poisoned| {
+            warn!("Recovering poisoned lock, a previous holder panicked");
+            poisoned.into_inner()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic;
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn recovers_a_lock_poisoned_by_a_panicking_operation() {
+        // given: a shared list, whose lock is poisoned by a panic while pushing to it
+        let known_links = Arc::new(Mutex::new(vec![String::from("https://example.com")]));
+        let known_links_cloned = known_links.clone();
+        let result = thread::spawn(move || {
+            let mut known_links_locked = known_links_cloned.lock_recovering();
+            known_links_locked.push(String::from("https://example.com/a"));
+            panic!("deliberate panic while holding the lock");
+        }).join();
+        assert_eq!(result.is_err(), true, "The operation should have panicked");
+        assert_eq!(known_links.is_poisoned(), true, "The lock should be poisoned");
+
+        // when: the list is used afterwards
+        known_links.lock_recovering().push(String::from("https://example.com/b"));
+
+        // then: all operations keep working on the data
+        assert_eq!(known_links.lock_recovering().len(), 3, "Should keep the pushes before and after the panic");
+        let second_result = panic::catch_unwind(|| known_links.lock_recovering().len());
+        assert_eq!(second_result.ok(), Some(3), "Should not panic on later locks");
+    }
+}
@@ -1,3 +1,7 @@
+pub mod anchor_registry;
+pub mod bloom_filter;
+pub mod lock_recovery;
+pub mod path_patterns;
 pub mod robots_service;
 pub mod task_context;
 
@@ -0,0 +1,137 @@
+use regex::{Regex, RegexBuilder};
+use tracing::warn;
+
+// Include/exclude regexes on the path of a link. Without include patterns, every path is included.
+// Invalid patterns are skipped, so a typo can't fail the whole crawl.
+#[derive(Clone, Debug)]
+pub struct PathPatterns {
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+    case_insensitive: bool,
+    trailing_slash_tolerant: bool,
+}
+
+impl Default for PathPatterns {
+    fn default() -> Self {
+        PathPatterns::new(vec![], vec![], true, true)
+    }
+}
+
+impl PathPatterns {
+    pub fn new(include: Vec<String>, exclude: Vec<String>, case_insensitive: bool, trailing_slash_tolerant: bool) -> PathPatterns {
+        PathPatterns {
+            include: PathPatterns::compile(&include, case_insensitive),
+            exclude: PathPatterns::compile(&exclude, case_insensitive),
+            case_insensitive,
+            trailing_slash_tolerant,
+        }
+    }
+
+    fn compile(patterns: &[String], case_insensitive: bool) -> Vec<Regex> {
+        patterns.iter()
+            .filter_map(|pattern| match RegexBuilder::new(pattern).case_insensitive(case_insensitive).build() {
+                Ok(regex) => Some(regex),
+                Err(error) => {
+                    warn!("Ignoring invalid path pattern {}: {}", pattern, error);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn is_allowed(&self, path: &str) -> bool {
+        (self.include.is_empty() || PathPatterns::any_matches(&self.include, &self.get_variants(path)))
+            && !PathPatterns::any_matches(&self.exclude, &self.get_variants(path))
+    }
+
+    fn any_matches(regexes: &[Regex], paths: &[String]) -> bool {
+        regexes.iter().any(|regex| paths.iter().any(|path| regex.is_match(path)))
+    }
+
+    // with trailing slash tolerance, "/blog" also matches patterns written for "/blog/" and vice versa
+    fn get_variants(&self, path: &str) -> Vec<String> {
+        let mut variants = vec![path.to_string()];
+        if self.trailing_slash_tolerant {
+            match path.strip_suffix('/') {
+                Some(stripped) if !stripped.is_empty() => variants.push(stripped.to_string()),
+                Some(_) => {}
+                None => variants.push(format!("{}/", path)),
+            }
+        }
+        variants
+    }
+
+    pub fn get_include(&self) -> Vec<String> {
+        self.include.iter().map(|regex| regex.as_str().to_string()).collect()
+    }
+
+    pub fn get_exclude(&self) -> Vec<String> {
+        self.exclude.iter().map(|regex| regex.as_str().to_string()).collect()
+    }
+
+    pub fn is_case_insensitive(&self) -> bool {
+        self.case_insensitive
+    }
+
+    pub fn is_trailing_slash_tolerant(&self) -> bool {
+        self.trailing_slash_tolerant
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_case_insensitively_by_default() {
+        // given: a pattern written in lowercase
+        let path_patterns = PathPatterns::new(vec![String::from("^/blog$")], vec![], true, true);
+
+        // when: a path differing in case is checked
+        let result = path_patterns.is_allowed("/Blog");
+
+        // then: it matches
+        assert_eq!(result, true, "Should match /Blog case-insensitively");
+    }
+
+    #[test]
+    fn matches_case_sensitively_if_configured() {
+        let path_patterns = PathPatterns::new(vec![String::from("^/blog$")], vec![], false, true);
+
+        assert_eq!(path_patterns.is_allowed("/Blog"), false, "Should not match /Blog case-sensitively");
+        assert_eq!(path_patterns.is_allowed("/blog"), true, "Should match /blog");
+    }
+
+    #[test]
+    fn tolerates_trailing_slashes_by_default() {
+        // given: patterns written with and without trailing slash
+        let path_patterns = PathPatterns::new(vec![String::from("^/blog/$"), String::from("^/docs$")], vec![], true, true);
+
+        // when/then: paths match regardless of their trailing slash
+        assert_eq!(path_patterns.is_allowed("/blog"), true, "Should match /blog against /blog/");
+        assert_eq!(path_patterns.is_allowed("/docs/"), true, "Should match /docs/ against /docs");
+        assert_eq!(path_patterns.is_allowed("/shop"), false, "Should not match other paths");
+    }
+
+    #[test]
+    fn requires_exact_trailing_slashes_if_configured() {
+        let path_patterns = PathPatterns::new(vec![String::from("^/blog/$")], vec![], true, false);
+
+        assert_eq!(path_patterns.is_allowed("/blog"), false, "Should not match /blog against /blog/");
+    }
+
+    #[test]
+    fn excludes_take_precedence_and_invalid_patterns_are_skipped() {
+        // given: an include pattern, an exclude pattern and an invalid one
+        let path_patterns = PathPatterns::new(vec![String::from("^/blog")], vec![String::from("^/blog/drafts/$"), String::from("(")], true, true);
+
+        // when/then: excluded paths aren't allowed, even if included
+        assert_eq!(path_patterns.get_exclude(), vec![String::from("^/blog/drafts/$")]);
+        assert_eq!(path_patterns.is_allowed("/blog/post"), true, "Should allow included paths");
+        assert_eq!(path_patterns.is_allowed("/Blog/Drafts"), false, "Should exclude drafts");
+    }
+}
@@ -1,22 +1,49 @@
+use std::collections::{BTreeMap, HashSet};
 use std::fmt::Debug;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::time::Duration;
 
+use chrono::{DateTime, Utc};
 use hyper::Uri;
+use responses::body_preprocessor::BodyPreprocessor;
+use responses::broken_anchor::BrokenAnchor;
+use responses::canonical_issue::CanonicalIssue;
+use responses::dedup_key_rules::DedupKeyRules;
+use responses::device_profile::DeviceProfile;
+use responses::domain_scope::DomainScope;
+use responses::duplicate_content::{DuplicateContentCluster, DuplicateContentKind};
+use responses::robots_error_policy::RobotsErrorPolicy;
+use responses::robots_fetch_timeout_policy::RobotsFetchTimeoutPolicy;
+use responses::robots_txt_summary::RobotsTxtSummary;
 use responses::run_config::RunConfig;
+use responses::slow_page::SlowPage;
+use responses::soft_limit::SoftLimit;
+use responses::terminal_reason::TerminalReason;
 use tokio::sync::mpsc::Sender;
 use tokio::time::Instant;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 use uuid::Uuid;
 
-use dom_parser::{DomParser, DomParserService};
+use dom_parser::{DEFAULT_MAX_LINK_TEXT_LEN, DomParser, DomParserService};
+use linkresult::dedup_key::to_known_path;
 use linkresult::link_type_checker::LinkTypeChecker;
-use linkresult::uri_service::UriService;
+use linkresult::uri_service::{DEFAULT_SESSION_QUERY_PARAMS, UriService};
 
 use crate::events::crawler_event::CrawlerEvent;
 use crate::http::http_client::{HttpClient, HttpClientImpl};
+use crate::http::http_utils::DEFAULT_RETRYABLE_STATUS_CODES;
+use crate::http::token_bucket::TokenBucket;
+use crate::task_context::anchor_registry::AnchorRegistry;
+use crate::task_context::bloom_filter::BloomFilter;
+use crate::task_context::lock_recovery::LockRecovering;
+use crate::task_context::path_patterns::PathPatterns;
 use crate::task_context::robots_service::{RobotsService, RobotsTxt};
 
+// roughly 12MB of memory, sized for a crawl of 10 million urls
+const APPROXIMATE_DEDUP_CAPACITY: usize = 10_000_000;
+const APPROXIMATE_DEDUP_FALSE_POSITIVE_RATE: f64 = 0.01;
+
 pub trait TaskContextInit {
     fn init(run_config: RunConfig, uuid: Uuid, response_channel: Sender<CrawlerEvent>) -> Self;
 }
@@ -35,60 +62,198 @@ pub trait TaskContext: Sync + Send + Registrar {
     fn set_last_command_received(&mut self, instant: Instant);
     fn can_be_garbage_collected(&self, gc_timeout_ms: u64) -> bool;
     fn get_response_channel(&self) -> &Sender<CrawlerEvent>;
+    fn get_request_counter(&self) -> Arc<AtomicUsize>;
+    fn get_downloaded_bytes_counter(&self) -> Arc<AtomicU64>;
+    fn get_page_counter(&self) -> Arc<AtomicUsize>;
+    fn get_broken_anchors(&self) -> Option<Vec<BrokenAnchor>>;
+    fn get_canonical_issues(&self) -> Option<Vec<CanonicalIssue>>;
+    fn get_duplicate_content(&self) -> Option<Vec<DuplicateContentCluster>>;
+    fn get_slowest_pages(&self) -> Option<Vec<SlowPage>>;
+    fn is_first_soft_limit_warning(&self, soft_limit: SoftLimit) -> bool;
+    fn get_robots_txt_summary(&self) -> Option<RobotsTxtSummary>;
+    fn cancel(&self);
+    fn set_crawl_delay_ms(&self, crawl_delay_ms: usize);
+    fn get_terminal_reason(&self) -> TerminalReason;
+    fn get_crawl_started_at(&self) -> DateTime<Utc>;
+    fn get_effective_config(&self) -> Option<RunConfig>;
 }
 
 pub trait TaskContextServices: Sync + Send {
     fn get_uri_service(&self) -> Arc<UriService>;
     fn get_dom_parser(&self) -> Arc<dyn DomParser>;
     fn get_http_client(&self) -> Arc<dyn HttpClient>;
+    fn get_asset_http_client(&self) -> Arc<dyn HttpClient>;
 }
 
 pub trait KnownLinks: Sync + Send {
     fn get_all_crawled_links(&self) -> Arc<Mutex<Vec<String>>>;
     fn get_all_tasked_links(&self) -> Arc<Mutex<Vec<String>>>;
     fn add_crawled_link(&self, link: String);
+    fn get_all_seen_hosts(&self) -> Arc<Mutex<HashSet<String>>>;
+    fn get_anchor_registry(&self) -> Arc<Mutex<AnchorRegistry>>;
+    fn get_canonicals(&self) -> Arc<Mutex<BTreeMap<String, String>>>;
+    fn get_followed_sitemaps(&self) -> Arc<Mutex<HashSet<String>>>;
+    fn get_titles(&self) -> Arc<Mutex<BTreeMap<String, String>>>;
+    fn get_descriptions(&self) -> Arc<Mutex<BTreeMap<String, String>>>;
+    fn get_response_times(&self) -> Arc<Mutex<BTreeMap<String, u64>>>;
+    fn get_approximate_known_links(&self) -> Option<Arc<Mutex<BloomFilter>>>;
+    fn get_known_paths(&self) -> Option<Arc<Mutex<HashSet<String>>>>;
 }
 
 pub trait FullTaskContext: TaskContext + TaskContextServices + KnownLinks + RobotsTxt {}
 
 #[derive(Clone)]
 pub struct DefaultTaskContext {
+    // as requested, kept for reporting the effective config
+    run_config: RunConfig,
     task_config: Arc<Mutex<TaskConfig>>,
     dom_parser: Arc<dyn DomParser>,
     uri_service: Arc<UriService>,
     robots_service: Arc<dyn RobotsTxt>,
     http_client: Arc<dyn HttpClient>,
+    asset_http_client: Arc<dyn HttpClient>,
     uuid: Uuid,
     last_command_received: Instant,
     all_crawled_links: Arc<Mutex<Vec<String>>>,
     all_tasked_links: Arc<Mutex<Vec<String>>>,
+    // replaces all_crawled_links and all_tasked_links for deduplication, if approximate_dedup is enabled
+    approximate_known_links: Option<Arc<Mutex<BloomFilter>>>,
+    // crawled and tasked links without their query, only kept with ignore_query_variants_of_known_paths
+    known_paths: Option<Arc<Mutex<HashSet<String>>>>,
+    all_seen_hosts: Arc<Mutex<HashSet<String>>>,
+    request_counter: Arc<AtomicUsize>,
+    // decoded bytes of downloaded pages, only counted with max_total_bytes
+    downloaded_bytes_counter: Arc<AtomicU64>,
+    // reported pages, canonical duplicates are counted with their canonical only
+    page_counter: Arc<AtomicUsize>,
+    anchor_registry: Arc<Mutex<AnchorRegistry>>,
+    // declared canonical per crawled page, only collected with detect_canonical_issues
+    canonicals: Arc<Mutex<BTreeMap<String, String>>>,
+    // sitemaps are referenced in the head of every page, but only loaded once
+    followed_sitemaps: Arc<Mutex<HashSet<String>>>,
+    // title and meta description per crawled page, only collected with detect_duplicate_content
+    titles: Arc<Mutex<BTreeMap<String, String>>>,
+    descriptions: Arc<Mutex<BTreeMap<String, String>>>,
+    // response time per page exceeding slow_threshold_ms
+    response_times: Arc<Mutex<BTreeMap<String, u64>>>,
+    soft_limit_warnings: Arc<Mutex<HashSet<SoftLimit>>>,
     response_channel: Sender<CrawlerEvent>,
     crawl_commands: Arc<Mutex<Vec<Uuid>>>,
+    started_at: Instant,
+    // wall clock time of started_at, for reporting
+    crawl_started_at: DateTime<Utc>,
+    cancelled: Arc<AtomicBool>,
 }
 
 impl TaskContextInit for DefaultTaskContext {
     fn init(run_config: RunConfig, uuid: Uuid, response_channel: Sender<CrawlerEvent>) -> DefaultTaskContext {
         let hyper_uri = run_config.url.parse::<hyper::Uri>().unwrap();
-        let task_config = Arc::new(Mutex::new(TaskConfig::new(run_config)));
-        let user_agent = task_config.lock().unwrap().user_agent.clone();
-        let crawl_delay_ms = task_config.lock().unwrap().crawl_delay_ms.clone();
-        let link_type_checker = Arc::new(LinkTypeChecker::new(hyper_uri.host().unwrap()));
-        let dom_parser = Arc::new(DomParserService::new(link_type_checker.clone()));
-        let uri_service = Arc::new(UriService::new(link_type_checker.clone()));
-        let robots_service = Arc::new(RobotsService::new(user_agent.clone()));
-        let http_client = Arc::new(HttpClientImpl::new(user_agent.clone(), crawl_delay_ms.clone()));
+        let task_config = Arc::new(Mutex::new(TaskConfig::new(run_config.clone())));
+        // all fields are read under a single guard
+        let config = task_config.lock_recovering();
+        let user_agent = config.user_agent.clone();
+        let request_user_agent = config.get_request_user_agent();
+        let device_headers = config.device_profile.as_ref().map(DeviceProfile::get_headers).unwrap_or_default();
+        let crawl_delay_ms = config.crawl_delay_ms;
+        let asset_crawl_delay_ms = config.asset_crawl_delay_ms;
+        let asset_max_concurrent_requests = config.asset_max_concurrent_requests;
+        let max_requests = config.max_requests;
+        let rng_seed = config.rng_seed;
+        let jitter_window = config.jitter_window;
+        let strict_rate_limit = config.strict_rate_limit;
+        let accept_encoding = config.accept_encoding.clone();
+        let pool_idle_timeout_ms = config.pool_idle_timeout_ms;
+        let pool_max_idle_per_host = config.pool_max_idle_per_host;
+        let robots_fetch_timeout_ms = config.robots_fetch_timeout_ms;
+        let robots_fetch_timeout_policy = config.robots_fetch_timeout_policy.clone();
+        let robots_on_error = config.robots_on_error.clone();
+        let body_preprocessors = config.body_preprocessors.clone();
+        let max_parse_ms = config.max_parse_ms;
+        let max_link_text_len = config.max_link_text_len;
+        let strip_default_ports = config.strip_default_ports;
+        let collapse_root_query = config.collapse_root_query;
+        let extract_json_ld_links = config.extract_json_ld_links;
+        let extract_microdata_links = config.extract_microdata_links;
+        let heuristic_link_extraction = config.heuristic_link_extraction;
+        let link_attributes = config.link_attributes.clone();
+        let capture_tls_info = config.capture_tls_info;
+        let domain_scope = config.domain_scope.clone();
+        let global_rate_limiter = config.global_requests_per_sec
+            .map(|requests_per_sec| Arc::new(Mutex::new(TokenBucket::new(requests_per_sec))));
+        let approximate_known_links = if config.approximate_dedup {
+            Some(Arc::new(Mutex::new(BloomFilter::new(APPROXIMATE_DEDUP_CAPACITY, APPROXIMATE_DEDUP_FALSE_POSITIVE_RATE))))
+        } else {
+            None
+        };
+        let known_paths = if config.ignore_query_variants_of_known_paths {
+            Some(Arc::new(Mutex::new(HashSet::new())))
+        } else {
+            None
+        };
+        let session_query_params = if config.strip_session_ids { config.session_query_params.clone() } else { vec![] };
+        drop(config);
+        let link_type_checker = Arc::new(LinkTypeChecker::new_with_domain_scope(hyper_uri.host().unwrap(), domain_scope));
+        let dom_parser = Arc::new(DomParserService::new_with_options(link_type_checker.clone(), body_preprocessors, max_parse_ms, max_link_text_len, extract_json_ld_links, extract_microdata_links, heuristic_link_extraction, link_attributes));
+        let uri_service = Arc::new(UriService::new_with_options(link_type_checker.clone(), session_query_params, strip_default_ports, collapse_root_query));
+        let robots_service = Arc::new(RobotsService::new_with_policies(user_agent.clone(), robots_fetch_timeout_ms, robots_fetch_timeout_policy, robots_on_error));
+        let request_counter = Arc::new(AtomicUsize::new(0));
+        let mut http_client = HttpClientImpl::new(request_user_agent.clone(), accept_encoding.clone(), crawl_delay_ms, request_counter.clone(), pool_idle_timeout_ms, pool_max_idle_per_host, capture_tls_info)
+            .with_headers(device_headers.clone());
+        let mut asset_http_client = HttpClientImpl::new(request_user_agent, accept_encoding, asset_crawl_delay_ms, request_counter.clone(), pool_idle_timeout_ms, pool_max_idle_per_host, false)
+            .with_headers(device_headers);
+        if let Some((jitter_min_ms, jitter_max_ms)) = jitter_window {
+            http_client = http_client.with_jitter_window(jitter_min_ms, jitter_max_ms);
+        }
+        if strict_rate_limit {
+            http_client = http_client.with_strict_rate_limit();
+        }
+        // only the jitter window of pages draws from the rng
+        if let Some(rng_seed) = rng_seed {
+            http_client = http_client.with_rng_seed(rng_seed);
+        }
+        if let Some(global_rate_limiter) = global_rate_limiter {
+            http_client = http_client.with_global_rate_limiter(global_rate_limiter.clone());
+            asset_http_client = asset_http_client.with_global_rate_limiter(global_rate_limiter);
+        }
+        if let Some(max_requests) = max_requests {
+            http_client = http_client.with_max_requests(max_requests);
+            asset_http_client = asset_http_client.with_max_requests(max_requests);
+        }
+        if let Some(asset_max_concurrent_requests) = asset_max_concurrent_requests {
+            asset_http_client = asset_http_client.with_max_concurrent_requests(asset_max_concurrent_requests);
+        }
+        let http_client = Arc::new(http_client);
+        let asset_http_client = Arc::new(asset_http_client);
         DefaultTaskContext {
+            run_config,
             task_config,
             dom_parser,
             uri_service,
             robots_service,
             http_client,
+            asset_http_client,
             uuid,
             last_command_received: Instant::now(),
             all_crawled_links: Arc::new(Mutex::new(vec![])),
             all_tasked_links: Arc::new(Mutex::new(vec![])),
+            approximate_known_links,
+            known_paths,
+            all_seen_hosts: Arc::new(Mutex::new(HashSet::new())),
+            request_counter,
+            downloaded_bytes_counter: Arc::new(AtomicU64::new(0)),
+            page_counter: Arc::new(AtomicUsize::new(0)),
+            anchor_registry: Arc::new(Mutex::new(AnchorRegistry::new())),
+            canonicals: Arc::new(Mutex::new(BTreeMap::new())),
+            followed_sitemaps: Arc::new(Mutex::new(HashSet::new())),
+            titles: Arc::new(Mutex::new(BTreeMap::new())),
+            descriptions: Arc::new(Mutex::new(BTreeMap::new())),
+            response_times: Arc::new(Mutex::new(BTreeMap::new())),
+            soft_limit_warnings: Arc::new(Mutex::new(HashSet::new())),
             response_channel,
             crawl_commands: Arc::new(Mutex::new(vec![])),
+            started_at: Instant::now(),
+            crawl_started_at: Utc::now(),
+            cancelled: Arc::new(AtomicBool::new(false)),
         }
     }
 }
@@ -100,7 +265,7 @@ impl TaskContext for DefaultTaskContext {
 
     fn get_config(&self) -> Arc<Mutex<TaskConfig>> { self.task_config.clone() }
 
-    fn get_url(&self) -> String { self.task_config.lock().unwrap().uri.to_string() }
+    fn get_url(&self) -> String { self.task_config.lock_recovering().uri.to_string() }
 
     fn get_last_command_received(&self) -> Instant {
         self.last_command_received
@@ -112,9 +277,9 @@ impl TaskContext for DefaultTaskContext {
 
     fn can_be_garbage_collected(&self, gc_timeout_ms: u64) -> bool {
         let now = Instant::now();
-        return if self.crawl_commands.lock().unwrap().len() == 0
+        return if self.crawl_commands.lock_recovering().len() == 0
             && self.last_command_received < now
-            && now - self.last_command_received > Duration::from_millis(self.task_config.lock().unwrap().crawl_delay_ms as u64 + gc_timeout_ms) {
+            && now - self.last_command_received > Duration::from_millis(self.task_config.lock_recovering().crawl_delay_ms as u64 + gc_timeout_ms) {
             true
         } else {
             false
@@ -124,6 +289,97 @@ impl TaskContext for DefaultTaskContext {
     fn get_response_channel(&self) -> &Sender<CrawlerEvent> {
         &self.response_channel
     }
+
+    fn get_request_counter(&self) -> Arc<AtomicUsize> {
+        self.request_counter.clone()
+    }
+
+    fn get_downloaded_bytes_counter(&self) -> Arc<AtomicU64> {
+        self.downloaded_bytes_counter.clone()
+    }
+
+    fn get_page_counter(&self) -> Arc<AtomicUsize> {
+        self.page_counter.clone()
+    }
+
+    fn get_broken_anchors(&self) -> Option<Vec<BrokenAnchor>> {
+        if !self.task_config.lock_recovering().detect_broken_anchors {
+            return None;
+        }
+        Some(self.anchor_registry.lock_recovering().get_broken_anchors())
+    }
+
+    fn get_canonical_issues(&self) -> Option<Vec<CanonicalIssue>> {
+        if !self.task_config.lock_recovering().detect_canonical_issues {
+            return None;
+        }
+        Some(CanonicalIssue::find_all(&self.canonicals.lock_recovering()))
+    }
+
+    fn get_duplicate_content(&self) -> Option<Vec<DuplicateContentCluster>> {
+        if !self.task_config.lock_recovering().detect_duplicate_content {
+            return None;
+        }
+        let mut clusters = DuplicateContentCluster::find_all(DuplicateContentKind::Title, &self.titles.lock_recovering());
+        clusters.append(&mut DuplicateContentCluster::find_all(DuplicateContentKind::Description, &self.descriptions.lock_recovering()));
+        Some(clusters)
+    }
+
+    fn get_slowest_pages(&self) -> Option<Vec<SlowPage>> {
+        self.task_config.lock_recovering().slow_threshold_ms?;
+        Some(SlowPage::find_slowest(&self.response_times.lock_recovering()))
+    }
+
+    fn is_first_soft_limit_warning(&self, soft_limit: SoftLimit) -> bool {
+        self.soft_limit_warnings.lock_recovering().insert(soft_limit)
+    }
+
+    fn get_robots_txt_summary(&self) -> Option<RobotsTxtSummary> {
+        if !self.task_config.lock_recovering().report_robots_txt {
+            return None;
+        }
+        Some(self.robots_service.get_summary())
+    }
+
+    fn get_effective_config(&self) -> Option<RunConfig> {
+        let task_config = self.task_config.lock_recovering();
+        if !task_config.include_effective_config {
+            return None;
+        }
+        Some(task_config.to_effective_run_config(&self.run_config))
+    }
+
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    // only pages are affected, assets keep their own delay
+    fn set_crawl_delay_ms(&self, crawl_delay_ms: usize) {
+        self.task_config.lock_recovering().crawl_delay_ms = crawl_delay_ms;
+        self.http_client.set_rate_limiting_ms(crawl_delay_ms);
+    }
+
+    // anything but Completed means the crawl was stopped early, and no new pages should be tasked
+    fn get_terminal_reason(&self) -> TerminalReason {
+        if self.cancelled.load(Ordering::SeqCst) {
+            return TerminalReason::Cancelled;
+        }
+        let config = self.task_config.lock_recovering();
+        if config.max_duration_ms.map_or(false, |max_duration_ms| self.started_at.elapsed() >= Duration::from_millis(max_duration_ms)) {
+            return TerminalReason::TimedOut;
+        }
+        if config.max_requests.map_or(false, |max_requests| self.request_counter.load(Ordering::SeqCst) >= max_requests) {
+            return TerminalReason::BudgetExhausted;
+        }
+        if config.max_total_bytes.map_or(false, |max_total_bytes| self.downloaded_bytes_counter.load(Ordering::SeqCst) >= max_total_bytes) {
+            return TerminalReason::BudgetExhausted;
+        }
+        TerminalReason::Completed
+    }
+
+    fn get_crawl_started_at(&self) -> DateTime<Utc> {
+        self.crawl_started_at
+    }
 }
 
 impl TaskContextServices for DefaultTaskContext {
@@ -132,6 +388,8 @@ impl TaskContextServices for DefaultTaskContext {
     }
     fn get_dom_parser(&self) -> Arc<dyn DomParser> { self.dom_parser.clone() }
     fn get_http_client(&self) -> Arc<dyn HttpClient> { self.http_client.clone() }
+
+    fn get_asset_http_client(&self) -> Arc<dyn HttpClient> { self.asset_http_client.clone() }
 }
 
 impl KnownLinks for DefaultTaskContext {
@@ -144,7 +402,46 @@ impl KnownLinks for DefaultTaskContext {
     }
 
     fn add_crawled_link(&self, link: String) {
-        self.all_crawled_links.lock().unwrap().push(link);
+        if let Some(known_paths) = &self.known_paths {
+            known_paths.lock_recovering().insert(to_known_path(&link).to_string());
+        }
+        self.all_crawled_links.lock_recovering().push(link);
+    }
+
+    fn get_all_seen_hosts(&self) -> Arc<Mutex<HashSet<String>>> {
+        self.all_seen_hosts.clone()
+    }
+
+    fn get_anchor_registry(&self) -> Arc<Mutex<AnchorRegistry>> {
+        self.anchor_registry.clone()
+    }
+
+    fn get_canonicals(&self) -> Arc<Mutex<BTreeMap<String, String>>> {
+        self.canonicals.clone()
+    }
+
+    fn get_followed_sitemaps(&self) -> Arc<Mutex<HashSet<String>>> {
+        self.followed_sitemaps.clone()
+    }
+
+    fn get_titles(&self) -> Arc<Mutex<BTreeMap<String, String>>> {
+        self.titles.clone()
+    }
+
+    fn get_descriptions(&self) -> Arc<Mutex<BTreeMap<String, String>>> {
+        self.descriptions.clone()
+    }
+
+    fn get_response_times(&self) -> Arc<Mutex<BTreeMap<String, u64>>> {
+        self.response_times.clone()
+    }
+
+    fn get_approximate_known_links(&self) -> Option<Arc<Mutex<BloomFilter>>> {
+        self.approximate_known_links.clone()
+    }
+
+    fn get_known_paths(&self) -> Option<Arc<Mutex<HashSet<String>>>> {
+        self.known_paths.clone()
     }
 }
 
@@ -152,22 +449,26 @@ impl RobotsTxt for DefaultTaskContext {
     fn can_access(&self, item_uri: &str) -> bool {
         self.robots_service.clone().can_access(item_uri)
     }
+
+    fn get_summary(&self) -> RobotsTxtSummary {
+        self.robots_service.get_summary()
+    }
 }
 
 impl Registrar for DefaultTaskContext {
     fn register_crawl_command(&self, uuid: Uuid, url: String) {
-        self.crawl_commands.lock().unwrap().push(uuid);
+        self.crawl_commands.lock_recovering().push(uuid);
         info!("Task [{}] Registered crawl command {}: {}", &self.uuid, &uuid, url);
     }
 
     fn unregister_crawl_command(&self, uuid: Uuid) {
-        let mut lock = self.crawl_commands.lock().unwrap();
+        let mut lock = self.crawl_commands.lock_recovering();
         lock.retain(|value| value != &uuid);
     }
 
     fn get_registered_tasks(&self) -> usize {
-        info!("{:?}", &self.crawl_commands.lock().unwrap());
-        self.crawl_commands.lock().unwrap().len()
+        debug!("{:?}", &self.crawl_commands.lock_recovering());
+        self.crawl_commands.lock_recovering().len()
     }
 }
 
@@ -190,6 +491,93 @@ pub struct TaskConfig {
     pub user_agent: String,
     pub robots_txt_info_url: Option<String>,
     pub crawl_delay_ms: usize,
+    pub extract_image_stats: bool,
+    pub seed_retries: u8,
+    pub seed_retry_backoff_ms: u64,
+    pub max_hosts: Option<usize>,
+    pub body_preprocessors: Vec<BodyPreprocessor>,
+    pub strip_session_ids: bool,
+    pub session_query_params: Vec<String>,
+    pub max_requests: Option<usize>,
+    pub max_total_bytes: Option<u64>,
+    pub detect_broken_anchors: bool,
+    pub ignore_query_variants_of_known_paths: bool,
+    pub rng_seed: Option<u64>,
+    pub assume_html_when_missing_content_type: bool,
+    pub sniff_content_type: bool,
+    pub check_external_links: bool,
+    pub robots_fetch_timeout_ms: u64,
+    pub robots_fetch_timeout_policy: RobotsFetchTimeoutPolicy,
+    pub emit_link_events: bool,
+    pub deterministic: bool,
+    pub max_parse_ms: Option<u64>,
+    pub accept_encoding: String,
+    pub soft_limit_percent: Option<u8>,
+    pub emit_soft_limit_events: bool,
+    pub pool_idle_timeout_ms: u64,
+    pub pool_max_idle_per_host: usize,
+    pub report_robots_txt: bool,
+    pub approximate_dedup: bool,
+    pub collapse_trailing_slash_redirects: bool,
+    pub max_link_text_len: usize,
+    pub respect_nofollow: bool,
+    pub global_requests_per_sec: Option<f64>,
+    pub seed_known_links: Vec<String>,
+    pub recrawl_only_failed: Option<Vec<String>>,
+    pub strip_default_ports: bool,
+    pub max_duration_ms: Option<u64>,
+    pub max_page_duration_ms: Option<usize>,
+    pub robots_on_error: RobotsErrorPolicy,
+    pub skip_alternate_variants: bool,
+    pub asset_crawl_delay_ms: usize,
+    pub asset_max_concurrent_requests: Option<usize>,
+    pub include_effective_config: bool,
+    pub extract_json_ld_links: bool,
+    pub thin_content_threshold: Option<usize>,
+    pub pagination_no_depth: bool,
+    pub max_reported_redirects: Option<usize>,
+    pub skip_extensions: Vec<String>,
+    pub only_extensions: Option<Vec<String>>,
+    pub allowed_subdomains: Option<Vec<String>>,
+    pub external_check_hosts: Option<Vec<String>>,
+    pub warmup: bool,
+    pub capture_tls_info: bool,
+    pub max_stored_links: Option<usize>,
+    pub domain_scope: DomainScope,
+    pub skip_head_request: bool,
+    pub dedup_by_canonical: bool,
+    pub dedup_key_rules: DedupKeyRules,
+    pub report_empty_body: bool,
+    pub follow_meta_refresh: bool,
+    pub link_attributes: Vec<String>,
+    pub path_patterns: PathPatterns,
+    pub check_assets: bool,
+    pub retryable_status_codes: Vec<u16>,
+    pub collapse_identical_redirects: bool,
+    pub homepage_plus_one: bool,
+    pub detect_canonical_issues: bool,
+    // lowercase, like the keys of the header maps
+    pub headers_of_interest: Option<Vec<String>>,
+    pub keep_all_headers: bool,
+    pub follow_sitemaps: bool,
+    pub detect_duplicate_content: bool,
+    // only applied to pages, assets keep asset_crawl_delay_ms
+    pub jitter_window: Option<(usize, usize)>,
+    pub max_requests_per_url: Option<usize>,
+    pub max_url_length: Option<usize>,
+    pub device_profile: Option<DeviceProfile>,
+    pub extract_microdata_links: bool,
+    pub slow_threshold_ms: Option<u64>,
+    // only pages of these content types are reported, all html is still crawled for links
+    pub report_content_types: Option<Vec<String>>,
+    // page requests to the same host start exactly crawl_delay_ms apart
+    pub strict_rate_limit: bool,
+    pub collapse_root_query: bool,
+    pub skip_known_redirect_targets: bool,
+    pub heuristic_link_extraction: bool,
+    pub max_path_depth: Option<usize>,
+    pub record_normalizations: bool,
+    pub robots_sitemap_audit: bool,
 }
 
 impl TaskConfig {
@@ -198,20 +586,260 @@ impl TaskConfig {
             uri: run_config.url.parse::<hyper::Uri>().unwrap(),
             ignore_redirects: run_config.ignore_redirects.unwrap_or_else(|| false),
             maximum_redirects: run_config.maximum_redirects.unwrap_or_else(|| 10),
-            maximum_depth: run_config.maximum_depth.unwrap_or_else(|| 16),
+            // pages are crawled below maximum_depth, so depth 0 (the seed) and depth 1 (its links) need a maximum_depth of 2
+            maximum_depth: if run_config.homepage_plus_one.unwrap_or(false) { 2 } else { run_config.maximum_depth.unwrap_or_else(|| 16) },
             ignore_robots_txt: run_config.ignore_robots_txt.unwrap_or_else(|| false),
             keep_html_in_memory: run_config.keep_html_in_memory.unwrap_or_else(|| false),
             user_agent: run_config.user_agent.unwrap_or_else(|| String::from("tarantula")),
             robots_txt_info_url: run_config.robots_txt_info_url,
             crawl_delay_ms: run_config.crawl_delay_ms.unwrap_or_else(|| 500),
+            extract_image_stats: run_config.extract_image_stats.unwrap_or_else(|| false),
+            seed_retries: run_config.seed_retries.unwrap_or_else(|| 0),
+            seed_retry_backoff_ms: run_config.seed_retry_backoff_ms.unwrap_or_else(|| 1000),
+            max_hosts: run_config.max_hosts,
+            body_preprocessors: run_config.body_preprocessors.unwrap_or_else(|| vec![]),
+            strip_session_ids: run_config.strip_session_ids.unwrap_or_else(|| true),
+            session_query_params: run_config.session_query_params.unwrap_or_else(|| DEFAULT_SESSION_QUERY_PARAMS.iter().map(|it| it.to_string()).collect()),
+            max_requests: run_config.max_requests,
+            max_total_bytes: run_config.max_total_bytes,
+            detect_broken_anchors: run_config.detect_broken_anchors.unwrap_or_else(|| false),
+            ignore_query_variants_of_known_paths: run_config.ignore_query_variants_of_known_paths.unwrap_or_else(|| false),
+            rng_seed: run_config.rng_seed,
+            assume_html_when_missing_content_type: run_config.assume_html_when_missing_content_type.unwrap_or_else(|| false),
+            sniff_content_type: run_config.sniff_content_type.unwrap_or_else(|| false),
+            check_external_links: run_config.check_external_links.unwrap_or_else(|| false),
+            robots_fetch_timeout_ms: run_config.robots_fetch_timeout_ms.unwrap_or_else(|| 5000),
+            robots_fetch_timeout_policy: run_config.robots_fetch_timeout_policy.unwrap_or_else(|| RobotsFetchTimeoutPolicy::AllowAll),
+            emit_link_events: run_config.emit_link_events.unwrap_or_else(|| false),
+            deterministic: run_config.deterministic.unwrap_or_else(|| false),
+            max_parse_ms: run_config.max_parse_ms,
+            accept_encoding: run_config.accept_encoding.unwrap_or_else(|| String::from("gzip, deflate, br")),
+            soft_limit_percent: run_config.soft_limit_percent,
+            emit_soft_limit_events: run_config.emit_soft_limit_events.unwrap_or_else(|| false),
+            pool_idle_timeout_ms: run_config.pool_idle_timeout_ms.unwrap_or_else(|| 90000),
+            pool_max_idle_per_host: run_config.pool_max_idle_per_host.unwrap_or_else(|| 32),
+            report_robots_txt: run_config.report_robots_txt.unwrap_or_else(|| false),
+            approximate_dedup: run_config.approximate_dedup.unwrap_or_else(|| false),
+            collapse_trailing_slash_redirects: run_config.collapse_trailing_slash_redirects.unwrap_or_else(|| false),
+            max_link_text_len: run_config.max_link_text_len.unwrap_or_else(|| DEFAULT_MAX_LINK_TEXT_LEN),
+            respect_nofollow: run_config.respect_nofollow.unwrap_or_else(|| false),
+            global_requests_per_sec: run_config.global_requests_per_sec.filter(|requests_per_sec| is_valid_requests_per_sec(*requests_per_sec)),
+            seed_known_links: run_config.seed_known_links.unwrap_or_else(|| vec![]),
+            recrawl_only_failed: run_config.recrawl_only_failed,
+            strip_default_ports: run_config.strip_default_ports.unwrap_or_else(|| true),
+            max_duration_ms: run_config.max_duration_ms,
+            max_page_duration_ms: run_config.max_page_duration_ms,
+            robots_on_error: run_config.robots_on_error.unwrap_or_else(|| RobotsErrorPolicy::AllowAll),
+            skip_alternate_variants: run_config.skip_alternate_variants.unwrap_or_else(|| false),
+            // assets are as polite as pages, unless configured otherwise
+            asset_crawl_delay_ms: run_config.asset_crawl_delay_ms.or(run_config.crawl_delay_ms).unwrap_or_else(|| 500),
+            asset_max_concurrent_requests: run_config.asset_max_concurrent_requests,
+            include_effective_config: run_config.include_effective_config.unwrap_or_else(|| false),
+            extract_json_ld_links: run_config.extract_json_ld_links.unwrap_or_else(|| false),
+            thin_content_threshold: run_config.thin_content_threshold,
+            pagination_no_depth: run_config.pagination_no_depth.unwrap_or_else(|| false),
+            max_reported_redirects: run_config.max_reported_redirects,
+            skip_extensions: run_config.skip_extensions.map_or_else(|| vec![], |extensions| normalize_extensions(extensions)),
+            only_extensions: run_config.only_extensions.map(|extensions| normalize_extensions(extensions)),
+            // an empty allowlist allows all subdomains, just like none
+            allowed_subdomains: run_config.allowed_subdomains
+                .map(|subdomains| normalize_subdomains(subdomains))
+                .filter(|subdomains| !subdomains.is_empty()),
+            external_check_hosts: run_config.external_check_hosts.map(|hosts| normalize_subdomains(hosts)),
+            warmup: run_config.warmup.unwrap_or_else(|| false),
+            capture_tls_info: run_config.capture_tls_info.unwrap_or_else(|| false),
+            max_stored_links: run_config.max_stored_links,
+            domain_scope: run_config.domain_scope.unwrap_or_else(|| DomainScope::Host),
+            skip_head_request: run_config.skip_head_request.unwrap_or_else(|| false),
+            dedup_by_canonical: run_config.dedup_by_canonical.unwrap_or_else(|| false),
+            dedup_key_rules: run_config.dedup_key_rules.unwrap_or_default(),
+            report_empty_body: run_config.report_empty_body.unwrap_or_else(|| false),
+            follow_meta_refresh: run_config.follow_meta_refresh.unwrap_or_else(|| false),
+            link_attributes: run_config.link_attributes.unwrap_or_else(|| vec![]),
+            path_patterns: PathPatterns::new(
+                run_config.include_path_patterns.unwrap_or_else(|| vec![]),
+                run_config.exclude_path_patterns.unwrap_or_else(|| vec![]),
+                run_config.path_patterns_case_insensitive.unwrap_or_else(|| true),
+                run_config.path_patterns_trailing_slash_tolerant.unwrap_or_else(|| true),
+            ),
+            check_assets: run_config.check_assets.unwrap_or_else(|| true),
+            retryable_status_codes: run_config.retryable_status_codes.unwrap_or_else(|| DEFAULT_RETRYABLE_STATUS_CODES.to_vec()),
+            collapse_identical_redirects: run_config.collapse_identical_redirects.unwrap_or_else(|| false),
+            homepage_plus_one: run_config.homepage_plus_one.unwrap_or_else(|| false),
+            detect_canonical_issues: run_config.detect_canonical_issues.unwrap_or_else(|| false),
+            headers_of_interest: run_config.headers_of_interest.map(|names| names.iter().map(|name| name.trim().to_lowercase()).collect()),
+            keep_all_headers: run_config.keep_all_headers.unwrap_or_else(|| true),
+            follow_sitemaps: run_config.follow_sitemaps.unwrap_or_else(|| false),
+            detect_duplicate_content: run_config.detect_duplicate_content.unwrap_or_else(|| false),
+            // a single bound is completed by the other, e.g. only jitter_max_ms means 0 up to that. strict_rate_limit rules out any jitter
+            jitter_window: match (run_config.jitter_min_ms, run_config.jitter_max_ms) {
+                _ if run_config.strict_rate_limit.unwrap_or(false) => None,
+                (None, None) => None,
+                (jitter_min_ms, jitter_max_ms) => Some((jitter_min_ms.unwrap_or(0), jitter_max_ms.or(jitter_min_ms).unwrap_or(0))),
+            },
+            max_requests_per_url: run_config.max_requests_per_url,
+            max_url_length: run_config.max_url_length,
+            device_profile: run_config.device_profile,
+            extract_microdata_links: run_config.extract_microdata_links.unwrap_or(false),
+            slow_threshold_ms: run_config.slow_threshold_ms,
+            report_content_types: run_config.report_content_types.map(|content_types| content_types.iter().map(|content_type| content_type.trim().to_lowercase()).collect()),
+            strict_rate_limit: run_config.strict_rate_limit.unwrap_or(false),
+            collapse_root_query: run_config.collapse_root_query.unwrap_or(false),
+            skip_known_redirect_targets: run_config.skip_known_redirect_targets.unwrap_or(false),
+            heuristic_link_extraction: run_config.heuristic_link_extraction.unwrap_or(false),
+            max_path_depth: run_config.max_path_depth,
+            record_normalizations: run_config.record_normalizations.unwrap_or(false),
+            robots_sitemap_audit: run_config.robots_sitemap_audit.unwrap_or(false),
         }
     }
+
+    // with a device profile, the crawler identifies itself after the device's user agent. robots.txt is still matched against user_agent
+    pub fn get_request_user_agent(&self) -> String {
+        match &self.device_profile {
+            Some(device_profile) => format!("{} {}", device_profile.get_user_agent(), self.user_agent),
+            None => self.user_agent.clone(),
+        }
+    }
+
+    // the requested run config with all defaults applied. Settings only the server resolves are kept as requested
+    pub fn to_effective_run_config(&self, run_config: &RunConfig) -> RunConfig {
+        RunConfig {
+            url: run_config.url.clone(),
+            ignore_redirects: Some(self.ignore_redirects),
+            maximum_redirects: Some(self.maximum_redirects),
+            maximum_depth: Some(self.maximum_depth),
+            ignore_robots_txt: Some(self.ignore_robots_txt),
+            keep_html_in_memory: Some(self.keep_html_in_memory),
+            user_agent: Some(self.user_agent.clone()),
+            robots_txt_info_url: self.robots_txt_info_url.clone(),
+            callback_url: run_config.callback_url.clone(),
+            callback_url_finished: run_config.callback_url_finished.clone(),
+            crawl_delay_ms: Some(self.crawl_delay_ms),
+            extract_image_stats: Some(self.extract_image_stats),
+            seed_retries: Some(self.seed_retries),
+            seed_retry_backoff_ms: Some(self.seed_retry_backoff_ms),
+            max_hosts: self.max_hosts,
+            body_preprocessors: Some(self.body_preprocessors.clone()),
+            strip_session_ids: Some(self.strip_session_ids),
+            session_query_params: Some(self.session_query_params.clone()),
+            max_requests: self.max_requests,
+            max_total_bytes: self.max_total_bytes,
+            detect_broken_anchors: Some(self.detect_broken_anchors),
+            ignore_query_variants_of_known_paths: Some(self.ignore_query_variants_of_known_paths),
+            common_log_file: run_config.common_log_file.clone(),
+            rng_seed: self.rng_seed,
+            assume_html_when_missing_content_type: Some(self.assume_html_when_missing_content_type),
+            sniff_content_type: Some(self.sniff_content_type),
+            check_external_links: Some(self.check_external_links),
+            robots_fetch_timeout_ms: Some(self.robots_fetch_timeout_ms),
+            robots_fetch_timeout_policy: Some(self.robots_fetch_timeout_policy.clone()),
+            report_phase_timings: Some(run_config.report_phase_timings.unwrap_or(false)),
+            folded_timings_file: run_config.folded_timings_file.clone(),
+            emit_link_events: Some(self.emit_link_events),
+            deterministic: Some(self.deterministic),
+            max_parse_ms: self.max_parse_ms,
+            accept_encoding: Some(self.accept_encoding.clone()),
+            soft_limit_percent: self.soft_limit_percent,
+            emit_soft_limit_events: Some(self.emit_soft_limit_events),
+            pool_idle_timeout_ms: Some(self.pool_idle_timeout_ms),
+            pool_max_idle_per_host: Some(self.pool_max_idle_per_host),
+            report_robots_txt: Some(self.report_robots_txt),
+            approximate_dedup: Some(self.approximate_dedup),
+            collapse_trailing_slash_redirects: Some(self.collapse_trailing_slash_redirects),
+            max_link_text_len: Some(self.max_link_text_len),
+            respect_nofollow: Some(self.respect_nofollow),
+            global_requests_per_sec: self.global_requests_per_sec,
+            seed_known_links: Some(self.seed_known_links.clone()),
+            recrawl_only_failed: self.recrawl_only_failed.clone(),
+            strip_default_ports: Some(self.strip_default_ports),
+            max_duration_ms: self.max_duration_ms,
+            max_page_duration_ms: self.max_page_duration_ms,
+            response_fields: run_config.response_fields.clone(),
+            robots_on_error: Some(self.robots_on_error.clone()),
+            skip_alternate_variants: Some(self.skip_alternate_variants),
+            asset_crawl_delay_ms: Some(self.asset_crawl_delay_ms),
+            asset_max_concurrent_requests: self.asset_max_concurrent_requests,
+            include_effective_config: Some(self.include_effective_config),
+            extract_json_ld_links: Some(self.extract_json_ld_links),
+            thin_content_threshold: self.thin_content_threshold,
+            pagination_no_depth: Some(self.pagination_no_depth),
+            max_reported_redirects: self.max_reported_redirects,
+            skip_extensions: Some(self.skip_extensions.clone()),
+            only_extensions: self.only_extensions.clone(),
+            allowed_subdomains: self.allowed_subdomains.clone(),
+            external_check_hosts: self.external_check_hosts.clone(),
+            warmup: Some(self.warmup),
+            capture_tls_info: Some(self.capture_tls_info),
+            max_stored_links: self.max_stored_links,
+            domain_scope: Some(self.domain_scope.clone()),
+            skip_head_request: Some(self.skip_head_request),
+            dedup_by_canonical: Some(self.dedup_by_canonical),
+            dedup_key_rules: Some(self.dedup_key_rules.clone()),
+            report_empty_body: Some(self.report_empty_body),
+            follow_meta_refresh: Some(self.follow_meta_refresh),
+            link_attributes: Some(self.link_attributes.clone()),
+            include_path_patterns: Some(self.path_patterns.get_include()),
+            exclude_path_patterns: Some(self.path_patterns.get_exclude()),
+            path_patterns_case_insensitive: Some(self.path_patterns.is_case_insensitive()),
+            path_patterns_trailing_slash_tolerant: Some(self.path_patterns.is_trailing_slash_tolerant()),
+            check_assets: Some(self.check_assets),
+            retryable_status_codes: Some(self.retryable_status_codes.clone()),
+            collapse_identical_redirects: Some(self.collapse_identical_redirects),
+            homepage_plus_one: Some(self.homepage_plus_one),
+            detect_canonical_issues: Some(self.detect_canonical_issues),
+            headers_of_interest: self.headers_of_interest.clone(),
+            keep_all_headers: Some(self.keep_all_headers),
+            follow_sitemaps: Some(self.follow_sitemaps),
+            detect_duplicate_content: Some(self.detect_duplicate_content),
+            jitter_min_ms: self.jitter_window.map(|(jitter_min_ms, _)| jitter_min_ms),
+            jitter_max_ms: self.jitter_window.map(|(_, jitter_max_ms)| jitter_max_ms),
+            max_requests_per_url: self.max_requests_per_url,
+            max_url_length: self.max_url_length,
+            device_profile: self.device_profile.clone(),
+            extract_microdata_links: Some(self.extract_microdata_links),
+            slow_threshold_ms: self.slow_threshold_ms,
+            report_content_types: self.report_content_types.clone(),
+            strict_rate_limit: Some(self.strict_rate_limit),
+            collapse_root_query: Some(self.collapse_root_query),
+            skip_known_redirect_targets: Some(self.skip_known_redirect_targets),
+            heuristic_link_extraction: Some(self.heuristic_link_extraction),
+            max_path_depth: self.max_path_depth,
+            record_normalizations: Some(self.record_normalizations),
+            robots_sitemap_audit: Some(self.robots_sitemap_audit),
+            collect_link_graph: Some(run_config.collect_link_graph.unwrap_or(false)),
+            sort_results: Some(run_config.sort_results.unwrap_or(false)),
+            // the url may contain credentials, which must not end up in the callbacks
+            database_url: run_config.database_url.as_ref().map(|_| String::from("<redacted>")),
+        }
+    }
+}
+
+// ".PDF" and "pdf" both match links with extension pdf
+fn normalize_extensions(extensions: Vec<String>) -> Vec<String> {
+    extensions.iter().map(|extension| extension.trim().trim_start_matches('.').to_lowercase()).collect()
+}
+
+fn normalize_subdomains(subdomains: Vec<String>) -> Vec<String> {
+    subdomains.iter()
+        .map(|subdomain| subdomain.trim().trim_matches('.').to_lowercase())
+        .filter(|subdomain| !subdomain.is_empty())
+        .collect()
+}
+
+// a rate of 0, a negative or a non-finite one can't space requests, the crawl runs without global rate limit instead
+fn is_valid_requests_per_sec(requests_per_sec: f64) -> bool {
+    let is_valid = requests_per_sec.is_finite() && requests_per_sec > 0.0;
+    if !is_valid {
+        warn!("Ignoring invalid global_requests_per_sec {}", requests_per_sec);
+    }
+    is_valid
 }
 
 #[cfg(test)]
 mod tests {
     use std::thread;
 
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
     use tokio::sync::mpsc;
 
     use super::*;
@@ -263,4 +891,199 @@ mod tests {
         // then: expect true
         assert_eq!(result, false, "TaskContext should not be garbage collectable at this point");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn asset_crawl_delay_falls_back_to_crawl_delay() {
+        // given: run configs with and without a dedicated asset delay
+        let mut run_config = RunConfig::new("https://example.com".into(), None);
+        run_config.crawl_delay_ms = Some(300);
+        let mut run_config_with_asset_delay = run_config.clone();
+        run_config_with_asset_delay.asset_crawl_delay_ms = Some(50);
+
+        // when: the task configs are created
+        let task_config = TaskConfig::new(run_config);
+        let task_config_with_asset_delay = TaskConfig::new(run_config_with_asset_delay);
+
+        // then: assets use their own delay, if given, while pages keep the crawl delay
+        assert_eq!(task_config.asset_crawl_delay_ms, 300, "Should fall back to the crawl delay");
+        assert_eq!(task_config_with_asset_delay.asset_crawl_delay_ms, 50, "Should use the asset delay");
+        assert_eq!(task_config_with_asset_delay.crawl_delay_ms, 300, "Should keep the crawl delay for pages");
+    }
+
+    #[tokio::test]
+    async fn canonical_issues_are_only_reported_if_enabled() {
+        // given: two task contexts knowing the same canonical loop, only one of them detecting canonical issues
+        let (resp_tx, _) = mpsc::channel(2);
+        let mut run_config = RunConfig::new("https://example.com".into(), None);
+        let context = DefaultTaskContext::init(run_config.clone(), Uuid::new_v4(), resp_tx.clone());
+        run_config.detect_canonical_issues = Some(true);
+        let detecting_context = DefaultTaskContext::init(run_config, Uuid::new_v4(), resp_tx);
+        for task_context in [&context, &detecting_context] {
+            task_context.get_canonicals().lock().unwrap().insert(String::from("https://example.com/a"), String::from("https://example.com/b"));
+            task_context.get_canonicals().lock().unwrap().insert(String::from("https://example.com/b"), String::from("https://example.com/a"));
+        }
+
+        // when / then: only the detecting context reports the loop
+        assert_eq!(context.get_canonical_issues(), None, "Should not report canonical issues by default");
+        assert_eq!(detecting_context.get_canonical_issues().unwrap().len(), 2, "Should report both pages of the loop");
+    }
+
+    #[tokio::test]
+    async fn duplicate_titles_and_descriptions_are_reported_as_clusters() {
+        // given: a task context detecting duplicate content, that knows three pages, two of them sharing a title
+        let (resp_tx, _) = mpsc::channel(2);
+        let mut run_config = RunConfig::new("https://example.com".into(), None);
+        run_config.detect_duplicate_content = Some(true);
+        let context = DefaultTaskContext::init(run_config, Uuid::new_v4(), resp_tx);
+        context.get_titles().lock().unwrap().insert(String::from("https://example.com/shoes"), String::from("Shop | Example"));
+        context.get_titles().lock().unwrap().insert(String::from("https://example.com/shirts"), String::from("Shop | Example"));
+        context.get_titles().lock().unwrap().insert(String::from("https://example.com/about"), String::from("About | Example"));
+        context.get_descriptions().lock().unwrap().insert(String::from("https://example.com/shoes"), String::from("Shoes"));
+        context.get_descriptions().lock().unwrap().insert(String::from("https://example.com/shirts"), String::from("Shirts"));
+
+        // when: the duplicate content is requested
+        let duplicate_content = context.get_duplicate_content().unwrap();
+
+        // then: only the shared title is reported, naming both pages
+        assert_eq!(duplicate_content, vec![DuplicateContentCluster {
+            kind: DuplicateContentKind::Title,
+            value: String::from("Shop | Example"),
+            urls: vec![String::from("https://example.com/shirts"), String::from("https://example.com/shoes")],
+        }]);
+    }
+
+    #[test]
+    fn device_profiles_select_their_user_agent() {
+        // given: run configs asking for a mobile and a desktop profile
+        let mut run_config = RunConfig::new("https://example.com".into(), None);
+        run_config.user_agent = Some(String::from("tarantula"));
+        run_config.device_profile = Some(DeviceProfile::Mobile);
+        let mobile_config = TaskConfig::new(run_config.clone());
+        run_config.device_profile = Some(DeviceProfile::Desktop);
+        let desktop_config = TaskConfig::new(run_config);
+
+        // when: the user agents of the requests are built
+        let mobile_user_agent = mobile_config.get_request_user_agent();
+        let desktop_user_agent = desktop_config.get_request_user_agent();
+
+        // then: each is the device's user agent, still naming the crawler
+        assert_eq!(mobile_user_agent.contains("Mobile"), true, "Should send a mobile user agent");
+        assert_eq!(desktop_user_agent.contains("Mobile"), false, "Should send a desktop user agent");
+        assert_eq!(desktop_user_agent.contains("Windows"), true, "Should send a desktop user agent");
+        assert_eq!(mobile_user_agent.ends_with(" tarantula"), true, "Should still identify the crawler");
+        assert_eq!(mobile_config.user_agent, String::from("tarantula"), "Should match robots.txt against the crawler");
+    }
+
+    #[tokio::test]
+    async fn slowest_pages_are_reported_slowest_first() {
+        // given: a task context flagging slow pages, which registered three of them
+        let (resp_tx, _) = mpsc::channel(2);
+        let mut run_config = RunConfig::new("https://example.com".into(), None);
+        run_config.slow_threshold_ms = Some(1000);
+        let context = DefaultTaskContext::init(run_config, Uuid::new_v4(), resp_tx);
+        context.get_response_times().lock().unwrap().insert(String::from("https://example.com/a"), 1500);
+        context.get_response_times().lock().unwrap().insert(String::from("https://example.com/b"), 4000);
+        context.get_response_times().lock().unwrap().insert(String::from("https://example.com/c"), 2000);
+
+        // when: the slowest pages are requested
+        let slowest_pages = context.get_slowest_pages().unwrap();
+
+        // then: they are sorted by response time
+        let urls: Vec<&str> = slowest_pages.iter().map(|slow_page| slow_page.url.as_str()).collect();
+        assert_eq!(urls, vec!["https://example.com/b", "https://example.com/c", "https://example.com/a"]);
+        assert_eq!(slowest_pages[0].response_time_ms, 4000);
+    }
+
+    #[tokio::test]
+    async fn strict_rate_limit_spaces_requests_by_exactly_the_crawl_delay() {
+        // given: a server recording its requests and a strict task context, that would otherwise wait 500-2000ms between requests
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let uri = format!("http://{}/", listener.local_addr().unwrap());
+        let request_times = Arc::new(Mutex::new(vec![]));
+        let request_times_server = request_times.clone();
+        tokio::spawn(async move {
+            while let Ok((mut connection, _)) = listener.accept().await {
+                let request_times = request_times_server.clone();
+                tokio::spawn(async move {
+                    let mut buffer = vec![0u8; 4096];
+                    while let Ok(read) = connection.read(&mut buffer).await {
+                        if read == 0 {
+                            break;
+                        }
+                        request_times.lock().unwrap().push(std::time::Instant::now());
+                        if connection.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n").await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        let (resp_tx, _) = mpsc::channel(2);
+        let mut run_config = RunConfig::new(uri.clone(), None);
+        run_config.crawl_delay_ms = Some(100);
+        run_config.jitter_min_ms = Some(500);
+        run_config.jitter_max_ms = Some(2000);
+        run_config.strict_rate_limit = Some(true);
+        let context = DefaultTaskContext::init(run_config, Uuid::new_v4(), resp_tx);
+        let http_client = context.get_http_client();
+
+        // when: several requests are sent one after another
+        for _ in 0..4 {
+            let response = http_client.get(uri.clone(), None).await.unwrap();
+            hyper::body::to_bytes(response.into_body()).await.unwrap();
+        }
+
+        // then: they arrived exactly the crawl delay apart
+        let request_times = request_times.lock().unwrap().clone();
+        assert_eq!(request_times.len(), 4);
+        for index in 1..request_times.len() {
+            let spacing_ms = request_times[index].duration_since(request_times[index - 1]).as_millis();
+            assert_eq!((95..120).contains(&spacing_ms), true, "Should space requests by 100ms, got {}ms", spacing_ms);
+        }
+        assert_eq!(context.get_config().lock().unwrap().jitter_window, None, "Should ignore the jitter window");
+    }
+
+    #[tokio::test]
+    async fn effective_config_reflects_applied_defaults() {
+        // given: a run config leaving some fields unset, asking for the effective config
+        let (resp_tx, _) = mpsc::channel(2);
+        let mut run_config = RunConfig::new("https://example.com".into(), None);
+        run_config.maximum_depth = None;
+        run_config.user_agent = None;
+        run_config.asset_crawl_delay_ms = None;
+        run_config.crawl_delay_ms = Some(100);
+        run_config.include_effective_config = Some(true);
+        let context = DefaultTaskContext::init(run_config, Uuid::new_v4(), resp_tx);
+
+        // when: the effective config is requested
+        let effective_config = context.get_effective_config().unwrap();
+
+        // then: unset fields carry their defaults, set ones are kept
+        assert_eq!(effective_config.maximum_depth, Some(16), "Should apply the default maximum depth");
+        assert_eq!(effective_config.user_agent, Some(String::from("tarantula")), "Should apply the default user agent");
+        assert_eq!(effective_config.asset_crawl_delay_ms, Some(100), "Should apply the crawl delay to assets");
+        assert_eq!(effective_config.crawl_delay_ms, Some(100), "Should keep the given crawl delay");
+    }
+
+    #[tokio::test]
+    async fn effective_config_is_omitted_by_default() {
+        // given: a usual task context
+        let (resp_tx, _) = mpsc::channel(2);
+        let context = DefaultTaskContext::init(RunConfig::new("https://example.com".into(), None), Uuid::new_v4(), resp_tx);
+
+        // when / then: no effective config is reported
+        assert_eq!(context.get_effective_config().is_none(), true, "Should not report the effective config unless requested");
+    }
+
+    #[test]
+    fn invalid_global_requests_per_sec_are_ignored() {
+        for requests_per_sec in [0.0, -1.0, f64::INFINITY, f64::NAN] {
+            let mut run_config = RunConfig::new("https://example.com".into(), None);
+            run_config.global_requests_per_sec = Some(requests_per_sec);
+
+            let task_config = TaskConfig::new(run_config);
+
+            assert_eq!(task_config.global_requests_per_sec, None, "Should ignore {}", requests_per_sec);
+        }
+    }
+}
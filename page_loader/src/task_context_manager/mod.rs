@@ -1,17 +1,23 @@
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::Ordering;
 use std::thread;
 use std::time::Duration;
 
+use responses::crawl_times::CrawlTimes;
 use tracing::{debug, error, info};
+use uuid::Uuid;
 
 use crate::events::crawler_event::CrawlerEvent;
+use crate::task_context::lock_recovery::LockRecovering;
 use crate::task_context::task_context::TaskContext;
 
 pub trait TaskManager: Sync + Send {
     fn add_task(&mut self, task: Arc<Mutex<dyn TaskContext>>);
     fn init(gc_timeout_ms: u64) -> Arc<Mutex<Self>> where Self: Sized;
     fn get_number_of_tasks(&self) -> usize;
+    fn remove_task(&mut self, uuid: &Uuid) -> Option<Arc<Mutex<dyn TaskContext>>>;
+    fn get_task(&self, uuid: &Uuid) -> Option<Arc<Mutex<dyn TaskContext>>>;
 }
 
 type TaskMap = HashMap<String, Arc<Mutex<dyn TaskContext>>>;
@@ -24,9 +30,9 @@ pub struct DefaultTaskManager {
 
 impl TaskManager for DefaultTaskManager {
     fn add_task(&mut self, task: Arc<Mutex<dyn TaskContext>>) {
-        let key = task.lock().unwrap().get_uuid().to_string();
+        let key = task.lock_recovering().get_uuid().to_string();
         debug!("Strong pointers to task {} before insert: {}", &key, Arc::strong_count(&task));
-        self.tasks.lock().unwrap().insert(key.clone(), task);
+        self.tasks.lock_recovering().insert(key.clone(), task);
     }
 
     fn init(gc_timeout_ms: u64) -> Arc<Mutex<Self>> {
@@ -46,7 +52,15 @@ impl TaskManager for DefaultTaskManager {
     }
 
     fn get_number_of_tasks(&self) -> usize {
-        self.tasks.lock().unwrap().len()
+        self.tasks.lock_recovering().len()
+    }
+
+    fn remove_task(&mut self, uuid: &Uuid) -> Option<Arc<Mutex<dyn TaskContext>>> {
+        self.tasks.lock_recovering().remove(&uuid.to_string())
+    }
+
+    fn get_task(&self, uuid: &Uuid) -> Option<Arc<Mutex<dyn TaskContext>>> {
+        self.tasks.lock_recovering().get(&uuid.to_string()).cloned()
     }
 }
 
@@ -55,23 +69,32 @@ impl DefaultTaskManager {
         loop {
             thread::sleep(gc_timeout_ms);
 
-            manager_instance.lock().unwrap().do_garbage_collection();
+            manager_instance.lock_recovering().do_garbage_collection();
         }
     }
 
     fn do_garbage_collection(&mut self) {
-        info!("Active tasks: {}", self.tasks.lock().unwrap().len());
-        info!("Active tasks uuids: {:?}", self.tasks.lock().unwrap().keys());
+        info!("Active tasks: {}", self.tasks.lock_recovering().len());
+        info!("Active tasks uuids: {:?}", self.tasks.lock_recovering().keys());
         let mut to_gc = vec![];
-        for (key, value) in self.tasks.lock().unwrap().iter() {
-            let can_gc = value.lock().unwrap().can_be_garbage_collected(self.gc_timeout_ms);
-            let uuid = value.lock().unwrap().get_uuid();
-            let registered_tasks = value.lock().unwrap().get_registered_tasks();
+        for (key, value) in self.tasks.lock_recovering().iter() {
+            let can_gc = value.lock_recovering().can_be_garbage_collected(self.gc_timeout_ms);
+            let uuid = value.lock_recovering().get_uuid();
+            let registered_tasks = value.lock_recovering().get_registered_tasks();
             info!("Active crawl commands for task {}: {}", key, registered_tasks);
             if can_gc {
-                if let Err(error) = value.lock().unwrap()
+                let broken_anchors = value.lock_recovering().get_broken_anchors();
+                let canonical_issues = value.lock_recovering().get_canonical_issues();
+                let duplicate_content = value.lock_recovering().get_duplicate_content();
+                let slowest_pages = value.lock_recovering().get_slowest_pages();
+                let robots_txt = value.lock_recovering().get_robots_txt_summary();
+                let terminal_reason = value.lock_recovering().get_terminal_reason();
+                let page_count = value.lock_recovering().get_page_counter().load(Ordering::SeqCst);
+                let effective_config = value.lock_recovering().get_effective_config();
+                let crawl_times = CrawlTimes::finish(value.lock_recovering().get_crawl_started_at());
+                if let Err(error) = value.lock_recovering()
                     .get_response_channel()
-                    .blocking_send(CrawlerEvent::CompleteEvent { uuid: uuid.clone() }) {
+                    .blocking_send(CrawlerEvent::CompleteEvent { uuid: uuid.clone(), crawl_status: None, broken_anchors, canonical_issues, duplicate_content, slowest_pages, robots_txt, sitemap_audit: None, terminal_reason, page_count, crawl_times, effective_config }) {
                     error!("Error while sending CompleteEvent to channel of task {}, error: {}", &uuid, error);
                 }
                 to_gc.push(key.clone());
@@ -82,17 +105,27 @@ impl DefaultTaskManager {
             info!("Removing task with id {}", key);
             debug!("Strong pointers to task {} before gc: {}",
                 key,
-                Arc::strong_count(&self.tasks.lock().unwrap()[key])
+                Arc::strong_count(&self.tasks.lock_recovering()[key])
             )});
-        self.tasks.lock().unwrap().retain(|key, _| !to_gc.contains(key));
+        self.tasks.lock_recovering().retain(|key, _| !to_gc.contains(key));
     }
 }
 
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
+    use std::sync::atomic::{AtomicU64, AtomicUsize};
 
+    use chrono::{DateTime, Utc};
     use mockall::*;
+    use responses::broken_anchor::BrokenAnchor;
+    use responses::canonical_issue::CanonicalIssue;
+    use responses::duplicate_content::DuplicateContentCluster;
+    use responses::slow_page::SlowPage;
+    use responses::robots_txt_summary::RobotsTxtSummary;
+    use responses::run_config::RunConfig;
+    use responses::soft_limit::SoftLimit;
+    use responses::terminal_reason::TerminalReason;
     use tokio::sync::mpsc;
     use tokio::sync::mpsc::Sender;
     use tokio::time::Duration;
@@ -114,6 +147,20 @@ mod tests {
             fn set_last_command_received(&mut self, instant: Instant);
             fn can_be_garbage_collected(&self, gc_timeout_ms: u64)-> bool;
             fn get_response_channel(&self) -> &Sender<CrawlerEvent>;
+            fn get_request_counter(&self) -> Arc<AtomicUsize>;
+            fn get_downloaded_bytes_counter(&self) -> Arc<AtomicU64>;
+            fn get_page_counter(&self) -> Arc<AtomicUsize>;
+            fn get_broken_anchors(&self) -> Option<Vec<BrokenAnchor>>;
+            fn get_canonical_issues(&self) -> Option<Vec<CanonicalIssue>>;
+            fn get_duplicate_content(&self) -> Option<Vec<DuplicateContentCluster>>;
+            fn get_slowest_pages(&self) -> Option<Vec<SlowPage>>;
+            fn is_first_soft_limit_warning(&self, soft_limit: SoftLimit) -> bool;
+            fn get_robots_txt_summary(&self) -> Option<RobotsTxtSummary>;
+            fn cancel(&self);
+            fn set_crawl_delay_ms(&self, crawl_delay_ms: usize);
+            fn get_terminal_reason(&self) -> TerminalReason;
+            fn get_crawl_started_at(&self) -> DateTime<Utc>;
+            fn get_effective_config(&self) -> Option<RunConfig>;
         }
         impl Registrar for MyTaskContext {
             fn register_crawl_command(&self, uuid: Uuid, url: String);
@@ -133,6 +180,15 @@ mod tests {
         mock_task_context.expect_get_url().returning(|| String::from("https://example.com"));
         mock_task_context.expect_get_response_channel().return_const(resp_tx);
         mock_task_context.expect_get_uuid().return_const(expected_uuid);
+        mock_task_context.expect_get_broken_anchors().returning(|| None);
+        mock_task_context.expect_get_canonical_issues().returning(|| None);
+        mock_task_context.expect_get_duplicate_content().returning(|| None);
+        mock_task_context.expect_get_slowest_pages().returning(|| None);
+        mock_task_context.expect_get_robots_txt_summary().returning(|| None);
+        mock_task_context.expect_get_terminal_reason().returning(|| TerminalReason::Completed);
+        mock_task_context.expect_get_page_counter().return_const(Arc::new(AtomicUsize::new(0)));
+        mock_task_context.expect_get_crawl_started_at().returning(Utc::now);
+        mock_task_context.expect_get_effective_config().returning(|| None);
         mock_task_context.expect_get_registered_tasks().return_const(0 as usize);
 
         let task_context = Arc::new(Mutex::new(mock_task_context));
@@ -147,8 +203,9 @@ mod tests {
             let num_tasks = task_manager.lock().unwrap().get_number_of_tasks();
             assert_eq!(num_tasks, 1, "task was not added");
             tokio::time::sleep(Duration::from_millis(gc_timeout_ms as u64 * 2)).await;
-            if let CrawlerEvent::CompleteEvent { uuid: actual_uuid } = resp_rx.recv().await.unwrap() {
+            if let CrawlerEvent::CompleteEvent { uuid: actual_uuid, terminal_reason, .. } = resp_rx.recv().await.unwrap() {
                 assert_eq!(expected_uuid, actual_uuid);
+                assert_eq!(terminal_reason, TerminalReason::Completed);
             } else {
                 panic!("No complete event received before garbage collection!");
             }
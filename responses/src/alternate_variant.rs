@@ -0,0 +1,19 @@
+use serde::Serialize;
+
+// <link rel="alternate"> (or rel="amphtml") with its media and type attributes
+#[derive(Debug, Clone, Serialize, Eq, PartialEq)]
+pub struct AlternateVariant {
+    pub rel: String,
+    pub media: Option<String>,
+    pub mime_type: Option<String>,
+}
+
+impl AlternateVariant {
+    // print and AMP versions duplicate the content of the canonical page
+    pub fn is_print_or_amp(&self) -> bool {
+        let contains = |value: &Option<String>, needle: &str| value.as_ref().is_some_and(|value| value.to_lowercase().contains(needle));
+        self.rel.to_lowercase().split_whitespace().any(|rel| rel == "amphtml")
+            || contains(&self.media, "print")
+            || contains(&self.mime_type, "amp")
+    }
+}
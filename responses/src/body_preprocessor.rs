@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum BodyPreprocessor {
+    // <!-- <a href="/hidden">hidden</a> --> is removed entirely
+    StripHtmlComments,
+    // <!-- <a href="/hidden">hidden</a> --> becomes <a href="/hidden">hidden</a>
+    UncommentHtmlComments,
+}
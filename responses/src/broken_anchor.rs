@@ -0,0 +1,8 @@
+use serde::Serialize;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BrokenAnchor {
+    pub source_url: String,
+    pub target_url: String,
+    pub fragment: String,
+}
@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::page_response::PageResponse;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum CanonicalIssueKind {
+    // the canonical declares yet another canonical
+    Chain,
+    // following the canonicals leads back to a page of the chain
+    Loop,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CanonicalIssue {
+    pub url: String,
+    pub kind: CanonicalIssueKind,
+    // starts with url, a loop ends with the page it returns to
+    pub chain: Vec<String>,
+}
+
+impl CanonicalIssue {
+    // canonicals maps crawled pages to the canonical they declare, self-references are fine. Sorted by url
+    pub fn find_all(canonicals: &BTreeMap<String, String>) -> Vec<CanonicalIssue> {
+        canonicals.keys()
+            .filter_map(|url| CanonicalIssue::find(url, canonicals))
+            .collect()
+    }
+
+    fn find(url: &str, canonicals: &BTreeMap<String, String>) -> Option<CanonicalIssue> {
+        let mut chain = vec![url.to_string()];
+        let mut current = url;
+        while let Some(next) = canonicals.get(current).filter(|next| next.as_str() != current) {
+            let is_loop = chain.contains(next);
+            chain.push(next.clone());
+            if is_loop {
+                return Some(CanonicalIssue { url: url.to_string(), kind: CanonicalIssueKind::Loop, chain });
+            }
+            current = next;
+        }
+        // a single hop is just a duplicate pointing to its canonical
+        if chain.len() > 2 {
+            Some(CanonicalIssue { url: url.to_string(), kind: CanonicalIssueKind::Chain, chain })
+        } else {
+            None
+        }
+    }
+
+    // pages are known by their requested and their final url
+    pub fn annotate(page_responses: &mut [PageResponse]) {
+        let mut canonicals = BTreeMap::new();
+        for page_response in page_responses.iter() {
+            if let Some(canonical_url) = &page_response.canonical_url {
+                canonicals.insert(page_response.original_requested_url.clone(), canonical_url.clone());
+                if let Some(final_url) = &page_response.final_url_after_redirects {
+                    canonicals.insert(final_url.clone(), canonical_url.clone());
+                }
+            }
+        }
+        for page_response in page_responses.iter_mut() {
+            page_response.canonical_issue = CanonicalIssue::find(&page_response.original_requested_url, &canonicals);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn page(url: &str, canonical_url: &str) -> PageResponse {
+        let mut page_response = PageResponse::new(String::from(url), String::from(url), Uuid::new_v4());
+        page_response.canonical_url = Some(String::from(canonical_url));
+        page_response
+    }
+
+    #[test]
+    fn reports_canonical_chains() {
+        // given: A declaring B as canonical, which declares C, which is canonical itself
+        let mut page_responses = vec![
+            page("https://example.com/a", "https://example.com/b"),
+            page("https://example.com/b", "https://example.com/c"),
+            page("https://example.com/c", "https://example.com/c"),
+        ];
+
+        // when: the pages are analyzed
+        CanonicalIssue::annotate(&mut page_responses);
+
+        // then: only A is part of a chain, B is an ordinary duplicate
+        assert_eq!(page_responses[0].canonical_issue, Some(CanonicalIssue {
+            url: String::from("https://example.com/a"),
+            kind: CanonicalIssueKind::Chain,
+            chain: vec![String::from("https://example.com/a"), String::from("https://example.com/b"), String::from("https://example.com/c")],
+        }));
+        assert_eq!(page_responses[1].canonical_issue, None, "A single hop is no chain");
+        assert_eq!(page_responses[2].canonical_issue, None, "Self-referencing canonicals are fine");
+    }
+
+    #[test]
+    fn flags_canonical_loops() {
+        // given: A and B declaring each other as canonical
+        let mut canonicals = BTreeMap::new();
+        canonicals.insert(String::from("https://example.com/a"), String::from("https://example.com/b"));
+        canonicals.insert(String::from("https://example.com/b"), String::from("https://example.com/a"));
+
+        // when: the canonicals are analyzed
+        let canonical_issues = CanonicalIssue::find_all(&canonicals);
+
+        // then: both pages are part of the loop
+        assert_eq!(canonical_issues.len(), 2, "Should flag both pages");
+        assert_eq!(canonical_issues[0].kind, CanonicalIssueKind::Loop);
+        assert_eq!(canonical_issues[0].chain, vec![String::from("https://example.com/a"), String::from("https://example.com/b"), String::from("https://example.com/a")]);
+    }
+}
@@ -0,0 +1,63 @@
+use crate::page_response::PageResponse;
+
+// renders a PageResponse as Common Log Format line, e.g.
+// - - - [10/Oct/2021:13:55:36 +0000] "GET https://example.com/ HTTP/1.1" 200 2326
+pub fn to_common_log_format(page_response: &PageResponse) -> Option<String> {
+    let (method, http_response_code, headers, body) = match (&page_response.get, &page_response.head) {
+        (Some(get), _) => ("GET", &get.http_response_code, &get.headers, get.body.as_ref()),
+        (None, Some(head)) => ("HEAD", &head.http_response_code, &head.headers, None),
+        _ => return None,
+    };
+    let url = page_response.final_url_after_redirects.as_ref().unwrap_or(&page_response.original_requested_url);
+    let timestamp = page_response.response_timings.start_time
+        .map_or(String::from("-"), |it| it.format("[%d/%b/%Y:%H:%M:%S %z]").to_string());
+    let bytes = headers.get("content-length").cloned()
+        .or_else(|| body.map(|it| it.len().to_string()))
+        .unwrap_or(String::from("-"));
+
+    Some(format!("- - - {} \"{} {} HTTP/1.1\" {} {}", timestamp, method, url, http_response_code.code, bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+    use uuid::Uuid;
+
+    use crate::get_response::GetResponse;
+    use crate::head_response::HeadResponse;
+    use crate::status_code::StatusCode;
+
+    use super::*;
+
+    #[test]
+    fn renders_page_response_as_common_log_format_line() {
+        // given: a crawled page
+        let mut page_response = PageResponse::new(String::from("https://example.com/"), String::from("/"), Uuid::new_v4());
+        page_response.response_timings.start_time = Some(DateTime::parse_from_rfc3339("2021-10-10T13:55:36Z").unwrap().with_timezone(&Utc));
+        page_response.final_url_after_redirects = Some(String::from("https://www.example.com/"));
+        page_response.head = Some(HeadResponse::new(String::from("https://example.com/"), StatusCode { code: 200, label: String::from("OK") }));
+        let mut get_response = GetResponse::new(String::from("https://www.example.com/"), StatusCode { code: 200, label: String::from("OK") });
+        get_response.headers.insert(String::from("content-length"), String::from("2326"));
+        page_response.get = Some(get_response);
+
+        // when: it is rendered
+        let result = to_common_log_format(&page_response);
+
+        // then: it matches the Common Log Format
+        assert_eq!(result.unwrap(), "- - - [10/Oct/2021:13:55:36 +0000] \"GET https://www.example.com/ HTTP/1.1\" 200 2326");
+    }
+
+    #[test]
+    fn renders_head_only_page_response_without_bytes() {
+        // given: a page that was not downloaded
+        let mut page_response = PageResponse::new(String::from("https://example.com/file.pdf"), String::from("/file.pdf"), Uuid::new_v4());
+        page_response.response_timings.start_time = Some(DateTime::parse_from_rfc3339("2021-10-10T13:55:36Z").unwrap().with_timezone(&Utc));
+        page_response.head = Some(HeadResponse::new(String::from("https://example.com/file.pdf"), StatusCode { code: 404, label: String::from("Not Found") }));
+
+        // when: it is rendered
+        let result = to_common_log_format(&page_response);
+
+        // then: it is rendered as HEAD request with unknown size
+        assert_eq!(result.unwrap(), "- - - [10/Oct/2021:13:55:36 +0000] \"HEAD https://example.com/file.pdf HTTP/1.1\" 404 -");
+    }
+}
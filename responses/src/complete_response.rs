@@ -1,7 +1,33 @@
 use serde::Serialize;
 use uuid::Uuid;
 
+use crate::broken_anchor::BrokenAnchor;
+use crate::canonical_issue::CanonicalIssue;
+use crate::crawl_status::CrawlStatus;
+use crate::crawl_times::CrawlTimes;
+use crate::duplicate_content::DuplicateContentCluster;
+use crate::phase_timings::PhaseTimings;
+use crate::robots_txt_summary::RobotsTxtSummary;
+use crate::slow_page::SlowPage;
+use crate::run_config::RunConfig;
+use crate::sitemap_audit::SitemapAudit;
+use crate::terminal_reason::TerminalReason;
+
 #[derive(Debug, Clone, Serialize)]
 pub struct CompleteResponse {
     pub uuid: Uuid,
+    pub crawl_status: Option<CrawlStatus>,
+    pub broken_anchors: Option<Vec<BrokenAnchor>>,
+    pub canonical_issues: Option<Vec<CanonicalIssue>>,
+    pub duplicate_content: Option<Vec<DuplicateContentCluster>>,
+    pub slowest_pages: Option<Vec<SlowPage>>,
+    pub phase_timings: Option<PhaseTimings>,
+    pub robots_txt: Option<RobotsTxtSummary>,
+    // only with robots_sitemap_audit, which doesn't crawl any pages
+    pub sitemap_audit: Option<SitemapAudit>,
+    pub terminal_reason: TerminalReason,
+    // reported pages, without canonical duplicates
+    pub page_count: usize,
+    pub crawl_times: CrawlTimes,
+    pub effective_config: Option<RunConfig>,
 }
\ No newline at end of file
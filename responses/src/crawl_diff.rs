@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::page_response::PageResponse;
+
+// the parts of a PageResponse, two crawls are compared by
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CrawledPage {
+    pub url: String,
+    pub http_response_code: Option<u16>,
+    pub redirect_target: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrawlDiffRequest {
+    pub previous: Vec<CrawledPage>,
+    pub current: Vec<CrawledPage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatusChange {
+    pub url: String,
+    pub previous: Option<u16>,
+    pub current: Option<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RedirectChange {
+    pub url: String,
+    pub previous: Option<String>,
+    pub current: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CrawlDiff {
+    pub added_urls: Vec<String>,
+    pub removed_urls: Vec<String>,
+    pub status_changes: Vec<StatusChange>,
+    pub redirect_changes: Vec<RedirectChange>,
+}
+
+impl From<&PageResponse> for CrawledPage {
+    fn from(page_response: &PageResponse) -> CrawledPage {
+        CrawledPage {
+            url: page_response.original_requested_url.clone(),
+            http_response_code: page_response.get.as_ref().map(|get| get.http_response_code.code)
+                .or_else(|| page_response.head.as_ref().map(|head| head.http_response_code.code)),
+            redirect_target: page_response.final_url_after_redirects.clone()
+                .filter(|final_url| final_url != &page_response.original_requested_url),
+        }
+    }
+}
+
+impl CrawlDiff {
+    pub fn from_page_responses(previous: &[PageResponse], current: &[PageResponse]) -> CrawlDiff {
+        let to_crawled_pages = |page_responses: &[PageResponse]| page_responses.iter().map(CrawledPage::from).collect::<Vec<CrawledPage>>();
+        CrawlDiff::between(&to_crawled_pages(previous), &to_crawled_pages(current))
+    }
+
+    // pages are keyed by their url, all lists are sorted by it
+    pub fn between(previous: &[CrawledPage], current: &[CrawledPage]) -> CrawlDiff {
+        let by_url = |pages: &[CrawledPage]| pages.iter().map(|page| (page.url.clone(), page.clone())).collect::<BTreeMap<String, CrawledPage>>();
+        let previous = by_url(previous);
+        let current = by_url(current);
+
+        let mut crawl_diff = CrawlDiff {
+            added_urls: current.keys().filter(|url| !previous.contains_key(*url)).cloned().collect(),
+            removed_urls: previous.keys().filter(|url| !current.contains_key(*url)).cloned().collect(),
+            status_changes: vec![],
+            redirect_changes: vec![],
+        };
+        for (url, previous_page) in previous.iter() {
+            let current_page = match current.get(url) {
+                Some(current_page) => current_page,
+                None => continue,
+            };
+            if previous_page.http_response_code != current_page.http_response_code {
+                crawl_diff.status_changes.push(StatusChange {
+                    url: url.clone(),
+                    previous: previous_page.http_response_code,
+                    current: current_page.http_response_code,
+                });
+            }
+            if previous_page.redirect_target != current_page.redirect_target {
+                crawl_diff.redirect_changes.push(RedirectChange {
+                    url: url.clone(),
+                    previous: previous_page.redirect_target.clone(),
+                    current: current_page.redirect_target.clone(),
+                });
+            }
+        }
+        crawl_diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::head_response::HeadResponse;
+    use crate::status_code::StatusCode;
+
+    use super::*;
+
+    fn page(url: &str, http_response_code: u16, final_url: &str) -> PageResponse {
+        let mut page_response = PageResponse::new(String::from(url), String::from(url), Uuid::new_v4());
+        page_response.head = Some(HeadResponse::new(String::from(url), StatusCode { code: http_response_code, label: String::from("") }));
+        page_response.final_url_after_redirects = Some(String::from(final_url));
+        page_response
+    }
+
+    #[test]
+    fn identifies_added_removed_and_changed_pages() {
+        // given: two crawls of the same site
+        let previous = vec![
+            page("https://example.com/", 200, "https://example.com/"),
+            page("https://example.com/old", 200, "https://example.com/old"),
+            page("https://example.com/flaky", 200, "https://example.com/flaky"),
+            page("https://example.com/moved", 301, "https://example.com/new-home"),
+        ];
+        let current = vec![
+            page("https://example.com/", 200, "https://example.com/"),
+            page("https://example.com/flaky", 500, "https://example.com/flaky"),
+            page("https://example.com/moved", 301, "https://example.com/other-home"),
+            page("https://example.com/fresh", 200, "https://example.com/fresh"),
+        ];
+
+        // when: they are diffed
+        let crawl_diff = CrawlDiff::from_page_responses(&previous, &current);
+
+        // then: every difference is reported once
+        assert_eq!(crawl_diff.added_urls, vec![String::from("https://example.com/fresh")]);
+        assert_eq!(crawl_diff.removed_urls, vec![String::from("https://example.com/old")]);
+        assert_eq!(crawl_diff.status_changes, vec![StatusChange { url: String::from("https://example.com/flaky"), previous: Some(200), current: Some(500) }]);
+        assert_eq!(crawl_diff.redirect_changes, vec![RedirectChange {
+            url: String::from("https://example.com/moved"),
+            previous: Some(String::from("https://example.com/new-home")),
+            current: Some(String::from("https://example.com/other-home")),
+        }]);
+    }
+
+    #[test]
+    fn identical_crawls_have_an_empty_diff() {
+        let crawl = vec![page("https://example.com/", 200, "https://example.com/")];
+
+        let crawl_diff = CrawlDiff::from_page_responses(&crawl, &crawl);
+
+        assert_eq!(crawl_diff.added_urls.is_empty() && crawl_diff.removed_urls.is_empty(), true, "Should neither add nor remove urls");
+        assert_eq!(crawl_diff.status_changes.is_empty() && crawl_diff.redirect_changes.is_empty(), true, "Should not report changes");
+    }
+}
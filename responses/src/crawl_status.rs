@@ -5,4 +5,26 @@ pub enum CrawlStatus {
     ConnectionError(String),
     RestrictedByRobotsTxt,
     MaximumCrawlDepthReached,
+    SeedUnreachable,
+    SeedUncrawlable(String),
+    HostLimitReached,
+    RequestBudgetExhausted,
+    ByteBudgetExhausted,
+    QueryVariantSkipped,
+    ParseSkipped,
+    ProtocolDowngrade,
+    // 2xx without any content, only with report_empty_body
+    EmptyBody,
+    // the page wasn't done within max_page_duration_ms
+    PageTimeout,
+    // a redirect to the very same url, only with collapse_identical_redirects
+    SelfRedirect,
+    // the chain stopped at a redirect to a url longer than max_url_length
+    RedirectUrlTooLong,
+    // the redirect chain ended at an already crawled url, only with skip_known_redirect_targets
+    RedirectToKnown,
+    // the url has more path segments than max_path_depth, regardless of its click distance
+    MaxPathDepthReached,
+    // the page declares another url as its canonical, only with dedup_by_canonical. It isn't counted as a page of its own
+    CanonicalDuplicate,
 }
@@ -0,0 +1,36 @@
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Serializer};
+use serde::ser::SerializeStruct;
+
+// when a crawl ran, from its CrawlDomainCommand to its completion
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrawlTimes {
+    pub crawl_started_at: DateTime<Utc>,
+    pub crawl_finished_at: DateTime<Utc>,
+    pub total_duration_ms: u64,
+}
+
+impl Serialize for CrawlTimes {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+    {
+        let mut s = serializer.serialize_struct("CrawlTimes", 3)?;
+        s.serialize_field("crawl_started_at", &self.crawl_started_at.to_string())?;
+        s.serialize_field("crawl_finished_at", &self.crawl_finished_at.to_string())?;
+        s.serialize_field("total_duration_ms", &self.total_duration_ms)?;
+        s.end()
+    }
+}
+
+impl CrawlTimes {
+    // finished now. A clock set back while crawling results in a duration of 0
+    pub fn finish(crawl_started_at: DateTime<Utc>) -> CrawlTimes {
+        let crawl_finished_at = Utc::now();
+        CrawlTimes {
+            crawl_started_at,
+            crawl_finished_at,
+            total_duration_ms: (crawl_finished_at - crawl_started_at).num_milliseconds().max(0) as u64,
+        }
+    }
+}
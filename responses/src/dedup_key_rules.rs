@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+use crate::url_fingerprint::UrlFingerprint;
+
+// how URLs are canonicalized, before checking whether they are already known.
+// the crawled URL itself is not changed by these rules
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DedupKeyRules {
+    // /page#top and /page are the same page
+    pub strip_fragment: bool,
+    // /page?a=1 and /page are the same page
+    pub ignore_query: bool,
+    // /page/ and /page are the same page
+    pub strip_trailing_slash: bool,
+    // /Page and /page are the same page. scheme and host are always compared case-insensitively
+    pub case_insensitive_path: bool,
+    // applied to the query, unless ignore_query drops it anyway
+    pub fingerprint: UrlFingerprint,
+}
+
+impl Default for DedupKeyRules {
+    fn default() -> DedupKeyRules {
+        DedupKeyRules {
+            strip_fragment: true,
+            ignore_query: false,
+            strip_trailing_slash: false,
+            case_insensitive_path: false,
+            fingerprint: UrlFingerprint::Exact,
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+// the device a crawl pretends to be. Responsive sites may serve other markup, and links, per device
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum DeviceProfile {
+    Desktop,
+    Mobile,
+}
+
+impl DeviceProfile {
+    pub fn get_user_agent(&self) -> &'static str {
+        match self {
+            DeviceProfile::Desktop => "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36",
+            DeviceProfile::Mobile => "Mozilla/5.0 (Linux; Android 10; K) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Mobile Safari/537.36",
+        }
+    }
+
+    // client hints, for sites picking the markup by viewport instead of user agent
+    pub fn get_headers(&self) -> Vec<(String, String)> {
+        let (mobile, platform, viewport_width) = match self {
+            DeviceProfile::Desktop => ("?0", "\"Windows\"", "1920"),
+            DeviceProfile::Mobile => ("?1", "\"Android\"", "412"),
+        };
+        vec![
+            (String::from("sec-ch-ua-mobile"), String::from(mobile)),
+            (String::from("sec-ch-ua-platform"), String::from(platform)),
+            (String::from("viewport-width"), String::from(viewport_width)),
+        ]
+    }
+}
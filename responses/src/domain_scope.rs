@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+// what counts as the crawled domain, which matters if the seed is a subdomain itself
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum DomainScope {
+    // exactly the host of the seed, e.g. blog.example.com
+    Host,
+    // the registrable domain (eTLD+1) of the seed, e.g. example.com for blog.example.com
+    RegistrableDomain,
+}
@@ -0,0 +1,55 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum DuplicateContentKind {
+    Title,
+    Description,
+}
+
+// pages sharing the very same title or meta description
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DuplicateContentCluster {
+    pub kind: DuplicateContentKind,
+    pub value: String,
+    pub urls: Vec<String>,
+}
+
+impl DuplicateContentCluster {
+    // values_by_url maps crawled pages to their title or description. Sorted by value, urls sorted as well
+    pub fn find_all(kind: DuplicateContentKind, values_by_url: &BTreeMap<String, String>) -> Vec<DuplicateContentCluster> {
+        let mut urls_by_value: BTreeMap<&String, Vec<String>> = BTreeMap::new();
+        for (url, value) in values_by_url {
+            urls_by_value.entry(value).or_default().push(url.clone());
+        }
+        urls_by_value.into_iter()
+            .filter(|(_, urls)| urls.len() > 1)
+            .map(|(value, urls)| DuplicateContentCluster { kind: kind.clone(), value: value.clone(), urls })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clusters_pages_sharing_a_value() {
+        // given: three pages, two of them sharing a title
+        let mut titles = BTreeMap::new();
+        titles.insert(String::from("https://example.com/b"), String::from("Shop"));
+        titles.insert(String::from("https://example.com/a"), String::from("Shop"));
+        titles.insert(String::from("https://example.com/c"), String::from("About us"));
+
+        // when: the titles are clustered
+        let clusters = DuplicateContentCluster::find_all(DuplicateContentKind::Title, &titles);
+
+        // then: only the shared title forms a cluster
+        assert_eq!(clusters, vec![DuplicateContentCluster {
+            kind: DuplicateContentKind::Title,
+            value: String::from("Shop"),
+            urls: vec![String::from("https://example.com/a"), String::from("https://example.com/b")],
+        }]);
+    }
+}
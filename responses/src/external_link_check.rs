@@ -0,0 +1,10 @@
+use serde::Serialize;
+
+use crate::status_code::StatusCode;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExternalLinkCheck {
+    pub uri: String,
+    pub http_response_code: Option<StatusCode>,
+    pub error: Option<String>,
+}
@@ -0,0 +1,83 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+// serializes only the selected fields. Nested fields are selected with dots, e.g. "head.http_response_code",
+// unknown fields are ignored. Without a selection, everything is serialized.
+pub fn to_json_with_fields<T: Serialize>(response: &T, response_fields: Option<&Vec<String>>) -> String {
+    let response_fields = match response_fields {
+        Some(response_fields) => response_fields,
+        None => return serde_json::to_string(response).unwrap(),
+    };
+    let paths: Vec<Vec<&str>> = response_fields.iter().map(|field| field.split('.').collect()).collect();
+    serde_json::to_string(&project(serde_json::to_value(response).unwrap(), &paths)).unwrap()
+}
+
+fn project(value: Value, paths: &[Vec<&str>]) -> Value {
+    let object = match value {
+        Value::Object(object) => object,
+        other => return other,
+    };
+    let mut projected = Map::new();
+    for (key, child) in object {
+        let child_paths: Vec<Vec<&str>> = paths.iter()
+            .filter(|path| path.first() == Some(&key.as_str()))
+            .map(|path| path[1..].to_vec())
+            .collect();
+        if child_paths.is_empty() {
+            continue;
+        }
+        if child_paths.iter().any(|path| path.is_empty()) {
+            projected.insert(key, child);
+        } else {
+            projected.insert(key, project(child, &child_paths));
+        }
+    }
+    Value::Object(projected)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    use crate::head_response::HeadResponse;
+    use crate::link::Link;
+    use crate::page_response::PageResponse;
+    use crate::status_code::StatusCode;
+
+    use super::*;
+
+    #[test]
+    fn projected_response_omits_unselected_fields() {
+        // given: a crawled page
+        let mut page_response = PageResponse::new(String::from("https://example.com/"), String::from("/"), Uuid::new_v4());
+        page_response.response_timings.end_time = Some(Utc::now());
+        let mut head_response = HeadResponse::new(String::from("https://example.com/"), StatusCode { code: 200, label: String::from("OK") });
+        head_response.response_timings.end_time = Some(Utc::now());
+        page_response.head = Some(head_response);
+        page_response.links = Some(vec![Link::from_str("/about")]);
+        let response_fields = vec![String::from("original_requested_url"), String::from("head.http_response_code"), String::from("links")];
+
+        // when: it is serialized with a field selection
+        let json: Value = serde_json::from_str(&to_json_with_fields(&page_response, Some(&response_fields))).unwrap();
+
+        // then: only the selected fields are present
+        let object = json.as_object().unwrap();
+        assert_eq!(object.len(), 3, "Should only contain the selected fields, but got {:?}", object.keys().collect::<Vec<_>>());
+        assert_eq!(json["original_requested_url"], "https://example.com/");
+        assert_eq!(json["links"].as_array().unwrap().len(), 1);
+        assert_eq!(json["head"].as_object().unwrap().len(), 1, "Should only contain the selected nested field");
+        assert_eq!(json["head"]["http_response_code"]["code"], 200);
+        assert_eq!(object.contains_key("get"), false, "Should omit unselected fields");
+    }
+
+    #[test]
+    fn serializes_everything_without_selection() {
+        let mut page_response = PageResponse::new(String::from("https://example.com/"), String::from("/"), Uuid::new_v4());
+        page_response.response_timings.end_time = Some(Utc::now());
+
+        let json = to_json_with_fields(&page_response, None);
+
+        assert_eq!(json, serde_json::to_string(&page_response).unwrap());
+    }
+}
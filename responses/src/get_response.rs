@@ -10,8 +10,12 @@ pub struct GetResponse {
     pub requested_url: String,
     pub http_response_code: StatusCode,
     pub headers: HashMap<String, String>,
+    // only the configured headers_of_interest, None if not configured
+    pub headers_of_interest: Option<HashMap<String, String>>,
     pub body: Option<String>,
     pub response_timings: ResponseTimings,
+    pub content_encoding: Option<String>,
+    pub x_robots_tag: Option<String>,
 }
 
 impl GetResponse {
@@ -20,8 +24,11 @@ impl GetResponse {
             requested_url: requested_url.clone(),
             http_response_code,
             headers: HashMap::new(),
+            headers_of_interest: None,
             body: None,
             response_timings: ResponseTimings::new(format!("GETResponse.{}", requested_url.clone())),
+            content_encoding: None,
+            x_robots_tag: None,
         }
     }
 }
\ No newline at end of file
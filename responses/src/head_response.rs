@@ -10,9 +10,18 @@ use crate::status_code::StatusCode;
 pub struct HeadResponse {
     pub requested_url: String,
     pub redirects: Vec<Redirect>,
+    // length of the whole chain, redirects may only hold its last hops
+    pub redirect_count: usize,
     pub http_response_code: StatusCode,
     pub headers: HashMap<String, String>,
+    // only the configured headers_of_interest, None if not configured
+    pub headers_of_interest: Option<HashMap<String, String>>,
     pub response_timings: ResponseTimings,
+    pub permanent_redirect: bool,
+    pub x_robots_tag: Option<String>,
+    pub protocol_downgrade: bool,
+    pub self_redirect: bool,
+    pub redirect_url_too_long: bool,
 }
 
 impl HeadResponse {
@@ -20,12 +29,47 @@ impl HeadResponse {
         HeadResponse {
             requested_url: requested_url.clone(),
             redirects: vec![],
+            redirect_count: 0,
             http_response_code,
             headers: HashMap::new(),
+            headers_of_interest: None,
             response_timings: ResponseTimings::new(format!("HEADResponse.{}", requested_url.clone())),
+            permanent_redirect: false,
+            x_robots_tag: None,
+            protocol_downgrade: false,
+            self_redirect: false,
+            redirect_url_too_long: false,
         }
     }
 
+    // a redirect chain is only permanent, if every single hop is
+    pub fn is_permanent_redirect_chain(redirects: &[Redirect]) -> bool {
+        !redirects.is_empty() && redirects.iter().all(|redirect| redirect.permanent)
+    }
+
+    // true, if any hop redirects from https to http
+    pub fn has_protocol_downgrade(redirects: &[Redirect]) -> bool {
+        redirects.iter().any(|redirect| redirect.source.starts_with("https://") && redirect.destination.starts_with("http://"))
+    }
+
+    // true, if any hop redirects to the url it was requested with
+    pub fn has_self_redirect(redirects: &[Redirect]) -> bool {
+        redirects.iter().any(|redirect| redirect.is_self_redirect())
+    }
+
+    // status of every reported hop in order, ending with the final status, e.g. [302, 301, 200]
+    pub fn get_hop_status_codes(&self) -> Vec<u16> {
+        self.redirects.iter()
+            .map(|redirect| redirect.http_response_code.code)
+            .chain(std::iter::once(self.http_response_code.code))
+            .collect()
+    }
+
+    // permanent and temporary hops in one chain leave search engines guessing which url to index
+    pub fn has_mixed_redirect_types(&self) -> bool {
+        self.redirects.iter().any(|redirect| redirect.permanent) && self.redirects.iter().any(|redirect| !redirect.permanent)
+    }
+
     pub fn get_final_uri(&self) -> String {
         if self.redirects.is_empty() {
             return self.requested_url.clone();
@@ -34,3 +78,44 @@ impl HeadResponse {
         self.redirects.last().unwrap().destination.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redirect(source: &str, destination: &str, code: u16) -> Redirect {
+        let mut redirect = Redirect::from(String::from(source), String::from(destination));
+        redirect.http_response_code = StatusCode { code, label: String::from("") };
+        redirect.permanent = Redirect::is_permanent_status_code(code);
+        redirect
+    }
+
+    #[test]
+    fn reports_the_status_of_each_hop() {
+        // given: a 302 followed by a 301, ending in a 200
+        let mut head_response = HeadResponse::new(String::from("https://example.com/a"), StatusCode { code: 200, label: String::from("OK") });
+        head_response.redirects = vec![
+            redirect("https://example.com/a", "https://example.com/b", 302),
+            redirect("https://example.com/b", "https://example.com/c", 301),
+        ];
+
+        // when: the hops are summarized
+        let hop_status_codes = head_response.get_hop_status_codes();
+
+        // then: each hop is listed in order, and the chain mixes redirect types
+        assert_eq!(hop_status_codes, vec![302, 301, 200]);
+        assert_eq!(head_response.has_mixed_redirect_types(), true, "Should flag the mix of temporary and permanent redirects");
+    }
+
+    #[test]
+    fn chains_of_one_redirect_type_are_not_mixed() {
+        let mut head_response = HeadResponse::new(String::from("https://example.com/a"), StatusCode { code: 200, label: String::from("OK") });
+        head_response.redirects = vec![
+            redirect("https://example.com/a", "https://example.com/b", 301),
+            redirect("https://example.com/b", "https://example.com/c", 308),
+        ];
+
+        assert_eq!(head_response.has_mixed_redirect_types(), false, "Should not flag permanent redirects only");
+        assert_eq!(HeadResponse::new(String::from("https://example.com/a"), StatusCode { code: 200, label: String::from("OK") }).get_hop_status_codes(), vec![200]);
+    }
+}
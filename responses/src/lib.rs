@@ -10,3 +10,33 @@ pub mod uri_scope;
 pub mod complete_response;
 pub mod run_config;
 pub mod crawl_status;
+pub mod body_preprocessor;
+pub mod resolve_response;
+pub mod broken_anchor;
+pub mod common_log_format;
+pub mod external_link_check;
+pub mod robots_fetch_timeout_policy;
+pub mod phase_timings;
+pub mod link_discovered_response;
+pub mod soft_limit;
+pub mod soft_limit_response;
+pub mod robots_txt_summary;
+pub mod sitemap_summary;
+pub mod robots_check_response;
+pub mod terminal_reason;
+pub mod field_selection;
+pub mod robots_error_policy;
+pub mod alternate_variant;
+pub mod tls_info;
+pub mod domain_scope;
+pub mod duplicate_content;
+pub mod crawl_diff;
+pub mod dedup_key_rules;
+pub mod canonical_issue;
+pub mod url_fingerprint;
+pub mod link_graph;
+pub mod device_profile;
+pub mod slow_page;
+pub mod crawl_times;
+pub mod url_normalization;
+pub mod sitemap_audit;
@@ -1,5 +1,6 @@
 use serde::Serialize;
 
+use crate::alternate_variant::AlternateVariant;
 use crate::uri_protocol::UriProtocol;
 use crate::uri_scope::UriScope;
 
@@ -9,6 +10,17 @@ pub struct Link {
     pub scope: Option<UriScope>,
     pub protocol: Option<UriProtocol>,
     pub source_tag: Option<String>,
+    // the href exactly as found in the markup, before trimming
+    pub raw_href: Option<String>,
+    pub text: Option<String>,
+    pub alternate: Option<AlternateVariant>,
+    pub rel: Option<String>,
+    pub extension: Option<String>,
+    pub fragment: Option<String>,
+    // appearance in the document, before links are sorted by uri. Only known for links found in the markup
+    pub dom_order: Option<usize>,
+    // number of enclosing elements, e.g. to tell nav links from deeply nested content links
+    pub dom_depth: Option<usize>,
 }
 
 impl Link {
@@ -18,15 +30,68 @@ impl Link {
             scope: None,
             protocol: None,
             source_tag: None,
+            raw_href: Some(s.to_string()),
+            text: None,
+            alternate: None,
+            rel: None,
+            extension: Link::get_extension(s),
+            fragment: Link::get_fragment(s),
+            dom_order: None,
+            dom_depth: None,
         }
     }
 
+    // lowercase extension of the last path segment, query and fragment are ignored
+    pub fn get_extension(uri: &str) -> Option<String> {
+        let without_fragment = uri.trim().split('#').next().unwrap_or("");
+        let without_query = without_fragment.split('?').next().unwrap_or("");
+        let path = match without_query.find("//") {
+            Some(authority_start) => without_query[authority_start + 2..].find('/').map_or("", |path_start| &without_query[authority_start + 2 + path_start..]),
+            None => without_query,
+        };
+        let last_segment = path.rsplit('/').next().unwrap_or("");
+        match last_segment.rfind('.') {
+            Some(dot) if dot > 0 && dot + 1 < last_segment.len() && last_segment[dot + 1..].chars().all(|c| c.is_ascii_alphanumeric()) => Some(last_segment[dot + 1..].to_lowercase()),
+            _ => None,
+        }
+    }
+
+    // the target of #fragment links, without the leading #. An empty fragment refers to no target
+    pub fn get_fragment(uri: &str) -> Option<String> {
+        uri.trim().split_once('#')
+            .map(|(_, fragment)| fragment.to_string())
+            .filter(|fragment| !fragment.is_empty())
+    }
+
+    // rel=next links lead to the next page of a paginated listing
+    pub fn is_pagination_next(&self) -> bool {
+        self.rel.as_ref().is_some_and(|rel| rel.split_whitespace().any(|it| it.eq_ignore_ascii_case("next")))
+    }
+
+    // <link rel="canonical"> names the preferred URL of duplicate content
+    pub fn is_canonical(&self) -> bool {
+        self.rel.as_ref().is_some_and(|rel| rel.split_whitespace().any(|it| it.eq_ignore_ascii_case("canonical")))
+    }
+
+    // <link rel="sitemap"> references a sitemap of the site, in addition to robots.txt
+    pub fn is_sitemap(&self) -> bool {
+        self.rel.as_ref().is_some_and(|rel| rel.split_whitespace().any(|it| it.eq_ignore_ascii_case("sitemap")))
+    }
+
     pub fn from_str_with_scope(s: &str, scope: Option<UriScope>) -> Link {
         Link {
             uri: s.trim().to_string(),
             scope,
             protocol: None,
             source_tag: None,
+            raw_href: Some(s.to_string()),
+            text: None,
+            alternate: None,
+            rel: None,
+            extension: Link::get_extension(s),
+            fragment: Link::get_fragment(s),
+            dom_order: None,
+            dom_depth: None,
         }
     }
 }
@@ -43,4 +108,18 @@ impl PartialEq for Link {
     fn ne(&self, other: &Self) -> bool {
         !self.eq(other)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extension_ignores_query_and_fragment() {
+        assert_eq!(Link::from_str("https://example.com/files/report.PDF?download=1#page=2").extension, Some(String::from("pdf")));
+        assert_eq!(Link::from_str("/styles/main.css").extension, Some(String::from("css")));
+        assert_eq!(Link::from_str("https://example.com/about").extension, None, "Paths without extension have none");
+        assert_eq!(Link::from_str("https://example.com").extension, None, "Hosts are no extensions");
+        assert_eq!(Link::from_str("/search?q=file.pdf").extension, None, "Extensions in queries don't count");
+    }
+}
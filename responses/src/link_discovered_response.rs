@@ -0,0 +1,11 @@
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::link::Link;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkDiscoveredResponse {
+    pub uuid: Uuid,
+    pub source_url: String,
+    pub link: Link,
+}
@@ -0,0 +1,117 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+// Directed graph of which page links to which url, rendered for visualization tools.
+// Urls can't be used as DOT or GraphML ids, so nodes get generated ids and carry their url as label.
+#[derive(Debug, Clone, Default)]
+pub struct LinkGraph {
+    edges: BTreeSet<(String, String)>,
+}
+
+impl LinkGraph {
+    pub fn new() -> LinkGraph {
+        LinkGraph::default()
+    }
+
+    pub fn add_edge(&mut self, source_url: &str, target_url: &str) {
+        self.edges.insert((source_url.to_string(), target_url.to_string()));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+
+    // n0, n1, ... in order of the urls
+    fn get_node_ids(&self) -> BTreeMap<&String, String> {
+        let urls: BTreeSet<&String> = self.edges.iter()
+            .flat_map(|(source_url, target_url)| [source_url, target_url])
+            .collect();
+        urls.into_iter()
+            .enumerate()
+            .map(|(index, url)| (url, format!("n{}", index)))
+            .collect()
+    }
+
+    pub fn to_dot(&self) -> String {
+        let node_ids = self.get_node_ids();
+        let mut dot = String::from("digraph crawl {\n");
+        for (url, node_id) in &node_ids {
+            dot.push_str(&format!("  {} [label=\"{}\"];\n", node_id, escape_dot(url)));
+        }
+        for (source_url, target_url) in &self.edges {
+            dot.push_str(&format!("  {} -> {};\n", node_ids[source_url], node_ids[target_url]));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_graphml(&self) -> String {
+        let node_ids = self.get_node_ids();
+        let mut graphml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        graphml.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        graphml.push_str("  <key id=\"url\" for=\"node\" attr.name=\"url\" attr.type=\"string\"/>\n");
+        graphml.push_str("  <graph id=\"crawl\" edgedefault=\"directed\">\n");
+        for (url, node_id) in &node_ids {
+            graphml.push_str(&format!("    <node id=\"{}\"><data key=\"url\">{}</data></node>\n", node_id, escape_xml(url)));
+        }
+        for (source_url, target_url) in &self.edges {
+            graphml.push_str(&format!("    <edge source=\"{}\" target=\"{}\"/>\n", node_ids[source_url], node_ids[target_url]));
+        }
+        graphml.push_str("  </graph>\n</graphml>\n");
+        graphml
+    }
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_small_crawl() -> LinkGraph {
+        let mut link_graph = LinkGraph::new();
+        link_graph.add_edge("https://example.com/", "https://example.com/about");
+        link_graph.add_edge("https://example.com/", "https://example.com/search?q=\"shoes\"&page=2");
+        link_graph.add_edge("https://example.com/about", "https://example.com/");
+        link_graph.add_edge("https://example.com/about", "https://example.com/");
+        link_graph
+    }
+
+    #[test]
+    fn renders_valid_dot() {
+        // given: a small crawl, with a url that isn't a valid DOT id
+        let link_graph = get_small_crawl();
+
+        // when: it's rendered as DOT
+        let dot = link_graph.to_dot();
+
+        // then: nodes use generated ids and carry their escaped url as label, duplicate links are a single edge
+        assert_eq!(dot, "digraph crawl {\n\
+            \x20 n0 [label=\"https://example.com/\"];\n\
+            \x20 n1 [label=\"https://example.com/about\"];\n\
+            \x20 n2 [label=\"https://example.com/search?q=\\\"shoes\\\"&page=2\"];\n\
+            \x20 n0 -> n1;\n\
+            \x20 n0 -> n2;\n\
+            \x20 n1 -> n0;\n\
+            }\n");
+    }
+
+    #[test]
+    fn renders_graphml_with_escaped_urls() {
+        let graphml = get_small_crawl().to_graphml();
+
+        assert_eq!(graphml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<graphml"), true, "Should be an xml document");
+        assert_eq!(graphml.contains("<node id=\"n2\"><data key=\"url\">https://example.com/search?q=&quot;shoes&quot;&amp;page=2</data></node>"), true, "Should escape the url");
+        assert_eq!(graphml.contains("<edge source=\"n1\" target=\"n0\"/>"), true, "Should contain the edges");
+        assert_eq!(graphml.matches("<edge ").count(), 3, "Should contain each edge once");
+    }
+}
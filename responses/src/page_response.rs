@@ -1,11 +1,16 @@
 use serde::Serialize;
 use uuid::Uuid;
 
+use crate::canonical_issue::CanonicalIssue;
 use crate::crawl_status::CrawlStatus;
+use crate::external_link_check::ExternalLinkCheck;
 use crate::get_response::GetResponse;
 use crate::head_response::HeadResponse;
 use crate::link::Link;
 use crate::response_timings::ResponseTimings;
+use crate::tls_info::TlsInfo;
+use crate::uri_scope::UriScope;
+use crate::url_normalization::UrlNormalization;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct PageResponse {
@@ -15,7 +20,33 @@ pub struct PageResponse {
     pub head: Option<HeadResponse>,
     pub get: Option<GetResponse>,
     pub links: Option<Vec<Link>>,
+    pub links_total: Option<usize>,
     pub crawl_status: Option<CrawlStatus>,
+    pub images_total: Option<usize>,
+    pub images_missing_alt: Option<usize>,
+    pub images_missing_dimensions: Option<usize>,
+    pub external_link_checks: Option<Vec<ExternalLinkCheck>>,
+    pub parse_timings: Option<ResponseTimings>,
+    pub noindex: bool,
+    pub nofollow: bool,
+    pub content_text_length: Option<usize>,
+    pub thin_content: bool,
+    // only set with slow_threshold_ms
+    pub slow: bool,
+    pub empty_body: bool,
+    pub language: Option<String>,
+    // media type of the final response, lowercase and without parameters, e.g. "application/pdf"
+    pub content_type: Option<String>,
+    pub title: Option<String>,
+    pub meta_description: Option<String>,
+    pub canonical_url: Option<String>,
+    // only set by CanonicalIssue::annotate, once all pages are known
+    pub canonical_issue: Option<CanonicalIssue>,
+    pub meta_refresh_url: Option<String>,
+    pub tls_info: Option<TlsInfo>,
+    pub depth: Option<u16>,
+    // only with record_normalizations, how the raw url became the crawled url and its dedup key
+    pub normalizations: Option<Vec<UrlNormalization>>,
     pub response_timings: ResponseTimings,
     pub task_uuid: Uuid,
 }
@@ -31,9 +62,116 @@ impl PageResponse {
             head: None,
             get: None,
             links: None,
+            links_total: None,
             crawl_status: None,
+            images_total: None,
+            images_missing_alt: None,
+            images_missing_dimensions: None,
+            external_link_checks: None,
+            parse_timings: None,
+            noindex: false,
+            nofollow: false,
+            content_text_length: None,
+            thin_content: false,
+            slow: false,
+            empty_body: false,
+            language: None,
+            content_type: None,
+            title: None,
+            meta_description: None,
+            canonical_url: None,
+            canonical_issue: None,
+            meta_refresh_url: None,
+            tls_info: None,
+            depth: None,
+            normalizations: None,
             response_timings,
             task_uuid: uuid,
         }
     }
+
+    // "type/*" matches all subtypes, e.g. "image/*". Pages without content type never match
+    pub fn has_content_type_of(&self, content_types: &[String]) -> bool {
+        let content_type = match &self.content_type {
+            Some(content_type) => content_type,
+            None => return false,
+        };
+        content_types.iter().any(|expected| match expected.strip_suffix("/*") {
+            Some(main_type) => content_type.split('/').next() == Some(main_type),
+            None => content_type == expected,
+        })
+    }
+
+    pub fn to_media_type(content_type: &str) -> Option<String> {
+        let media_type = content_type.split(';').next().unwrap_or_default().trim().to_lowercase();
+        if media_type.is_empty() { None } else { Some(media_type) }
+    }
+
+    pub fn external_links(&self) -> Vec<&Link> {
+        self.links.as_ref().map_or(vec![], |links| links.iter()
+            .filter(|link| link.scope == Some(UriScope::External))
+            .collect())
+    }
+
+    // the page declares another URL as the preferred one for its content
+    pub fn is_duplicate_of_canonical(&self) -> bool {
+        match &self.canonical_url {
+            Some(canonical_url) => canonical_url != &self.original_requested_url && Some(canonical_url) != self.final_url_after_redirects.as_ref(),
+            None => false,
+        }
+    }
+
+    // a stable order for consumers of the whole result, independent of the order pages were crawled in
+    pub fn sort_by_depth_and_url(page_responses: &mut [PageResponse]) {
+        page_responses.sort_by(|a, b| (a.depth, &a.original_requested_url).cmp(&(b.depth, &b.original_requested_url)));
+    }
+
+    // in-page navigation, like a table of contents
+    pub fn in_page_anchors(&self) -> Vec<&Link> {
+        self.links.as_ref().map_or(vec![], |links| links.iter()
+            .filter(|link| link.scope == Some(UriScope::Anchor))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn in_page_anchors_only_contains_anchor_links() {
+        let mut page_response = PageResponse::new(String::from("https://example.com"), String::from("https://example.com"), Uuid::new_v4());
+        page_response.links = Some(vec![
+            Link::from_str_with_scope("#installation", Some(UriScope::Anchor)),
+            Link::from_str_with_scope("/docs#usage", Some(UriScope::SameDomain)),
+        ]);
+
+        let in_page_anchors = page_response.in_page_anchors();
+
+        assert_eq!(in_page_anchors.len(), 1, "Should only contain the anchor link");
+        assert_eq!(in_page_anchors[0].fragment, Some(String::from("installation")));
+    }
+
+    #[test]
+    fn sorts_by_depth_then_url() {
+        // given: pages of a crawl in arrival order
+        let page = |url: &str, depth: u16| {
+            let mut page_response = PageResponse::new(String::from(url), String::from(url), Uuid::new_v4());
+            page_response.depth = Some(depth);
+            page_response
+        };
+        let mut page_responses = vec![
+            page("https://example.com/b", 1),
+            page("https://example.com/a/deep", 2),
+            page("https://example.com", 0),
+            page("https://example.com/a", 1),
+        ];
+
+        // when: they are sorted
+        PageResponse::sort_by_depth_and_url(&mut page_responses);
+
+        // then: shallower pages come first, pages of the same depth by url
+        let urls: Vec<&str> = page_responses.iter().map(|page_response| page_response.original_requested_url.as_str()).collect();
+        assert_eq!(urls, vec!["https://example.com", "https://example.com/a", "https://example.com/b", "https://example.com/a/deep"]);
+    }
 }
@@ -0,0 +1,119 @@
+use serde::Serialize;
+
+use crate::page_response::PageResponse;
+use crate::response_timings::ResponseTimings;
+
+// aggregated durations in milliseconds of all crawled pages, per phase
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PhaseTimings {
+    pub head_ms: i64,
+    pub redirects_ms: i64,
+    pub get_ms: i64,
+    pub parse_ms: i64,
+}
+
+impl PhaseTimings {
+    pub fn new() -> PhaseTimings {
+        PhaseTimings::default()
+    }
+
+    pub fn add_page_response(&mut self, page_response: &PageResponse) {
+        let phases = get_phases(page_response);
+        self.head_ms += phases[0].1;
+        self.redirects_ms += phases[1].1;
+        self.get_ms += phases[2].1;
+        self.parse_ms += phases[3].1;
+    }
+}
+
+// renders the phases of a single page in folded stack format, as consumed by flamegraph tooling, e.g.
+// tarantula;https://example.com/;head 12
+pub fn to_folded_stacks(page_response: &PageResponse) -> Vec<String> {
+    let url = page_response.final_url_after_redirects.as_ref().unwrap_or(&page_response.original_requested_url);
+    get_phases(page_response).iter()
+        .filter(|(_, duration_ms)| *duration_ms > 0)
+        // semicolons separate the frames, so they must not be part of the url
+        .map(|(phase, duration_ms)| format!("tarantula;{};{} {}", url.replace(';', "%3B"), phase, duration_ms))
+        .collect()
+}
+
+fn get_phases(page_response: &PageResponse) -> [(&'static str, i64); 4] {
+    let head_ms = page_response.head.as_ref().map_or(0, |head| get_duration_ms(&head.response_timings));
+    let redirects_ms = page_response.head.as_ref().map_or(0, |head| head.redirects.iter()
+        .map(|redirect| get_duration_ms(&redirect.response_timings))
+        .sum());
+    let get_ms = page_response.get.as_ref().map_or(0, |get| get_duration_ms(&get.response_timings));
+    let parse_ms = page_response.parse_timings.as_ref().map_or(0, get_duration_ms);
+    [("head", head_ms), ("redirects", redirects_ms), ("get", get_ms), ("parse", parse_ms)]
+}
+
+fn get_duration_ms(response_timings: &ResponseTimings) -> i64 {
+    match (response_timings.start_time, response_timings.end_time) {
+        (Some(start_time), Some(end_time)) => (end_time - start_time).num_milliseconds(),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, Utc};
+    use uuid::Uuid;
+
+    use crate::get_response::GetResponse;
+    use crate::head_response::HeadResponse;
+    use crate::redirect::Redirect;
+    use crate::status_code::StatusCode;
+
+    use super::*;
+
+    fn timings(duration_ms: i64) -> ResponseTimings {
+        let start_time = DateTime::parse_from_rfc3339("2021-10-10T13:55:36Z").unwrap().with_timezone(&Utc);
+        ResponseTimings::from(String::from("test"), start_time, start_time + Duration::milliseconds(duration_ms))
+    }
+
+    fn page_response(url: &str, head_ms: i64, redirect_ms: i64, get_ms: i64, parse_ms: i64) -> PageResponse {
+        let mut page_response = PageResponse::new(String::from(url), String::from(url), Uuid::new_v4());
+        let mut head = HeadResponse::new(String::from(url), StatusCode { code: 200, label: String::from("OK") });
+        head.response_timings = timings(head_ms);
+        let mut redirect = Redirect::from(String::from(url), String::from(url));
+        redirect.response_timings = timings(redirect_ms);
+        head.redirects.push(redirect);
+        page_response.head = Some(head);
+        let mut get = GetResponse::new(String::from(url), StatusCode { code: 200, label: String::from("OK") });
+        get.response_timings = timings(get_ms);
+        page_response.get = Some(get);
+        page_response.parse_timings = Some(timings(parse_ms));
+        page_response
+    }
+
+    #[test]
+    fn aggregates_phase_timings_of_all_pages() {
+        // given: pages with known timings
+        let first_page = page_response("https://example.com/", 10, 5, 100, 20);
+        let second_page = page_response("https://example.com/other", 30, 0, 200, 40);
+
+        // when: they are aggregated
+        let mut phase_timings = PhaseTimings::new();
+        phase_timings.add_page_response(&first_page);
+        phase_timings.add_page_response(&second_page);
+
+        // then: totals per phase match
+        assert_eq!(phase_timings, PhaseTimings { head_ms: 40, redirects_ms: 5, get_ms: 300, parse_ms: 60 });
+    }
+
+    #[test]
+    fn renders_folded_stacks_per_phase() {
+        // given: a page with known timings
+        let page = page_response("https://example.com/", 10, 0, 100, 20);
+
+        // when: it is rendered
+        let folded_stacks = to_folded_stacks(&page);
+
+        // then: each phase with a duration becomes a line
+        assert_eq!(folded_stacks, vec![
+            "tarantula;https://example.com/;head 10",
+            "tarantula;https://example.com/;get 100",
+            "tarantula;https://example.com/;parse 20",
+        ]);
+    }
+}
@@ -12,6 +12,7 @@ pub struct Redirect {
     pub http_response_code: StatusCode,
     pub headers: HashMap<String, String>,
     pub response_timings: ResponseTimings,
+    pub permanent: bool,
 }
 
 impl Redirect {
@@ -22,6 +23,31 @@ impl Redirect {
             http_response_code: StatusCode { code: 200, label: "OK".into() },
             headers: HashMap::new(),
             response_timings: ResponseTimings::new(format!("Redirects.{}", source)),
+            permanent: false,
+        }
+    }
+
+    pub fn is_self_redirect(&self) -> bool {
+        self.source == self.destination
+    }
+
+    // 301 and 308 are permanent, 302, 303 and 307 are temporary
+    pub fn is_permanent_status_code(code: u16) -> bool {
+        code == 301 || code == 308
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_permanent_and_temporary_redirects() {
+        for code in [301, 308] {
+            assert_eq!(Redirect::is_permanent_status_code(code), true, "{} should be permanent", code);
+        }
+        for code in [302, 303, 307] {
+            assert_eq!(Redirect::is_permanent_status_code(code), false, "{} should be temporary", code);
         }
     }
 }
@@ -0,0 +1,24 @@
+use serde::Serialize;
+
+use crate::head_response::HeadResponse;
+use crate::redirect::Redirect;
+use crate::status_code::StatusCode;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolveResponse {
+    pub requested_url: String,
+    pub final_url: String,
+    pub http_response_code: StatusCode,
+    pub redirects: Vec<Redirect>,
+}
+
+impl ResolveResponse {
+    pub fn from(requested_url: String, head_response: HeadResponse) -> ResolveResponse {
+        ResolveResponse {
+            requested_url,
+            final_url: head_response.get_final_uri(),
+            http_response_code: head_response.http_response_code,
+            redirects: head_response.redirects,
+        }
+    }
+}
@@ -0,0 +1,10 @@
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RobotsCheckResponse {
+    pub url: String,
+    pub user_agent: String,
+    pub robots_txt_url: String,
+    pub allowed: bool,
+    pub matching_rule: Option<String>,
+}
@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+// applied, if robots.txt can't be fetched: 4xx (except 401/403, which always disallow), 5xx or connection errors
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum RobotsErrorPolicy {
+    // crawl as if there was no robots.txt
+    AllowAll,
+    // don't crawl anything on that host
+    DisallowAll,
+}
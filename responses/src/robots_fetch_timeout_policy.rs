@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum RobotsFetchTimeoutPolicy {
+    // crawl as if there was no robots.txt
+    AllowAll,
+    // don't crawl anything on that host
+    DisallowAll,
+}
@@ -0,0 +1,18 @@
+use serde::Serialize;
+
+use crate::sitemap_summary::SitemapSummary;
+
+#[derive(Debug, Clone, Serialize, Eq, PartialEq)]
+pub enum RobotsTxtStatus {
+    NotFetched,
+    Parsed,
+    AllowAll,
+    DisallowAll,
+}
+
+#[derive(Debug, Clone, Serialize, Eq, PartialEq)]
+pub struct RobotsTxtSummary {
+    pub url: Option<String>,
+    pub status: RobotsTxtStatus,
+    pub sitemaps: Vec<SitemapSummary>,
+}
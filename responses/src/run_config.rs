@@ -1,5 +1,12 @@
 use serde::{Deserialize, Serialize};
 
+use crate::body_preprocessor::BodyPreprocessor;
+use crate::dedup_key_rules::DedupKeyRules;
+use crate::device_profile::DeviceProfile;
+use crate::domain_scope::DomainScope;
+use crate::robots_error_policy::RobotsErrorPolicy;
+use crate::robots_fetch_timeout_policy::RobotsFetchTimeoutPolicy;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RunConfig {
     pub url: String,
@@ -13,6 +20,101 @@ pub struct RunConfig {
     pub callback_url: Option<String>,
     pub callback_url_finished: Option<String>,
     pub crawl_delay_ms: Option<usize>,
+    pub extract_image_stats: Option<bool>,
+    pub seed_retries: Option<u8>,
+    pub seed_retry_backoff_ms: Option<u64>,
+    pub max_hosts: Option<usize>,
+    pub body_preprocessors: Option<Vec<BodyPreprocessor>>,
+    pub strip_session_ids: Option<bool>,
+    pub session_query_params: Option<Vec<String>>,
+    pub max_requests: Option<usize>,
+    pub max_total_bytes: Option<u64>,
+    pub detect_broken_anchors: Option<bool>,
+    pub ignore_query_variants_of_known_paths: Option<bool>,
+    pub common_log_file: Option<String>,
+    // seeds the draws from the jitter window, requires jitter_min_ms or jitter_max_ms
+    pub rng_seed: Option<u64>,
+    pub assume_html_when_missing_content_type: Option<bool>,
+    pub sniff_content_type: Option<bool>,
+    pub check_external_links: Option<bool>,
+    pub robots_fetch_timeout_ms: Option<u64>,
+    pub robots_fetch_timeout_policy: Option<RobotsFetchTimeoutPolicy>,
+    pub report_phase_timings: Option<bool>,
+    pub folded_timings_file: Option<String>,
+    pub emit_link_events: Option<bool>,
+    pub deterministic: Option<bool>,
+    pub max_parse_ms: Option<u64>,
+    pub accept_encoding: Option<String>,
+    pub soft_limit_percent: Option<u8>,
+    pub emit_soft_limit_events: Option<bool>,
+    pub pool_idle_timeout_ms: Option<u64>,
+    pub pool_max_idle_per_host: Option<usize>,
+    pub report_robots_txt: Option<bool>,
+    pub approximate_dedup: Option<bool>,
+    pub collapse_trailing_slash_redirects: Option<bool>,
+    pub max_link_text_len: Option<usize>,
+    pub respect_nofollow: Option<bool>,
+    pub global_requests_per_sec: Option<f64>,
+    pub seed_known_links: Option<Vec<String>>,
+    pub recrawl_only_failed: Option<Vec<String>>,
+    pub strip_default_ports: Option<bool>,
+    pub max_duration_ms: Option<u64>,
+    pub max_page_duration_ms: Option<usize>,
+    pub response_fields: Option<Vec<String>>,
+    pub robots_on_error: Option<RobotsErrorPolicy>,
+    pub skip_alternate_variants: Option<bool>,
+    pub asset_crawl_delay_ms: Option<usize>,
+    pub asset_max_concurrent_requests: Option<usize>,
+    pub include_effective_config: Option<bool>,
+    pub extract_json_ld_links: Option<bool>,
+    pub thin_content_threshold: Option<usize>,
+    pub pagination_no_depth: Option<bool>,
+    pub max_reported_redirects: Option<usize>,
+    pub skip_extensions: Option<Vec<String>>,
+    pub only_extensions: Option<Vec<String>>,
+    pub allowed_subdomains: Option<Vec<String>>,
+    pub external_check_hosts: Option<Vec<String>>,
+    pub warmup: Option<bool>,
+    pub capture_tls_info: Option<bool>,
+    pub max_stored_links: Option<usize>,
+    pub domain_scope: Option<DomainScope>,
+    pub skip_head_request: Option<bool>,
+    pub dedup_by_canonical: Option<bool>,
+    pub dedup_key_rules: Option<DedupKeyRules>,
+    pub report_empty_body: Option<bool>,
+    pub follow_meta_refresh: Option<bool>,
+    pub link_attributes: Option<Vec<String>>,
+    pub include_path_patterns: Option<Vec<String>>,
+    pub exclude_path_patterns: Option<Vec<String>>,
+    pub path_patterns_case_insensitive: Option<bool>,
+    pub path_patterns_trailing_slash_tolerant: Option<bool>,
+    pub check_assets: Option<bool>,
+    pub retryable_status_codes: Option<Vec<u16>>,
+    pub collapse_identical_redirects: Option<bool>,
+    pub homepage_plus_one: Option<bool>,
+    pub detect_canonical_issues: Option<bool>,
+    pub headers_of_interest: Option<Vec<String>>,
+    pub keep_all_headers: Option<bool>,
+    pub follow_sitemaps: Option<bool>,
+    pub detect_duplicate_content: Option<bool>,
+    pub jitter_min_ms: Option<usize>,
+    pub jitter_max_ms: Option<usize>,
+    pub max_requests_per_url: Option<usize>,
+    pub collect_link_graph: Option<bool>,
+    pub max_url_length: Option<usize>,
+    pub device_profile: Option<DeviceProfile>,
+    pub extract_microdata_links: Option<bool>,
+    pub slow_threshold_ms: Option<u64>,
+    pub report_content_types: Option<Vec<String>>,
+    pub strict_rate_limit: Option<bool>,
+    pub sort_results: Option<bool>,
+    pub collapse_root_query: Option<bool>,
+    pub skip_known_redirect_targets: Option<bool>,
+    pub heuristic_link_extraction: Option<bool>,
+    pub max_path_depth: Option<usize>,
+    pub record_normalizations: Option<bool>,
+    pub robots_sitemap_audit: Option<bool>,
+    pub database_url: Option<String>,
 }
 
 impl RunConfig {
@@ -29,6 +131,100 @@ impl RunConfig {
             callback_url,
             callback_url_finished: None,
             crawl_delay_ms: Some(500),
+            extract_image_stats: Some(false),
+            seed_retries: Some(0),
+            seed_retry_backoff_ms: Some(1000),
+            max_hosts: None,
+            body_preprocessors: None,
+            strip_session_ids: Some(true),
+            session_query_params: None,
+            max_requests: None,
+            max_total_bytes: None,
+            detect_broken_anchors: Some(false),
+            ignore_query_variants_of_known_paths: Some(false),
+            common_log_file: None,
+            rng_seed: None,
+            assume_html_when_missing_content_type: Some(false),
+            sniff_content_type: Some(false),
+            check_external_links: Some(false),
+            robots_fetch_timeout_ms: Some(5000),
+            robots_fetch_timeout_policy: Some(RobotsFetchTimeoutPolicy::AllowAll),
+            report_phase_timings: Some(false),
+            folded_timings_file: None,
+            emit_link_events: Some(false),
+            deterministic: Some(false),
+            max_parse_ms: None,
+            accept_encoding: Some(String::from("gzip, deflate, br")),
+            soft_limit_percent: Some(90),
+            emit_soft_limit_events: Some(false),
+            pool_idle_timeout_ms: Some(90000),
+            pool_max_idle_per_host: Some(32),
+            report_robots_txt: Some(false),
+            approximate_dedup: Some(false),
+            collapse_trailing_slash_redirects: Some(false),
+            max_link_text_len: Some(256),
+            respect_nofollow: Some(false),
+            global_requests_per_sec: None,
+            seed_known_links: None,
+            recrawl_only_failed: None,
+            strip_default_ports: Some(true),
+            max_duration_ms: None,
+            max_page_duration_ms: None,
+            response_fields: None,
+            robots_on_error: Some(RobotsErrorPolicy::AllowAll),
+            skip_alternate_variants: Some(false),
+            asset_crawl_delay_ms: None,
+            asset_max_concurrent_requests: None,
+            include_effective_config: Some(false),
+            extract_json_ld_links: Some(false),
+            thin_content_threshold: None,
+            pagination_no_depth: Some(false),
+            max_reported_redirects: None,
+            skip_extensions: None,
+            only_extensions: None,
+            allowed_subdomains: None,
+            external_check_hosts: None,
+            warmup: Some(false),
+            capture_tls_info: Some(false),
+            max_stored_links: None,
+            domain_scope: Some(DomainScope::Host),
+            skip_head_request: Some(false),
+            dedup_by_canonical: Some(false),
+            dedup_key_rules: Some(DedupKeyRules::default()),
+            report_empty_body: Some(false),
+            follow_meta_refresh: Some(false),
+            link_attributes: None,
+            include_path_patterns: None,
+            exclude_path_patterns: None,
+            path_patterns_case_insensitive: Some(true),
+            path_patterns_trailing_slash_tolerant: Some(true),
+            check_assets: Some(true),
+            retryable_status_codes: None,
+            collapse_identical_redirects: Some(false),
+            homepage_plus_one: Some(false),
+            detect_canonical_issues: Some(false),
+            headers_of_interest: None,
+            keep_all_headers: Some(true),
+            follow_sitemaps: Some(false),
+            detect_duplicate_content: Some(false),
+            jitter_min_ms: None,
+            jitter_max_ms: None,
+            max_requests_per_url: None,
+            collect_link_graph: Some(false),
+            max_url_length: None,
+            device_profile: None,
+            extract_microdata_links: Some(false),
+            slow_threshold_ms: None,
+            report_content_types: None,
+            strict_rate_limit: Some(false),
+            sort_results: Some(false),
+            collapse_root_query: Some(false),
+            skip_known_redirect_targets: Some(false),
+            heuristic_link_extraction: Some(false),
+            max_path_depth: None,
+            record_normalizations: Some(false),
+            robots_sitemap_audit: Some(false),
+            database_url: None,
         }
     }
 }
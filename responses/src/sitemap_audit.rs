@@ -0,0 +1,20 @@
+use serde::Serialize;
+
+use crate::sitemap_summary::SitemapSummary;
+
+// result of robots_sitemap_audit: inconsistencies between robots.txt, the sitemaps and the links of the seed
+#[derive(Debug, Clone, Serialize, Eq, PartialEq)]
+pub struct SitemapAudit {
+    pub robots_txt_url: Option<String>,
+    pub sitemaps: Vec<SitemapSummary>,
+    // listed in a sitemap, but disallowed by robots.txt
+    pub disallowed_sitemap_urls: Vec<String>,
+    // listed in a sitemap, but answered with 404 or 410
+    pub not_found_sitemap_urls: Vec<String>,
+    // linked by the seed and allowed by robots.txt, but in none of the sitemaps
+    pub unlisted_urls: Vec<String>,
+    // sitemaps and listed urls on other hosts than the seed and its robots.txt, they aren't requested
+    pub foreign_urls: Vec<String>,
+    // the audit stopped at its maximum number of sitemaps or sitemap urls
+    pub truncated: bool,
+}
@@ -0,0 +1,8 @@
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize, Eq, PartialEq)]
+pub struct SitemapSummary {
+    pub url: String,
+    // None, as long as the sitemap itself hasn't been fetched
+    pub url_count: Option<usize>,
+}
@@ -0,0 +1,26 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+// only the slowest pages are reported, a slow server would otherwise flood the completion summary
+pub const MAX_REPORTED_SLOW_PAGES: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SlowPage {
+    pub url: String,
+    // from the start of the head request to the end of the page's download
+    pub response_time_ms: u64,
+}
+
+impl SlowPage {
+    // response_times_ms maps slow pages to their response time. Slowest first, equally slow pages sorted by url
+    pub fn find_slowest(response_times_ms: &BTreeMap<String, u64>) -> Vec<SlowPage> {
+        let mut slow_pages: Vec<SlowPage> = response_times_ms.iter()
+            .map(|(url, response_time_ms)| SlowPage { url: url.clone(), response_time_ms: *response_time_ms })
+            .collect();
+        slow_pages.sort_by_key(|slow_page| Reverse(slow_page.response_time_ms));
+        slow_pages.truncate(MAX_REPORTED_SLOW_PAGES);
+        slow_pages
+    }
+}
@@ -0,0 +1,7 @@
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, Serialize, Eq, PartialEq, Hash)]
+pub enum SoftLimit {
+    MaximumDepth,
+    MaxRequests,
+}
@@ -0,0 +1,12 @@
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::soft_limit::SoftLimit;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SoftLimitResponse {
+    pub uuid: Uuid,
+    pub soft_limit: SoftLimit,
+    pub current: usize,
+    pub maximum: usize,
+}
@@ -0,0 +1,10 @@
+use serde::Serialize;
+
+// why a crawl ended, reported with every CompleteEvent
+#[derive(Debug, Clone, Serialize, Eq, PartialEq)]
+pub enum TerminalReason {
+    Completed,
+    Cancelled,
+    TimedOut,
+    BudgetExhausted,
+}
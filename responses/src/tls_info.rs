@@ -0,0 +1,9 @@
+use serde::Serialize;
+
+// certificate details of the TLS connection to a host. Names are formatted as comma separated "key=value" pairs
+#[derive(Debug, Clone, Serialize, Eq, PartialEq)]
+pub struct TlsInfo {
+    pub subject: Option<String>,
+    pub issuer: Option<String>,
+    pub not_after: Option<String>,
+}
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+// which part of the query is part of a URL's dedup key
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum UrlFingerprint {
+    // the query as is, /page?b=2&a=1 and /page?a=1&b=2 are different pages
+    Exact,
+    // all query variants of a path are the same page
+    PathOnly,
+    // only the listed params count, sorted, so tracking params and their order don't matter
+    PathPlusSortedQueryWhitelist(Vec<String>),
+}
@@ -0,0 +1,17 @@
+use serde::Serialize;
+
+// a step that changed a URL on its way from the raw href to its dedup key, see record_normalizations
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum UrlNormalization {
+    // the href was resolved against its page, incl. stripping session ids and default ports, if enabled
+    Resolved,
+    // scheme and host were lowercased or the default port was removed
+    OriginCanonicalized,
+    PathLowercased,
+    TrailingSlashStripped,
+    QueryIgnored,
+    // params not in the fingerprint's whitelist were dropped
+    QueryParamsDropped,
+    QuerySorted,
+    FragmentStripped,
+}
@@ -0,0 +1,80 @@
+use sqlx::any::{AnyKind, AnyPool, AnyPoolOptions};
+use uuid::Uuid;
+
+use responses::page_response::PageResponse;
+
+const CREATE_CRAWLS_TABLE: &str = "CREATE TABLE IF NOT EXISTS crawls (\
+    uuid VARCHAR(36) PRIMARY KEY, \
+    url TEXT NOT NULL)";
+const CREATE_PAGES_TABLE: &str = "CREATE TABLE IF NOT EXISTS pages (\
+    crawl_uuid VARCHAR(36) NOT NULL REFERENCES crawls (uuid), \
+    url TEXT NOT NULL, \
+    final_url TEXT, \
+    status_code INTEGER, \
+    depth INTEGER, \
+    start_time TEXT, \
+    end_time TEXT, \
+    duration_ms BIGINT)";
+
+// Writes the pages of a single crawl to a SQL database, creating the schema if absent
+pub struct DatabaseSink {
+    pool: AnyPool,
+    crawl_uuid: Uuid,
+}
+
+impl DatabaseSink {
+    pub async fn connect(database_url: &str, crawl_uuid: Uuid, crawl_url: &str) -> Result<DatabaseSink, sqlx::Error> {
+        // a single connection keeps the pages in order and works with in-memory sqlite, too
+        let pool = AnyPoolOptions::new()
+            .max_connections(1)
+            .connect(database_url).await?;
+        sqlx::query(CREATE_CRAWLS_TABLE).execute(&pool).await?;
+        sqlx::query(CREATE_PAGES_TABLE).execute(&pool).await?;
+
+        let insert_crawl = format!("INSERT INTO crawls (uuid, url) VALUES ({})", placeholders(pool.any_kind(), 2));
+        sqlx::query(&insert_crawl)
+            .bind(crawl_uuid.to_string())
+            .bind(crawl_url.to_string())
+            .execute(&pool).await?;
+
+        Ok(DatabaseSink { pool, crawl_uuid })
+    }
+
+    pub async fn insert_page(&self, page_response: &PageResponse) -> Result<(), sqlx::Error> {
+        let status_code = page_response.get.as_ref().map(|get| get.http_response_code.code)
+            .or(page_response.head.as_ref().map(|head| head.http_response_code.code));
+        let timings = &page_response.response_timings;
+        let duration_ms = match (timings.start_time, timings.end_time) {
+            (Some(start_time), Some(end_time)) => Some((end_time - start_time).num_milliseconds()),
+            _ => None,
+        };
+
+        let insert_page = format!("INSERT INTO pages (crawl_uuid, url, final_url, status_code, depth, start_time, end_time, duration_ms) VALUES ({})", placeholders(self.pool.any_kind(), 8));
+        sqlx::query(&insert_page)
+            .bind(self.crawl_uuid.to_string())
+            .bind(page_response.original_requested_url.clone())
+            .bind(page_response.final_url_after_redirects.clone())
+            .bind(status_code.map(|code| code as i32))
+            .bind(page_response.depth.map(|depth| depth as i32))
+            .bind(timings.start_time.map(|start_time| start_time.to_rfc3339()))
+            .bind(timings.end_time.map(|end_time| end_time.to_rfc3339()))
+            .bind(duration_ms)
+            .execute(&self.pool).await?;
+        Ok(())
+    }
+
+    pub fn get_pool(&self) -> &AnyPool {
+        &self.pool
+    }
+}
+
+// the any driver passes queries on as they are, so each database needs its own placeholder syntax
+fn placeholders(any_kind: AnyKind, count: usize) -> String {
+    (1..=count)
+        .map(|index| match any_kind {
+            AnyKind::Postgres => format!("${}", index),
+            _ => String::from("?"),
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
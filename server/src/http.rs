@@ -1,34 +1,283 @@
+use std::collections::{HashMap, VecDeque};
+use std::env;
 use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use hyper::{Body, Client, Request};
+use hyper::client::HttpConnector;
 use hyper_tls::HttpsConnector;
 use rocket::{Build, Rocket, State, tokio};
+use rocket::http::{ContentType, Header, Status};
+use rocket::tokio::fs::OpenOptions;
+use rocket::tokio::io::AsyncWriteExt;
 use rocket::response::status;
 use rocket::serde::json::Json;
 use rocket::tokio::sync::mpsc;
 use rocket::tokio::sync::mpsc::Sender;
 use uuid::Uuid;
 
+use linkresult::uri_service::resolve_reference;
+use page_loader::commands::fetch_header_command::{DefaultFetchHeaderCommand, FetchHeaderCommand};
 use page_loader::events::crawler_event::CrawlerEvent;
 use page_loader::page_loader_service::PageLoaderServiceCommand;
-use page_loader::page_loader_service::PageLoaderServiceCommand::CrawlDomainCommand;
+use page_loader::page_loader_service::PageLoaderServiceCommand::{CancelCrawlCommand, CrawlDomainCommand, UpdateCrawlDelayCommand};
+use page_loader::task_context::robots_service::{RobotsService, RobotsTxt, RobotsTxtInit};
+use page_loader::task_context::task_context::{DefaultTaskContext, TaskContext, TaskContextInit, TaskContextServices};
+use responses::common_log_format::to_common_log_format;
 use responses::complete_response::CompleteResponse;
+use responses::crawl_diff::{CrawlDiff, CrawlDiffRequest};
+use responses::field_selection::to_json_with_fields;
+use responses::link_discovered_response::LinkDiscoveredResponse;
+use responses::link_graph::LinkGraph;
+use responses::page_response::PageResponse;
+use responses::phase_timings::{PhaseTimings, to_folded_stacks};
+use responses::resolve_response::ResolveResponse;
+use responses::robots_check_response::RobotsCheckResponse;
 use responses::run_config::RunConfig;
+use responses::soft_limit_response::SoftLimitResponse;
+use responses::uri_scope::UriScope;
+
+#[cfg(feature = "database")]
+use crate::database_sink::DatabaseSink;
+
+pub const MAX_CONCURRENT_CRAWLS_ENV: &str = "TARANTULA_MAX_CONCURRENT_CRAWLS";
+// files requested by a crawl are only ever written to this directory, without it they are rejected
+pub const OUTPUT_DIR_ENV: &str = "TARANTULA_OUTPUT_DIR";
+const RETRY_AFTER_SECONDS: u64 = 60;
+// link graphs are kept in memory, beyond this number the graph of the oldest crawl is dropped
+pub const MAX_STORED_LINK_GRAPHS: usize = 100;
+
+// crawls are counted from the /crawl request until their CompleteEvent has been processed. The task manager of the
+// page loader service can't be used for this: it lives behind the command channel and only adds a task once the
+// CrawlDomainCommand has been received, so concurrent requests would all pass a check against it
+pub struct CrawlCapacity {
+    max_concurrent_crawls: Option<usize>,
+    running_crawls: Arc<AtomicUsize>,
+}
+
+// releases the slot of a crawl when dropped, also when processing the crawl panics, e.g. on a failing callback
+struct CrawlSlot {
+    running_crawls: Arc<AtomicUsize>,
+}
+
+impl Drop for CrawlSlot {
+    fn drop(&mut self) {
+        self.running_crawls.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// link graphs of crawls with collect_link_graph, kept after completion for the graph endpoints
+#[derive(Default)]
+pub struct CrawlGraphs {
+    link_graphs: Arc<Mutex<StoredLinkGraphs>>,
+}
+
+#[derive(Default)]
+struct StoredLinkGraphs {
+    link_graphs: HashMap<Uuid, LinkGraph>,
+    insertion_order: VecDeque<Uuid>,
+}
+
+impl StoredLinkGraphs {
+    fn insert(&mut self, task_context_uuid: Uuid, link_graph: LinkGraph) {
+        while self.insertion_order.len() >= MAX_STORED_LINK_GRAPHS {
+            if let Some(oldest_uuid) = self.insertion_order.pop_front() {
+                self.link_graphs.remove(&oldest_uuid);
+            }
+        }
+        self.insertion_order.push_back(task_context_uuid);
+        self.link_graphs.insert(task_context_uuid, link_graph);
+    }
+}
+
+#[derive(Responder)]
+#[response(status = 429)]
+pub struct TooManyCrawls {
+    message: String,
+    retry_after: Header<'static>,
+}
+
+#[derive(Responder)]
+pub enum CrawlRejected {
+    TooManyCrawls(TooManyCrawls),
+    InvalidRunConfig(status::Custom<String>),
+}
+
+pub struct OutputDir(Option<PathBuf>);
 
 pub fn rocket(page_loader_tx_channel: Sender<PageLoaderServiceCommand>) -> Rocket<Build> {
+    let max_concurrent_crawls = env::var(MAX_CONCURRENT_CRAWLS_ENV).ok()
+        .and_then(|value| value.parse::<usize>().ok());
+    rocket_with_max_concurrent_crawls(page_loader_tx_channel, max_concurrent_crawls)
+}
+
+pub fn rocket_with_max_concurrent_crawls(page_loader_tx_channel: Sender<PageLoaderServiceCommand>, max_concurrent_crawls: Option<usize>) -> Rocket<Build> {
+    let output_dir = env::var(OUTPUT_DIR_ENV).ok().map(PathBuf::from);
+    rocket_with_output_dir(page_loader_tx_channel, max_concurrent_crawls, output_dir)
+}
+
+pub fn rocket_with_output_dir(page_loader_tx_channel: Sender<PageLoaderServiceCommand>, max_concurrent_crawls: Option<usize>, output_dir: Option<PathBuf>) -> Rocket<Build> {
     rocket::build()
-        .mount("/", routes![crawl])
+        .mount("/", routes![crawl, cancel_crawl, update_crawl_rate, resolve, robots_check, crawl_diff, crawl_graph_dot, crawl_graph_graphml])
         .manage(page_loader_tx_channel)
+        .manage(CrawlGraphs::default())
+        .manage(CrawlCapacity { max_concurrent_crawls, running_crawls: Arc::new(AtomicUsize::new(0)) })
+        .manage(OutputDir(output_dir))
 }
 
 #[put("/crawl", data = "<run_config>")]
-pub fn crawl(run_config: Json<RunConfig>, page_loader_tx_channel: &State<Sender<PageLoaderServiceCommand>>) -> status::Accepted<String> {
+pub fn crawl(run_config: Json<RunConfig>, page_loader_tx_channel: &State<Sender<PageLoaderServiceCommand>>, crawl_capacity: &State<CrawlCapacity>, crawl_graphs: &State<CrawlGraphs>, output_dir: &State<OutputDir>) -> Result<status::Accepted<String>, CrawlRejected> {
+    let common_log_file = match run_config.common_log_file.as_ref().map(|file_name| resolve_output_file(output_dir.0.as_deref(), file_name)).transpose() {
+        Ok(common_log_file) => common_log_file,
+        Err(error) => return Err(CrawlRejected::InvalidRunConfig(status::Custom(Status::BadRequest, format!("Invalid common_log_file: {}", error)))),
+    };
+    let folded_timings_file = match run_config.folded_timings_file.as_ref().map(|file_name| resolve_output_file(output_dir.0.as_deref(), file_name)).transpose() {
+        Ok(folded_timings_file) => folded_timings_file,
+        Err(error) => return Err(CrawlRejected::InvalidRunConfig(status::Custom(Status::BadRequest, format!("Invalid folded_timings_file: {}", error)))),
+    };
+    // the seed only applies to delays drawn from a jitter window, which strict_rate_limit rules out
+    let has_jitter_window = (run_config.jitter_min_ms.is_some() || run_config.jitter_max_ms.is_some()) && !run_config.strict_rate_limit.unwrap_or(false);
+    if run_config.rng_seed.is_some() && !has_jitter_window {
+        return Err(CrawlRejected::InvalidRunConfig(status::Custom(Status::BadRequest, String::from("Invalid rng_seed: requires a jitter window"))));
+    }
+
+    let max_concurrent_crawls = crawl_capacity.max_concurrent_crawls.unwrap_or(usize::MAX);
+    let has_capacity = crawl_capacity.running_crawls
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |running_crawls| if running_crawls < max_concurrent_crawls { Some(running_crawls + 1) } else { None })
+        .is_ok();
+    if !has_capacity {
+        return Err(CrawlRejected::TooManyCrawls(TooManyCrawls {
+            message: format!("Maximum of {} concurrent crawls reached", max_concurrent_crawls),
+            retry_after: Header::new("Retry-After", RETRY_AFTER_SECONDS.to_string()),
+        }));
+    }
+
     let task_context_uuid = Uuid::new_v4();
-    tokio::spawn(process(run_config.0, task_context_uuid.clone(), page_loader_tx_channel.deref().clone()));
-    status::Accepted(Some(format!("{}", task_context_uuid)))
+    let crawl_slot = CrawlSlot { running_crawls: crawl_capacity.running_crawls.clone() };
+    let page_loader_tx_channel = page_loader_tx_channel.deref().clone();
+    let link_graphs = if run_config.collect_link_graph.unwrap_or(false) {
+        crawl_graphs.link_graphs.lock().unwrap().insert(task_context_uuid, LinkGraph::new());
+        Some(crawl_graphs.link_graphs.clone())
+    } else {
+        None
+    };
+    tokio::spawn(async move {
+        let _crawl_slot = crawl_slot;
+        process(run_config.0, task_context_uuid.clone(), page_loader_tx_channel, link_graphs, common_log_file, folded_timings_file).await;
+    });
+    Ok(status::Accepted(Some(format!("{}", task_context_uuid))))
 }
 
-async fn process(run_config: RunConfig, task_context_uuid: Uuid, page_loader_tx_channel: Sender<PageLoaderServiceCommand>) {
+#[delete("/crawl/<uuid>")]
+pub async fn cancel_crawl(uuid: String, page_loader_tx_channel: &State<Sender<PageLoaderServiceCommand>>) -> Result<status::Accepted<String>, status::Custom<String>> {
+    let task_context_uuid = match Uuid::parse_str(&uuid) {
+        Ok(task_context_uuid) => task_context_uuid,
+        Err(_) => return Err(status::Custom(Status::BadRequest, format!("Invalid uuid: {}", uuid))),
+    };
+    if page_loader_tx_channel.send(CancelCrawlCommand { task_context_uuid }).await.is_err() {
+        return Err(status::Custom(Status::InternalServerError, String::from("Page loader unavailable")));
+    }
+    Ok(status::Accepted(Some(uuid)))
+}
+
+#[patch("/crawl/<uuid>/rate?<delay_ms>")]
+pub async fn update_crawl_rate(uuid: String, delay_ms: usize, page_loader_tx_channel: &State<Sender<PageLoaderServiceCommand>>) -> Result<status::Accepted<String>, status::Custom<String>> {
+    let task_context_uuid = match Uuid::parse_str(&uuid) {
+        Ok(task_context_uuid) => task_context_uuid,
+        Err(_) => return Err(status::Custom(Status::BadRequest, format!("Invalid uuid: {}", uuid))),
+    };
+    if page_loader_tx_channel.send(UpdateCrawlDelayCommand { task_context_uuid, crawl_delay_ms: delay_ms }).await.is_err() {
+        return Err(status::Custom(Status::InternalServerError, String::from("Page loader unavailable")));
+    }
+    Ok(status::Accepted(Some(uuid)))
+}
+
+#[get("/resolve?<url>")]
+pub async fn resolve(url: String) -> Result<Json<ResolveResponse>, status::Custom<String>> {
+    let is_valid_url = url.parse::<hyper::Uri>()
+        .map_or(false, |uri| uri.scheme_str().is_some() && uri.host().is_some());
+    if !is_valid_url {
+        return Err(status::Custom(Status::BadRequest, format!("Invalid url: {}", url)));
+    }
+
+    let (resp_tx, _) = mpsc::channel(1);
+    let task_context = DefaultTaskContext::init(RunConfig::new(url.clone(), None), Uuid::new_v4(), resp_tx);
+    let config = task_context.get_config().lock().unwrap().clone();
+    let fetch_header_response = DefaultFetchHeaderCommand::from_config(&config).fetch_header(
+        url.clone(),
+        config.ignore_redirects,
+        config.maximum_redirects,
+        task_context.get_uri_service(),
+        task_context.get_http_client(),
+        None,
+        config.robots_txt_info_url.clone(),
+    ).await;
+
+    match fetch_header_response {
+        Ok((head_response, _)) => Ok(Json(ResolveResponse::from(url, head_response))),
+        Err(error) => Err(status::Custom(Status::BadGateway, error)),
+    }
+}
+
+#[get("/robots-check?<url>&<agent>")]
+pub async fn robots_check(url: String, agent: Option<String>) -> Result<Json<RobotsCheckResponse>, status::Custom<String>> {
+    let robots_txt_uri = match url.parse::<hyper::Uri>().ok().and_then(|uri| RobotsService::get_robots_txt_uri(&uri)) {
+        Some(robots_txt_uri) => robots_txt_uri,
+        None => return Err(status::Custom(Status::BadRequest, format!("Invalid url: {}", url))),
+    };
+    let robots_txt_url = robots_txt_uri.to_string();
+    let user_agent = agent.unwrap_or_else(|| RunConfig::new(url.clone(), None).user_agent.unwrap());
+
+    let mut robots_service = RobotsService::new(user_agent.clone());
+    robots_service.init(robots_txt_uri).await;
+
+    Ok(Json(RobotsCheckResponse {
+        allowed: robots_service.can_access(&url),
+        matching_rule: robots_service.get_matching_rule(&url),
+        url,
+        user_agent,
+        robots_txt_url,
+    }))
+}
+
+#[get("/crawl/<uuid>/graph.dot")]
+pub fn crawl_graph_dot(uuid: String, crawl_graphs: &State<CrawlGraphs>) -> Option<(ContentType, String)> {
+    let link_graph = get_link_graph(&uuid, crawl_graphs)?;
+    Some((ContentType::new("text", "vnd.graphviz"), link_graph.to_dot()))
+}
+
+#[get("/crawl/<uuid>/graph.graphml")]
+pub fn crawl_graph_graphml(uuid: String, crawl_graphs: &State<CrawlGraphs>) -> Option<(ContentType, String)> {
+    let link_graph = get_link_graph(&uuid, crawl_graphs)?;
+    Some((ContentType::XML, link_graph.to_graphml()))
+}
+
+// crawls without collect_link_graph have no graph, just like unknown crawls
+fn get_link_graph(uuid: &str, crawl_graphs: &CrawlGraphs) -> Option<LinkGraph> {
+    let task_context_uuid = Uuid::parse_str(uuid).ok()?;
+    crawl_graphs.link_graphs.lock().unwrap().link_graphs.get(&task_context_uuid).cloned()
+}
+
+// compares the results of two completed crawls, keyed by url
+#[post("/crawl-diff", data = "<crawl_diff_request>")]
+pub fn crawl_diff(crawl_diff_request: Json<CrawlDiffRequest>) -> Json<CrawlDiff> {
+    Json(CrawlDiff::between(&crawl_diff_request.previous, &crawl_diff_request.current))
+}
+
+// a file of the RunConfig is only a name within the output directory of the server, never a path
+fn resolve_output_file(output_dir: Option<&Path>, file_name: &str) -> Result<PathBuf, String> {
+    let output_dir = output_dir.ok_or_else(|| format!("{} isn't set on the server", OUTPUT_DIR_ENV))?;
+    let is_plain_file_name = !file_name.contains('/') && !file_name.contains('\\')
+        && Path::new(file_name).file_name().map_or(false, |name| name == file_name);
+    if !is_plain_file_name {
+        return Err(format!("{} is no plain file name", file_name));
+    }
+    Ok(output_dir.join(file_name))
+}
+
+async fn process(run_config: RunConfig, task_context_uuid: Uuid, page_loader_tx_channel: Sender<PageLoaderServiceCommand>, link_graphs: Option<Arc<Mutex<StoredLinkGraphs>>>, common_log_file: Option<PathBuf>, folded_timings_file: Option<PathBuf>) {
     let num_cpus = num_cpus::get();
     let (resp_tx, mut resp_rx) = mpsc::channel(num_cpus * 2);
     if let Ok(_) = page_loader_tx_channel.send(CrawlDomainCommand {
@@ -41,26 +290,65 @@ async fn process(run_config: RunConfig, task_context_uuid: Uuid, page_loader_tx_
         let client = Client::builder().build::<_, hyper::Body>(connector);
 
         drop(page_loader_tx_channel);
+        let database_sink = connect_database_sink(&run_config, task_context_uuid).await;
         let mut responses = 0;
         let mut callback_url = run_config.callback_url.clone();
+        let mut phase_timings = PhaseTimings::new();
+        // sorted results are held back until the crawl completes
+        let sort_results = run_config.sort_results.unwrap_or(false);
+        let mut sorted_page_responses = vec![];
         while let Some(event) = resp_rx.recv().await {
             let payload: String;
             let do_break: bool;
             match event {
                 CrawlerEvent::PageEvent { page_response } => {
-                    let page_response_json = rocket::serde::json::serde_json::to_string(&page_response).unwrap();
                     info!("Received from threads - PageEvent: {:?}, numLinks: {}", page_response.final_url_after_redirects.as_ref(), page_response.links.as_ref().unwrap_or(&vec![]).len());
                     responses = responses + 1;
                     info!(". -> {}", responses);
+                    if let Some(common_log_file) = common_log_file.as_ref() {
+                        append_lines(common_log_file, to_common_log_format(&page_response).into_iter().collect()).await;
+                    }
+                    if let Some(folded_timings_file) = folded_timings_file.as_ref() {
+                        append_lines(folded_timings_file, to_folded_stacks(&page_response)).await;
+                    }
+                    phase_timings.add_page_response(&page_response);
+                    // the graph may have been dropped already, in favor of newer crawls
+                    if let Some(link_graphs) = link_graphs.as_ref() {
+                        if let Some(link_graph) = link_graphs.lock().unwrap().link_graphs.get_mut(&task_context_uuid) {
+                            add_to_link_graph(link_graph, &page_response);
+                        }
+                    }
+                    write_to_database_sink(&database_sink, &page_response).await;
 
-                    payload = page_response_json;
+                    if sort_results {
+                        sorted_page_responses.push(page_response);
+                        continue;
+                    }
+                    payload = to_json_with_fields(&page_response, run_config.response_fields.as_ref());
                     drop(page_response);
                     do_break = false;
                 }
-                CrawlerEvent::CompleteEvent { uuid } => {
-                    let complete_response = CompleteResponse { uuid };
+                CrawlerEvent::LinkDiscoveredEvent { uuid, source_url, link } => {
+                    let link_discovered_response = LinkDiscoveredResponse { uuid, source_url, link };
+                    payload = rocket::serde::json::serde_json::to_string(&link_discovered_response).unwrap();
+                    do_break = false;
+                }
+                CrawlerEvent::SoftLimitEvent { uuid, soft_limit, current, maximum } => {
+                    let soft_limit_response = SoftLimitResponse { uuid, soft_limit, current, maximum };
+                    payload = rocket::serde::json::serde_json::to_string(&soft_limit_response).unwrap();
+                    do_break = false;
+                }
+                CrawlerEvent::CompleteEvent { uuid, crawl_status, broken_anchors, canonical_issues, duplicate_content, slowest_pages, robots_txt, sitemap_audit, terminal_reason, page_count, crawl_times, effective_config } => {
+                    let phase_timings = if run_config.report_phase_timings.unwrap_or(false) { Some(phase_timings.clone()) } else { None };
+                    let complete_response = CompleteResponse { uuid, crawl_status, broken_anchors, canonical_issues, duplicate_content, slowest_pages, phase_timings, robots_txt, sitemap_audit, terminal_reason, page_count, crawl_times, effective_config };
                     info!("Received from threads - CompleteEvent: {:?}", complete_response);
                     payload = rocket::serde::json::serde_json::to_string(&complete_response).unwrap();
+                    PageResponse::sort_by_depth_and_url(&mut sorted_page_responses);
+                    for page_response in sorted_page_responses.drain(..) {
+                        if let Some(callback_url_unwrapped) = callback_url.as_ref() {
+                            post_to_callback(&client, &run_config, callback_url_unwrapped, to_json_with_fields(&page_response, run_config.response_fields.as_ref())).await;
+                        }
+                    }
                     callback_url = run_config.callback_url_finished.clone();
 
                     drop(complete_response);
@@ -69,13 +357,7 @@ async fn process(run_config: RunConfig, task_context_uuid: Uuid, page_loader_tx_
             }
 
             if let Some(callback_url_unwrapped) = callback_url.as_ref() {
-                let req = Request::builder()
-                    .header("user-agent", run_config.user_agent.as_ref().unwrap().clone())
-                    .method("POST")
-                    .uri(callback_url_unwrapped)
-                    .body(Body::from(payload))
-                    .expect(&format!("POST request builder"));
-                client.request(req).await.expect("Couldn't send request to callback");
+                post_to_callback(&client, &run_config, callback_url_unwrapped, payload).await;
             } else {
                 drop(payload);
             }
@@ -92,6 +374,74 @@ async fn process(run_config: RunConfig, task_context_uuid: Uuid, page_loader_tx_
     info!("Finished crawl.");
 }
 
+async fn post_to_callback(client: &Client<HttpsConnector<HttpConnector>>, run_config: &RunConfig, callback_url: &str, payload: String) {
+    let req = Request::builder()
+        .header("user-agent", run_config.user_agent.as_ref().unwrap().clone())
+        .method("POST")
+        .uri(callback_url)
+        .body(Body::from(payload))
+        .expect(&format!("POST request builder"));
+    client.request(req).await.expect("Couldn't send request to callback");
+}
+
+// links are reported as found in the markup, so they are resolved against the page. In-page anchors aren't edges
+fn add_to_link_graph(link_graph: &mut LinkGraph, page_response: &PageResponse) {
+    let source_url = page_response.final_url_after_redirects.as_ref().unwrap_or(&page_response.original_requested_url);
+    for link in page_response.links.iter().flatten().filter(|link| link.scope != Some(UriScope::Anchor)) {
+        let target_url = resolve_reference(source_url, &link.uri);
+        let target_url = target_url.split('#').next().unwrap_or("");
+        if target_url.starts_with("http://") || target_url.starts_with("https://") {
+            link_graph.add_edge(source_url, target_url);
+        }
+    }
+}
+
+#[cfg(feature = "database")]
+async fn connect_database_sink(run_config: &RunConfig, task_context_uuid: Uuid) -> Option<DatabaseSink> {
+    let database_url = run_config.database_url.as_ref()?;
+    match DatabaseSink::connect(database_url, task_context_uuid, &run_config.url).await {
+        Ok(database_sink) => Some(database_sink),
+        Err(error) => {
+            error!("Couldn't connect to database of task {}, error: {}", task_context_uuid, error);
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "database"))]
+async fn connect_database_sink(run_config: &RunConfig, task_context_uuid: Uuid) -> Option<()> {
+    if run_config.database_url.is_some() {
+        warn!("database_url of task {} is ignored, the server was built without the database feature", task_context_uuid);
+    }
+    None
+}
+
+#[cfg(feature = "database")]
+async fn write_to_database_sink(database_sink: &Option<DatabaseSink>, page_response: &PageResponse) {
+    if let Some(database_sink) = database_sink {
+        if let Err(error) = database_sink.insert_page(page_response).await {
+            error!("Couldn't write {} to database, error: {}", page_response.original_requested_url, error);
+        }
+    }
+}
+
+#[cfg(not(feature = "database"))]
+async fn write_to_database_sink(_database_sink: &Option<()>, _page_response: &PageResponse) {}
+
+async fn append_lines(file_path: &Path, lines: Vec<String>) {
+    if lines.is_empty() {
+        return;
+    }
+    let content = lines.iter().map(|line| format!("{}\n", line)).collect::<String>();
+    let file = OpenOptions::new().create(true).append(true).open(file_path).await;
+    if let Err(error) = match file {
+        Ok(mut file) => file.write_all(content.as_bytes()).await,
+        Err(error) => Err(error),
+    } {
+        error!("Couldn't write to file {}, error: {}", file_path.display(), error);
+    }
+}
+
 // use rocket_contrib::json::{Json, JsonError};
 // use rocket_contrib::json::JsonValue;
 //
@@ -1,3 +1,5 @@
 #[macro_use] extern crate rocket;
 
+#[cfg(feature = "database")]
+pub mod database_sink;
 pub mod http;
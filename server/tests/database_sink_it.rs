@@ -0,0 +1,35 @@
+#![cfg(feature = "database")]
+
+use uuid::Uuid;
+
+use responses::page_response::PageResponse;
+use server::database_sink::DatabaseSink;
+
+#[rocket::async_test]
+async fn pages_of_a_crawl_are_inserted() {
+    // given: a sink writing to an in-memory sqlite database
+    let crawl_uuid = Uuid::new_v4();
+    let sink = DatabaseSink::connect("sqlite::memory:", crawl_uuid, "https://example.com").await.unwrap();
+    let create_page_response = |url: &str, depth: u16| {
+        let mut page_response = PageResponse::new(String::from(url), String::from(url), crawl_uuid);
+        page_response.depth = Some(depth);
+        page_response.response_timings.end_time = page_response.response_timings.start_time;
+        page_response
+    };
+
+    // when: the pages of a stubbed crawl are written
+    sink.insert_page(&create_page_response("https://example.com", 0)).await.unwrap();
+    sink.insert_page(&create_page_response("https://example.com/inner", 1)).await.unwrap();
+
+    // then: one row per page is inserted, referencing the crawl
+    let page_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM pages WHERE crawl_uuid = ?")
+        .bind(crawl_uuid.to_string())
+        .fetch_one(sink.get_pool()).await.unwrap();
+    assert_eq!(page_count, 2, "Should have inserted both pages");
+    let max_depth: i32 = sqlx::query_scalar("SELECT MAX(depth) FROM pages")
+        .fetch_one(sink.get_pool()).await.unwrap();
+    assert_eq!(max_depth, 1, "Should store the depth");
+    let crawl_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM crawls")
+        .fetch_one(sink.get_pool()).await.unwrap();
+    assert_eq!(crawl_count, 1, "Should have inserted the crawl");
+}
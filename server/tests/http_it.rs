@@ -1,3 +1,11 @@
+use std::convert::Infallible;
+use std::fs::read_to_string;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use hyper::{Body, Request, Response, Server};
+use hyper::service::{make_service_fn, service_fn};
 use rocket::http::Status;
 use rocket::local::asynchronous::Client;
 use rocket::serde::json::serde_json;
@@ -19,4 +27,365 @@ async fn valid_request_responses_with_task_uuid() {
     let response_body = response.into_string().await.unwrap();
     println!("{:?}", response_body);
     assert_eq!(response_body.len(), 36);
-}
\ No newline at end of file
+}
+
+#[rocket::async_test]
+async fn crawl_requests_beyond_max_concurrent_crawls_are_rejected() {
+    // given: a server accepting a single concurrent crawl
+    let page_loader_tx_channel = PageLoaderService::init();
+    let rocket = server::http::rocket_with_max_concurrent_crawls(page_loader_tx_channel, Some(1));
+    let client = Client::tracked(rocket).await.unwrap();
+    let task = serde_json::to_string(&RunConfig::new("https://foo".into(), None)).unwrap();
+
+    // when: two crawls are started
+    let first_response = client.put("/crawl").body(&task).dispatch().await;
+    let second_response = client.put("/crawl").body(&task).dispatch().await;
+
+    // then: the crawl under the cap is accepted, the one beyond is rejected
+    assert_eq!(first_response.status().code, Status::Accepted.code);
+    assert_eq!(second_response.status().code, Status::TooManyRequests.code);
+    assert_eq!(second_response.headers().get_one("Retry-After"), Some("60"), "Should tell clients when to retry");
+}
+
+#[rocket::async_test]
+async fn crawl_slot_is_released_when_the_callback_fails() {
+    // given: a server accepting a single concurrent crawl, with a crawl whose callback can't be reached
+    let page_loader_tx_channel = PageLoaderService::init();
+    let rocket = server::http::rocket_with_max_concurrent_crawls(page_loader_tx_channel, Some(1));
+    let client = Client::tracked(rocket).await.unwrap();
+    let failing_task = serde_json::to_string(&RunConfig::new("http://127.0.0.1:1/".into(), Some("http://127.0.0.1:1/callback".into()))).unwrap();
+    let task = serde_json::to_string(&RunConfig::new("https://foo".into(), None)).unwrap();
+    let first_response = client.put("/crawl").body(&failing_task).dispatch().await;
+    assert_eq!(first_response.status().code, Status::Accepted.code);
+
+    // when: another crawl is started after the failing one is done
+    let mut second_response_code = Status::TooManyRequests.code;
+    for _ in 0..100 {
+        rocket::tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        second_response_code = client.put("/crawl").body(&task).dispatch().await.status().code;
+        if second_response_code != Status::TooManyRequests.code {
+            break;
+        }
+    }
+
+    // then: the slot of the failed crawl has been released
+    assert_eq!(second_response_code, Status::Accepted.code, "Should release the slot of a panicked crawl");
+}
+
+#[rocket::async_test]
+async fn common_log_file_is_only_accepted_as_file_name_within_the_output_dir() {
+    // given: a server with an output directory and one without
+    let output_dir = std::env::temp_dir();
+    let client = Client::tracked(server::http::rocket_with_output_dir(PageLoaderService::init(), None, Some(output_dir))).await.unwrap();
+    let client_without_output_dir = Client::tracked(server::http::rocket_with_output_dir(PageLoaderService::init(), None, None)).await.unwrap();
+    let task_with_common_log_file = |common_log_file: &str| {
+        let mut task = RunConfig::new("https://foo".into(), None);
+        task.common_log_file = Some(String::from(common_log_file));
+        serde_json::to_string(&task).unwrap()
+    };
+
+    // when: crawls with different common log files are requested
+    let plain_response = client.put("/crawl").body(task_with_common_log_file("crawl.log")).dispatch().await;
+    let traversing_response = client.put("/crawl").body(task_with_common_log_file("../crawl.log")).dispatch().await;
+    let absolute_response = client.put("/crawl").body(task_with_common_log_file("/root/.ssh/authorized_keys")).dispatch().await;
+    let parent_response = client.put("/crawl").body(task_with_common_log_file("..")).dispatch().await;
+    let without_output_dir_response = client_without_output_dir.put("/crawl").body(task_with_common_log_file("crawl.log")).dispatch().await;
+
+    // then: only plain file names within the output directory are accepted
+    assert_eq!(plain_response.status().code, Status::Accepted.code);
+    assert_eq!(traversing_response.status().code, Status::BadRequest.code, "Should reject path traversal");
+    assert_eq!(absolute_response.status().code, Status::BadRequest.code, "Should reject absolute paths");
+    assert_eq!(parent_response.status().code, Status::BadRequest.code, "Should reject the parent directory");
+    assert_eq!(without_output_dir_response.status().code, Status::BadRequest.code, "Should reject files without output directory");
+}
+
+#[rocket::async_test]
+async fn folded_timings_file_is_only_accepted_as_file_name_within_the_output_dir() {
+    // given: a server with an output directory
+    let client = Client::tracked(server::http::rocket_with_output_dir(PageLoaderService::init(), None, Some(std::env::temp_dir()))).await.unwrap();
+    let task_with_folded_timings_file = |folded_timings_file: &str| {
+        let mut task = RunConfig::new("https://foo".into(), None);
+        task.folded_timings_file = Some(String::from(folded_timings_file));
+        serde_json::to_string(&task).unwrap()
+    };
+
+    // when: crawls with a plain and a traversing file name are requested
+    let plain_response = client.put("/crawl").body(task_with_folded_timings_file("timings.folded")).dispatch().await;
+    let traversing_response = client.put("/crawl").body(task_with_folded_timings_file("../../etc/cron.d/timings")).dispatch().await;
+
+    // then: only the plain file name is accepted
+    assert_eq!(plain_response.status().code, Status::Accepted.code);
+    assert_eq!(traversing_response.status().code, Status::BadRequest.code, "Should reject path traversal");
+}
+
+#[rocket::async_test]
+async fn rng_seed_is_only_accepted_with_a_jitter_window() {
+    // given: a server
+    let client = Client::tracked(server::http::rocket(PageLoaderService::init())).await.unwrap();
+    let task_with_rng_seed = |jitter_max_ms: Option<usize>, strict_rate_limit: bool| {
+        let mut task = RunConfig::new("https://foo".into(), None);
+        task.rng_seed = Some(42);
+        task.jitter_max_ms = jitter_max_ms;
+        task.strict_rate_limit = Some(strict_rate_limit);
+        serde_json::to_string(&task).unwrap()
+    };
+
+    // when: seeded crawls with and without jitter window are requested
+    let with_window_response = client.put("/crawl").body(task_with_rng_seed(Some(500), false)).dispatch().await;
+    let without_window_response = client.put("/crawl").body(task_with_rng_seed(None, false)).dispatch().await;
+    let strict_response = client.put("/crawl").body(task_with_rng_seed(Some(500), true)).dispatch().await;
+
+    // then: the seed is only accepted, if there is a window to draw delays from
+    assert_eq!(with_window_response.status().code, Status::Accepted.code);
+    assert_eq!(without_window_response.status().code, Status::BadRequest.code, "Should reject a seed without jitter window");
+    assert_eq!(strict_response.status().code, Status::BadRequest.code, "Should reject a seed with strict rate limit");
+}
+
+#[rocket::async_test]
+async fn link_graph_is_only_served_for_crawls_collecting_it() {
+    // given: a crawl collecting its link graph and one that doesn't
+    let page_loader_tx_channel = PageLoaderService::init();
+    let rocket = server::http::rocket(page_loader_tx_channel);
+    let client = Client::tracked(rocket).await.unwrap();
+    let mut collecting_task = RunConfig::new("https://foo".into(), None);
+    collecting_task.collect_link_graph = Some(true);
+    let collecting_uuid = client.put("/crawl").body(&serde_json::to_string(&collecting_task).unwrap()).dispatch().await.into_string().await.unwrap();
+    let other_uuid = client.put("/crawl").body(&serde_json::to_string(&RunConfig::new("https://foo".into(), None)).unwrap()).dispatch().await.into_string().await.unwrap();
+
+    // when: their graphs are requested
+    let dot_response = client.get(format!("/crawl/{}/graph.dot", collecting_uuid)).dispatch().await;
+    let graphml_response = client.get(format!("/crawl/{}/graph.graphml", collecting_uuid)).dispatch().await;
+    let other_response = client.get(format!("/crawl/{}/graph.dot", other_uuid)).dispatch().await;
+
+    // then: only the collecting crawl has a graph
+    assert_eq!(dot_response.status().code, Status::Ok.code);
+    assert_eq!(dot_response.into_string().await.unwrap().starts_with("digraph crawl {"), true, "Should render DOT");
+    assert_eq!(graphml_response.status().code, Status::Ok.code);
+    assert_eq!(other_response.status().code, Status::NotFound.code);
+}
+
+#[rocket::async_test]
+async fn link_graph_of_the_oldest_crawl_is_dropped_beyond_max_stored_link_graphs() {
+    // given: more crawls collecting their link graph than are stored
+    let page_loader_tx_channel = PageLoaderService::init();
+    let rocket = server::http::rocket(page_loader_tx_channel);
+    let client = Client::tracked(rocket).await.unwrap();
+    let mut collecting_task = RunConfig::new("http://127.0.0.1:1".into(), None);
+    collecting_task.collect_link_graph = Some(true);
+    let collecting_task = serde_json::to_string(&collecting_task).unwrap();
+    let mut uuids = vec![];
+    for _ in 0..=server::http::MAX_STORED_LINK_GRAPHS {
+        uuids.push(client.put("/crawl").body(&collecting_task).dispatch().await.into_string().await.unwrap());
+    }
+
+    // when: the graphs of the oldest and the newest crawl are requested
+    let oldest_response = client.get(format!("/crawl/{}/graph.dot", uuids.first().unwrap())).dispatch().await;
+    let second_oldest_response = client.get(format!("/crawl/{}/graph.dot", uuids[1])).dispatch().await;
+    let newest_response = client.get(format!("/crawl/{}/graph.dot", uuids.last().unwrap())).dispatch().await;
+
+    // then: only the oldest graph has been dropped
+    assert_eq!(oldest_response.status().code, Status::NotFound.code);
+    assert_eq!(second_oldest_response.status().code, Status::Ok.code);
+    assert_eq!(newest_response.status().code, Status::Ok.code);
+}
+
+#[rocket::async_test]
+async fn sorted_results_are_sent_to_the_callback_by_depth_and_url() {
+    // given: a local site linking to /b before /a, so /b is crawled first, and a callback recording the pages it receives
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let received_urls = Arc::new(Mutex::new(vec![]));
+    let is_finished = Arc::new(Mutex::new(false));
+    let received_urls_clone = received_urls.clone();
+    let is_finished_clone = is_finished.clone();
+    let make_service = make_service_fn(move |_| {
+        let received_urls = received_urls_clone.clone();
+        let is_finished = is_finished_clone.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                let received_urls = received_urls.clone();
+                let is_finished = is_finished.clone();
+                async move {
+                    let html = |body: &str| Response::builder()
+                        .status(200)
+                        .header("content-type", "text/html")
+                        .body(Body::from(format!("<html><body>{}</body></html>", body)));
+                    let response = match request.uri().path() {
+                        "/" => html(&format!("<a href=\"http://127.0.0.1:{0}/b\">b</a><a href=\"http://127.0.0.1:{0}/a\">a</a>", port)),
+                        "/a" => html(&format!("<a href=\"http://127.0.0.1:{}/c\">c</a>", port)),
+                        "/b" | "/c" => html(""),
+                        "/callback" => {
+                            let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+                            let page_response: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                            received_urls.lock().unwrap().push(String::from(page_response["original_requested_url"].as_str().unwrap()));
+                            Response::builder().status(200).body(Body::empty())
+                        }
+                        "/finished" => {
+                            *is_finished.lock().unwrap() = true;
+                            Response::builder().status(200).body(Body::empty())
+                        }
+                        _ => Response::builder().status(404).body(Body::empty()),
+                    };
+                    Ok::<_, Infallible>(response.unwrap())
+                }
+            }))
+        }
+    });
+    let site_server = Server::from_tcp(listener).unwrap().serve(make_service);
+    rocket::tokio::spawn(site_server);
+
+    let page_loader_tx_channel = PageLoaderService::init();
+    let rocket = server::http::rocket(page_loader_tx_channel);
+    let client = Client::tracked(rocket).await.unwrap();
+    let mut task = RunConfig::new(format!("http://127.0.0.1:{}/", port), Some(format!("http://127.0.0.1:{}/callback", port)));
+    task.callback_url_finished = Some(format!("http://127.0.0.1:{}/finished", port));
+    task.crawl_delay_ms = Some(0);
+    task.sort_results = Some(true);
+    // completes the crawl right after its pages are done, instead of waiting for garbage collection
+    task.max_duration_ms = Some(2000);
+
+    // when: the site is crawled
+    let response = client.put("/crawl").body(&serde_json::to_string(&task).unwrap()).dispatch().await;
+    assert_eq!(response.status().code, Status::Accepted.code);
+    for _ in 0..100 {
+        if *is_finished.lock().unwrap() { break; }
+        rocket::tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    // then: the pages were sent before the completion, ordered by depth and then url
+    assert_eq!(*is_finished.lock().unwrap(), true, "Should have completed the crawl");
+    let received_paths = received_urls.lock().unwrap().iter()
+        .map(|url| url.replace(&format!("http://127.0.0.1:{}", port), ""))
+        .collect::<Vec<String>>();
+    assert_eq!(received_paths, vec!["/", "/a", "/b", "/c"]);
+}
+
+#[rocket::async_test]
+async fn resolve_returns_redirect_chain_and_final_url() {
+    // given: a local server redirecting /start to /final
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let make_service = make_service_fn(move |_| async move {
+        Ok::<_, Infallible>(service_fn(move |request: Request<Body>| async move {
+            let response = match request.uri().path() {
+                "/start" => Response::builder()
+                    .status(301)
+                    .header("location", format!("http://127.0.0.1:{}/final", port))
+                    .body(Body::empty()),
+                _ => Response::builder()
+                    .status(200)
+                    .body(Body::empty()),
+            };
+            Ok::<_, Infallible>(response.unwrap())
+        }))
+    });
+    let redirecting_server = Server::from_tcp(listener).unwrap().serve(make_service);
+    rocket::tokio::spawn(redirecting_server);
+
+    let page_loader_tx_channel = PageLoaderService::init();
+    let rocket = server::http::rocket(page_loader_tx_channel);
+    let client = Client::tracked(rocket).await.unwrap();
+
+    // when: the redirecting url is resolved
+    let response = client.get(format!("/resolve?url=http%3A%2F%2F127.0.0.1%3A{}%2Fstart", port)).dispatch().await;
+
+    // then: the redirect chain and the final url are returned
+    assert_eq!(response.status().code, Status::Ok.code);
+    let response_body: serde_json::Value = serde_json::from_str(&response.into_string().await.unwrap()).unwrap();
+    assert_eq!(response_body["final_url"], format!("http://127.0.0.1:{}/final", port));
+    assert_eq!(response_body["http_response_code"]["code"], 200);
+    assert_eq!(response_body["redirects"].as_array().unwrap().len(), 1, "Should have one redirect");
+    assert_eq!(response_body["redirects"][0]["source"], format!("http://127.0.0.1:{}/start", port));
+    assert_eq!(response_body["redirects"][0]["http_response_code"]["code"], 301);
+}
+
+#[rocket::async_test]
+async fn resolve_rejects_invalid_url() {
+    let page_loader_tx_channel = PageLoaderService::init();
+    let rocket = server::http::rocket(page_loader_tx_channel);
+    let client = Client::tracked(rocket).await.unwrap();
+
+    let response = client.get("/resolve?url=not-a-url").dispatch().await;
+
+    assert_eq!(response.status().code, Status::BadRequest.code);
+}
+
+#[rocket::async_test]
+async fn robots_check_returns_verdict_and_matching_rule() {
+    // given: a local server serving a robots.txt fixture
+    let mut fixture_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    fixture_path.push("tests/resources/robots.txt");
+    let robots_body = read_to_string(&fixture_path).unwrap();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let make_service = make_service_fn(move |_| {
+        let robots_body = robots_body.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                let robots_body = robots_body.clone();
+                async move {
+                    let response = match request.uri().path() {
+                        "/robots.txt" => Response::builder()
+                            .status(200)
+                            .body(Body::from(robots_body)),
+                        _ => Response::builder()
+                            .status(404)
+                            .body(Body::empty()),
+                    };
+                    Ok::<_, Infallible>(response.unwrap())
+                }
+            }))
+        }
+    });
+    let robots_server = Server::from_tcp(listener).unwrap().serve(make_service);
+    rocket::tokio::spawn(robots_server);
+
+    let page_loader_tx_channel = PageLoaderService::init();
+    let rocket = server::http::rocket(page_loader_tx_channel);
+    let client = Client::tracked(rocket).await.unwrap();
+    let check = |path: &str, agent: &str| format!("/robots-check?url=http%3A%2F%2F127.0.0.1%3A{}{}&agent={}", port, path.replace("/", "%2F"), agent);
+
+    // when: representative paths are checked
+    let mut verdicts = vec![];
+    for (path, agent) in [("/index.html", "tarantula"), ("/private/secret.html", "tarantula"), ("/private/public-note.html", "tarantula"), ("/index.html", "badbot")] {
+        let response = client.get(check(path, agent)).dispatch().await;
+        assert_eq!(response.status().code, Status::Ok.code);
+        let response_body: serde_json::Value = serde_json::from_str(&response.into_string().await.unwrap()).unwrap();
+        assert_eq!(response_body["robots_txt_url"], format!("http://127.0.0.1:{}/robots.txt", port));
+        verdicts.push((response_body["allowed"].as_bool().unwrap(), response_body["matching_rule"].as_str().map(String::from)));
+    }
+
+    // then: the verdicts and matching rules reflect the fixture
+    assert_eq!(verdicts[0], (true, None), "Unrestricted path should be allowed");
+    assert_eq!(verdicts[1], (false, Some(String::from("Disallow: /private/"))), "Private path should be disallowed");
+    assert_eq!(verdicts[2], (true, Some(String::from("Allow: /private/public-note.html"))), "More specific allow rule should win");
+    assert_eq!(verdicts[3], (false, Some(String::from("Disallow: /"))), "Agent specific group should apply");
+}
+
+#[rocket::async_test]
+async fn crawl_diff_reports_changed_pages() {
+    // given: the results of two crawls
+    let page_loader_tx_channel = PageLoaderService::init();
+    let rocket = server::http::rocket(page_loader_tx_channel);
+    let client = Client::tracked(rocket).await.unwrap();
+    let crawl_diff_request = r#"{
+        "previous": [
+            {"url": "https://example.com/", "http_response_code": 200, "redirect_target": null},
+            {"url": "https://example.com/old", "http_response_code": 200, "redirect_target": null}
+        ],
+        "current": [
+            {"url": "https://example.com/", "http_response_code": 503, "redirect_target": null},
+            {"url": "https://example.com/new", "http_response_code": 200, "redirect_target": null}
+        ]
+    }"#;
+
+    // when: they are diffed
+    let response = client.post("/crawl-diff").body(crawl_diff_request).dispatch().await;
+
+    // then: added, removed and changed pages are reported
+    assert_eq!(response.status().code, Status::Ok.code);
+    let response_body: serde_json::Value = serde_json::from_str(&response.into_string().await.unwrap()).unwrap();
+    assert_eq!(response_body["added_urls"], serde_json::json!(["https://example.com/new"]));
+    assert_eq!(response_body["removed_urls"], serde_json::json!(["https://example.com/old"]));
+    assert_eq!(response_body["status_changes"], serde_json::json!([{"url": "https://example.com/", "previous": 200, "current": 503}]));
+}